@@ -1,6 +1,11 @@
 mod mem;
 mod task;
 
+cfg_task! {
+    mod sync;
+    pub use sync::*;
+}
+
 cfg_fs! {
     mod fs;
     pub use fs::*;
@@ -33,6 +38,103 @@ mod stdio {
     }
 }
 
+#[cfg(feature = "smp")]
+mod smp {
+    use axerrno::ax_err;
+
+    pub fn ax_cpu_offline(cpu_id: usize) -> crate::AxResult {
+        #[cfg(all(feature = "multitask", feature = "irq"))]
+        {
+            if axruntime::offline_cpu(cpu_id) {
+                Ok(())
+            } else {
+                ax_err!(InvalidInput, "the CPU can't be taken offline")
+            }
+        }
+        #[cfg(not(all(feature = "multitask", feature = "irq")))]
+        {
+            let _ = cpu_id;
+            ax_err!(Unsupported, "CPU hotplug requires multitask and irq")
+        }
+    }
+
+    pub fn ax_cpu_online(cpu_id: usize) -> crate::AxResult {
+        #[cfg(all(feature = "multitask", feature = "irq"))]
+        {
+            if axruntime::online_cpu(cpu_id) {
+                Ok(())
+            } else {
+                ax_err!(InvalidInput, "the CPU can't be brought online")
+            }
+        }
+        #[cfg(not(all(feature = "multitask", feature = "irq")))]
+        {
+            let _ = cpu_id;
+            ax_err!(Unsupported, "CPU hotplug requires multitask and irq")
+        }
+    }
+}
+
+#[cfg(feature = "trace")]
+mod trace {
+    use core::fmt;
+
+    pub use axtrace::{
+        clear as ax_trace_clear, export_chrome_json as ax_trace_export,
+        is_enabled as ax_trace_is_enabled, set_enabled as ax_trace_set_enabled,
+    };
+
+    struct ConsoleWriter;
+
+    impl fmt::Write for ConsoleWriter {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            axhal::console::write_bytes(s.as_bytes());
+            Ok(())
+        }
+    }
+
+    pub fn ax_trace_dump_console() {
+        let _ = axtrace::export_chrome_json(&mut ConsoleWriter);
+    }
+
+    pub fn ax_trace_dump_file(path: &str) -> crate::AxResult {
+        #[cfg(feature = "fs")]
+        {
+            use axio::Write;
+
+            /// Writes to a file, and keeps the I/O error which can't be
+            /// returned by [`fmt::Write`].
+            struct FileWriter {
+                file: axfs::api::File,
+                result: crate::AxResult,
+            }
+
+            impl fmt::Write for FileWriter {
+                fn write_str(&mut self, s: &str) -> fmt::Result {
+                    self.file.write_all(s.as_bytes()).map_err(|e| {
+                        self.result = Err(e);
+                        fmt::Error
+                    })
+                }
+            }
+
+            let mut w = FileWriter {
+                file: axfs::api::File::create(path)?,
+                result: Ok(()),
+            };
+            if axtrace::export_chrome_json(&mut w).is_err() {
+                return w.result;
+            }
+            w.file.flush()
+        }
+        #[cfg(not(feature = "fs"))]
+        {
+            let _ = path;
+            axerrno::ax_err!(Unsupported, "dumping trace events requires fs")
+        }
+    }
+}
+
 mod time {
     pub use axhal::time::{
         monotonic_time as ax_monotonic_time, wall_time as ax_wall_time, TimeValue as AxTimeValue,
@@ -40,9 +142,13 @@ mod time {
 }
 
 pub use self::mem::*;
+#[cfg(feature = "smp")]
+pub use self::smp::*;
 pub use self::stdio::*;
 pub use self::task::*;
 pub use self::time::*;
+#[cfg(feature = "trace")]
+pub use self::trace::*;
 
 pub use axhal::misc::terminate as ax_terminate;
 pub use axio::PollState as AxPollState;
@@ -2,6 +2,8 @@ use crate::io::AxPollState;
 use axerrno::AxResult;
 use axnet::{UdpSocket, TcpSocket};
 use core::net::{IpAddr, SocketAddr};
+#[cfg(feature = "net-async")]
+use core::task::{Context, Poll};
 
 /// A handle to a TCP socket.
 pub struct AxTcpSocketHandle(TcpSocket);
@@ -56,6 +58,36 @@ pub fn ax_tcp_recv(socket: &AxTcpSocketHandle, buf: &mut [u8]) -> AxResult<usize
     socket.0.recv(buf)
 }
 
+#[cfg(feature = "net-async")]
+pub fn ax_tcp_poll_accept(
+    socket: &AxTcpSocketHandle,
+    cx: &mut Context<'_>,
+) -> Poll<AxResult<(AxTcpSocketHandle, SocketAddr)>> {
+    socket.0.poll_accept(cx).map(|res| {
+        let new_sock = res?;
+        let addr = new_sock.peer_addr()?;
+        Ok((AxTcpSocketHandle(new_sock), addr))
+    })
+}
+
+#[cfg(feature = "net-async")]
+pub fn ax_tcp_poll_send(
+    socket: &AxTcpSocketHandle,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+) -> Poll<AxResult<usize>> {
+    socket.0.poll_send(cx, buf)
+}
+
+#[cfg(feature = "net-async")]
+pub fn ax_tcp_poll_recv(
+    socket: &AxTcpSocketHandle,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+) -> Poll<AxResult<usize>> {
+    socket.0.poll_recv(cx, buf)
+}
+
 pub fn ax_tcp_poll(socket: &AxTcpSocketHandle) -> AxResult<AxPollState> {
     socket.0.poll()
 }
@@ -101,6 +133,25 @@ pub fn ax_udp_send_to(socket: &AxUdpSocketHandle, buf: &[u8], addr: SocketAddr)
     socket.0.send_to(buf, addr)
 }
 
+#[cfg(feature = "net-async")]
+pub fn ax_udp_poll_recv_from(
+    socket: &AxUdpSocketHandle,
+    cx: &mut Context<'_>,
+    buf: &mut [u8],
+) -> Poll<AxResult<(usize, SocketAddr)>> {
+    socket.0.poll_recv_from(cx, buf)
+}
+
+#[cfg(feature = "net-async")]
+pub fn ax_udp_poll_send_to(
+    socket: &AxUdpSocketHandle,
+    cx: &mut Context<'_>,
+    buf: &[u8],
+    addr: SocketAddr,
+) -> Poll<AxResult<usize>> {
+    socket.0.poll_send_to(cx, buf, addr)
+}
+
 pub fn ax_udp_connect(socket: &AxUdpSocketHandle, addr: SocketAddr) -> AxResult {
     socket.0.connect(addr)
 }
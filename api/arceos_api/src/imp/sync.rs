@@ -0,0 +1,204 @@
+use core::time::Duration;
+
+/// A handle to a condition variable.
+#[derive(Default)]
+pub struct AxCondvarHandle(axsync::Condvar);
+
+impl AxCondvarHandle {
+    /// Creates a new condition variable.
+    pub const fn new() -> Self {
+        Self(axsync::Condvar::new())
+    }
+}
+
+/// A handle to a reader-writer lock, which does not protect any data.
+#[derive(Default)]
+pub struct AxRwLockHandle(axsync::RawRwLock);
+
+impl AxRwLockHandle {
+    /// Creates a new unlocked reader-writer lock.
+    pub const fn new() -> Self {
+        Self(axsync::RawRwLock::new())
+    }
+}
+
+/// A handle to a counting semaphore.
+pub struct AxSemaphoreHandle(axsync::Semaphore);
+
+impl AxSemaphoreHandle {
+    /// Creates a new semaphore with the given number of permits.
+    pub const fn new(permits: usize) -> Self {
+        Self(axsync::Semaphore::new(permits))
+    }
+}
+
+/// A handle to a barrier.
+pub struct AxBarrierHandle(axsync::Barrier);
+
+impl AxBarrierHandle {
+    /// Creates a new barrier that blocks a group of `n` tasks.
+    pub const fn new(n: usize) -> Self {
+        Self(axsync::Barrier::new(n))
+    }
+}
+
+/// A handle to a one-time initialization.
+#[derive(Default)]
+pub struct AxOnceHandle(axsync::Once);
+
+impl AxOnceHandle {
+    /// Creates a new handle whose initialization has not run.
+    pub const fn new() -> Self {
+        Self(axsync::Once::new())
+    }
+}
+
+/// A handle to the sending half of a channel.
+pub struct AxSenderHandle<T>(axsync::channel::Sender<T>);
+
+/// A handle to the receiving half of a channel.
+pub struct AxReceiverHandle<T>(axsync::channel::Receiver<T>);
+
+impl<T> Clone for AxSenderHandle<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+pub use axsync::channel::{
+    RecvError as AxRecvError, RecvTimeoutError as AxRecvTimeoutError, SendError as AxSendError,
+    TryRecvError as AxTryRecvError, TrySendError as AxTrySendError,
+};
+
+pub fn ax_condvar_wait(
+    cv: &AxCondvarHandle,
+    unlock: impl FnOnce(),
+    lock: impl FnOnce(),
+    timeout: Option<Duration>,
+) -> bool {
+    #[cfg(feature = "irq")]
+    if let Some(dur) = timeout {
+        return cv.0.wait_timeout_with(dur, unlock, lock).1.timed_out();
+    }
+
+    if timeout.is_some() {
+        axlog::warn!(
+            "ax_condvar_wait: the `timeout` argument is ignored without the `irq` feature"
+        );
+    }
+    cv.0.wait_with(unlock, lock);
+    false
+}
+
+pub fn ax_condvar_notify_one(cv: &AxCondvarHandle) {
+    cv.0.notify_one();
+}
+
+pub fn ax_condvar_notify_all(cv: &AxCondvarHandle) {
+    cv.0.notify_all();
+}
+
+pub fn ax_rwlock_read(lock: &AxRwLockHandle) {
+    lock.0.read();
+}
+
+pub fn ax_rwlock_try_read(lock: &AxRwLockHandle) -> bool {
+    lock.0.try_read()
+}
+
+pub fn ax_rwlock_write(lock: &AxRwLockHandle) {
+    lock.0.write();
+}
+
+pub fn ax_rwlock_try_write(lock: &AxRwLockHandle) -> bool {
+    lock.0.try_write()
+}
+
+pub unsafe fn ax_rwlock_read_unlock(lock: &AxRwLockHandle) {
+    lock.0.read_unlock();
+}
+
+pub unsafe fn ax_rwlock_write_unlock(lock: &AxRwLockHandle) {
+    lock.0.write_unlock();
+}
+
+pub fn ax_semaphore_acquire(sem: &AxSemaphoreHandle, timeout: Option<Duration>) -> bool {
+    #[cfg(feature = "irq")]
+    if let Some(dur) = timeout {
+        return sem.0.acquire_timeout(dur);
+    }
+
+    if timeout.is_some() {
+        axlog::warn!(
+            "ax_semaphore_acquire: the `timeout` argument is ignored without the `irq` feature"
+        );
+    }
+    sem.0.acquire();
+    true
+}
+
+pub fn ax_semaphore_try_acquire(sem: &AxSemaphoreHandle) -> bool {
+    sem.0.try_acquire()
+}
+
+pub fn ax_semaphore_release(sem: &AxSemaphoreHandle) {
+    sem.0.release();
+}
+
+pub fn ax_semaphore_available_permits(sem: &AxSemaphoreHandle) -> usize {
+    sem.0.available_permits()
+}
+
+pub fn ax_barrier_wait(barrier: &AxBarrierHandle) -> bool {
+    barrier.0.wait().is_leader()
+}
+
+pub fn ax_once_call(once: &AxOnceHandle, f: impl FnOnce()) {
+    once.0.call_once(f);
+}
+
+pub fn ax_once_is_completed(once: &AxOnceHandle) -> bool {
+    once.0.is_completed()
+}
+
+pub fn ax_channel<T>(capacity: Option<usize>) -> (AxSenderHandle<T>, AxReceiverHandle<T>) {
+    let (tx, rx) = match capacity {
+        Some(cap) => axsync::channel::bounded(cap),
+        None => axsync::channel::unbounded(),
+    };
+    (AxSenderHandle(tx), AxReceiverHandle(rx))
+}
+
+pub fn ax_channel_send<T>(tx: &AxSenderHandle<T>, msg: T) -> Result<(), AxSendError<T>> {
+    tx.0.send(msg)
+}
+
+pub fn ax_channel_try_send<T>(tx: &AxSenderHandle<T>, msg: T) -> Result<(), AxTrySendError<T>> {
+    tx.0.try_send(msg)
+}
+
+pub fn ax_channel_recv<T>(rx: &AxReceiverHandle<T>) -> Result<T, AxRecvError> {
+    rx.0.recv()
+}
+
+pub fn ax_channel_try_recv<T>(rx: &AxReceiverHandle<T>) -> Result<T, AxTryRecvError> {
+    rx.0.try_recv()
+}
+
+pub fn ax_channel_recv_timeout<T>(
+    rx: &AxReceiverHandle<T>,
+    timeout: Duration,
+) -> Result<T, AxRecvTimeoutError> {
+    #[cfg(feature = "irq")]
+    return rx.0.recv_timeout(timeout);
+
+    // Timers can't wake us up without IRQs, so never block.
+    #[cfg(not(feature = "irq"))]
+    {
+        let _ = timeout;
+        rx.0.try_recv().map_err(|err| match err {
+            AxTryRecvError::Empty => AxRecvTimeoutError::Timeout,
+            AxTryRecvError::Disconnected => AxRecvTimeoutError::Disconnected,
+        })
+    }
+}
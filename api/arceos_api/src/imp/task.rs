@@ -24,6 +24,7 @@ pub fn ax_exit(_exit_code: i32) -> ! {
 }
 
 cfg_task! {
+    use core::sync::atomic::AtomicU32;
     use core::time::Duration;
 
     /// A handle to a task.
@@ -42,6 +43,22 @@ cfg_task! {
     /// A mask to specify the CPU affinity.
     pub use axtask::AxCpuMask;
 
+    /// CPU time and scheduling statistics of a task.
+    pub use axtask::TaskStats as AxTaskStats;
+
+    /// Information about a task, for introspection.
+    pub use axtask::TaskInfo as AxTaskInfo;
+
+    /// State of a task.
+    pub use axtask::TaskState as AxTaskState;
+
+    /// Scheduling policy of a task.
+    pub use axtask::SchedPolicy as AxSchedPolicy;
+
+    /// Configuration of the watchdog.
+    #[cfg(feature = "watchdog")]
+    pub use axtask::WatchdogConfig as AxWatchdogConfig;
+
     /// A handle to a wait queue.
     ///
     /// A wait queue is used to store sleeping tasks waiting for a certain event
@@ -55,6 +72,23 @@ cfg_task! {
         }
     }
 
+    /// A handle to an asynchronous task spawned by [`ax_spawn_async`].
+    ///
+    /// It's a future that resolves when the task completes.
+    pub type AxAsyncTaskHandle = axtask::future::JoinHandle<()>;
+
+    /// A future that completes at a deadline.
+    pub use axtask::future::Sleep as AxSleepFuture;
+
+    /// A handle to a timer.
+    ///
+    /// Cloned handles refer to the same timer.
+    #[derive(Clone)]
+    pub struct AxTimerHandle {
+        #[cfg(feature = "irq")]
+        inner: axtask::Timer,
+    }
+
     pub fn ax_current_task_id() -> u64 {
         axtask::current().id().as_u64()
     }
@@ -74,6 +108,38 @@ cfg_task! {
         task.inner.join()
     }
 
+    pub fn ax_cancel_task(task: &AxTaskHandle) -> bool {
+        axtask::cancel_task(&task.inner)
+    }
+
+    pub fn ax_set_current_cancelable(enabled: bool) -> bool {
+        axtask::set_current_cancelable(enabled)
+    }
+
+    pub fn ax_test_cancel() {
+        axtask::test_cancel()
+    }
+
+    pub fn ax_current_task_stats() -> AxTaskStats {
+        axtask::current().stats()
+    }
+
+    pub fn ax_task_stats(task: &AxTaskHandle) -> AxTaskStats {
+        task.inner.stats()
+    }
+
+    pub fn ax_task_info(id: u64) -> Option<AxTaskInfo> {
+        axtask::get_task(axtask::TaskId::from_u64(id)).map(|task| task.info())
+    }
+
+    pub fn ax_all_tasks_info() -> alloc::vec::Vec<AxTaskInfo> {
+        axtask::all_tasks().iter().map(|task| task.info()).collect()
+    }
+
+    pub fn ax_busy_cpu_time() -> Duration {
+        axtask::busy_cpu_time()
+    }
+
     pub fn ax_set_current_priority(prio: isize) -> crate::AxResult {
         if axtask::set_priority(prio) {
             Ok(())
@@ -85,6 +151,41 @@ cfg_task! {
         }
     }
 
+    pub fn ax_set_current_sched_policy(policy: AxSchedPolicy, prio: isize) -> crate::AxResult {
+        if axtask::set_current_sched_policy(policy, prio) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(
+                InvalidInput,
+                "ax_set_current_sched_policy: failed to set scheduling policy"
+            )
+        }
+    }
+
+    pub fn ax_set_current_deadline(
+        runtime: Duration,
+        deadline: Duration,
+        period: Duration,
+    ) -> crate::AxResult {
+        let params = axtask::DeadlineParams {
+            runtime,
+            deadline,
+            period,
+        };
+        if axtask::set_current_deadline(params) {
+            Ok(())
+        } else {
+            axerrno::ax_err!(
+                InvalidInput,
+                "ax_set_current_deadline: failed to admit the deadline task"
+            )
+        }
+    }
+
+    pub fn ax_wait_next_period() {
+        axtask::wait_next_period()
+    }
+
     pub fn ax_set_current_affinity(cpumask: AxCpuMask) -> crate::AxResult {
         if axtask::set_current_affinity(cpumask) {
             Ok(())
@@ -135,4 +236,141 @@ cfg_task! {
             }
         }
     }
+
+    pub fn ax_futex_wait(
+        futex: &AtomicU32,
+        expected: u32,
+        timeout: Option<Duration>,
+    ) -> crate::AxResult<bool> {
+        #[cfg(feature = "irq")]
+        let res = match timeout {
+            Some(dur) => axtask::futex_wait_timeout(futex, expected, dur),
+            None => axtask::futex_wait(futex, expected),
+        };
+        #[cfg(not(feature = "irq"))]
+        let res = {
+            if timeout.is_some() {
+                axlog::warn!("ax_futex_wait: the `timeout` argument is ignored without the `irq` feature");
+            }
+            axtask::futex_wait(futex, expected)
+        };
+        match res {
+            Ok(()) => Ok(false),
+            Err(axtask::FutexError::WouldBlock) => Err(crate::AxError::WouldBlock),
+            Err(axtask::FutexError::TimedOut) => Ok(true),
+        }
+    }
+
+    pub fn ax_futex_wake(futex: &AtomicU32, count: u32) -> u32 {
+        axtask::futex_wake(futex, count as usize) as u32
+    }
+
+    pub fn ax_futex_requeue(
+        futex: &AtomicU32,
+        expected: Option<u32>,
+        wake_count: u32,
+        target: &AtomicU32,
+        requeue_count: u32,
+    ) -> crate::AxResult<u32> {
+        axtask::futex_requeue(
+            futex,
+            expected,
+            wake_count as usize,
+            target,
+            requeue_count as usize,
+        )
+        .map(|n| n as u32)
+        .map_err(|_| crate::AxError::WouldBlock)
+    }
+
+    #[cfg(feature = "watchdog")]
+    pub use axtask::{
+        set_watchdog_config as ax_set_watchdog_config, watchdog_config as ax_watchdog_config,
+    };
+
+    #[cfg(feature = "watchdog")]
+    pub fn ax_set_current_hung_task_check(enabled: bool) {
+        axtask::current().set_hung_task_check(enabled);
+    }
+
+    pub fn ax_spawn_async<F>(f: F) -> AxAsyncTaskHandle
+    where
+        F: core::future::Future<Output = ()> + Send + 'static,
+    {
+        axtask::future::spawn(f)
+    }
+
+    pub fn ax_block_on<F: core::future::Future<Output = ()>>(f: F) {
+        axtask::future::block_on(f)
+    }
+
+    pub fn ax_sleep_until_async(deadline: crate::time::AxTimeValue) -> AxSleepFuture {
+        axtask::future::sleep_until(deadline)
+    }
+
+    pub fn ax_timer_create<F>(callback: F) -> crate::AxResult<AxTimerHandle>
+    where
+        F: Fn(&AxTimerHandle) + Send + Sync + 'static,
+    {
+        #[cfg(feature = "irq")]
+        {
+            let inner = axtask::Timer::new(move |timer| {
+                callback(&AxTimerHandle {
+                    inner: timer.clone(),
+                })
+            });
+            Ok(AxTimerHandle { inner })
+        }
+        #[cfg(not(feature = "irq"))]
+        {
+            let _ = callback;
+            axerrno::ax_err!(Unsupported, "ax_timer_create: IRQs are not enabled")
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub fn ax_timer_set(
+        timer: &AxTimerHandle,
+        deadline: crate::time::AxTimeValue,
+        interval: Option<Duration>,
+    ) {
+        #[cfg(feature = "irq")]
+        timer.inner.set(deadline, interval);
+    }
+
+    #[allow(unused_variables)]
+    pub fn ax_timer_cancel(timer: &AxTimerHandle) -> bool {
+        #[cfg(feature = "irq")]
+        {
+            timer.inner.cancel()
+        }
+        #[cfg(not(feature = "irq"))]
+        {
+            false
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub fn ax_timer_deadline(timer: &AxTimerHandle) -> Option<crate::time::AxTimeValue> {
+        #[cfg(feature = "irq")]
+        {
+            timer.inner.deadline()
+        }
+        #[cfg(not(feature = "irq"))]
+        {
+            None
+        }
+    }
+
+    #[allow(unused_variables)]
+    pub fn ax_timer_interval(timer: &AxTimerHandle) -> Option<Duration> {
+        #[cfg(feature = "irq")]
+        {
+            timer.inner.interval()
+        }
+        #[cfg(not(feature = "irq"))]
+        {
+            None
+        }
+    }
 }
@@ -33,6 +33,15 @@ pub mod sys {
         /// Shutdown the whole system and all CPUs.
         pub fn ax_terminate() -> !;
     }
+    define_api! {
+        @cfg "smp";
+        /// Takes the given secondary CPU offline, after migrating its tasks
+        /// and timer events to other CPUs.
+        pub fn ax_cpu_offline(cpu_id: usize) -> crate::AxResult;
+        /// Brings the given secondary CPU back online after
+        /// [`ax_cpu_offline`].
+        pub fn ax_cpu_online(cpu_id: usize) -> crate::AxResult;
+    }
 }
 
 /// Time-related operations.
@@ -122,6 +131,13 @@ pub mod task {
         pub type AxTaskHandle;
         pub type AxWaitQueueHandle;
         pub type AxCpuMask;
+        pub type AxTaskStats;
+        pub type AxTaskInfo;
+        pub type AxTaskState;
+        pub type AxSchedPolicy;
+        pub type AxTimerHandle;
+        pub type AxAsyncTaskHandle;
+        pub type AxSleepFuture;
     }
 
     define_api! {
@@ -154,8 +170,44 @@ pub mod task {
         /// Waits for the given task to exit, and returns its exit code (the
         /// argument of [`ax_exit`]).
         pub fn ax_wait_for_exit(task: AxTaskHandle) -> Option<i32>;
+        /// Requests the cancellation of the given task, returns `false` if it
+        /// has already exited.
+        ///
+        /// The task exits at its next cancellation point, and the joiner gets
+        /// the exit code `axtask::CANCELED_EXIT_CODE`.
+        pub fn ax_cancel_task(task: &AxTaskHandle) -> bool;
+        /// Enables or disables the cancellation of the current task, returns
+        /// whether it was enabled before.
+        pub fn ax_set_current_cancelable(enabled: bool) -> bool;
+        /// A cancellation point: exits the current task if its cancellation has
+        /// been requested and is enabled.
+        pub fn ax_test_cancel();
+        /// Returns the CPU time and scheduling statistics of the current task.
+        pub fn ax_current_task_stats() -> AxTaskStats;
+        /// Returns the CPU time and scheduling statistics of the given task.
+        pub fn ax_task_stats(task: &AxTaskHandle) -> AxTaskStats;
+        /// Returns the information (name, state, CPU, stack usage, etc.) of the
+        /// task with the given ID, or [`None`] if it does not exist.
+        pub fn ax_task_info(id: u64) -> Option<AxTaskInfo>;
+        /// Returns the information of all live tasks, ordered by task IDs.
+        pub fn ax_all_tasks_info() -> alloc::vec::Vec<AxTaskInfo>;
+        /// Returns the total CPU time consumed by all tasks except the idle
+        /// ones since boot.
+        pub fn ax_busy_cpu_time() -> core::time::Duration;
         /// Sets the priority of the current task.
         pub fn ax_set_current_priority(prio: isize) -> crate::AxResult;
+        /// Sets the scheduling policy and priority of the current task.
+        pub fn ax_set_current_sched_policy(policy: AxSchedPolicy, prio: isize) -> crate::AxResult;
+        /// Makes the current task a periodic deadline task, which runs for
+        /// `runtime` before `deadline` in every `period`.
+        pub fn ax_set_current_deadline(
+            runtime: core::time::Duration,
+            deadline: core::time::Duration,
+            period: core::time::Duration,
+        ) -> crate::AxResult;
+        /// Current deadline task completes its job, and sleeps until the next
+        /// period.
+        pub fn ax_wait_next_period();
         /// Sets the cpu affinity of the current task.
         pub fn ax_set_current_affinity(cpumask: AxCpuMask) -> crate::AxResult;
         /// Blocks the current task and put it into the wait queue, until
@@ -175,6 +227,216 @@ pub mod task {
         /// The maximum number of tasks to wake up is specified by `count`. If
         /// `count` is `u32::MAX`, it will wake up all tasks in the wait queue.
         pub fn ax_wait_queue_wake(wq: &AxWaitQueueHandle, count: u32);
+        /// Blocks the current task if the value of `futex` is `expected`,
+        /// until other tasks wake it up by [`ax_futex_wake`], or the given
+        /// duration has elapsed (if specified). Returns whether it timed out.
+        ///
+        /// [`Err(WouldBlock)`](crate::AxError::WouldBlock) is returned if the
+        /// value is not `expected`.
+        pub fn ax_futex_wait(
+            futex: &core::sync::atomic::AtomicU32,
+            expected: u32,
+            timeout: Option<core::time::Duration>,
+        ) -> crate::AxResult<bool>;
+        /// Wakes up at most `count` tasks waiting on `futex`, returns the
+        /// number of tasks woken up.
+        pub fn ax_futex_wake(futex: &core::sync::atomic::AtomicU32, count: u32) -> u32;
+        /// Wakes up at most `wake_count` tasks waiting on `futex`, and moves
+        /// at most `requeue_count` of the others to wait on `target`. Returns
+        /// the number of tasks woken up and moved.
+        ///
+        /// If `expected` is given but the value of `futex` is different,
+        /// [`Err(WouldBlock)`](crate::AxError::WouldBlock) is returned.
+        pub fn ax_futex_requeue(
+            futex: &core::sync::atomic::AtomicU32,
+            expected: Option<u32>,
+            wake_count: u32,
+            target: &core::sync::atomic::AtomicU32,
+            requeue_count: u32,
+        ) -> crate::AxResult<u32>;
+        /// Spawns an asynchronous task to run the given future on the
+        /// executor.
+        pub fn ax_spawn_async(
+            f: impl core::future::Future<Output = ()> + Send + 'static
+        ) -> AxAsyncTaskHandle;
+        /// Runs the given future on the current task, and blocks it until the
+        /// future completes.
+        pub fn ax_block_on(f: impl core::future::Future<Output = ()>);
+        /// Returns a future that completes at the given deadline (in wall
+        /// time).
+        pub fn ax_sleep_until_async(deadline: crate::time::AxTimeValue) -> AxSleepFuture;
+        /// Creates a timer that calls `callback` each time it expires.
+        ///
+        /// The callback runs in the interrupt context, so it must not block.
+        /// Returns [`AxError::Unsupported`](crate::AxError::Unsupported) if
+        /// the feature `irq` is not enabled.
+        pub fn ax_timer_create(
+            callback: impl Fn(&AxTimerHandle) + Send + Sync + 'static
+        ) -> crate::AxResult<AxTimerHandle>;
+        /// Arms the timer to expire at the given deadline (in wall time), and
+        /// then every `interval` if it's not [`None`] or zero. The previous
+        /// setting is replaced.
+        ///
+        /// It can be called in the callback of the timer to re-arm it.
+        pub fn ax_timer_set(
+            timer: &AxTimerHandle,
+            deadline: crate::time::AxTimeValue,
+            interval: Option<core::time::Duration>,
+        );
+        /// Cancels the timer, returns whether it was armed.
+        pub fn ax_timer_cancel(timer: &AxTimerHandle) -> bool;
+        /// Returns the next expiration time (in wall time) of the timer, or
+        /// [`None`] if it's not armed.
+        pub fn ax_timer_deadline(timer: &AxTimerHandle) -> Option<crate::time::AxTimeValue>;
+        /// Returns the interval of the periodic timer, or [`None`] if it's a
+        /// one-shot timer.
+        pub fn ax_timer_interval(timer: &AxTimerHandle) -> Option<core::time::Duration>;
+    }
+
+    define_api_type! {
+        @cfg "watchdog";
+        pub type AxWatchdogConfig;
+    }
+
+    define_api! {
+        @cfg "watchdog";
+
+        /// Sets the configuration of the watchdog, which reports soft lockups
+        /// and hung tasks.
+        pub fn ax_set_watchdog_config(config: AxWatchdogConfig);
+        /// Returns the current configuration of the watchdog.
+        pub fn ax_watchdog_config() -> AxWatchdogConfig;
+        /// Enables or disables the hung task detection for the current task.
+        pub fn ax_set_current_hung_task_check(enabled: bool);
+    }
+}
+
+/// Synchronization primitives.
+pub mod sync {
+    define_api_type! {
+        @cfg "multitask";
+        pub type AxCondvarHandle;
+        pub type AxRwLockHandle;
+        pub type AxSemaphoreHandle;
+        pub type AxBarrierHandle;
+        pub type AxOnceHandle;
+        pub type AxSenderHandle<T>;
+        pub type AxReceiverHandle<T>;
+        pub type AxSendError<T>;
+        pub type AxTrySendError<T>;
+        pub type AxRecvError;
+        pub type AxTryRecvError;
+        pub type AxRecvTimeoutError;
+    }
+
+    define_api! {
+        @cfg "multitask";
+
+        /// Blocks the current task until the condition variable is notified,
+        /// or the given duration has elapsed (if specified). Returns whether
+        /// it timed out.
+        ///
+        /// The lock associated with the condition variable is released by
+        /// calling `unlock` after the task starts to wait, so notifications
+        /// sent after that are not lost. It's re-acquired by calling `lock`
+        /// before returning.
+        pub fn ax_condvar_wait(
+            cv: &AxCondvarHandle,
+            unlock: impl FnOnce(),
+            lock: impl FnOnce(),
+            timeout: Option<core::time::Duration>,
+        ) -> bool;
+        /// Wakes up one task waiting on the condition variable.
+        pub fn ax_condvar_notify_one(cv: &AxCondvarHandle);
+        /// Wakes up all tasks waiting on the condition variable.
+        pub fn ax_condvar_notify_all(cv: &AxCondvarHandle);
+
+        /// Acquires a read lock, blocking the current task until it's
+        /// available. New readers are blocked while a writer is waiting.
+        pub fn ax_rwlock_read(lock: &AxRwLockHandle);
+        /// Tries to acquire a read lock, returns whether it succeeded.
+        pub fn ax_rwlock_try_read(lock: &AxRwLockHandle) -> bool;
+        /// Acquires the write lock, blocking the current task until it's
+        /// available.
+        pub fn ax_rwlock_write(lock: &AxRwLockHandle);
+        /// Tries to acquire the write lock, returns whether it succeeded.
+        pub fn ax_rwlock_try_write(lock: &AxRwLockHandle) -> bool;
+
+        /// Acquires a permit of the semaphore, blocking the current task until
+        /// one is available or the given duration has elapsed (if specified).
+        /// Returns whether it succeeded.
+        pub fn ax_semaphore_acquire(
+            sem: &AxSemaphoreHandle,
+            timeout: Option<core::time::Duration>,
+        ) -> bool;
+        /// Tries to acquire a permit of the semaphore without blocking,
+        /// returns whether it succeeded.
+        pub fn ax_semaphore_try_acquire(sem: &AxSemaphoreHandle) -> bool;
+        /// Releases a permit of the semaphore.
+        pub fn ax_semaphore_release(sem: &AxSemaphoreHandle);
+        /// Returns the number of available permits of the semaphore.
+        pub fn ax_semaphore_available_permits(sem: &AxSemaphoreHandle) -> usize;
+
+        /// Blocks the current task until all tasks of the group have reached
+        /// the barrier. Returns `true` for exactly one task (the leader) in
+        /// each round.
+        pub fn ax_barrier_wait(barrier: &AxBarrierHandle) -> bool;
+
+        /// Runs `f` if the initialization has not run, or blocks the current
+        /// task until it completes if it's running in another task.
+        pub fn ax_once_call(once: &AxOnceHandle, f: impl FnOnce());
+        /// Whether the initialization has completed.
+        pub fn ax_once_is_completed(once: &AxOnceHandle) -> bool;
+
+        /// Creates a multi-producer, multi-consumer channel, returning the
+        /// sending and receiving halves.
+        ///
+        /// The channel buffers at most `capacity` messages, or unlimited
+        /// messages if it's `None`. With a capacity of 0, each send blocks
+        /// until the message is received.
+        pub fn ax_channel<T>(
+            capacity: Option<usize>,
+        ) -> (AxSenderHandle<T>, AxReceiverHandle<T>);
+        /// Sends a message, blocking the current task until there is space in
+        /// the channel. The message is returned back if all receivers are
+        /// dropped.
+        pub fn ax_channel_send<T>(tx: &AxSenderHandle<T>, msg: T) -> Result<(), AxSendError<T>>;
+        /// Tries to send a message without blocking.
+        pub fn ax_channel_try_send<T>(
+            tx: &AxSenderHandle<T>,
+            msg: T,
+        ) -> Result<(), AxTrySendError<T>>;
+        /// Receives a message, blocking the current task until there is one.
+        /// Fails if the channel is empty and all senders are dropped.
+        pub fn ax_channel_recv<T>(rx: &AxReceiverHandle<T>) -> Result<T, AxRecvError>;
+        /// Tries to receive a message without blocking.
+        pub fn ax_channel_try_recv<T>(rx: &AxReceiverHandle<T>) -> Result<T, AxTryRecvError>;
+        /// Receives a message, blocking the current task until there is one or
+        /// the given duration has elapsed.
+        ///
+        /// Without the `irq` feature, it never blocks, and returns a timeout
+        /// error if the channel is empty.
+        pub fn ax_channel_recv_timeout<T>(
+            rx: &AxReceiverHandle<T>,
+            timeout: core::time::Duration,
+        ) -> Result<T, AxRecvTimeoutError>;
+    }
+
+    define_api! {
+        @cfg "multitask";
+
+        /// Releases a read lock.
+        ///
+        /// # Safety
+        ///
+        /// The current task must hold a read lock.
+        pub unsafe fn ax_rwlock_read_unlock(lock: &AxRwLockHandle);
+        /// Releases the write lock.
+        ///
+        /// # Safety
+        ///
+        /// The current task must hold the write lock.
+        pub unsafe fn ax_rwlock_write_unlock(lock: &AxRwLockHandle);
     }
 }
 
@@ -356,6 +618,48 @@ pub mod net {
         /// packets to the NIC.
         pub fn ax_poll_interfaces() -> AxResult;
     }
+
+    define_api! {
+        @cfg "net-async";
+
+        /// Polls to accept a new connection on the TCP socket.
+        ///
+        /// If there is no connection yet, the waker in `cx` is registered to be
+        /// woken up when there is one.
+        pub fn ax_tcp_poll_accept(
+            socket: &AxTcpSocketHandle,
+            cx: &mut core::task::Context<'_>,
+        ) -> core::task::Poll<AxResult<(AxTcpSocketHandle, SocketAddr)>>;
+        /// Polls to transmit data on the TCP socket, registering the waker in
+        /// `cx` if the send buffer is full.
+        pub fn ax_tcp_poll_send(
+            socket: &AxTcpSocketHandle,
+            cx: &mut core::task::Context<'_>,
+            buf: &[u8],
+        ) -> core::task::Poll<AxResult<usize>>;
+        /// Polls to receive data on the TCP socket, registering the waker in
+        /// `cx` if there is no data.
+        pub fn ax_tcp_poll_recv(
+            socket: &AxTcpSocketHandle,
+            cx: &mut core::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> core::task::Poll<AxResult<usize>>;
+        /// Polls to receive a single datagram on the UDP socket, registering
+        /// the waker in `cx` if there is none.
+        pub fn ax_udp_poll_recv_from(
+            socket: &AxUdpSocketHandle,
+            cx: &mut core::task::Context<'_>,
+            buf: &mut [u8],
+        ) -> core::task::Poll<AxResult<(usize, SocketAddr)>>;
+        /// Polls to send data on the UDP socket to the given address,
+        /// registering the waker in `cx` if the send buffer is full.
+        pub fn ax_udp_poll_send_to(
+            socket: &AxUdpSocketHandle,
+            cx: &mut core::task::Context<'_>,
+            buf: &[u8],
+            addr: SocketAddr,
+        ) -> core::task::Poll<AxResult<usize>>;
+    }
 }
 
 /// Graphics manipulation operations.
@@ -381,6 +685,34 @@ pub mod io {
     }
 }
 
+/// Kernel event tracing.
+pub mod trace {
+    use crate::AxResult;
+
+    define_api! {
+        @cfg "trace";
+
+        /// Enables or disables the recording of kernel events.
+        pub fn ax_trace_set_enabled(enabled: bool);
+        /// Whether the recording of kernel events is enabled.
+        pub fn ax_trace_is_enabled() -> bool;
+        /// Discards all kernel events recorded so far.
+        pub fn ax_trace_clear();
+        /// Writes the recorded kernel events to `w` in the Chrome trace event
+        /// format (JSON), which can be loaded by Perfetto.
+        pub fn ax_trace_export(w: &mut dyn core::fmt::Write) -> core::fmt::Result;
+        /// Writes the recorded kernel events to the console, in the same
+        /// format as [`ax_trace_export`].
+        pub fn ax_trace_dump_console();
+        /// Writes the recorded kernel events to the file at `path`, in the same
+        /// format as [`ax_trace_export`]. The file is created or truncated.
+        ///
+        /// Returns [`AxError::Unsupported`](crate::AxError::Unsupported) if
+        /// the feature `fs` is not enabled.
+        pub fn ax_trace_dump_file(path: &str) -> AxResult;
+    }
+}
+
 /// Re-exports of ArceOS modules.
 ///
 /// You should prefer to use other APIs rather than these modules. The modules
@@ -408,4 +740,6 @@ pub mod modules {
     pub use axnet;
     #[cfg(feature = "multitask")]
     pub use axtask;
+    #[cfg(feature = "trace")]
+    pub use axtrace;
 }
@@ -6,7 +6,7 @@ macro_rules! define_api_type {
             $vis use $crate::imp::$name;
         )+
     };
-    ( @cfg $feature:literal; $( $(#[$attr:meta])* $vis:vis type $name:ident; )+ ) => {
+    ( @cfg $feature:literal; $( $(#[$attr:meta])* $vis:vis type $name:ident $(<$($gen:ident),+>)?; )+ ) => {
         $(
             #[cfg(feature = $feature)]
             $(#[$attr])*
@@ -14,62 +14,62 @@ macro_rules! define_api_type {
 
             #[cfg(all(feature = "dummy-if-not-enabled", not(feature = $feature)))]
             $(#[$attr])*
-            $vis struct $name;
+            $vis struct $name $(<$($gen),+>(core::marker::PhantomData<($($gen,)+)>))?;
         )+
     };
 }
 
 macro_rules! define_api {
-    ($( $(#[$attr:meta])* $vis:vis fn $name:ident( $($arg:ident : $type:ty),* $(,)? ) $( -> $ret:ty )? ; )+) => {
+    ($( $(#[$attr:meta])* $vis:vis fn $name:ident $(<$($gen:ident),+>)? ( $($arg:ident : $type:ty),* $(,)? ) $( -> $ret:ty )? ; )+) => {
         $(
             $(#[$attr])*
-            $vis fn $name( $($arg : $type),* ) $( -> $ret )? {
+            $vis fn $name $(<$($gen),+>)? ( $($arg : $type),* ) $( -> $ret )? {
                 $crate::imp::$name( $($arg),* )
             }
         )+
     };
-    ($( $(#[$attr:meta])* $vis:vis unsafe fn $name:ident( $($arg:ident : $type:ty),* $(,)? ) $( -> $ret:ty )? ; )+) => {
+    ($( $(#[$attr:meta])* $vis:vis unsafe fn $name:ident $(<$($gen:ident),+>)? ( $($arg:ident : $type:ty),* $(,)? ) $( -> $ret:ty )? ; )+) => {
         $(
             $(#[$attr])*
-            $vis unsafe fn $name( $($arg : $type),* ) $( -> $ret )? {
+            $vis unsafe fn $name $(<$($gen),+>)? ( $($arg : $type),* ) $( -> $ret )? {
                 $crate::imp::$name( $($arg),* )
             }
         )+
     };
     (
         @cfg $feature:literal;
-        $( $(#[$attr:meta])* $vis:vis fn $name:ident( $($arg:ident : $type:ty),* $(,)? ) $( -> $ret:ty )? ; )+
+        $( $(#[$attr:meta])* $vis:vis fn $name:ident $(<$($gen:ident),+>)? ( $($arg:ident : $type:ty),* $(,)? ) $( -> $ret:ty )? ; )+
     ) => {
         $(
             #[cfg(feature = $feature)]
             $(#[$attr])*
-            $vis fn $name( $($arg : $type),* ) $( -> $ret )? {
+            $vis fn $name $(<$($gen),+>)? ( $($arg : $type),* ) $( -> $ret )? {
                 $crate::imp::$name( $($arg),* )
             }
 
             #[allow(unused_variables)]
             #[cfg(all(feature = "dummy-if-not-enabled", not(feature = $feature)))]
             $(#[$attr])*
-            $vis fn $name( $($arg : $type),* ) $( -> $ret )? {
+            $vis fn $name $(<$($gen),+>)? ( $($arg : $type),* ) $( -> $ret )? {
                 unimplemented!(stringify!($name))
             }
         )+
     };
     (
         @cfg $feature:literal;
-        $( $(#[$attr:meta])* $vis:vis unsafe fn $name:ident( $($arg:ident : $type:ty),* $(,)? ) $( -> $ret:ty )? ; )+
+        $( $(#[$attr:meta])* $vis:vis unsafe fn $name:ident $(<$($gen:ident),+>)? ( $($arg:ident : $type:ty),* $(,)? ) $( -> $ret:ty )? ; )+
     ) => {
         $(
             #[cfg(feature = $feature)]
             $(#[$attr])*
-            $vis unsafe fn $name( $($arg : $type),* ) $( -> $ret )? {
+            $vis unsafe fn $name $(<$($gen),+>)? ( $($arg : $type),* ) $( -> $ret )? {
                 $crate::imp::$name( $($arg),* )
             }
 
             #[allow(unused_variables)]
             #[cfg(all(feature = "dummy-if-not-enabled", not(feature = $feature)))]
             $(#[$attr])*
-            $vis unsafe fn $name( $($arg : $type),* ) $( -> $ret )? {
+            $vis unsafe fn $name $(<$($gen),+>)? ( $($arg : $type),* ) $( -> $ret )? {
                 unimplemented!(stringify!($name))
             }
         )+
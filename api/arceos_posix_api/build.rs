@@ -2,16 +2,22 @@ fn main() {
     use std::io::Write;
 
     fn gen_pthread_mutex(out_file: &str) -> std::io::Result<()> {
-        // TODO: generate size and initial content automatically.
-        let (mutex_size, mutex_init) = if cfg!(feature = "multitask") {
-            if cfg!(feature = "smp") {
-                (6, "{0, 0, 8, 0, 0, 0}") // core::mem::transmute::<_, [usize; 6]>(axsync::Mutex::new(()))
-            } else {
-                (5, "{0, 8, 0, 0, 0}") // core::mem::transmute::<_, [usize; 5]>(axsync::Mutex::new(()))
+        // The size must be the same as `PthreadMutex`, which is checked by
+        // `const_assert_eq`. It's initialized on the first use if it's all
+        // zeros, so the initializer doesn't depend on the layout.
+        let mutex_size = if cfg!(feature = "multitask") {
+            // The tag, the state of `LazyInit` and a `Mutex`, which is a wait
+            // queue (4 words, and the lock word with `smp`) and the owner.
+            let mut size = if cfg!(feature = "smp") { 8 } else { 7 };
+            if cfg!(feature = "lockdep") {
+                // The lock classes of the `Mutex` and its wait queue.
+                size += 2;
             }
+            size
         } else {
-            (1, "{0}")
+            1
         };
+        let mutex_init = "{0}";
 
         let mut output = Vec::new();
         writeln!(
@@ -48,10 +54,26 @@ typedef struct {{
             "pthread_attr_t",
             "pthread_mutex_t",
             "pthread_mutexattr_t",
+            "pthread_cond_t",
+            "pthread_condattr_t",
+            "pthread_rwlock_t",
+            "pthread_rwlockattr_t",
+            "pthread_barrier_t",
+            "pthread_barrierattr_t",
+            "sem_t",
             "epoll_event",
             "iovec",
             "clockid_t",
             "rlimit",
+            "rusage",
+            "sched_param",
+            "sigaction",
+            "sigevent",
+            "siginfo_t",
+            "sigval",
+            "timer_t",
+            "itimerspec",
+            "itimerval",
             "aibuf",
         ];
         let allow_vars = [
@@ -65,9 +87,25 @@ typedef struct {{
             "_SC_.*",
             "EPOLL_CTL_.*",
             "EPOLL.*",
+            "FUTEX_.*",
             "RLIMIT_.*",
+            "RUSAGE_.*",
+            "SCHED_.*",
+            "PTHREAD_CANCEL_.*",
+            "PTHREAD_BARRIER_SERIAL_THREAD",
+            "SEM_VALUE_MAX",
+            "SIG.*",
+            "SA_.*",
+            "SI_.*",
+            "_NSIG",
+            "ITIMER_.*",
+            "TIMER_ABSTIME",
             "EAI_.*",
             "MAXADDRS",
+            "PROT_.*",
+            "MAP_.*",
+            "MS_.*",
+            "MADV_.*",
         ];
 
         #[derive(Debug)]
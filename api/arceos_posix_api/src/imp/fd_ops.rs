@@ -1,10 +1,9 @@
-use alloc::sync::Arc;
+use alloc::{sync::Arc, vec::Vec};
 use core::ffi::c_int;
 
 use axerrno::{LinuxError, LinuxResult};
 use axio::PollState;
-use flatten_objects::FlattenObjects;
-use spin::RwLock;
+use axsync::rcu::{rcu_read_lock, RcuCell};
 
 use super::stdio::{stdin, stdout};
 use crate::ctypes;
@@ -21,32 +20,87 @@ pub trait FileLike: Send + Sync {
     fn set_nonblocking(&self, nonblocking: bool) -> LinuxResult;
 }
 
+/// The file descriptor table, indexed by the file descriptors.
+///
+/// It's protected by RCU, so that looking up a file takes no lock. Opening
+/// or closing a file copies the table, which is only as long as the largest
+/// file descriptor in use.
+///
+/// The table is always updated by [`RcuCell::update_sync`], which drops the
+/// old copy before returning. Otherwise old copies would keep the closed
+/// files open until a later grace period, e.g., a pipe would not see its
+/// write end closed.
+#[derive(Clone, Default)]
+struct FdTable(Vec<Option<Arc<dyn FileLike>>>);
+
+impl FdTable {
+    fn get(&self, fd: usize) -> Option<&Arc<dyn FileLike>> {
+        self.0.get(fd)?.as_ref()
+    }
+
+    /// Adds a file at the lowest unused file descriptor.
+    fn add(&mut self, f: Arc<dyn FileLike>) -> Option<usize> {
+        let fd = match self.0.iter().position(Option::is_none) {
+            Some(fd) => fd,
+            None if self.0.len() < AX_FILE_LIMIT => {
+                self.0.push(None);
+                self.0.len() - 1
+            }
+            None => return None,
+        };
+        self.0[fd] = Some(f);
+        Some(fd)
+    }
+
+    /// Adds a file at the given file descriptor, if it's unused.
+    fn add_at(&mut self, fd: usize, f: Arc<dyn FileLike>) -> Option<usize> {
+        if fd >= AX_FILE_LIMIT || self.get(fd).is_some() {
+            return None;
+        }
+        if fd >= self.0.len() {
+            self.0.resize(fd + 1, None);
+        }
+        self.0[fd] = Some(f);
+        Some(fd)
+    }
+
+    fn remove(&mut self, fd: usize) -> Option<Arc<dyn FileLike>> {
+        let f = self.0.get_mut(fd)?.take();
+        while self.0.last().is_some_and(Option::is_none) {
+            self.0.pop();
+        }
+        f
+    }
+}
+
 lazy_static::lazy_static! {
-    static ref FD_TABLE: RwLock<FlattenObjects<Arc<dyn FileLike>, AX_FILE_LIMIT>> = {
-        let mut fd_table = FlattenObjects::new();
+    static ref FD_TABLE: RcuCell<FdTable> = {
+        let mut fd_table = FdTable::default();
         fd_table.add_at(0, Arc::new(stdin()) as _).unwrap(); // stdin
         fd_table.add_at(1, Arc::new(stdout()) as _).unwrap(); // stdout
         fd_table.add_at(2, Arc::new(stdout()) as _).unwrap(); // stderr
-        RwLock::new(fd_table)
+        RcuCell::new(fd_table)
     };
 }
 
 pub fn get_file_like(fd: c_int) -> LinuxResult<Arc<dyn FileLike>> {
+    let guard = rcu_read_lock();
     FD_TABLE
-        .read()
+        .read(&guard)
         .get(fd as usize)
         .cloned()
         .ok_or(LinuxError::EBADF)
 }
 
 pub fn add_file_like(f: Arc<dyn FileLike>) -> LinuxResult<c_int> {
-    Ok(FD_TABLE.write().add(f).ok_or(LinuxError::EMFILE)? as c_int)
+    Ok(FD_TABLE
+        .update_sync(|t| t.add(f))
+        .ok_or(LinuxError::EMFILE)? as c_int)
 }
 
 pub fn close_file_like(fd: c_int) -> LinuxResult {
     let f = FD_TABLE
-        .write()
-        .remove(fd as usize)
+        .update_sync(|t| t.remove(fd as usize))
         .ok_or(LinuxError::EBADF)?;
     drop(f);
     Ok(())
@@ -93,8 +147,7 @@ pub fn sys_dup2(old_fd: c_int, new_fd: c_int) -> c_int {
 
         let f = get_file_like(old_fd)?;
         FD_TABLE
-            .write()
-            .add_at(new_fd as usize, f)
+            .update_sync(|t| t.add_at(new_fd as usize, f))
             .ok_or(LinuxError::EMFILE)?;
 
         Ok(new_fd)
@@ -127,3 +180,76 @@ pub fn sys_fcntl(fd: c_int, cmd: c_int, arg: usize) -> c_int {
         }
     })
 }
+
+#[cfg(all(test, feature = "pipe", feature = "multitask"))]
+mod tests {
+    use alloc::sync::Arc;
+    use core::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Once;
+
+    use super::*;
+    use crate::imp::pipe::Pipe;
+
+    static INIT: Once = Once::new();
+
+    #[test]
+    fn test_close_with_concurrent_reader() {
+        INIT.call_once(axtask::init_scheduler);
+
+        let (read_end, write_end) = Pipe::new();
+        let (read_end, write_end) = (Arc::new(read_end), Arc::new(write_end));
+        let read_fd = add_file_like(read_end.clone()).unwrap();
+        let write_fd = add_file_like(write_end.clone()).unwrap();
+
+        // Another task keeps looking up the files while they are closed.
+        let stop = Arc::new(AtomicBool::new(false));
+        let reader = axtask::spawn({
+            let stop = stop.clone();
+            move || {
+                while !stop.load(Ordering::Acquire) {
+                    let _ = get_file_like(read_fd);
+                    let _ = get_file_like(write_fd);
+                    axtask::yield_now();
+                }
+            }
+        });
+
+        close_file_like(write_fd).unwrap();
+        assert_eq!(get_file_like(write_fd).err(), Some(LinuxError::EBADF));
+        stop.store(true, Ordering::Release);
+        reader.join();
+
+        // The write end is only referenced here after closing, then the
+        // reader sees EOF once it's dropped.
+        assert_eq!(Arc::strong_count(&write_end), 1);
+        drop(write_end);
+        let mut buf = [0; 8];
+        assert_eq!(get_file_like(read_fd).unwrap().read(&mut buf), Ok(0));
+        close_file_like(read_fd).unwrap();
+        assert_eq!(Arc::strong_count(&read_end), 1);
+    }
+
+    #[test]
+    fn test_pipe_eof_after_close() {
+        INIT.call_once(axtask::init_scheduler);
+
+        let mut fds = [0; 2];
+        assert_eq!(crate::sys_pipe(&mut fds), 0);
+        let dup_fd = dup_fd(fds[1]).unwrap();
+        let write_end = get_file_like(fds[1]).unwrap();
+        assert_eq!(write_end.write(b"hello"), Ok(5));
+        drop(write_end);
+
+        // Both file descriptors of the write end must be closed.
+        close_file_like(fds[1]).unwrap();
+        close_file_like(dup_fd).unwrap();
+
+        let read_end = get_file_like(fds[0]).unwrap();
+        let mut buf = [0; 8];
+        assert_eq!(read_end.read(&mut buf), Ok(5));
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(read_end.read(&mut buf), Ok(0));
+        drop(read_end);
+        close_file_like(fds[0]).unwrap();
+    }
+}
@@ -9,14 +9,26 @@ use axsync::Mutex;
 use super::fd_ops::{get_file_like, FileLike};
 use crate::{ctypes, utils::char_ptr_to_str};
 
+/// An open file.
+///
+/// With the `mmap` feature, the pages of the file that are mapped are cached
+/// in a page cache, which is shared by the mappings of the file, and by its
+/// reads and writes, so that they all see the same data. Files opened
+/// separately have separate page caches, as file systems don't identify their
+/// nodes, so their mappings only see the changes made through each other at
+/// `msync()` or `munmap()`, and only in the pages they have not cached yet.
 pub struct File {
     inner: Mutex<axfs::fops::File>,
+    #[cfg(feature = "mmap")]
+    page_cache: axmm::PageCache,
 }
 
 impl File {
     fn new(inner: axfs::fops::File) -> Self {
         Self {
             inner: Mutex::new(inner),
+            #[cfg(feature = "mmap")]
+            page_cache: axmm::PageCache::new(),
         }
     }
 
@@ -24,21 +36,41 @@ impl File {
         super::fd_ops::add_file_like(Arc::new(self))
     }
 
-    fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
+    pub(crate) fn from_fd(fd: c_int) -> LinuxResult<Arc<Self>> {
         let f = super::fd_ops::get_file_like(fd)?;
         f.into_any()
             .downcast::<Self>()
             .map_err(|_| LinuxError::EINVAL)
     }
+
+    /// Whether the file is opened for reading.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn is_readable(&self) -> bool {
+        self.inner.lock().is_readable()
+    }
+
+    /// Whether the file is opened for writing.
+    #[cfg(feature = "mmap")]
+    pub(crate) fn is_writable(&self) -> bool {
+        self.inner.lock().is_writable()
+    }
 }
 
 impl FileLike for File {
     fn read(&self, buf: &mut [u8]) -> LinuxResult<usize> {
-        Ok(self.inner.lock().read(buf)?)
+        let mut file = self.inner.lock();
+        let len = file.read(buf)?;
+        #[cfg(feature = "mmap")]
+        self.read_cached(&mut file, &mut buf[..len])?;
+        Ok(len)
     }
 
     fn write(&self, buf: &[u8]) -> LinuxResult<usize> {
-        Ok(self.inner.lock().write(buf)?)
+        let mut file = self.inner.lock();
+        let len = file.write(buf)?;
+        #[cfg(feature = "mmap")]
+        self.write_cached(&mut file, &buf[..len])?;
+        Ok(len)
     }
 
     fn stat(&self) -> LinuxResult<ctypes::stat> {
@@ -75,6 +107,49 @@ impl FileLike for File {
     }
 }
 
+#[cfg(feature = "mmap")]
+impl File {
+    /// Copies the cached pages over `buf`, which has just been read from
+    /// `file`, as they may have been changed by shared mappings.
+    fn read_cached(&self, file: &mut axfs::fops::File, buf: &mut [u8]) -> LinuxResult {
+        if !self.page_cache.is_empty() {
+            let end = file.seek(SeekFrom::Current(0))?;
+            self.page_cache.read(end - buf.len() as u64, buf);
+        }
+        Ok(())
+    }
+
+    /// Copies `buf`, which has just been written to `file`, into the cached
+    /// pages. It's done before `file` is unlocked, so that the cached pages are
+    /// not written back in between, over the new data.
+    fn write_cached(&self, file: &mut axfs::fops::File, buf: &[u8]) -> LinuxResult {
+        if !self.page_cache.is_empty() {
+            let end = file.seek(SeekFrom::Current(0))?;
+            self.page_cache.write(end - buf.len() as u64, buf);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "mmap")]
+impl axmm::MappedFile for File {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> axerrno::AxResult<usize> {
+        self.inner.lock().read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> axerrno::AxResult<usize> {
+        self.inner.lock().write_at(offset, buf)
+    }
+
+    fn size(&self) -> axerrno::AxResult<u64> {
+        Ok(self.inner.lock().get_attr()?.size())
+    }
+
+    fn page_cache(&self) -> &axmm::PageCache {
+        &self.page_cache
+    }
+}
+
 /// Convert open flags to [`OpenOptions`].
 fn flags_to_options(flags: c_int, _mode: ctypes::mode_t) -> OpenOptions {
     let flags = flags as u32;
@@ -0,0 +1,96 @@
+use core::ffi::{c_int, c_long};
+use core::sync::atomic::AtomicU32;
+use core::time::Duration;
+
+use axerrno::LinuxError;
+use axtask::FutexError;
+
+use crate::ctypes;
+
+fn futex_err(e: FutexError) -> LinuxError {
+    match e {
+        FutexError::WouldBlock => LinuxError::EAGAIN,
+        FutexError::TimedOut => LinuxError::ETIMEDOUT,
+    }
+}
+
+/// Fast user-space locking.
+///
+/// Supports `FUTEX_WAIT` (with a relative timeout), `FUTEX_WAKE`,
+/// `FUTEX_REQUEUE` and `FUTEX_CMP_REQUEUE`. All futexes are private to the
+/// only process, so `FUTEX_PRIVATE_FLAG` is ignored. For the requeue
+/// operations, the `timeout` argument is the maximum number of tasks to
+/// requeue instead, as in Linux.
+pub unsafe fn sys_futex(
+    uaddr: *mut u32,
+    futex_op: c_int,
+    val: u32,
+    timeout: *const ctypes::timespec,
+    uaddr2: *mut u32,
+    val3: u32,
+) -> c_long {
+    debug!(
+        "sys_futex <= {:#x}, {:#x}, {}",
+        uaddr as usize, futex_op, val
+    );
+    syscall_body!(sys_futex, {
+        if uaddr.is_null() || uaddr as usize % 4 != 0 {
+            return Err(LinuxError::EFAULT);
+        }
+        let futex = unsafe { AtomicU32::from_ptr(uaddr) };
+        let target = || {
+            if uaddr2.is_null() || uaddr2 as usize % 4 != 0 {
+                return Err(LinuxError::EFAULT);
+            }
+            Ok(unsafe { AtomicU32::from_ptr(uaddr2) })
+        };
+        let cmd = (futex_op as u32) & !(ctypes::FUTEX_PRIVATE_FLAG | ctypes::FUTEX_CLOCK_REALTIME);
+        match cmd {
+            ctypes::FUTEX_WAIT => {
+                let dur = if timeout.is_null() {
+                    None
+                } else {
+                    let ts = unsafe { *timeout };
+                    if ts.tv_sec < 0 || !(0..1_000_000_000).contains(&ts.tv_nsec) {
+                        return Err(LinuxError::EINVAL);
+                    }
+                    Some(Duration::from(ts))
+                };
+                #[cfg(feature = "irq")]
+                let res = match dur {
+                    Some(dur) => axtask::futex_wait_timeout(futex, val, dur),
+                    None => axtask::futex_wait(futex, val),
+                };
+                #[cfg(not(feature = "irq"))]
+                let res = {
+                    if let Some(dur) = dur {
+                        warn!(
+                            "sys_futex: the timeout {:?} is ignored without the `irq` feature",
+                            dur
+                        );
+                    }
+                    axtask::futex_wait(futex, val)
+                };
+                res.map_err(futex_err)?;
+                Ok(0)
+            }
+            ctypes::FUTEX_WAKE => Ok(axtask::futex_wake(futex, val as usize)),
+            ctypes::FUTEX_REQUEUE | ctypes::FUTEX_CMP_REQUEUE => {
+                let expected = (cmd == ctypes::FUTEX_CMP_REQUEUE).then_some(val3);
+                let requeue_count = timeout as usize as u32;
+                axtask::futex_requeue(
+                    futex,
+                    expected,
+                    val as usize,
+                    target()?,
+                    requeue_count as usize,
+                )
+                .map_err(futex_err)
+            }
+            _ => {
+                warn!("sys_futex: unsupported operation {:#x}", futex_op);
+                Err(LinuxError::ENOSYS)
+            }
+        }
+    })
+}
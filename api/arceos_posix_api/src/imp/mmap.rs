@@ -0,0 +1,249 @@
+//! Memory mappings in the kernel address space.
+//!
+//! Mappings are placed in a dedicated region of the kernel address space,
+//! below the region of task stacks at the top of it. Anonymous mappings are
+//! backed by zeroed frames allocated on demand. File mappings map the pages
+//! in the page cache of the file, which are loaded when the mapping is
+//! created, as page faults never read files (see [`axmm::PageCache`]). Unlike
+//! Linux, which reads them on demand, the mapped part of a file must thus fit
+//! in memory.
+
+use core::ffi::{c_int, c_void};
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::mem::{MemoryAddr, VirtAddr, PAGE_SIZE_4K};
+use axhal::paging::MappingFlags;
+use memory_addr::VirtAddrRange;
+
+use crate::ctypes;
+
+/// End of the mmap region, below the task stack region (4G).
+const MMAP_REGION_END: usize =
+    axconfig::KERNEL_ASPACE_BASE + axconfig::KERNEL_ASPACE_SIZE - (1 << 32);
+/// Size of the mmap region.
+const MMAP_REGION_SIZE: usize = 1 << 36; // 64G
+
+fn mmap_region() -> VirtAddrRange {
+    VirtAddrRange::from_start_size(
+        VirtAddr::from(MMAP_REGION_END - MMAP_REGION_SIZE),
+        MMAP_REGION_SIZE,
+    )
+}
+
+/// Checks that `[addr, addr + len)` is page aligned and in the mmap region,
+/// returns the range with `len` rounded up to pages.
+fn validate_range(addr: *mut c_void, len: ctypes::size_t) -> LinuxResult<(VirtAddr, usize)> {
+    let start = VirtAddr::from(addr as usize);
+    if !start.is_aligned_4k() || len == 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let size = len
+        .checked_next_multiple_of(PAGE_SIZE_4K)
+        .ok_or(LinuxError::ENOMEM)?;
+    let range = VirtAddrRange::try_from_start_size(start, size).ok_or(LinuxError::ENOMEM)?;
+    if !mmap_region().contains_range(range) {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok((start, size))
+}
+
+fn prot_to_flags(prot: c_int) -> LinuxResult<MappingFlags> {
+    let prot = prot as u32;
+    if prot & !(ctypes::PROT_READ | ctypes::PROT_WRITE | ctypes::PROT_EXEC) != 0 {
+        return Err(LinuxError::EINVAL);
+    }
+    let mut flags = MappingFlags::empty();
+    if prot & ctypes::PROT_READ != 0 {
+        flags |= MappingFlags::READ;
+    }
+    if prot & ctypes::PROT_WRITE != 0 {
+        flags |= MappingFlags::WRITE;
+    }
+    if prot & ctypes::PROT_EXEC != 0 {
+        flags |= MappingFlags::EXECUTE;
+    }
+    Ok(flags)
+}
+
+/// Creates a new mapping of `len` bytes, returns its start address.
+///
+/// Anonymous mappings (`MAP_ANONYMOUS`) are zeroed. Otherwise, the file `fd`
+/// is mapped from `off`, and its pages are read now, except the ones beyond
+/// the end of the file, which can't be accessed. The file must be opened for
+/// reading, and for writing as well if the mapping is `MAP_SHARED` and
+/// `PROT_WRITE`, or `EACCES` is returned. If the pages to read don't fit in
+/// half of the free memory, `ENOMEM` is returned.
+///
+/// Changes to `MAP_SHARED` mappings are written back to the file by
+/// [`sys_msync`] and [`sys_munmap`], and `MAP_PRIVATE` mappings are
+/// copy-on-write. `addr` is a hint unless `MAP_FIXED` is given, in which case
+/// existing mappings in the range are removed, and their changes are written
+/// back after the new mapping is created, or fails to be. If the write-back
+/// fails, the new mapping is kept, as it replaces the old one anyway, and the
+/// changes stay in the page
+/// caches of the files, to be written back by the next `msync()` or
+/// `munmap()` of their pages.
+///
+/// Returns the negated error code on failure.
+pub fn sys_mmap(
+    addr: *mut c_void,
+    len: ctypes::size_t,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    off: ctypes::off_t,
+) -> *mut c_void {
+    debug!(
+        "sys_mmap <= {:#x} {:#x} {:#x} {:#x} {} {:#x}",
+        addr as usize, len, prot, flags, fd, off
+    );
+    syscall_body!(sys_mmap, {
+        let map_flags = flags as u32;
+        let shared = match map_flags & ctypes::MAP_TYPE {
+            ctypes::MAP_SHARED | ctypes::MAP_SHARED_VALIDATE => true,
+            ctypes::MAP_PRIVATE => false,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        if len == 0 || off < 0 || off as usize % PAGE_SIZE_4K != 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        let size = len
+            .checked_next_multiple_of(PAGE_SIZE_4K)
+            .ok_or(LinuxError::ENOMEM)?;
+        let mapping_flags = prot_to_flags(prot)?;
+        let fixed = if map_flags & ctypes::MAP_FIXED != 0 {
+            Some(validate_range(addr, size)?.0)
+        } else {
+            None
+        };
+        let file = if map_flags & ctypes::MAP_ANONYMOUS != 0 {
+            None
+        } else {
+            let write = shared && mapping_flags.contains(MappingFlags::WRITE);
+            let file = mapped_file(fd, write)?;
+            // Loaded before locking the address space, as the I/O may block.
+            file.page_cache().load(&*file, off as u64, size)?;
+            Some(file)
+        };
+
+        let mut replaced = axmm::DirtyPages::new();
+        let res = axmm::with_kernel_aspace(|aspace| -> LinuxResult<_> {
+            let start = match fixed {
+                // The replaced mappings are unmapped under the same lock as the
+                // new one is mapped, so that no other mapping takes the range.
+                Some(start) => {
+                    aspace.unmap_dirty(start, size, &mut replaced)?;
+                    start
+                }
+                None => {
+                    let hint = VirtAddr::from(addr as usize).align_down_4k();
+                    aspace
+                        .find_free_area(hint, size, mmap_region())
+                        .or_else(|| aspace.find_free_area(mmap_region().start, size, mmap_region()))
+                        .ok_or(LinuxError::ENOMEM)?
+                }
+            };
+            match file {
+                Some(file) => {
+                    aspace.map_file(start, size, mapping_flags, file, off as u64, shared)?
+                }
+                // Shared anonymous mappings are not shared with any other one.
+                None => aspace.map_alloc(start, size, mapping_flags, false)?,
+            }
+            Ok(start)
+        });
+
+        // The replaced mappings are gone even if the new one fails.
+        if let Err(e) = replaced.write_back() {
+            warn!(
+                "sys_mmap: failed to write back the replaced mappings: {:?}",
+                e
+            );
+        }
+        Ok(res?.as_mut_ptr() as *mut c_void)
+    })
+}
+
+/// Returns the file `fd` to be mapped, which must be opened for reading, and
+/// for writing if `write` is `true`, as Linux does.
+#[cfg(feature = "fs")]
+fn mapped_file(fd: c_int, write: bool) -> LinuxResult<alloc::sync::Arc<dyn axmm::MappedFile>> {
+    let file = super::fs::File::from_fd(fd)?;
+    if !file.is_readable() || (write && !file.is_writable()) {
+        return Err(LinuxError::EACCES);
+    }
+    Ok(file as alloc::sync::Arc<dyn axmm::MappedFile>)
+}
+
+#[cfg(not(feature = "fs"))]
+fn mapped_file(_fd: c_int, _write: bool) -> LinuxResult<alloc::sync::Arc<dyn axmm::MappedFile>> {
+    Err(LinuxError::EBADF)
+}
+
+/// Removes the mappings in `[addr, addr + len)`.
+///
+/// Changes to shared file mappings are written back to the files. They are
+/// taken from the page caches under the same lock as the mappings are
+/// removed, so no write to the mappings is lost, and written after the lock
+/// is released, as the I/O may block. The mappings are removed even if the
+/// write-back fails, in which case `EIO` is returned, and the changes stay in
+/// the page caches, to be written back by the next `msync()` or `munmap()` of
+/// their pages.
+pub fn sys_munmap(addr: *mut c_void, len: ctypes::size_t) -> c_int {
+    debug!("sys_munmap <= {:#x} {:#x}", addr as usize, len);
+    syscall_body!(sys_munmap, {
+        let (start, size) = validate_range(addr, len)?;
+        let mut dirty = axmm::DirtyPages::new();
+        let res = axmm::with_kernel_aspace(|aspace| aspace.unmap_dirty(start, size, &mut dirty));
+        // Written back even if some mappings fail to be removed, as the ones
+        // before them are gone.
+        let written = dirty.write_back();
+        res?;
+        written.map_err(|_| LinuxError::EIO)?;
+        Ok(0)
+    })
+}
+
+/// Changes the access protections of the mappings in `[addr, addr + len)`.
+pub fn sys_mprotect(addr: *mut c_void, len: ctypes::size_t, prot: c_int) -> c_int {
+    debug!(
+        "sys_mprotect <= {:#x} {:#x} {:#x}",
+        addr as usize, len, prot
+    );
+    syscall_body!(sys_mprotect, {
+        let (start, size) = validate_range(addr, len)?;
+        let flags = prot_to_flags(prot)?;
+        axmm::with_kernel_aspace(|aspace| aspace.protect(start, size, flags))?;
+        Ok(0)
+    })
+}
+
+/// Writes the changes to the shared file mappings in `[addr, addr + len)`
+/// back to the files.
+///
+/// `MS_ASYNC` is handled as `MS_SYNC`, and `MS_INVALIDATE` is ignored.
+pub fn sys_msync(addr: *mut c_void, len: ctypes::size_t, flags: c_int) -> c_int {
+    debug!("sys_msync <= {:#x} {:#x} {:#x}", addr as usize, len, flags);
+    syscall_body!(sys_msync, {
+        let flags = flags as u32;
+        if flags & !(ctypes::MS_ASYNC | ctypes::MS_SYNC | ctypes::MS_INVALIDATE) != 0
+            || (flags & ctypes::MS_ASYNC != 0 && flags & ctypes::MS_SYNC != 0)
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        let (start, size) = validate_range(addr, len)?;
+        axmm::sync_kernel_aspace(start, size).map_err(|_| LinuxError::EIO)?;
+        Ok(0)
+    })
+}
+
+/// Gives advice about the use of the mappings in `[addr, addr + len)`.
+///
+/// The advice is accepted but ignored.
+pub fn sys_madvise(addr: *mut c_void, len: ctypes::size_t, advice: c_int) -> c_int {
+    debug!("sys_madvise <= {:#x} {:#x} {}", addr as usize, len, advice);
+    syscall_body!(sys_madvise, {
+        validate_range(addr, len)?;
+        Ok(0)
+    })
+}
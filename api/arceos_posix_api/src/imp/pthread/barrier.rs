@@ -0,0 +1,59 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::LinuxError;
+use axsync::Barrier;
+
+use core::ffi::c_int;
+use core::mem::size_of;
+
+static_assertions::const_assert!(size_of::<Barrier>() <= size_of::<ctypes::pthread_barrier_t>());
+
+/// Initialize a barrier for `count` threads.
+pub unsafe fn sys_pthread_barrier_init(
+    barrier: *mut ctypes::pthread_barrier_t,
+    _attr: *const ctypes::pthread_barrierattr_t,
+    count: u32,
+) -> c_int {
+    debug!(
+        "sys_pthread_barrier_init <= {:#x}, {}",
+        barrier as usize, count
+    );
+    syscall_body!(sys_pthread_barrier_init, {
+        check_null_mut_ptr(barrier)?;
+        if count == 0 {
+            return Err(LinuxError::EINVAL);
+        }
+        unsafe {
+            barrier
+                .cast::<Barrier>()
+                .write(Barrier::new(count as usize))
+        };
+        Ok(0)
+    })
+}
+
+/// Destroy a barrier.
+pub unsafe fn sys_pthread_barrier_destroy(barrier: *mut ctypes::pthread_barrier_t) -> c_int {
+    debug!("sys_pthread_barrier_destroy <= {:#x}", barrier as usize);
+    syscall_body!(sys_pthread_barrier_destroy, {
+        check_null_mut_ptr(barrier)?;
+        unsafe { barrier.cast::<Barrier>().drop_in_place() };
+        Ok(0)
+    })
+}
+
+/// Wait until all threads have reached the barrier.
+///
+/// Returns `PTHREAD_BARRIER_SERIAL_THREAD` for one of the threads, and 0 for
+/// the others.
+pub unsafe fn sys_pthread_barrier_wait(barrier: *mut ctypes::pthread_barrier_t) -> c_int {
+    debug!("sys_pthread_barrier_wait <= {:#x}", barrier as usize);
+    syscall_body!(sys_pthread_barrier_wait, {
+        check_null_mut_ptr(barrier)?;
+        if unsafe { (*barrier.cast::<Barrier>()).wait() }.is_leader() {
+            Ok(ctypes::PTHREAD_BARRIER_SERIAL_THREAD)
+        } else {
+            Ok(0)
+        }
+    })
+}
@@ -0,0 +1,157 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::{LinuxError, LinuxResult};
+use axsync::Condvar;
+
+use core::ffi::c_int;
+use core::mem::size_of;
+
+use super::mutex::PthreadMutex;
+use super::LazyInit;
+
+static_assertions::const_assert!(size_of::<PthreadCond>() <= size_of::<ctypes::pthread_cond_t>());
+
+/// The clock for `pthread_cond_timedwait`, which is 0 (`CLOCK_REALTIME`) for
+/// a condition variable created by `PTHREAD_COND_INITIALIZER`.
+#[repr(C)]
+pub struct PthreadCond {
+    clock: u32,
+    inner: LazyInit<Condvar>,
+}
+
+impl PthreadCond {
+    fn new(clock: u32) -> Self {
+        Self {
+            clock,
+            inner: LazyInit::new(Condvar::new()),
+        }
+    }
+
+    fn wait(&self, mutex: &PthreadMutex) -> LinuxResult {
+        self.inner.get().wait_with(
+            || {
+                let _ = mutex.unlock();
+            },
+            || mutex.lock(),
+        )
+    }
+
+    fn timed_wait(&self, mutex: &PthreadMutex, abstime: &ctypes::timespec) -> LinuxResult {
+        let dur = crate::imp::time::duration_until(self.clock, abstime)?;
+        #[cfg(feature = "irq")]
+        {
+            let (res, timeout) = self.inner.get().wait_timeout_with(
+                dur,
+                || {
+                    let _ = mutex.unlock();
+                },
+                || mutex.lock(),
+            );
+            res?;
+            if timeout.timed_out() {
+                return Err(LinuxError::ETIMEDOUT);
+            }
+            Ok(())
+        }
+        #[cfg(not(feature = "irq"))]
+        {
+            warn!(
+                "pthread_cond_timedwait: the timeout {:?} is ignored without the `irq` feature",
+                dur
+            );
+            self.wait(mutex)
+        }
+    }
+}
+
+/// Initialize a condition variable.
+pub unsafe fn sys_pthread_cond_init(
+    cond: *mut ctypes::pthread_cond_t,
+    attr: *const ctypes::pthread_condattr_t,
+) -> c_int {
+    debug!("sys_pthread_cond_init <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_init, {
+        check_null_mut_ptr(cond)?;
+        let clock = if attr.is_null() {
+            ctypes::CLOCK_REALTIME
+        } else {
+            unsafe { (*attr).__attr & 0x7fff_ffff }
+        };
+        if clock != ctypes::CLOCK_REALTIME && clock != ctypes::CLOCK_MONOTONIC {
+            return Err(LinuxError::EINVAL);
+        }
+        unsafe { cond.cast::<PthreadCond>().write(PthreadCond::new(clock)) };
+        Ok(0)
+    })
+}
+
+/// Destroy a condition variable.
+pub unsafe fn sys_pthread_cond_destroy(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_destroy <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_destroy, {
+        check_null_mut_ptr(cond)?;
+        unsafe { cond.cast::<PthreadCond>().drop_in_place() };
+        Ok(0)
+    })
+}
+
+/// Wait on a condition variable, the mutex is released during the wait.
+pub unsafe fn sys_pthread_cond_wait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+) -> c_int {
+    debug!(
+        "sys_pthread_cond_wait <= {:#x}, {:#x}",
+        cond as usize, mutex as usize
+    );
+    syscall_body!(sys_pthread_cond_wait, {
+        check_null_mut_ptr(cond)?;
+        check_null_mut_ptr(mutex)?;
+        unsafe { (*cond.cast::<PthreadCond>()).wait(&*mutex.cast::<PthreadMutex>())? };
+        Ok(0)
+    })
+}
+
+/// Wait on a condition variable until the absolute time `abstime` of the
+/// clock of the condition variable.
+pub unsafe fn sys_pthread_cond_timedwait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    debug!(
+        "sys_pthread_cond_timedwait <= {:#x}, {:#x}",
+        cond as usize, mutex as usize
+    );
+    syscall_body!(sys_pthread_cond_timedwait, {
+        check_null_mut_ptr(cond)?;
+        check_null_mut_ptr(mutex)?;
+        if abstime.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        unsafe {
+            (*cond.cast::<PthreadCond>()).timed_wait(&*mutex.cast::<PthreadMutex>(), &*abstime)?
+        };
+        Ok(0)
+    })
+}
+
+/// Wake up one thread waiting on the condition variable.
+pub unsafe fn sys_pthread_cond_signal(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_signal <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_signal, {
+        check_null_mut_ptr(cond)?;
+        unsafe { (*cond.cast::<PthreadCond>()).inner.get().notify_one() };
+        Ok(0)
+    })
+}
+
+/// Wake up all threads waiting on the condition variable.
+pub unsafe fn sys_pthread_cond_broadcast(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    debug!("sys_pthread_cond_broadcast <= {:#x}", cond as usize);
+    syscall_body!(sys_pthread_cond_broadcast, {
+        check_null_mut_ptr(cond)?;
+        unsafe { (*cond.cast::<PthreadCond>()).inner.get().notify_all() };
+        Ok(0)
+    })
+}
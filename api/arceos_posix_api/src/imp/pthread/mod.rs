@@ -1,14 +1,22 @@
 use alloc::{boxed::Box, collections::BTreeMap, sync::Arc};
 use core::cell::UnsafeCell;
 use core::ffi::{c_int, c_void};
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicI32, AtomicU8, Ordering};
 
 use axerrno::{LinuxError, LinuxResult};
-use axtask::AxTaskRef;
+use axtask::{AxTaskRef, SchedPolicy};
 use spin::RwLock;
 
 use crate::ctypes;
 
+pub mod barrier;
+pub mod cond;
 pub mod mutex;
+pub mod rwlock;
+
+/// The return value of cancelled threads, i.e., `PTHREAD_CANCELED`.
+const PTHREAD_CANCELED: *mut c_void = usize::MAX as *mut c_void;
 
 lazy_static::lazy_static! {
     static ref TID_TO_PTHREAD: RwLock<BTreeMap<u64, ForceSendSync<ctypes::pthread_t>>> = {
@@ -20,6 +28,7 @@ lazy_static::lazy_static! {
             retval: Arc::new(Packet {
                 result: UnsafeCell::new(core::ptr::null_mut()),
             }),
+            cancel_type: AtomicI32::new(ctypes::PTHREAD_CANCEL_DEFERRED as _),
         };
         let ptr = Box::into_raw(Box::new(main_thread)) as *mut c_void;
         map.insert(main_tid, ForceSendSync(ptr));
@@ -37,6 +46,9 @@ unsafe impl<T> Sync for Packet<T> {}
 pub struct Pthread {
     inner: AxTaskRef,
     retval: Arc<Packet<*mut c_void>>,
+    /// Only the deferred cancellation is supported, the asynchronous type is
+    /// recorded but acts the same.
+    cancel_type: AtomicI32,
 }
 
 impl Pthread {
@@ -64,6 +76,7 @@ impl Pthread {
         let thread = Pthread {
             inner: task_inner,
             retval: my_packet,
+            cancel_type: AtomicI32::new(ctypes::PTHREAD_CANCEL_DEFERRED as _),
         };
         let ptr = Box::into_raw(Box::new(thread)) as *mut c_void;
         TID_TO_PTHREAD.write().insert(tid, ForceSendSync(ptr));
@@ -94,9 +107,13 @@ impl Pthread {
         }
 
         let thread = unsafe { Box::from_raw(ptr as *mut Pthread) };
-        thread.inner.join();
+        let exit_code = thread.inner.join();
         let tid = thread.inner.id().as_u64();
-        let retval = unsafe { *thread.retval.result.get() };
+        let retval = if exit_code == Some(axtask::CANCELED_EXIT_CODE) {
+            PTHREAD_CANCELED
+        } else {
+            unsafe { *thread.retval.result.get() }
+        };
         TID_TO_PTHREAD.write().remove(&tid);
         drop(thread);
         Ok(retval)
@@ -147,6 +164,203 @@ pub unsafe fn sys_pthread_join(thread: ctypes::pthread_t, retval: *mut *mut c_vo
     })
 }
 
+/// Requests the cancellation of the given thread.
+///
+/// The thread exits when it reaches a cancellation point (e.g.,
+/// `pthread_testcancel` or `nanosleep`) with cancellation enabled, and the
+/// joiner gets `PTHREAD_CANCELED`.
+pub unsafe fn sys_pthread_cancel(thread: ctypes::pthread_t) -> c_int {
+    debug!("sys_pthread_cancel <= {:#x}", thread as usize);
+    syscall_body!(sys_pthread_cancel, {
+        if thread.is_null() {
+            return Err(LinuxError::EINVAL);
+        }
+        let thread = unsafe { &*(thread as *const Pthread) };
+        if axtask::cancel_task(&thread.inner) {
+            Ok(0)
+        } else {
+            Err(LinuxError::ESRCH)
+        }
+    })
+}
+
+/// Enables or disables the cancellation of the current thread, and stores the
+/// previous state in `oldstate` if it's not null.
+pub unsafe fn sys_pthread_setcancelstate(state: c_int, oldstate: *mut c_int) -> c_int {
+    debug!("sys_pthread_setcancelstate <= {}", state);
+    syscall_body!(sys_pthread_setcancelstate, {
+        let enabled = match state as u32 {
+            ctypes::PTHREAD_CANCEL_ENABLE => true,
+            ctypes::PTHREAD_CANCEL_DISABLE => false,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let old = if axtask::set_current_cancelable(enabled) {
+            ctypes::PTHREAD_CANCEL_ENABLE
+        } else {
+            ctypes::PTHREAD_CANCEL_DISABLE
+        };
+        if !oldstate.is_null() {
+            unsafe { *oldstate = old as c_int };
+        }
+        Ok(0)
+    })
+}
+
+/// Sets the cancellation type of the current thread, and stores the previous
+/// type in `oldtype` if it's not null.
+///
+/// Asynchronous cancellation is not supported, the cancellation is always
+/// deferred to the next cancellation point.
+pub unsafe fn sys_pthread_setcanceltype(ty: c_int, oldtype: *mut c_int) -> c_int {
+    debug!("sys_pthread_setcanceltype <= {}", ty);
+    syscall_body!(sys_pthread_setcanceltype, {
+        match ty as u32 {
+            ctypes::PTHREAD_CANCEL_DEFERRED | ctypes::PTHREAD_CANCEL_ASYNCHRONOUS => {}
+            _ => return Err(LinuxError::EINVAL),
+        }
+        let thread = Pthread::current().ok_or(LinuxError::ESRCH)?;
+        let old = thread.cancel_type.swap(ty, Ordering::Relaxed);
+        if !oldtype.is_null() {
+            unsafe { *oldtype = old };
+        }
+        Ok(0)
+    })
+}
+
+/// A cancellation point: exits the current thread if its cancellation has been
+/// requested and is enabled.
+pub fn sys_pthread_testcancel() {
+    axtask::test_cancel();
+}
+
+/// Registers `routine` to be called with `arg` when the current thread exits or
+/// is cancelled, unless it's removed by [`sys_pthread_cleanup_pop`] before.
+pub fn sys_pthread_cleanup_push(routine: extern "C" fn(arg: *mut c_void), arg: *mut c_void) {
+    let arg = ForceSendSync(arg);
+    axtask::push_cleanup_handler(move || {
+        let arg = arg;
+        routine(arg.0)
+    });
+}
+
+/// Removes the most recently registered cleanup routine of the current thread,
+/// and calls it if `execute` is non-zero.
+pub fn sys_pthread_cleanup_pop(execute: c_int) {
+    axtask::pop_cleanup_handler(execute != 0);
+}
+
+/// Sets the scheduling policy and priority of the given thread.
+///
+/// The new policy takes effect immediately, a ready thread is re-queued with
+/// it.
+pub unsafe fn sys_pthread_setschedparam(
+    thread: ctypes::pthread_t,
+    policy: c_int,
+    param: *const ctypes::sched_param,
+) -> c_int {
+    debug!(
+        "sys_pthread_setschedparam <= {:#x} {}",
+        thread as usize, policy
+    );
+    syscall_body!(sys_pthread_setschedparam, {
+        if thread.is_null() || param.is_null() {
+            return Err(LinuxError::EINVAL);
+        }
+        let policy = match policy as u32 {
+            ctypes::SCHED_OTHER => SchedPolicy::Normal,
+            ctypes::SCHED_FIFO => SchedPolicy::Fifo,
+            ctypes::SCHED_RR => SchedPolicy::RoundRobin,
+            _ => return Err(LinuxError::EINVAL),
+        };
+        let prio = unsafe { (*param).sched_priority } as isize;
+        let thread = unsafe { &*(thread as *const Pthread) };
+        if axtask::set_task_sched_policy(&thread.inner, policy, prio) {
+            Ok(0)
+        } else {
+            Err(LinuxError::EINVAL)
+        }
+    })
+}
+
+/// Gets the scheduling policy and priority of the given thread.
+pub unsafe fn sys_pthread_getschedparam(
+    thread: ctypes::pthread_t,
+    policy: *mut c_int,
+    param: *mut ctypes::sched_param,
+) -> c_int {
+    debug!("sys_pthread_getschedparam <= {:#x}", thread as usize);
+    syscall_body!(sys_pthread_getschedparam, {
+        if thread.is_null() || policy.is_null() || param.is_null() {
+            return Err(LinuxError::EINVAL);
+        }
+        let thread = unsafe { &*(thread as *const Pthread) };
+        let sched_policy = match thread.inner.sched_policy() {
+            SchedPolicy::Normal => ctypes::SCHED_OTHER,
+            SchedPolicy::Fifo => ctypes::SCHED_FIFO,
+            SchedPolicy::RoundRobin => ctypes::SCHED_RR,
+            SchedPolicy::Deadline => ctypes::SCHED_DEADLINE,
+        };
+        unsafe {
+            *policy = sched_policy as c_int;
+            (*param).sched_priority = thread.inner.sched_priority() as c_int;
+        }
+        Ok(0)
+    })
+}
+
+/// A value that is initialized on its first use, so that the static
+/// initializers of C (e.g., `PTHREAD_COND_INITIALIZER`), which are all zeros,
+/// create valid primitives.
+#[repr(C)]
+struct LazyInit<T> {
+    state: AtomicU8,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> LazyInit<T> {
+    const UNINIT: u8 = 0;
+    const INITIALIZING: u8 = 1;
+    const READY: u8 = 2;
+
+    fn new(value: T) -> Self {
+        Self {
+            state: AtomicU8::new(Self::READY),
+            value: UnsafeCell::new(MaybeUninit::new(value)),
+        }
+    }
+
+    fn get(&self) -> &T
+    where
+        T: Default,
+    {
+        loop {
+            match self.state.compare_exchange(
+                Self::UNINIT,
+                Self::INITIALIZING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    unsafe { (*self.value.get()).write(T::default()) };
+                    self.state.store(Self::READY, Ordering::Release);
+                    break;
+                }
+                Err(Self::READY) => break,
+                Err(_) => core::hint::spin_loop(),
+            }
+        }
+        unsafe { (*self.value.get()).assume_init_ref() }
+    }
+}
+
+impl<T> Drop for LazyInit<T> {
+    fn drop(&mut self) {
+        if *self.state.get_mut() == Self::READY {
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
+
 #[derive(Clone, Copy)]
 struct ForceSendSync<T>(T);
 
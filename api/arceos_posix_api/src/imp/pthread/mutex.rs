@@ -1,45 +1,83 @@
 use crate::{ctypes, utils::check_null_mut_ptr};
 
-use axerrno::LinuxResult;
-use axsync::Mutex;
+use axerrno::{LinuxError, LinuxResult};
+use axsync::{Mutex, PiMutex};
 
 use core::ffi::c_int;
 use core::mem::{size_of, ManuallyDrop};
 
+use super::LazyInit;
+
 static_assertions::const_assert_eq!(
     size_of::<ctypes::pthread_mutex_t>(),
     size_of::<PthreadMutex>()
 );
 
+/// The bit in `pthread_mutexattr_t` that indicates the `PTHREAD_PRIO_INHERIT`
+/// protocol, same as musl.
+const MUTEXATTR_PRIO_INHERIT: u32 = 8;
+
+/// The tag is placed first, so that a zeroed tag created by
+/// `PTHREAD_MUTEX_INITIALIZER` means a normal mutex, which is initialized on
+/// the first use.
 #[repr(C)]
-pub struct PthreadMutex(Mutex<()>);
+pub(super) enum PthreadMutex {
+    Normal(LazyInit<Mutex<()>>),
+    Inherit(LazyInit<PiMutex<()>>),
+}
 
 impl PthreadMutex {
-    const fn new() -> Self {
-        Self(Mutex::new(()))
+    fn new() -> Self {
+        Self::Normal(LazyInit::new(Mutex::new(())))
     }
 
-    fn lock(&self) -> LinuxResult {
-        let _guard = ManuallyDrop::new(self.0.lock());
+    fn new_inherit() -> Self {
+        Self::Inherit(LazyInit::new(PiMutex::new(())))
+    }
+
+    pub(super) fn lock(&self) -> LinuxResult {
+        match self {
+            Self::Normal(m) => {
+                let _guard = ManuallyDrop::new(m.get().lock());
+            }
+            Self::Inherit(m) => {
+                let _guard = ManuallyDrop::new(m.get().lock());
+            }
+        }
         Ok(())
     }
 
-    fn unlock(&self) -> LinuxResult {
-        unsafe { self.0.force_unlock() };
+    pub(super) fn unlock(&self) -> LinuxResult {
+        match self {
+            Self::Normal(m) => unsafe { m.get().force_unlock() },
+            Self::Inherit(m) => unsafe { m.get().force_unlock() },
+        }
         Ok(())
     }
 }
 
 /// Initialize a mutex.
+///
+/// Returns `ENOTSUP` (`EOPNOTSUPP`) for the `PTHREAD_PRIO_INHERIT` protocol if the scheduler
+/// has no priorities to inherit (see [`axtask::pi_supported`]).
 pub fn sys_pthread_mutex_init(
     mutex: *mut ctypes::pthread_mutex_t,
-    _attr: *const ctypes::pthread_mutexattr_t,
+    attr: *const ctypes::pthread_mutexattr_t,
 ) -> c_int {
     debug!("sys_pthread_mutex_init <= {:#x}", mutex as usize);
     syscall_body!(sys_pthread_mutex_init, {
         check_null_mut_ptr(mutex)?;
+        let inherit = !attr.is_null() && unsafe { (*attr).__attr } & MUTEXATTR_PRIO_INHERIT != 0;
+        let new_mutex = if inherit {
+            if !axtask::pi_supported() {
+                return Err(LinuxError::EOPNOTSUPP);
+            }
+            PthreadMutex::new_inherit()
+        } else {
+            PthreadMutex::new()
+        };
         unsafe {
-            mutex.cast::<PthreadMutex>().write(PthreadMutex::new());
+            mutex.cast::<PthreadMutex>().write(new_mutex);
         }
         Ok(0)
     })
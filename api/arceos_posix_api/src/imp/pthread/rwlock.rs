@@ -0,0 +1,111 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::{LinuxError, LinuxResult};
+use axsync::RawRwLock;
+
+use core::ffi::c_int;
+use core::mem::size_of;
+
+use super::LazyInit;
+
+static_assertions::const_assert!(
+    size_of::<PthreadRwLock>() <= size_of::<ctypes::pthread_rwlock_t>()
+);
+
+/// A reader-writer lock with writer preference. It's initialized on the first
+/// use if it's created by `PTHREAD_RWLOCK_INITIALIZER`.
+type PthreadRwLock = LazyInit<RawRwLock>;
+
+fn unlock(lock: &RawRwLock) -> LinuxResult {
+    if lock.is_write_locked() {
+        unsafe { lock.write_unlock() };
+    } else if lock.is_locked() {
+        unsafe { lock.read_unlock() };
+    } else {
+        return Err(LinuxError::EPERM);
+    }
+    Ok(())
+}
+
+/// Initialize a reader-writer lock.
+pub unsafe fn sys_pthread_rwlock_init(
+    rwlock: *mut ctypes::pthread_rwlock_t,
+    _attr: *const ctypes::pthread_rwlockattr_t,
+) -> c_int {
+    debug!("sys_pthread_rwlock_init <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_init, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe {
+            rwlock
+                .cast::<PthreadRwLock>()
+                .write(LazyInit::new(RawRwLock::new()))
+        };
+        Ok(0)
+    })
+}
+
+/// Destroy a reader-writer lock.
+pub unsafe fn sys_pthread_rwlock_destroy(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_destroy <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_destroy, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { rwlock.cast::<PthreadRwLock>().drop_in_place() };
+        Ok(0)
+    })
+}
+
+/// Acquire a read lock, blocking until it's available.
+pub unsafe fn sys_pthread_rwlock_rdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_rdlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_rdlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { (*rwlock.cast::<PthreadRwLock>()).get().read() };
+        Ok(0)
+    })
+}
+
+/// Try to acquire a read lock, returns `EBUSY` if it's not available.
+pub unsafe fn sys_pthread_rwlock_tryrdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_tryrdlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_tryrdlock, {
+        check_null_mut_ptr(rwlock)?;
+        if unsafe { (*rwlock.cast::<PthreadRwLock>()).get().try_read() } {
+            Ok(0)
+        } else {
+            Err(LinuxError::EBUSY)
+        }
+    })
+}
+
+/// Acquire the write lock, blocking until it's available.
+pub unsafe fn sys_pthread_rwlock_wrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_wrlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_wrlock, {
+        check_null_mut_ptr(rwlock)?;
+        unsafe { (*rwlock.cast::<PthreadRwLock>()).get().write() };
+        Ok(0)
+    })
+}
+
+/// Try to acquire the write lock, returns `EBUSY` if it's not available.
+pub unsafe fn sys_pthread_rwlock_trywrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_trywrlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_trywrlock, {
+        check_null_mut_ptr(rwlock)?;
+        if unsafe { (*rwlock.cast::<PthreadRwLock>()).get().try_write() } {
+            Ok(0)
+        } else {
+            Err(LinuxError::EBUSY)
+        }
+    })
+}
+
+/// Release the read or write lock held by the current thread.
+pub unsafe fn sys_pthread_rwlock_unlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    debug!("sys_pthread_rwlock_unlock <= {:#x}", rwlock as usize);
+    syscall_body!(sys_pthread_rwlock_unlock, {
+        check_null_mut_ptr(rwlock)?;
+        unlock(unsafe { (*rwlock.cast::<PthreadRwLock>()).get() })?;
+        Ok(0)
+    })
+}
@@ -49,3 +49,51 @@ pub unsafe fn sys_setrlimit(resource: c_int, rlimits: *mut crate::ctypes::rlimit
         Ok(0)
     })
 }
+
+/// Get resource usage
+///
+/// Only the CPU time and context switch counts are reported. ArceOS runs
+/// everything in kernel mode, so the CPU time is accounted as user time.
+pub unsafe fn sys_getrusage(who: c_int, usage: *mut ctypes::rusage) -> c_int {
+    debug!("sys_getrusage <= {} {:#x}", who, usage as usize);
+    syscall_body!(sys_getrusage, {
+        if usage.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        const RUSAGE_SELF: c_int = ctypes::RUSAGE_SELF as _;
+        const RUSAGE_THREAD: c_int = ctypes::RUSAGE_THREAD as _;
+        const RUSAGE_CHILDREN: c_int = ctypes::RUSAGE_CHILDREN as _;
+
+        let mut ru = ctypes::rusage::default();
+        match who {
+            RUSAGE_SELF => {
+                #[cfg(feature = "multitask")]
+                {
+                    ru.ru_utime = axtask::busy_cpu_time().into();
+                }
+                #[cfg(not(feature = "multitask"))]
+                {
+                    ru.ru_utime = axhal::time::monotonic_time().into();
+                }
+            }
+            RUSAGE_THREAD => {
+                #[cfg(feature = "multitask")]
+                {
+                    let stats = axtask::current().stats();
+                    ru.ru_utime = stats.cpu_time.into();
+                    ru.ru_nvcsw = stats.nr_voluntary_switches as _;
+                    ru.ru_nivcsw = stats.nr_involuntary_switches as _;
+                }
+                #[cfg(not(feature = "multitask"))]
+                {
+                    ru.ru_utime = axhal::time::monotonic_time().into();
+                }
+            }
+            // There are no child processes.
+            RUSAGE_CHILDREN => {}
+            _ => return Err(LinuxError::EINVAL),
+        }
+        unsafe { *usage = ru };
+        Ok(0)
+    })
+}
@@ -0,0 +1,120 @@
+use crate::{ctypes, utils::check_null_mut_ptr};
+
+use axerrno::{LinuxError, LinuxResult};
+use axsync::Semaphore;
+
+use core::ffi::{c_int, c_uint};
+use core::mem::size_of;
+
+static_assertions::const_assert!(size_of::<Semaphore>() <= size_of::<ctypes::sem_t>());
+
+fn semaphore<'a>(sem: *mut ctypes::sem_t) -> LinuxResult<&'a Semaphore> {
+    check_null_mut_ptr(sem)?;
+    Ok(unsafe { &*sem.cast::<Semaphore>() })
+}
+
+/// Initialize an unnamed semaphore with the given value.
+///
+/// Semaphores shared between processes are not distinguished, as there is
+/// only one process.
+pub unsafe fn sys_sem_init(sem: *mut ctypes::sem_t, _pshared: c_int, value: c_uint) -> c_int {
+    debug!("sys_sem_init <= {:#x}, {}", sem as usize, value);
+    syscall_body!(sys_sem_init, {
+        check_null_mut_ptr(sem)?;
+        if value > ctypes::SEM_VALUE_MAX {
+            return Err(LinuxError::EINVAL);
+        }
+        unsafe {
+            sem.cast::<Semaphore>()
+                .write(Semaphore::new(value as usize))
+        };
+        Ok(0)
+    })
+}
+
+/// Destroy an unnamed semaphore.
+pub unsafe fn sys_sem_destroy(sem: *mut ctypes::sem_t) -> c_int {
+    debug!("sys_sem_destroy <= {:#x}", sem as usize);
+    syscall_body!(sys_sem_destroy, {
+        check_null_mut_ptr(sem)?;
+        unsafe { sem.cast::<Semaphore>().drop_in_place() };
+        Ok(0)
+    })
+}
+
+/// Decrement the semaphore, blocking until its value is greater than zero.
+pub unsafe fn sys_sem_wait(sem: *mut ctypes::sem_t) -> c_int {
+    debug!("sys_sem_wait <= {:#x}", sem as usize);
+    syscall_body!(sys_sem_wait, {
+        semaphore(sem)?.acquire();
+        Ok(0)
+    })
+}
+
+/// Decrement the semaphore, returns `EAGAIN` if its value is zero.
+pub unsafe fn sys_sem_trywait(sem: *mut ctypes::sem_t) -> c_int {
+    debug!("sys_sem_trywait <= {:#x}", sem as usize);
+    syscall_body!(sys_sem_trywait, {
+        if semaphore(sem)?.try_acquire() {
+            Ok(0)
+        } else {
+            Err(LinuxError::EAGAIN)
+        }
+    })
+}
+
+/// Decrement the semaphore, blocking until its value is greater than zero or
+/// the absolute time `abstime` of `CLOCK_REALTIME` is reached.
+pub unsafe fn sys_sem_timedwait(
+    sem: *mut ctypes::sem_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    debug!("sys_sem_timedwait <= {:#x}", sem as usize);
+    syscall_body!(sys_sem_timedwait, {
+        let sem = semaphore(sem)?;
+        if sem.try_acquire() {
+            return Ok(0);
+        }
+        if abstime.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let dur = crate::imp::time::duration_until(ctypes::CLOCK_REALTIME, unsafe { &*abstime })?;
+        #[cfg(feature = "irq")]
+        if !sem.acquire_timeout(dur) {
+            return Err(LinuxError::ETIMEDOUT);
+        }
+        #[cfg(not(feature = "irq"))]
+        {
+            warn!(
+                "sem_timedwait: the timeout {:?} is ignored without the `irq` feature",
+                dur
+            );
+            sem.acquire();
+        }
+        Ok(0)
+    })
+}
+
+/// Increment the semaphore, and wake up a thread waiting on it.
+pub unsafe fn sys_sem_post(sem: *mut ctypes::sem_t) -> c_int {
+    debug!("sys_sem_post <= {:#x}", sem as usize);
+    syscall_body!(sys_sem_post, {
+        let sem = semaphore(sem)?;
+        if sem.available_permits() >= ctypes::SEM_VALUE_MAX as usize {
+            return Err(LinuxError::EOVERFLOW);
+        }
+        sem.release();
+        Ok(0)
+    })
+}
+
+/// Get the value of the semaphore.
+pub unsafe fn sys_sem_getvalue(sem: *mut ctypes::sem_t, sval: *mut c_int) -> c_int {
+    debug!("sys_sem_getvalue <= {:#x}", sem as usize);
+    syscall_body!(sys_sem_getvalue, {
+        let sem = semaphore(sem)?;
+        check_null_mut_ptr(sval)?;
+        unsafe { *sval = sem.available_permits() as c_int };
+        Ok(0)
+    })
+}
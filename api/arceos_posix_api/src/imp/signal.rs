@@ -0,0 +1,70 @@
+use core::ffi::c_int;
+
+use axerrno::LinuxError;
+use spin::Mutex;
+
+use crate::ctypes;
+
+const NSIG: usize = ctypes::_NSIG as usize;
+
+/// Actions of all signals, a zeroed action means `SIG_DFL`.
+static SIGACTIONS: Mutex<[ctypes::sigaction; NSIG]> = Mutex::new(unsafe { core::mem::zeroed() });
+
+/// Examine and change the action of a signal.
+pub unsafe fn sys_sigaction(
+    signum: c_int,
+    act: *const ctypes::sigaction,
+    oldact: *mut ctypes::sigaction,
+) -> c_int {
+    debug!("sys_sigaction <= {}", signum);
+    syscall_body!(sys_sigaction, {
+        if signum <= 0
+            || signum as usize >= NSIG
+            || signum as u32 == ctypes::SIGKILL
+            || signum as u32 == ctypes::SIGSTOP
+        {
+            return Err(LinuxError::EINVAL);
+        }
+        let mut actions = SIGACTIONS.lock();
+        if !oldact.is_null() {
+            unsafe { *oldact = actions[signum as usize] };
+        }
+        if !act.is_null() {
+            actions[signum as usize] = unsafe { *act };
+        }
+        Ok(0)
+    })
+}
+
+/// Handles the signal `info.si_signo` in the current thread.
+///
+/// Signal masks and interrupting other threads are not supported yet, so
+/// signals generated asynchronously (e.g., by timers) are handled by the
+/// thread that generates them.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub fn handle_signal(mut info: ctypes::siginfo_t) {
+    let signum = info.si_signo;
+    let action = SIGACTIONS.lock()[signum as usize];
+    let handler = unsafe { action.__sa_handler.sa_handler }.map_or(0, |f| f as usize);
+    match handler {
+        // SIG_DFL
+        0 => match signum as u32 {
+            ctypes::SIGCHLD | ctypes::SIGCONT | ctypes::SIGURG | ctypes::SIGWINCH => {}
+            _ => {
+                warn!("terminated by signal {}", signum);
+                axhal::misc::terminate();
+            }
+        },
+        // SIG_IGN
+        1 => {}
+        _ => unsafe {
+            if action.sa_flags as u32 & ctypes::SA_SIGINFO != 0 {
+                let sa_sigaction = action.__sa_handler.sa_sigaction.unwrap();
+                sa_sigaction(signum, &mut info, core::ptr::null_mut());
+            } else {
+                let sa_handler = action.__sa_handler.sa_handler.unwrap();
+                sa_handler(signum);
+            }
+        },
+    }
+}
@@ -1,9 +1,11 @@
-use axerrno::LinuxError;
+use axerrno::{LinuxError, LinuxResult};
 use core::ffi::{c_int, c_long};
 use core::time::Duration;
 
 use crate::ctypes;
-use crate::ctypes::{CLOCK_MONOTONIC, CLOCK_REALTIME};
+use crate::ctypes::{
+    CLOCK_MONOTONIC, CLOCK_PROCESS_CPUTIME_ID, CLOCK_REALTIME, CLOCK_THREAD_CPUTIME_ID,
+};
 
 impl From<ctypes::timespec> for Duration {
     fn from(ts: ctypes::timespec) -> Self {
@@ -44,6 +46,8 @@ pub unsafe fn sys_clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::timespe
         let now = match clk as u32 {
             CLOCK_REALTIME => axhal::time::wall_time().into(),
             CLOCK_MONOTONIC => axhal::time::monotonic_time().into(),
+            CLOCK_PROCESS_CPUTIME_ID => process_cpu_time().into(),
+            CLOCK_THREAD_CPUTIME_ID => thread_cpu_time().into(),
             _ => {
                 warn!("Called sys_clock_gettime for unsupported clock {}", clk);
                 return Err(LinuxError::EINVAL);
@@ -55,6 +59,27 @@ pub unsafe fn sys_clock_gettime(clk: ctypes::clockid_t, ts: *mut ctypes::timespe
     })
 }
 
+/// Returns the CPU time consumed by all threads.
+fn process_cpu_time() -> Duration {
+    #[cfg(feature = "multitask")]
+    {
+        axtask::busy_cpu_time()
+    }
+    // The only thread is always running.
+    #[cfg(not(feature = "multitask"))]
+    axhal::time::monotonic_time()
+}
+
+/// Returns the CPU time consumed by the current thread.
+fn thread_cpu_time() -> Duration {
+    #[cfg(feature = "multitask")]
+    {
+        axtask::current().stats().cpu_time
+    }
+    #[cfg(not(feature = "multitask"))]
+    axhal::time::monotonic_time()
+}
+
 /// Sleep some nanoseconds
 ///
 /// TODO: should be woken by signals, and set errno
@@ -73,8 +98,11 @@ pub unsafe fn sys_nanosleep(req: *const ctypes::timespec, rem: *mut ctypes::time
 
         let now = axhal::time::monotonic_time();
 
+        // A cancellation point.
         #[cfg(feature = "multitask")]
-        axtask::sleep(dur);
+        if axtask::sleep_cancellable(dur).is_err() {
+            axtask::test_cancel();
+        }
         #[cfg(not(feature = "multitask"))]
         axhal::time::busy_wait(dur);
 
@@ -90,3 +118,17 @@ pub unsafe fn sys_nanosleep(req: *const ctypes::timespec, rem: *mut ctypes::time
         Ok(0)
     })
 }
+
+/// Returns the duration from now until the absolute time `abstime` of the
+/// clock `clk`, or zero if it has passed. Used by the timed waits.
+pub(crate) fn duration_until(clk: u32, abstime: &ctypes::timespec) -> LinuxResult<Duration> {
+    if abstime.tv_sec < 0 || abstime.tv_nsec < 0 || abstime.tv_nsec > 999999999 {
+        return Err(LinuxError::EINVAL);
+    }
+    let now = match clk {
+        CLOCK_REALTIME => axhal::time::wall_time(),
+        CLOCK_MONOTONIC => axhal::time::monotonic_time(),
+        _ => return Err(LinuxError::EINVAL),
+    };
+    Ok(Duration::from(*abstime).saturating_sub(now))
+}
@@ -0,0 +1,445 @@
+use alloc::{collections::BTreeMap, sync::Arc, vec::Vec};
+use core::ffi::{c_int, c_uint, c_void};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axerrno::{LinuxError, LinuxResult};
+use axhal::time::{monotonic_time, wall_time};
+use axtask::{Timer, WaitQueue};
+use spin::Mutex;
+
+use crate::ctypes;
+use crate::imp::signal::handle_signal;
+
+static TIMERS: Mutex<BTreeMap<usize, PosixTimer>> = Mutex::new(BTreeMap::new());
+static NEXT_TIMER_ID: AtomicUsize = AtomicUsize::new(1);
+
+/// The timer used by `setitimer(ITIMER_REAL)` and `alarm`.
+static REAL_TIMER: Mutex<Option<PosixTimer>> = Mutex::new(None);
+
+/// The thread that notifies the expirations of all timers, see
+/// [`notify_expirations`].
+static NOTIFIER: spin::Once<()> = spin::Once::new();
+static NOTIFIER_WQ: WaitQueue = WaitQueue::new();
+/// Whether some timers may have expirations to notify.
+static HAS_EXPIRED: AtomicBool = AtomicBool::new(false);
+
+/// How to notify the expirations of a timer.
+#[derive(Clone, Copy)]
+enum Notify {
+    None,
+    Signal {
+        signo: c_int,
+        value: ctypes::sigval,
+    },
+    Thread {
+        function: unsafe extern "C" fn(ctypes::sigval),
+        value: ctypes::sigval,
+    },
+}
+
+// The user data in `sigval` is only passed back to the user.
+unsafe impl Send for Notify {}
+unsafe impl Sync for Notify {}
+
+/// States shared by a timer and the notifier thread.
+struct TimerShared {
+    id: usize,
+    notify: Notify,
+    /// Expirations that have not been notified.
+    pending: AtomicU32,
+    /// Extra expirations before the last notification.
+    overrun: AtomicI32,
+}
+
+impl TimerShared {
+    fn new(id: usize, notify: Notify) -> Self {
+        Self {
+            id,
+            notify,
+            pending: AtomicU32::new(0),
+            overrun: AtomicI32::new(0),
+        }
+    }
+
+    /// Records an expiration, it's called in the timer IRQ handler.
+    fn expire(&self) {
+        self.pending.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Takes the pending expirations to notify, and returns the overrun count,
+    /// or [`None`] if there are no pending expirations.
+    fn take_expirations(&self) -> Option<i32> {
+        let count = self.pending.swap(0, Ordering::AcqRel);
+        if count == 0 {
+            return None;
+        }
+        let overrun = (count - 1).min(i32::MAX as u32) as i32;
+        self.overrun.store(overrun, Ordering::Release);
+        Some(overrun)
+    }
+}
+
+struct PosixTimer {
+    clock: u32,
+    timer: Timer,
+    shared: Arc<TimerShared>,
+}
+
+impl PosixTimer {
+    fn new(id: usize, clock: u32, notify: Notify) -> Self {
+        let shared = Arc::new(TimerShared::new(id, notify));
+        let timer = if matches!(notify, Notify::None) {
+            Timer::new(|_| {})
+        } else {
+            NOTIFIER.call_once(|| {
+                axtask::spawn(notify_expirations);
+            });
+            let shared = shared.clone();
+            // Runs in the timer IRQ handler, leave the work to the notifier.
+            Timer::new(move |_| {
+                shared.expire();
+                HAS_EXPIRED.store(true, Ordering::Release);
+                NOTIFIER_WQ.notify_one(false);
+            })
+        };
+        Self {
+            clock,
+            timer,
+            shared,
+        }
+    }
+
+    /// Arms the timer with `value` and `interval`, or disarms it if `value`
+    /// is zero. `value` is an absolute time of the timer's clock if
+    /// `abstime` is true, otherwise it's relative to now.
+    fn set(&self, value: Duration, interval: Duration, abstime: bool) {
+        if value.is_zero() {
+            self.timer.cancel();
+            return;
+        }
+        let deadline = if !abstime {
+            wall_time() + value
+        } else if self.clock == ctypes::CLOCK_MONOTONIC {
+            wall_time() + value.saturating_sub(monotonic_time())
+        } else {
+            value
+        };
+        self.timer.set(deadline, Some(interval));
+    }
+
+    /// Returns the time until the next expiration (zero if disarmed) and the
+    /// interval.
+    fn get(&self) -> (Duration, Duration) {
+        let remaining = self.timer.deadline().map_or(Duration::ZERO, |deadline| {
+            deadline.saturating_sub(wall_time())
+        });
+        // A timer that has expired but not been notified is not reported as
+        // disarmed.
+        let remaining = if self.timer.is_armed() && remaining.is_zero() {
+            Duration::from_nanos(1)
+        } else {
+            remaining
+        };
+        (remaining, self.timer.interval().unwrap_or_default())
+    }
+
+    /// Disarms the timer. Pending expirations are dropped as it's no longer
+    /// reachable by the notifier.
+    fn delete(&self) {
+        self.timer.cancel();
+    }
+}
+
+/// The thread that notifies the expirations of all timers.
+///
+/// Timers expire in the timer IRQ handler of axtask, which only records the
+/// expirations, since signal handlers and `SIGEV_THREAD` functions must run
+/// in a thread. They run one after another in this thread, so a blocking one
+/// delays the notifications of other timers.
+fn notify_expirations() {
+    loop {
+        NOTIFIER_WQ.wait_until(|| HAS_EXPIRED.load(Ordering::Acquire));
+        // Expirations since now are seen by the following scan or the next one.
+        HAS_EXPIRED.store(false, Ordering::Release);
+        let mut expired = TIMERS
+            .lock()
+            .values()
+            .map(|timer| timer.shared.clone())
+            .collect::<Vec<_>>();
+        if let Some(timer) = REAL_TIMER.lock().as_ref() {
+            expired.push(timer.shared.clone());
+        }
+        for shared in expired {
+            if let Some(overrun) = shared.take_expirations() {
+                notify(&shared, overrun);
+            }
+        }
+    }
+}
+
+fn notify(shared: &TimerShared, overrun: i32) {
+    match shared.notify {
+        Notify::None => {}
+        Notify::Signal { signo, value } => {
+            let mut info: ctypes::siginfo_t = unsafe { core::mem::zeroed() };
+            info.si_signo = signo;
+            info.si_code = ctypes::SI_TIMER;
+            unsafe {
+                let common = &mut info.__si_fields.__si_common;
+                common.__first.__timer.si_timerid = shared.id as c_int;
+                common.__first.__timer.si_overrun = overrun;
+                common.__second.si_value = value;
+            }
+            handle_signal(info);
+        }
+        Notify::Thread { function, value } => unsafe { function(value) },
+    }
+}
+
+fn timespec_to_duration(ts: &ctypes::timespec) -> LinuxResult<Duration> {
+    if ts.tv_sec < 0 || ts.tv_nsec < 0 || ts.tv_nsec > 999999999 {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(Duration::from(*ts))
+}
+
+fn timeval_to_duration(tv: &ctypes::timeval) -> LinuxResult<Duration> {
+    if tv.tv_sec < 0 || tv.tv_usec < 0 || tv.tv_usec > 999999 {
+        return Err(LinuxError::EINVAL);
+    }
+    Ok(Duration::from(*tv))
+}
+
+fn with_timer<T>(timerid: ctypes::timer_t, f: impl FnOnce(&PosixTimer) -> T) -> LinuxResult<T> {
+    let timers = TIMERS.lock();
+    let timer = timers.get(&(timerid as usize)).ok_or(LinuxError::EINVAL)?;
+    Ok(f(timer))
+}
+
+/// Create a per-process timer.
+pub unsafe fn sys_timer_create(
+    clockid: ctypes::clockid_t,
+    sevp: *mut ctypes::sigevent,
+    timerid: *mut ctypes::timer_t,
+) -> c_int {
+    debug!("sys_timer_create <= {}", clockid);
+    syscall_body!(sys_timer_create, {
+        if timerid.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let clock = clockid as u32;
+        if clock != ctypes::CLOCK_REALTIME && clock != ctypes::CLOCK_MONOTONIC {
+            return Err(LinuxError::EINVAL);
+        }
+        let id = NEXT_TIMER_ID.fetch_add(1, Ordering::AcqRel);
+        let notify = if sevp.is_null() {
+            Notify::Signal {
+                signo: ctypes::SIGALRM as c_int,
+                value: ctypes::sigval {
+                    sival_ptr: id as *mut c_void,
+                },
+            }
+        } else {
+            let sev = unsafe { &*sevp };
+            match sev.sigev_notify as u32 {
+                ctypes::SIGEV_NONE => Notify::None,
+                ctypes::SIGEV_SIGNAL => {
+                    if sev.sigev_signo <= 0 || sev.sigev_signo >= ctypes::_NSIG as c_int {
+                        return Err(LinuxError::EINVAL);
+                    }
+                    Notify::Signal {
+                        signo: sev.sigev_signo,
+                        value: sev.sigev_value,
+                    }
+                }
+                ctypes::SIGEV_THREAD => Notify::Thread {
+                    function: unsafe { sev.__sev_fields.__sev_thread.sigev_notify_function }
+                        .ok_or(LinuxError::EINVAL)?,
+                    value: sev.sigev_value,
+                },
+                _ => return Err(LinuxError::EINVAL),
+            }
+        };
+        let timer = PosixTimer::new(id, clock, notify);
+        TIMERS.lock().insert(id, timer);
+        unsafe { *timerid = id as ctypes::timer_t };
+        Ok(0)
+    })
+}
+
+/// Arm or disarm a per-process timer.
+pub unsafe fn sys_timer_settime(
+    timerid: ctypes::timer_t,
+    flags: c_int,
+    new_value: *const ctypes::itimerspec,
+    old_value: *mut ctypes::itimerspec,
+) -> c_int {
+    debug!("sys_timer_settime <= {:#x}", timerid as usize);
+    syscall_body!(sys_timer_settime, {
+        if new_value.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let new_value = unsafe { &*new_value };
+        let value = timespec_to_duration(&new_value.it_value)?;
+        let interval = timespec_to_duration(&new_value.it_interval)?;
+        let abstime = flags as u32 & ctypes::TIMER_ABSTIME != 0;
+        with_timer(timerid, |timer| {
+            if !old_value.is_null() {
+                let (value, interval) = timer.get();
+                unsafe {
+                    *old_value = ctypes::itimerspec {
+                        it_interval: interval.into(),
+                        it_value: value.into(),
+                    }
+                };
+            }
+            timer.set(value, interval, abstime);
+        })?;
+        Ok(0)
+    })
+}
+
+/// Get the time until the next expiration of a per-process timer.
+pub unsafe fn sys_timer_gettime(
+    timerid: ctypes::timer_t,
+    curr_value: *mut ctypes::itimerspec,
+) -> c_int {
+    syscall_body!(sys_timer_gettime, {
+        if curr_value.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let (value, interval) = with_timer(timerid, PosixTimer::get)?;
+        unsafe {
+            *curr_value = ctypes::itimerspec {
+                it_interval: interval.into(),
+                it_value: value.into(),
+            }
+        };
+        Ok(0)
+    })
+}
+
+/// Get the overrun count of a per-process timer.
+pub unsafe fn sys_timer_getoverrun(timerid: ctypes::timer_t) -> c_int {
+    syscall_body!(sys_timer_getoverrun, {
+        with_timer(timerid, |timer| {
+            timer.shared.overrun.load(Ordering::Acquire)
+        })
+    })
+}
+
+/// Delete a per-process timer.
+pub unsafe fn sys_timer_delete(timerid: ctypes::timer_t) -> c_int {
+    debug!("sys_timer_delete <= {:#x}", timerid as usize);
+    syscall_body!(sys_timer_delete, {
+        let timer = TIMERS
+            .lock()
+            .remove(&(timerid as usize))
+            .ok_or(LinuxError::EINVAL)?;
+        timer.delete();
+        Ok(0)
+    })
+}
+
+/// Set the value of an interval timer.
+///
+/// Only `ITIMER_REAL` is supported, which delivers `SIGALRM` on expiration.
+pub unsafe fn sys_setitimer(
+    which: c_int,
+    new_value: *const ctypes::itimerval,
+    old_value: *mut ctypes::itimerval,
+) -> c_int {
+    debug!("sys_setitimer <= {}", which);
+    syscall_body!(sys_setitimer, {
+        if which as u32 != ctypes::ITIMER_REAL {
+            return Err(LinuxError::EINVAL);
+        }
+        if new_value.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let new_value = unsafe { &*new_value };
+        let value = timeval_to_duration(&new_value.it_value)?;
+        let interval = timeval_to_duration(&new_value.it_interval)?;
+        let (old, old_interval) = set_real_timer(value, interval);
+        if !old_value.is_null() {
+            unsafe {
+                *old_value = ctypes::itimerval {
+                    it_interval: old_interval.into(),
+                    it_value: old.into(),
+                }
+            };
+        }
+        Ok(0)
+    })
+}
+
+/// Get the value of an interval timer.
+pub unsafe fn sys_getitimer(which: c_int, curr_value: *mut ctypes::itimerval) -> c_int {
+    syscall_body!(sys_getitimer, {
+        if which as u32 != ctypes::ITIMER_REAL {
+            return Err(LinuxError::EINVAL);
+        }
+        if curr_value.is_null() {
+            return Err(LinuxError::EFAULT);
+        }
+        let (value, interval) = REAL_TIMER
+            .lock()
+            .as_ref()
+            .map_or((Duration::ZERO, Duration::ZERO), PosixTimer::get);
+        unsafe {
+            *curr_value = ctypes::itimerval {
+                it_interval: interval.into(),
+                it_value: value.into(),
+            }
+        };
+        Ok(0)
+    })
+}
+
+/// Schedule a `SIGALRM` after `seconds`, or cancel it if `seconds` is zero.
+///
+/// Returns the seconds remaining until the previously scheduled alarm.
+pub fn sys_alarm(seconds: c_uint) -> c_uint {
+    debug!("sys_alarm <= {}", seconds);
+    let (old, _) = set_real_timer(Duration::from_secs(seconds as u64), Duration::ZERO);
+    // Do not return zero if an alarm was scheduled.
+    (old.as_secs() + (old.subsec_nanos() > 0) as u64) as c_uint
+}
+
+fn set_real_timer(value: Duration, interval: Duration) -> (Duration, Duration) {
+    let mut real_timer = REAL_TIMER.lock();
+    let timer = real_timer.get_or_insert_with(|| {
+        let id = NEXT_TIMER_ID.fetch_add(1, Ordering::AcqRel);
+        let notify = Notify::Signal {
+            signo: ctypes::SIGALRM as c_int,
+            value: ctypes::sigval { sival_int: 0 },
+        };
+        PosixTimer::new(id, ctypes::CLOCK_REALTIME, notify)
+    });
+    let old = timer.get();
+    timer.set(value, interval, false);
+    old
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_expirations() {
+        let shared = TimerShared::new(1, Notify::None);
+        assert_eq!(shared.take_expirations(), None);
+        for _ in 0..3 {
+            shared.expire();
+        }
+        assert_eq!(shared.take_expirations(), Some(2));
+        assert_eq!(shared.overrun.load(Ordering::Acquire), 2);
+        assert_eq!(shared.take_expirations(), None);
+        // The overrun count is kept until the next notification.
+        assert_eq!(shared.overrun.load(Ordering::Acquire), 2);
+        shared.expire();
+        assert_eq!(shared.take_expirations(), Some(0));
+        assert_eq!(shared.overrun.load(Ordering::Acquire), 0);
+    }
+}
@@ -0,0 +1 @@
+app-objs := mmap.o
@@ -0,0 +1,129 @@
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+#define PAGE_SIZE 4096
+#define FILE_SIZE (2 * PAGE_SIZE)
+
+const char path[] = "/mmap_test.txt";
+
+#define CHECK(cond)                                                         \
+    do {                                                                    \
+        if (!(cond)) {                                                      \
+            printf("%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond); \
+            exit(1);                                                        \
+        }                                                                   \
+    } while (0)
+
+// Reads the byte at `off` in `fd`.
+static char read_byte(int fd, off_t off)
+{
+    char c = 0;
+    CHECK(lseek(fd, off, SEEK_SET) == off);
+    CHECK(read(fd, &c, 1) == 1);
+    return c;
+}
+
+static void write_byte(int fd, off_t off, char c)
+{
+    CHECK(lseek(fd, off, SEEK_SET) == off);
+    CHECK(write(fd, &c, 1) == 1);
+}
+
+static void create_file(void)
+{
+    char buf[FILE_SIZE];
+    int fd = open(path, O_RDWR | O_CREAT | O_TRUNC, 0644);
+    CHECK(fd >= 0);
+    memset(buf, 'a', sizeof(buf));
+    CHECK(write(fd, buf, sizeof(buf)) == sizeof(buf));
+    close(fd);
+}
+
+// Mappings must not be more permissive than the file.
+static void test_access_mode(void)
+{
+    int fd = open(path, O_RDONLY);
+    CHECK(fd >= 0);
+    void *p = mmap(NULL, FILE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    CHECK(p == MAP_FAILED && errno == EACCES);
+
+    // Private mappings are never written back, so they can be writable.
+    char *q = mmap(NULL, FILE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE, fd, 0);
+    CHECK(q != MAP_FAILED);
+    CHECK(q[0] == 'a');
+    q[0] = 'p';
+    CHECK(msync(q, FILE_SIZE, MS_SYNC) == 0);
+    CHECK(munmap(q, FILE_SIZE) == 0);
+    CHECK(read_byte(fd, 0) == 'a');
+    close(fd);
+
+    fd = open(path, O_WRONLY);
+    CHECK(fd >= 0);
+    p = mmap(NULL, FILE_SIZE, PROT_READ, MAP_SHARED, fd, 0);
+    CHECK(p == MAP_FAILED && errno == EACCES);
+    close(fd);
+    puts("test_access_mode OK!");
+}
+
+// Shared mappings, reads and writes of a file see the same data.
+static void test_shared(void)
+{
+    int fd = open(path, O_RDWR);
+    int other = open(path, O_RDONLY);
+    CHECK(fd >= 0 && other >= 0);
+    char *p = mmap(NULL, FILE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    char *q = mmap(NULL, FILE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    CHECK(p != MAP_FAILED && q != MAP_FAILED && p != q);
+
+    p[0] = 'b';
+    CHECK(q[0] == 'b');
+    CHECK(read_byte(fd, 0) == 'b');
+    write_byte(fd, 1, 'c');
+    CHECK(p[1] == 'c' && q[1] == 'c');
+
+    // Writing from a mapping of the same file must not deadlock.
+    CHECK(lseek(fd, 0, SEEK_SET) == 0);
+    CHECK(write(fd, p + PAGE_SIZE, 16) == 16);
+    CHECK(p[0] == 'a' && p[1] == 'a');
+
+    // Dirty pages are written back even if they have been made read-only.
+    p[PAGE_SIZE] = 'd';
+    CHECK(mprotect(p, FILE_SIZE, PROT_READ) == 0);
+    CHECK(msync(p, FILE_SIZE, MS_SYNC) == 0);
+    CHECK(read_byte(other, PAGE_SIZE) == 'd');
+
+    q[2] = 'e';
+    CHECK(munmap(q, FILE_SIZE) == 0);
+    CHECK(read_byte(other, 2) == 'e');
+    CHECK(p[2] == 'e');
+    CHECK(munmap(p, FILE_SIZE) == 0);
+
+    // Mappings replaced by `MAP_FIXED` are written back as well.
+    p = mmap(NULL, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    CHECK(p != MAP_FAILED);
+    p[3] = 'f';
+    char *r = mmap(p, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+                   -1, 0);
+    CHECK(r == p && r[3] == 0);
+    CHECK(read_byte(other, 3) == 'f');
+    CHECK(munmap(r, PAGE_SIZE) == 0);
+
+    close(other);
+    close(fd);
+    puts("test_shared OK!");
+}
+
+int main()
+{
+    puts("Running mmap tests...");
+    create_file();
+    test_access_mode();
+    test_shared();
+    puts("All mmap tests passed!");
+    return 0;
+}
@@ -0,0 +1,27 @@
+//! Runs small programs in user mode, each in its own address space, and checks
+//! that they make syscalls and fail as expected.
+
+#![cfg_attr(feature = "axstd", no_std)]
+#![cfg_attr(feature = "axstd", no_main)]
+
+#[macro_use]
+#[cfg(feature = "axstd")]
+extern crate axstd as std;
+
+#[cfg(feature = "axstd")]
+mod user;
+
+#[cfg(feature = "axstd")]
+#[no_mangle]
+fn main() {
+    println!("Running user mode tests...");
+    user::test_syscall();
+    user::test_page_fault();
+    user::test_illegal_instruction();
+    println!("All user mode tests passed!");
+}
+
+#[cfg(not(feature = "axstd"))]
+fn main() {
+    println!("User mode tests only run on ArceOS.");
+}
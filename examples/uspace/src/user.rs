@@ -0,0 +1,182 @@
+use std::sync::{Arc, Mutex};
+use std::vec::Vec;
+
+use axhal::arch::{TrapFrame, UserContext};
+use axhal::paging::MappingFlags;
+use axhal::trap::{register_trap_handler, SYSCALL};
+use axlockdep::spin::SpinNoIrq;
+use axmm::AddrSpace;
+use axtask::TaskInner;
+use memory_addr::{va, VirtAddr, PAGE_SIZE_4K};
+
+const USER_ASPACE_BASE: usize = 0x1000;
+const USER_ASPACE_SIZE: usize = 0x1000_0000;
+const USER_CODE: usize = 0x40_0000;
+const USER_STACK_TOP: usize = 0x80_0000;
+const USER_STACK_SIZE: usize = 0x4000;
+const KERNEL_STACK_SIZE: usize = 0x10000;
+
+/// Returns its argument plus one.
+const SYS_ECHO: usize = 1;
+/// Exits with its argument as the exit code.
+const SYS_EXIT: usize = 2;
+
+/// The argument of `SYS_ECHO` in [`SYSCALL_CODE`].
+const ECHO_ARG: i32 = 0x1234;
+
+/// The exit code of tasks killed by user exceptions (see `axruntime`).
+const KILLED: i32 = -1;
+
+/// Machine code, in bytes or in fixed-size instructions.
+#[cfg(target_arch = "x86_64")]
+type Code = &'static [u8];
+#[cfg(not(target_arch = "x86_64"))]
+type Code = &'static [u32];
+
+// Calls `SYS_ECHO` with `ECHO_ARG`, then `SYS_EXIT` with the result.
+#[cfg(target_arch = "x86_64")]
+const SYSCALL_CODE: Code = &[
+    0xbf, 0x34, 0x12, 0x00, 0x00, // mov edi, 0x1234
+    0xb8, 0x01, 0x00, 0x00, 0x00, // mov eax, SYS_ECHO
+    0x0f, 0x05, // syscall
+    0x48, 0x89, 0xc7, // mov rdi, rax
+    0xb8, 0x02, 0x00, 0x00, 0x00, // mov eax, SYS_EXIT
+    0x0f, 0x05, // syscall
+    0x0f, 0x0b, // ud2
+];
+// Loads from address 0, which is not mapped.
+#[cfg(target_arch = "x86_64")]
+const FAULT_CODE: Code = &[
+    0x48, 0x8b, 0x04, 0x25, 0x00, 0x00, 0x00, 0x00, // mov rax, [0]
+];
+#[cfg(target_arch = "x86_64")]
+const ILLEGAL_CODE: Code = &[
+    0x0f, 0x0b, // ud2
+];
+
+#[cfg(target_arch = "aarch64")]
+const SYSCALL_CODE: Code = &[
+    0xd282_4680, // mov x0, #0x1234
+    0xd280_0028, // mov x8, #SYS_ECHO
+    0xd400_0001, // svc #0
+    0xd280_0048, // mov x8, #SYS_EXIT
+    0xd400_0001, // svc #0
+    0x0000_0000, // udf #0
+];
+#[cfg(target_arch = "aarch64")]
+const FAULT_CODE: Code = &[
+    0xd280_0001, // mov x1, #0
+    0xf940_0020, // ldr x0, [x1]
+];
+#[cfg(target_arch = "aarch64")]
+const ILLEGAL_CODE: Code = &[
+    0x0000_0000, // udf #0
+];
+
+#[cfg(target_arch = "riscv64")]
+const SYSCALL_CODE: Code = &[
+    0x0000_1537, // lui a0, 0x1
+    0x2345_0513, // addi a0, a0, 0x234
+    0x0010_0893, // li a7, SYS_ECHO
+    0x0000_0073, // ecall
+    0x0020_0893, // li a7, SYS_EXIT
+    0x0000_0073, // ecall
+    0x0000_0000, // illegal instruction
+];
+#[cfg(target_arch = "riscv64")]
+const FAULT_CODE: Code = &[
+    0x0000_3503, // ld a0, 0(zero)
+];
+#[cfg(target_arch = "riscv64")]
+const ILLEGAL_CODE: Code = &[
+    0x0000_0000, // illegal instruction
+];
+
+/// Instructions, in little endian.
+#[cfg(not(target_arch = "x86_64"))]
+fn code_bytes(code: Code) -> Vec<u8> {
+    code.iter().flat_map(|inst| inst.to_le_bytes()).collect()
+}
+
+#[cfg(target_arch = "x86_64")]
+fn code_bytes(code: Code) -> Vec<u8> {
+    code.to_vec()
+}
+
+/// The syscalls made by user code, for checking their arguments.
+static SYSCALLS: Mutex<Vec<(usize, usize)>> = Mutex::new(Vec::new());
+
+#[register_trap_handler(SYSCALL)]
+fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
+    SYSCALLS.lock().push((syscall_num, tf.arg0()));
+    match syscall_num {
+        SYS_ECHO => tf.arg0() as isize + 1,
+        SYS_EXIT => axtask::exit(tf.arg0() as i32),
+        _ => -38, // ENOSYS
+    }
+}
+
+/// Creates an address space with `code` at `USER_CODE`, and a stack.
+fn new_user_aspace(code: Code) -> AddrSpace {
+    let mut aspace =
+        axmm::new_user_aspace(va!(USER_ASPACE_BASE), USER_ASPACE_SIZE).expect("user aspace");
+    let code_flags = MappingFlags::READ | MappingFlags::EXECUTE | MappingFlags::USER;
+    let start = va!(USER_CODE);
+    aspace
+        .map_alloc(start, PAGE_SIZE_4K, code_flags | MappingFlags::WRITE, true)
+        .unwrap();
+    aspace.write(start, &code_bytes(code)).unwrap();
+    aspace.protect(start, PAGE_SIZE_4K, code_flags).unwrap();
+    let stack_flags = MappingFlags::READ | MappingFlags::WRITE | MappingFlags::USER;
+    aspace
+        .map_alloc(
+            va!(USER_STACK_TOP - USER_STACK_SIZE),
+            USER_STACK_SIZE,
+            stack_flags,
+            false,
+        )
+        .unwrap();
+    aspace
+}
+
+/// Runs `code` in user mode in a new task, and returns its exit code.
+fn run_user(name: &str, code: Code) -> i32 {
+    let aspace = Arc::new(SpinNoIrq::new(new_user_aspace(code)));
+    let mut task = TaskInner::new(
+        || {
+            let ctx = UserContext::new(USER_CODE, va!(USER_STACK_TOP), 0);
+            let kstack_top: VirtAddr = axtask::current().kernel_stack_top().unwrap();
+            unsafe { ctx.enter_uspace(kstack_top) };
+        },
+        name.into(),
+        KERNEL_STACK_SIZE,
+    );
+    task.set_user_aspace(aspace);
+    axtask::spawn_task(task).join().unwrap()
+}
+
+pub fn test_syscall() {
+    SYSCALLS.lock().clear();
+    let exit_code = run_user("user_syscall", SYSCALL_CODE);
+    assert_eq!(exit_code, ECHO_ARG + 1);
+    assert_eq!(
+        *SYSCALLS.lock(),
+        [
+            (SYS_ECHO, ECHO_ARG as usize),
+            (SYS_EXIT, ECHO_ARG as usize + 1)
+        ]
+    );
+    println!("test_syscall OK!");
+}
+
+pub fn test_page_fault() {
+    assert_eq!(run_user("user_page_fault", FAULT_CODE), KILLED);
+    println!("test_page_fault OK!");
+}
+
+pub fn test_illegal_instruction() {
+    SYSCALLS.lock().clear();
+    assert_eq!(run_user("user_illegal_inst", ILLEGAL_CODE), KILLED);
+    assert!(SYSCALLS.lock().is_empty());
+    println!("test_illegal_instruction OK!");
+}
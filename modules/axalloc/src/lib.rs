@@ -14,9 +14,9 @@ extern crate alloc;
 mod page;
 
 use allocator::{AllocResult, BaseAllocator, BitmapPageAllocator, ByteAllocator, PageAllocator};
+use axlockdep::spin::SpinNoIrq;
 use core::alloc::{GlobalAlloc, Layout};
 use core::ptr::NonNull;
-use kspin::SpinNoIrq;
 
 const PAGE_SIZE: usize = 0x1000;
 const MIN_HEAP_SIZE: usize = 0x8000; // 32 K
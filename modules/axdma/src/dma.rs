@@ -3,7 +3,7 @@ use core::{alloc::Layout, ptr::NonNull};
 use allocator::{AllocError, AllocResult, BaseAllocator, ByteAllocator};
 use axalloc::{global_allocator, DefaultByteAllocator};
 use axhal::{mem::virt_to_phys, paging::MappingFlags};
-use kspin::SpinNoIrq;
+use axlockdep::spin::SpinNoIrq;
 use log::{debug, error};
 use memory_addr::{va, VirtAddr, PAGE_SIZE_4K};
 
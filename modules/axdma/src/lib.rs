@@ -34,7 +34,9 @@ pub const fn phys_to_bus(paddr: PhysAddr) -> BusAddr {
 /// # Safety
 /// This function is unsafe because it directly interacts with the global allocator, which can potentially cause memory leaks or other issues if not used correctly.
 pub unsafe fn alloc_coherent(layout: Layout) -> AllocResult<DMAInfo> {
-    ALLOCATOR.lock().alloc_coherent(layout)
+    let res = ALLOCATOR.lock().alloc_coherent(layout);
+    flush_tlb();
+    res
 }
 
 /// Frees coherent memory previously allocated.
@@ -45,7 +47,15 @@ pub unsafe fn alloc_coherent(layout: Layout) -> AllocResult<DMAInfo> {
 /// # Safety
 /// This function is unsafe because it directly interacts with the global allocator, which can potentially cause memory leaks or other issues if not used correctly.
 pub unsafe fn dealloc_coherent(dma: DMAInfo, layout: Layout) {
-    ALLOCATOR.lock().dealloc_coherent(dma, layout)
+    ALLOCATOR.lock().dealloc_coherent(dma, layout);
+    flush_tlb();
+}
+
+/// Flushes the TLBs of all CPUs for the pages whose flags are changed by the
+/// allocator. It waits for the other CPUs, so it's done after the allocator
+/// is unlocked (see [`axmm::with_kernel_aspace`]).
+fn flush_tlb() {
+    axmm::with_kernel_aspace(|_| {});
 }
 
 /// A bus memory address.
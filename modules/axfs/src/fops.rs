@@ -246,6 +246,16 @@ impl File {
     pub fn get_attr(&self) -> AxResult<FileAttr> {
         self.access_node(Cap::empty())?.get_attr()
     }
+
+    /// Whether the file is opened for reading.
+    pub fn is_readable(&self) -> bool {
+        self.access_node(Cap::READ).is_ok()
+    }
+
+    /// Whether the file is opened for writing.
+    pub fn is_writable(&self) -> bool {
+        self.access_node(Cap::WRITE).is_ok()
+    }
 }
 
 impl Directory {
@@ -15,6 +15,170 @@ pub struct TrapFrame {
     pub spsr: u64,
 }
 
+impl TrapFrame {
+    /// Whether the trap is from userspace (EL0).
+    pub const fn is_user(&self) -> bool {
+        self.spsr & 0b1111 == 0 // SPSR_EL1.M: EL0t
+    }
+
+    /// Gets the 0th syscall argument.
+    pub const fn arg0(&self) -> usize {
+        self.r[0] as _
+    }
+
+    /// Gets the 1st syscall argument.
+    pub const fn arg1(&self) -> usize {
+        self.r[1] as _
+    }
+
+    /// Gets the 2nd syscall argument.
+    pub const fn arg2(&self) -> usize {
+        self.r[2] as _
+    }
+
+    /// Gets the 3rd syscall argument.
+    pub const fn arg3(&self) -> usize {
+        self.r[3] as _
+    }
+
+    /// Gets the 4th syscall argument.
+    pub const fn arg4(&self) -> usize {
+        self.r[4] as _
+    }
+
+    /// Gets the 5th syscall argument.
+    pub const fn arg5(&self) -> usize {
+        self.r[5] as _
+    }
+}
+
+/// Context to enter user space.
+///
+/// It's a trap frame that is restored to the CPU by [`enter_uspace`], as if
+/// returning from a trap of user space.
+///
+/// [`enter_uspace`]: UserContext::enter_uspace
+#[cfg(feature = "uspace")]
+#[derive(Debug, Clone)]
+pub struct UserContext(TrapFrame);
+
+#[cfg(feature = "uspace")]
+impl UserContext {
+    /// Creates a new context with the given entry point, user stack pointer,
+    /// and the argument passed in `X0`.
+    pub fn new(entry: usize, ustack_top: VirtAddr, arg0: usize) -> Self {
+        use aarch64_cpu::registers::SPSR_EL1;
+        let mut regs = [0; 31];
+        regs[0] = arg0 as _;
+        Self(TrapFrame {
+            r: regs,
+            usp: ustack_top.as_usize() as _,
+            elr: entry as _,
+            spsr: (SPSR_EL1::M::EL0t
+                + SPSR_EL1::D::Masked
+                + SPSR_EL1::A::Masked
+                + SPSR_EL1::I::Unmasked
+                + SPSR_EL1::F::Masked)
+                .value,
+        })
+    }
+
+    /// Creates a new context from the trap frame of a user task, e.g., to
+    /// return to a forked task.
+    pub const fn from_trap_frame(tf: &TrapFrame) -> Self {
+        Self(*tf)
+    }
+
+    /// Gets the instruction pointer.
+    pub const fn ip(&self) -> usize {
+        self.0.elr as _
+    }
+
+    /// Gets the stack pointer.
+    pub const fn sp(&self) -> usize {
+        self.0.usp as _
+    }
+
+    /// Sets the instruction pointer.
+    pub fn set_ip(&mut self, pc: usize) {
+        self.0.elr = pc as _;
+    }
+
+    /// Sets the stack pointer.
+    pub fn set_sp(&mut self, sp: usize) {
+        self.0.usp = sp as _;
+    }
+
+    /// Sets the return value register.
+    pub fn set_retval(&mut self, r0: usize) {
+        self.0.r[0] = r0 as _;
+    }
+
+    /// Enters user space.
+    ///
+    /// It restores the user registers and jumps to the user entry point
+    /// (saved in `ELR_EL1`). Traps from user space then use `kstack_top` as
+    /// the kernel stack.
+    ///
+    /// This function never returns. The user task goes back to the kernel
+    /// only by traps.
+    ///
+    /// # Safety
+    ///
+    /// `kstack_top` must be the top of the kernel stack of the current task,
+    /// and the page table of the user address space must have been loaded
+    /// (into `TTBR0_EL1`).
+    pub unsafe fn enter_uspace(&self, kstack_top: VirtAddr) -> ! {
+        super::disable_irqs();
+        // `SP_EL1` is kept unchanged while running in user space, so traps
+        // from user space start with the empty kernel stack.
+        asm!("
+            mov     sp, x1
+            ldp     x30, x9, [x0, 30 * 8]
+            ldp     x10, x11, [x0, 32 * 8]
+            msr     sp_el0, x9
+            msr     elr_el1, x10
+            msr     spsr_el1, x11
+
+            ldp     x28, x29, [x0, 28 * 8]
+            ldp     x26, x27, [x0, 26 * 8]
+            ldp     x24, x25, [x0, 24 * 8]
+            ldp     x22, x23, [x0, 22 * 8]
+            ldp     x20, x21, [x0, 20 * 8]
+            ldp     x18, x19, [x0, 18 * 8]
+            ldp     x16, x17, [x0, 16 * 8]
+            ldp     x14, x15, [x0, 14 * 8]
+            ldp     x12, x13, [x0, 12 * 8]
+            ldp     x10, x11, [x0, 10 * 8]
+            ldp     x8, x9, [x0, 8 * 8]
+            ldp     x6, x7, [x0, 6 * 8]
+            ldp     x4, x5, [x0, 4 * 8]
+            ldp     x2, x3, [x0, 2 * 8]
+            ldp     x0, x1, [x0]
+            eret",
+            in("x0") &self.0,
+            in("x1") kstack_top.as_usize(),
+            options(noreturn),
+        )
+    }
+}
+
+#[cfg(feature = "uspace")]
+impl core::ops::Deref for UserContext {
+    type Target = TrapFrame;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "uspace")]
+impl core::ops::DerefMut for UserContext {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 /// FP & SIMD registers.
 #[repr(C, align(16))]
 #[derive(Debug, Default)]
@@ -81,6 +245,16 @@ impl TaskContext {
         self.tpidr_el0 = tls_area.as_usize() as u64;
     }
 
+    /// Returns the program counter and the frame pointer where the task is
+    /// switched out, to walk its stack.
+    ///
+    /// # Safety
+    ///
+    /// The task must not be running.
+    pub unsafe fn switch_frame(&self) -> (usize, usize) {
+        (self.lr as usize, self.r29 as usize)
+    }
+
     /// Switches to another task.
     ///
     /// It first saves the current task's context from CPU to this place, and then
@@ -13,6 +13,9 @@ use tock_registers::interfaces::{Readable, Writeable};
 
 pub use self::context::{FpState, TaskContext, TrapFrame};
 
+#[cfg(feature = "uspace")]
+pub use self::context::UserContext;
+
 /// Allows the current CPU to respond to interrupts.
 #[inline]
 pub fn enable_irqs() {
@@ -39,6 +42,18 @@ pub fn wait_for_irqs() {
     aarch64_cpu::asm::wfi();
 }
 
+/// Enables interrupts and waits for one atomically.
+///
+/// It's called with interrupts disabled, so that an interrupt arriving after
+/// the caller decides to sleep still wakes it up: `wfi` returns on a pending
+/// interrupt even if it's masked, which is then taken once interrupts are
+/// enabled before returning.
+#[inline]
+pub fn enable_irqs_and_wait() {
+    aarch64_cpu::asm::wfi();
+    enable_irqs();
+}
+
 /// Halt the current CPU.
 #[inline]
 pub fn halt() {
@@ -151,6 +166,14 @@ pub fn flush_dcache_line(vaddr: VirtAddr) {
     unsafe { asm!("dc ivac, {0:x}; dsb sy; isb", in(reg) vaddr.as_usize()) };
 }
 
+/// Reads the frame pointer (`x29`) of the current function.
+#[inline(always)]
+pub fn read_frame_pointer() -> usize {
+    let fp;
+    unsafe { core::arch::asm!("mov {}, x29", out(reg) fp) };
+    fp
+}
+
 /// Reads the thread pointer of the current CPU.
 ///
 /// It is used to implement TLS (Thread Local Storage).
@@ -5,8 +5,13 @@ use page_table_entry::MappingFlags;
 use tock_registers::interfaces::Readable;
 
 use super::TrapFrame;
+#[cfg(feature = "uspace")]
+use crate::trap::{handle_user_exception, UserException};
 
-global_asm!(include_str!("trap.S"));
+global_asm!(
+    include_str!("trap.S"),
+    cache_current_task_ptr = sym crate::cpu::cache_current_task_ptr,
+);
 
 #[repr(u8)]
 #[derive(Debug)]
@@ -52,6 +57,10 @@ fn handle_instruction_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
     if !matches!(iss & 0b111100, 0b0100 | 0b1100) // IFSC or DFSC bits
         || !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user)
     {
+        #[cfg(feature = "uspace")]
+        if is_user {
+            handle_user_exception(tf, UserException::PageFault(vaddr, access_flags));
+        }
         panic!(
             "Unhandled {} Instruction Abort @ {:#x}, fault_vaddr={:#x}, ISS={:#x} ({:?}):\n{:#x?}",
             if is_user { "EL0" } else { "EL1" },
@@ -81,6 +90,10 @@ fn handle_data_abort(tf: &TrapFrame, iss: u64, is_user: bool) {
     if !matches!(iss & 0b111100, 0b0100 | 0b1100) // IFSC or DFSC bits
         || !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user)
     {
+        #[cfg(feature = "uspace")]
+        if is_user {
+            handle_user_exception(tf, UserException::PageFault(vaddr, access_flags));
+        }
         panic!(
             "Unhandled {} Data Abort @ {:#x}, fault_vaddr={:#x}, ISS=0b{:08b} ({:?}):\n{:#x?}",
             if is_user { "EL0" } else { "EL1" },
@@ -98,6 +111,11 @@ fn handle_sync_exception(tf: &mut TrapFrame) {
     let esr = ESR_EL1.extract();
     let iss = esr.read(ESR_EL1::ISS);
     match esr.read_as_enum(ESR_EL1::EC) {
+        #[cfg(feature = "uspace")]
+        Some(ESR_EL1::EC::Value::SVC64) => {
+            tf.r[0] = crate::trap::handle_syscall(tf, tf.r[8] as usize) as u64;
+        }
+        #[cfg(not(feature = "uspace"))]
         Some(ESR_EL1::EC::Value::SVC64) => {
             warn!("No syscall is supported currently!");
         }
@@ -109,6 +127,16 @@ fn handle_sync_exception(tf: &mut TrapFrame) {
             debug!("BRK #{:#x} @ {:#x} ", iss, tf.elr);
             tf.elr += 4;
         }
+        #[cfg(feature = "uspace")]
+        Some(ESR_EL1::EC::Value::Unknown) if tf.is_user() => {
+            handle_user_exception(tf, UserException::IllegalInstruction);
+        }
+        // E.g., PC or SP alignment faults, trapped FP or SVE instructions.
+        #[cfg(feature = "uspace")]
+        _ if tf.is_user() => {
+            let ec = esr.read(ESR_EL1::EC) as usize;
+            handle_user_exception(tf, UserException::Other(ec));
+        }
         _ => {
             panic!(
                 "Unhandled synchronous exception @ {:#x}: ESR={:#x} (EC {:#08b}, ISS {:#x})",
@@ -53,6 +53,155 @@ pub struct TrapFrame {
     pub sstatus: usize,
 }
 
+impl TrapFrame {
+    /// Gets the 0th syscall argument.
+    pub const fn arg0(&self) -> usize {
+        self.regs.a0
+    }
+
+    /// Gets the 1st syscall argument.
+    pub const fn arg1(&self) -> usize {
+        self.regs.a1
+    }
+
+    /// Gets the 2nd syscall argument.
+    pub const fn arg2(&self) -> usize {
+        self.regs.a2
+    }
+
+    /// Gets the 3rd syscall argument.
+    pub const fn arg3(&self) -> usize {
+        self.regs.a3
+    }
+
+    /// Gets the 4th syscall argument.
+    pub const fn arg4(&self) -> usize {
+        self.regs.a4
+    }
+
+    /// Gets the 5th syscall argument.
+    pub const fn arg5(&self) -> usize {
+        self.regs.a5
+    }
+}
+
+/// Context to enter user space.
+///
+/// It's a trap frame that is restored to the CPU by [`enter_uspace`], as if
+/// returning from a trap of user space.
+///
+/// [`enter_uspace`]: UserContext::enter_uspace
+#[cfg(feature = "uspace")]
+#[derive(Debug, Clone)]
+pub struct UserContext(TrapFrame);
+
+#[cfg(feature = "uspace")]
+impl UserContext {
+    /// Creates a new context with the given entry point, user stack pointer,
+    /// and the argument passed in `a0`.
+    pub fn new(entry: usize, ustack_top: VirtAddr, arg0: usize) -> Self {
+        const SPIE: usize = 1 << 5; // enable interrupts after `sret`
+        const SUM: usize = 1 << 18; // allow the kernel to access user memory
+        Self(TrapFrame {
+            regs: GeneralRegisters {
+                a0: arg0,
+                sp: ustack_top.as_usize(),
+                ..Default::default()
+            },
+            sepc: entry,
+            sstatus: SPIE | SUM, // SPP = 0, return to U-mode
+        })
+    }
+
+    /// Creates a new context from the trap frame of a user task, e.g., to
+    /// return to a forked task.
+    pub fn from_trap_frame(tf: &TrapFrame) -> Self {
+        Self(tf.clone())
+    }
+
+    /// Gets the instruction pointer.
+    pub const fn ip(&self) -> usize {
+        self.0.sepc
+    }
+
+    /// Gets the stack pointer.
+    pub const fn sp(&self) -> usize {
+        self.0.regs.sp
+    }
+
+    /// Sets the instruction pointer.
+    pub fn set_ip(&mut self, pc: usize) {
+        self.0.sepc = pc;
+    }
+
+    /// Sets the stack pointer.
+    pub fn set_sp(&mut self, sp: usize) {
+        self.0.regs.sp = sp;
+    }
+
+    /// Sets the return value register.
+    pub fn set_retval(&mut self, a0: usize) {
+        self.0.regs.a0 = a0;
+    }
+
+    /// Enters user space.
+    ///
+    /// It restores the user registers and jumps to the user entry point
+    /// (saved in `sepc`). Traps from user space then use `kstack_top` as the
+    /// kernel stack.
+    ///
+    /// This function never returns. The user task goes back to the kernel
+    /// only by traps.
+    ///
+    /// # Safety
+    ///
+    /// `kstack_top` must be the top of the kernel stack of the current task,
+    /// and the page table of the user address space must have been loaded.
+    pub unsafe fn enter_uspace(&self, kstack_top: VirtAddr) -> ! {
+        use riscv::register::{sepc, sscratch};
+
+        super::disable_irqs();
+        sscratch::write(kstack_top.as_usize());
+        sepc::write(self.0.sepc);
+        // The trap frame of the next trap from user space is saved here, from
+        // where the supervisor `gp` and `tp` are restored.
+        let kernel_trap_frame = kstack_top.as_usize() - core::mem::size_of::<TrapFrame>();
+        asm!("
+            mv      sp, {tf}
+
+            STR     gp, {kernel_trap_frame}, 2
+            LDR     gp, sp, 2
+            STR     tp, {kernel_trap_frame}, 3
+            LDR     tp, sp, 3
+
+            LDR     t0, sp, 32
+            csrw    sstatus, t0
+            POP_GENERAL_REGS
+            LDR     sp, sp, 1
+            sret",
+            tf = in(reg) &self.0,
+            kernel_trap_frame = in(reg) kernel_trap_frame,
+            options(noreturn),
+        )
+    }
+}
+
+#[cfg(feature = "uspace")]
+impl core::ops::Deref for UserContext {
+    type Target = TrapFrame;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "uspace")]
+impl core::ops::DerefMut for UserContext {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
 /// Saved hardware states of a task.
 ///
 /// The context usually includes:
@@ -103,6 +252,16 @@ impl TaskContext {
         self.tp = tls_area.as_usize();
     }
 
+    /// Returns the program counter and the frame pointer where the task is
+    /// switched out, to walk its stack.
+    ///
+    /// # Safety
+    ///
+    /// The task must not be running.
+    pub unsafe fn switch_frame(&self) -> (usize, usize) {
+        (self.ra, self.s0)
+    }
+
     /// Switches to another task.
     ///
     /// It first saves the current task's context from CPU to this place, and then
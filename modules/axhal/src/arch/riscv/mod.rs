@@ -10,6 +10,9 @@ use riscv::register::{satp, sstatus, stvec};
 
 pub use self::context::{GeneralRegisters, TaskContext, TrapFrame};
 
+#[cfg(feature = "uspace")]
+pub use self::context::UserContext;
+
 /// Allows the current CPU to respond to interrupts.
 #[inline]
 pub fn enable_irqs() {
@@ -36,6 +39,18 @@ pub fn wait_for_irqs() {
     riscv::asm::wfi()
 }
 
+/// Enables interrupts and waits for one atomically.
+///
+/// It's called with interrupts disabled, so that an interrupt arriving after
+/// the caller decides to sleep still wakes it up: `wfi` returns on a pending
+/// interrupt even if it's masked, which is then taken once interrupts are
+/// enabled before returning.
+#[inline]
+pub fn enable_irqs_and_wait() {
+    riscv::asm::wfi();
+    enable_irqs();
+}
+
 /// Halt the current CPU.
 #[inline]
 pub fn halt() {
@@ -86,6 +101,14 @@ pub fn set_trap_vector_base(stvec: usize) {
     unsafe { stvec::write(stvec, stvec::TrapMode::Direct) }
 }
 
+/// Reads the frame pointer (`s0`) of the current function.
+#[inline(always)]
+pub fn read_frame_pointer() -> usize {
+    let fp;
+    unsafe { core::arch::asm!("mv {}, s0", out(reg) fp) };
+    fp
+}
+
 /// Reads the thread pointer of the current CPU.
 ///
 /// It is used to implement TLS (Thread Local Storage).
@@ -3,6 +3,8 @@ use riscv::register::scause::{self, Exception as E, Trap};
 use riscv::register::stval;
 
 use super::TrapFrame;
+#[cfg(feature = "uspace")]
+use crate::trap::{handle_user_exception, UserException};
 
 include_asm_marcos!();
 
@@ -22,6 +24,10 @@ fn handle_page_fault(tf: &TrapFrame, mut access_flags: MappingFlags, is_user: bo
     }
     let vaddr = va!(stval::read());
     if !handle_trap!(PAGE_FAULT, vaddr, access_flags, is_user) {
+        #[cfg(feature = "uspace")]
+        if is_user {
+            handle_user_exception(tf, UserException::PageFault(vaddr, access_flags));
+        }
         panic!(
             "Unhandled {} Page Fault @ {:#x}, fault_vaddr={:#x} ({:?}):\n{:#x?}",
             if is_user { "User" } else { "Supervisor" },
@@ -43,9 +49,22 @@ fn riscv_trap_handler(tf: &mut TrapFrame, from_user: bool) {
             handle_page_fault(tf, MappingFlags::EXECUTE, from_user)
         }
         Trap::Exception(E::Breakpoint) => handle_breakpoint(&mut tf.sepc),
+        #[cfg(feature = "uspace")]
+        Trap::Exception(E::UserEnvCall) => {
+            tf.sepc += 4;
+            tf.regs.a0 = crate::trap::handle_syscall(tf, tf.regs.a7) as usize;
+        }
         Trap::Interrupt(_) => {
             handle_trap!(IRQ, scause.bits());
         }
+        #[cfg(feature = "uspace")]
+        Trap::Exception(E::IllegalInstruction) if from_user => {
+            handle_user_exception(tf, UserException::IllegalInstruction);
+        }
+        #[cfg(feature = "uspace")]
+        Trap::Exception(_) if from_user => {
+            handle_user_exception(tf, UserException::Other(scause.code()));
+        }
         _ => {
             panic!(
                 "Unhandled trap {:?} @ {:#x}:\n{:#x?}",
@@ -39,6 +39,160 @@ impl TrapFrame {
     pub const fn is_user(&self) -> bool {
         self.cs & 0b11 == 3
     }
+
+    /// Gets the 0th syscall argument.
+    pub const fn arg0(&self) -> usize {
+        self.rdi as _
+    }
+
+    /// Gets the 1st syscall argument.
+    pub const fn arg1(&self) -> usize {
+        self.rsi as _
+    }
+
+    /// Gets the 2nd syscall argument.
+    pub const fn arg2(&self) -> usize {
+        self.rdx as _
+    }
+
+    /// Gets the 3rd syscall argument.
+    pub const fn arg3(&self) -> usize {
+        self.r10 as _
+    }
+
+    /// Gets the 4th syscall argument.
+    pub const fn arg4(&self) -> usize {
+        self.r8 as _
+    }
+
+    /// Gets the 5th syscall argument.
+    pub const fn arg5(&self) -> usize {
+        self.r9 as _
+    }
+}
+
+/// Context to enter user space.
+///
+/// It's a trap frame that is restored to the CPU by [`enter_uspace`], as if
+/// returning from a trap of user space.
+///
+/// [`enter_uspace`]: UserContext::enter_uspace
+#[cfg(feature = "uspace")]
+#[derive(Debug, Clone)]
+pub struct UserContext(TrapFrame);
+
+#[cfg(feature = "uspace")]
+impl UserContext {
+    /// Creates a new context with the given entry point, user stack pointer,
+    /// and the argument passed in `RDI`.
+    pub fn new(entry: usize, ustack_top: VirtAddr, arg0: usize) -> Self {
+        use super::GdtStruct;
+        use x86_64::registers::rflags::RFlags;
+        Self(TrapFrame {
+            rdi: arg0 as _,
+            rip: entry as _,
+            cs: GdtStruct::UCODE64_SELECTOR.0 as _,
+            rflags: RFlags::INTERRUPT_FLAG.bits(), // IOPL = 0, IF = 1
+            rsp: ustack_top.as_usize() as _,
+            ss: GdtStruct::UDATA_SELECTOR.0 as _,
+            ..Default::default()
+        })
+    }
+
+    /// Creates a new context from the trap frame of a user task, e.g., to
+    /// return to a forked task.
+    pub fn from_trap_frame(tf: &TrapFrame) -> Self {
+        Self(tf.clone())
+    }
+
+    /// Gets the instruction pointer.
+    pub const fn ip(&self) -> usize {
+        self.0.rip as _
+    }
+
+    /// Gets the stack pointer.
+    pub const fn sp(&self) -> usize {
+        self.0.rsp as _
+    }
+
+    /// Sets the instruction pointer.
+    pub fn set_ip(&mut self, rip: usize) {
+        self.0.rip = rip as _;
+    }
+
+    /// Sets the stack pointer.
+    pub fn set_sp(&mut self, rsp: usize) {
+        self.0.rsp = rsp as _;
+    }
+
+    /// Sets the return value register.
+    pub fn set_retval(&mut self, rax: usize) {
+        self.0.rax = rax as _;
+    }
+
+    /// Enters user space.
+    ///
+    /// It restores the user registers and jumps to the user entry point
+    /// (saved in `RIP`). Traps and syscalls from user space then use
+    /// `kstack_top` as the kernel stack.
+    ///
+    /// This function never returns. The user task goes back to the kernel
+    /// only by traps. If `RIP` is not canonical, which would fault in kernel
+    /// mode, the user exception handler is called instead.
+    ///
+    /// # Safety
+    ///
+    /// `kstack_top` must be the top of the kernel stack of the current task,
+    /// and the page table of the user address space must have been loaded.
+    pub unsafe fn enter_uspace(&self, kstack_top: VirtAddr) -> ! {
+        if !super::syscall::is_canonical(self.0.rip) {
+            crate::trap::handle_user_exception(
+                &self.0,
+                crate::trap::UserException::Other(x86::irq::GENERAL_PROTECTION_FAULT_VECTOR as _),
+            );
+        }
+        super::disable_irqs();
+        super::set_kernel_stack_top(kstack_top);
+        asm!("
+            mov     rsp, {tf}
+            pop     rax
+            pop     rcx
+            pop     rdx
+            pop     rbx
+            pop     rbp
+            pop     rsi
+            pop     rdi
+            pop     r8
+            pop     r9
+            pop     r10
+            pop     r11
+            pop     r12
+            pop     r13
+            pop     r14
+            pop     r15
+            add     rsp, 16     // skip vector, error_code
+            swapgs
+            iretq",
+            tf = in(reg) &self.0,
+            options(noreturn),
+        )
+    }
+}
+
+#[cfg(feature = "uspace")]
+impl core::ops::Deref for UserContext {
+    type Target = TrapFrame;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(feature = "uspace")]
+impl core::ops::DerefMut for UserContext {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
 }
 
 #[repr(C)]
@@ -177,6 +331,18 @@ impl TaskContext {
         self.fs_base = tls_area.as_usize();
     }
 
+    /// Returns the program counter and the frame pointer where the task is
+    /// switched out, to walk its stack.
+    ///
+    /// # Safety
+    ///
+    /// The task must not be running, and the stack pointed by the context
+    /// must be valid.
+    pub unsafe fn switch_frame(&self) -> (usize, usize) {
+        let frame = unsafe { &*(self.rsp as *const ContextSwitchFrame) };
+        (frame.rip as usize, frame.rbp as usize)
+    }
+
     /// Switches to another task.
     ///
     /// It first saves the current task's context from CPU to this place, and then
@@ -192,6 +358,10 @@ impl TaskContext {
             self.fs_base = super::read_thread_pointer();
             unsafe { super::write_thread_pointer(next_ctx.fs_base) };
         }
+        #[cfg(feature = "uspace")]
+        unsafe {
+            super::set_kernel_stack_top(next_ctx.kstack_top)
+        };
         unsafe { context_switch(&mut self.rsp, &next_ctx.rsp) }
     }
 }
@@ -6,6 +6,13 @@ use x86_64::structures::gdt::{Descriptor, DescriptorFlags};
 use x86_64::structures::{tss::TaskStateSegment, DescriptorTablePointer};
 use x86_64::{addr::VirtAddr, PrivilegeLevel};
 
+/// The Task State Segment (TSS) of each CPU.
+///
+/// It holds the interrupt stacks, and the kernel stack (`RSP0`) for traps
+/// from user space.
+#[percpu::def_percpu]
+pub static TSS: TaskStateSegment = TaskStateSegment::new();
+
 /// A wrapper of the Global Descriptor Table (GDT) with maximum 16 entries.
 #[repr(align(16))]
 pub struct GdtStruct {
@@ -13,6 +13,14 @@ pub struct IdtStruct {
 }
 
 impl IdtStruct {
+    /// The index of the Interrupt Stack Table (IST) entry in the TSS, which
+    /// is used as the stack of the double fault handler.
+    ///
+    /// The handler needs a known good stack, as a double fault usually occurs
+    /// when the page fault handler can't push the trap frame on an overflowed
+    /// kernel stack.
+    pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+
     /// Constructs a new IDT struct that filled with entries from
     /// `trap_handler_table`.
     #[allow(clippy::new_without_default)]
@@ -33,7 +41,10 @@ impl IdtStruct {
         };
         for i in 0..NUM_INT {
             #[allow(clippy::missing_transmute_annotations)]
-            entries[i].set_handler_fn(unsafe { core::mem::transmute(ENTRIES[i]) });
+            let opts = entries[i].set_handler_fn(unsafe { core::mem::transmute(ENTRIES[i]) });
+            if i == x86::irq::DOUBLE_FAULT_VECTOR as usize {
+                unsafe { opts.set_stack_index(Self::DOUBLE_FAULT_IST_INDEX) };
+            }
         }
         idt
     }
@@ -5,6 +5,9 @@ mod idt;
 #[cfg(target_os = "none")]
 mod trap;
 
+#[cfg(feature = "uspace")]
+mod syscall;
+
 use core::arch::asm;
 
 use memory_addr::{MemoryAddr, PhysAddr, VirtAddr};
@@ -12,10 +15,15 @@ use x86::{controlregs, msr, tlb};
 use x86_64::instructions::interrupts;
 
 pub use self::context::{ExtendedState, FxsaveArea, TaskContext, TrapFrame};
-pub use self::gdt::GdtStruct;
+pub use self::gdt::{GdtStruct, TSS};
 pub use self::idt::IdtStruct;
 pub use x86_64::structures::tss::TaskStateSegment;
 
+#[cfg(feature = "uspace")]
+pub use self::context::UserContext;
+#[cfg(feature = "uspace")]
+pub(crate) use self::syscall::{init_syscall, set_kernel_stack_top};
+
 /// Allows the current CPU to respond to interrupts.
 #[inline]
 pub fn enable_irqs() {
@@ -56,6 +64,20 @@ pub fn wait_for_irqs() {
     }
 }
 
+/// Enables interrupts and waits for one atomically, with interrupts disabled
+/// on entry and enabled on return.
+///
+/// `sti` only takes effect after the following `hlt`, so no interrupt can be
+/// handled between them and leave the CPU halted.
+#[inline]
+pub fn enable_irqs_and_wait() {
+    if cfg!(target_os = "none") {
+        interrupts::enable_and_hlt()
+    } else {
+        core::hint::spin_loop()
+    }
+}
+
 /// Halt the current CPU.
 #[inline]
 pub fn halt() {
@@ -90,6 +112,11 @@ pub unsafe fn write_page_table_root(root_paddr: PhysAddr) {
 /// entry that maps the given virtual address.
 #[inline]
 pub fn flush_tlb(vaddr: Option<VirtAddr>) {
+    // Hosted builds (e.g., unit tests) run in user mode, and have no TLB to
+    // manage.
+    if cfg!(not(target_os = "none")) {
+        return;
+    }
     if let Some(vaddr) = vaddr {
         unsafe { tlb::flush(vaddr.into()) }
     } else {
@@ -97,6 +124,14 @@ pub fn flush_tlb(vaddr: Option<VirtAddr>) {
     }
 }
 
+/// Reads the frame pointer (`RBP`) of the current function.
+#[inline(always)]
+pub fn read_frame_pointer() -> usize {
+    let fp;
+    unsafe { core::arch::asm!("mov {}, rbp", out(reg) fp) };
+    fp
+}
+
 /// Reads the thread pointer of the current CPU.
 ///
 /// It is used to implement TLS (Thread Local Storage).
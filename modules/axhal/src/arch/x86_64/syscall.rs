@@ -0,0 +1,81 @@
+use memory_addr::VirtAddr;
+use x86_64::registers::model_specific::{Efer, EferFlags, KernelGsBase, LStar, SFMask, Star};
+use x86_64::registers::rflags::RFlags;
+
+use super::{GdtStruct, TrapFrame, TSS};
+
+/// The user stack pointer saved on syscall entry, before any register can be
+/// pushed to the kernel stack.
+#[percpu::def_percpu]
+static USER_RSP: usize = 0;
+
+/// The kernel stack pointer loaded on syscall entry, same as `RSP0` in the
+/// TSS.
+#[percpu::def_percpu]
+static KERNEL_RSP: usize = 0;
+
+core::arch::global_asm!(
+    include_str!("syscall.S"),
+    user_rsp = sym __PERCPU_USER_RSP,
+    kernel_rsp = sym __PERCPU_KERNEL_RSP,
+    ucode_selector = const GdtStruct::UCODE64_SELECTOR.0,
+    udata_selector = const GdtStruct::UDATA_SELECTOR.0,
+);
+
+#[no_mangle]
+fn x86_syscall_handler(tf: &mut TrapFrame) {
+    tf.rax = crate::trap::handle_syscall(tf, tf.rax as usize) as u64;
+    // `sysretq` raises #GP in kernel mode with the user RSP if RIP is not
+    // canonical, so fail it as the user jump would have.
+    if !is_canonical(tf.rip) {
+        crate::trap::handle_user_exception(
+            tf,
+            crate::trap::UserException::Other(x86::irq::GENERAL_PROTECTION_FAULT_VECTOR as _),
+        );
+    }
+}
+
+/// Whether `vaddr` is a canonical address with 48-bit virtual addresses.
+pub(super) const fn is_canonical(vaddr: u64) -> bool {
+    ((vaddr << 16) as i64 >> 16) as u64 == vaddr
+}
+
+/// Sets the kernel stack used by traps and syscalls from user space.
+///
+/// # Safety
+///
+/// It must be called with preemption disabled, and `kstack_top` must be the
+/// top of a valid kernel stack.
+pub(crate) unsafe fn set_kernel_stack_top(kstack_top: VirtAddr) {
+    let top = kstack_top.as_usize();
+    TSS.current_ref_mut_raw().privilege_stack_table[0] = x86_64::VirtAddr::new(top as u64);
+    KERNEL_RSP.write_current_raw(top);
+}
+
+/// Enables the `syscall` instruction on the current CPU.
+pub(crate) fn init_syscall() {
+    extern "C" {
+        fn syscall_entry();
+    }
+    LStar::write(x86_64::VirtAddr::new(syscall_entry as usize as _));
+    Star::write(
+        GdtStruct::UCODE64_SELECTOR,
+        GdtStruct::UDATA_SELECTOR,
+        GdtStruct::KCODE64_SELECTOR,
+        GdtStruct::KDATA_SELECTOR,
+    )
+    .unwrap();
+    // Clear these flags on syscall entry, as what an interrupt gate does.
+    SFMask::write(
+        RFlags::TRAP_FLAG
+            | RFlags::INTERRUPT_FLAG
+            | RFlags::DIRECTION_FLAG
+            | RFlags::IOPL_LOW
+            | RFlags::IOPL_HIGH
+            | RFlags::NESTED_TASK
+            | RFlags::ALIGNMENT_CHECK,
+    );
+    // The user GS base, swapped in before entering user space.
+    KernelGsBase::write(x86_64::VirtAddr::new(0));
+    unsafe { Efer::update(|efer| *efer |= EferFlags::SYSTEM_CALL_EXTENSIONS) };
+}
@@ -3,6 +3,8 @@ use x86::{controlregs::cr2, irq::*};
 use x86_64::structures::idt::PageFaultErrorCode;
 
 use super::context::TrapFrame;
+#[cfg(feature = "uspace")]
+use crate::trap::{handle_user_exception, UserException};
 
 core::arch::global_asm!(include_str!("trap.S"));
 
@@ -14,6 +16,10 @@ fn handle_page_fault(tf: &TrapFrame) {
         .unwrap_or_else(|e| panic!("Invalid #PF error code: {:#x}", e));
     let vaddr = va!(unsafe { cr2() });
     if !handle_trap!(PAGE_FAULT, vaddr, access_flags, tf.is_user()) {
+        #[cfg(feature = "uspace")]
+        if tf.is_user() {
+            handle_user_exception(tf, UserException::PageFault(vaddr, access_flags));
+        }
         panic!(
             "Unhandled {} #PF @ {:#x}, fault_vaddr={:#x}, error_code={:#x} ({:?}):\n{:#x?}",
             if tf.is_user() { "user" } else { "kernel" },
@@ -26,11 +32,35 @@ fn handle_page_fault(tf: &TrapFrame) {
     }
 }
 
+fn handle_double_fault(tf: &TrapFrame) -> ! {
+    // It usually occurs when the trap frame of a page fault can't be pushed
+    // onto the kernel stack, e.g., on a stack overflow. Give the page fault
+    // handlers a chance to recognize it, as CR2 still holds the fault address.
+    let vaddr = va!(unsafe { cr2() });
+    handle_trap!(PAGE_FAULT, vaddr, MappingFlags::WRITE, false);
+    panic!("#DF @ {:#x}, fault_vaddr={:#x}:\n{:#x?}", tf.rip, vaddr, tf);
+}
+
 #[no_mangle]
 fn x86_trap_handler(tf: &TrapFrame) {
     match tf.vector as u8 {
         PAGE_FAULT_VECTOR => handle_page_fault(tf),
         BREAKPOINT_VECTOR => debug!("#BP @ {:#x} ", tf.rip),
+        DOUBLE_FAULT_VECTOR => handle_double_fault(tf),
+        #[cfg(feature = "uspace")]
+        INVALID_OPCODE_VECTOR if tf.is_user() => {
+            handle_user_exception(tf, UserException::IllegalInstruction);
+        }
+        // Faults caused by user code, e.g., #GP, #SS, #DE. NMIs and #MC are
+        // not related to the code that is interrupted.
+        #[cfg(feature = "uspace")]
+        vector
+            if tf.is_user()
+                && vector < IRQ_VECTOR_START
+                && !matches!(vector, NONMASKABLE_INTERRUPT_VECTOR | MACHINE_CHECK_VECTOR) =>
+        {
+            handle_user_exception(tf, UserException::Other(vector as _));
+        }
         GENERAL_PROTECTION_FAULT_VECTOR => {
             panic!(
                 "#GP @ {:#x}, error_code={:#x}:\n{:#x?}",
@@ -0,0 +1,102 @@
+//! Stack backtraces by walking the chain of frame pointers.
+//!
+//! It only works if the kernel is compiled with frame pointers (e.g., with
+//! `-C force-frame-pointers=yes`), otherwise the backtraces are truncated.
+//! The return addresses are not symbolized, they can be resolved by
+//! `addr2line` with the kernel ELF.
+
+use core::fmt;
+use core::ops::Range;
+
+use crate::arch::TaskContext;
+
+/// The maximum number of frames to walk.
+const MAX_DEPTH: usize = 64;
+
+/// A backtrace of a stack, which iterates over the program counters of each
+/// frame, from the innermost to the outermost.
+#[derive(Clone)]
+pub struct Backtrace {
+    pc: usize,
+    fp: usize,
+    stack: Range<usize>,
+    depth: usize,
+}
+
+impl Backtrace {
+    /// Captures the backtrace of the caller, on the stack occupying the
+    /// address range `stack`.
+    #[inline(always)]
+    pub fn current(stack: Range<usize>) -> Self {
+        Self::new(0, crate::arch::read_frame_pointer(), stack)
+    }
+
+    /// Captures the backtrace of a task that is switched out with the context
+    /// `ctx`, on the stack occupying the address range `stack`.
+    ///
+    /// # Safety
+    ///
+    /// The task must not be running, and its stack must be valid during the
+    /// iteration.
+    pub unsafe fn from_task_context(ctx: &TaskContext, stack: Range<usize>) -> Self {
+        let (pc, fp) = unsafe { ctx.switch_frame() };
+        Self::new(pc, fp, stack)
+    }
+
+    const fn new(pc: usize, fp: usize, stack: Range<usize>) -> Self {
+        Self {
+            pc,
+            fp,
+            stack,
+            depth: 0,
+        }
+    }
+
+    /// Reads the previous frame pointer and the return address saved in the
+    /// frame `fp`, if the frame record lies in the stack.
+    fn read_frame(&self, fp: usize) -> Option<(usize, usize)> {
+        const WORD: usize = core::mem::size_of::<usize>();
+        // The address of the frame record `[prev_fp, return_address]`.
+        #[cfg(any(target_arch = "riscv32", target_arch = "riscv64"))]
+        let record = fp.checked_sub(2 * WORD)?;
+        #[cfg(not(any(target_arch = "riscv32", target_arch = "riscv64")))]
+        let record = fp;
+        if record % WORD != 0 || record < self.stack.start || record + 2 * WORD > self.stack.end {
+            return None;
+        }
+        let record = record as *const usize;
+        // Safety: the frame record is in the stack, which is valid.
+        unsafe { Some((record.read_volatile(), record.add(1).read_volatile())) }
+    }
+}
+
+impl Iterator for Backtrace {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.depth >= MAX_DEPTH {
+            return None;
+        }
+        self.depth += 1;
+        if self.pc != 0 {
+            return Some(core::mem::take(&mut self.pc));
+        }
+        let (prev_fp, ret_addr) = self.read_frame(self.fp)?;
+        // Frames of callers are at higher addresses, it also stops loops.
+        self.fp = if prev_fp > self.fp { prev_fp } else { 0 };
+        if ret_addr == 0 {
+            self.depth = MAX_DEPTH;
+            return None;
+        }
+        Some(ret_addr)
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, pc) in self.clone().enumerate() {
+            writeln!(f, "  #{:<2} {:#x}", i, pc)?;
+        }
+        Ok(())
+    }
+}
@@ -69,10 +69,21 @@ pub unsafe fn set_current_task_ptr<T>(ptr: *const T) {
     #[cfg(target_arch = "aarch64")]
     {
         use tock_registers::interfaces::Writeable;
+        // Also saved in `CURRENT_TASK_PTR`, as `SP_EL0` is overwritten in
+        // user space.
+        CURRENT_TASK_PTR.write_current_raw(ptr as usize);
         aarch64_cpu::registers::SP_EL0.set(ptr as u64)
     }
 }
 
+/// Restores the current task pointer in `SP_EL0` from `CURRENT_TASK_PTR`,
+/// called on trap entry.
+#[cfg(target_arch = "aarch64")]
+pub(crate) extern "C" fn cache_current_task_ptr() {
+    use tock_registers::interfaces::Writeable;
+    aarch64_cpu::registers::SP_EL0.set(unsafe { CURRENT_TASK_PTR.read_current_raw() } as u64);
+}
+
 #[allow(dead_code)]
 pub(crate) fn init_primary(cpu_id: usize) {
     percpu::init(axconfig::SMP);
@@ -0,0 +1,239 @@
+//! Inter-processor interrupts (IPIs).
+//!
+//! Besides interrupting other CPUs with [`send_ipi`], it supports cross-CPU
+//! function calls ([`run_on_cpu`], [`run_on_other_cpus`]) and TLB shootdown
+//! ([`flush_tlb_all_cpus`]). Functions to call are put into the per-CPU call
+//! queues of the target CPUs, and are called by the IPI handler there with
+//! IRQs disabled.
+//!
+//! A CPU can only receive IPIs after [`init_percpu`] is called on it, and until
+//! [`deinit_percpu`] is called when it goes offline.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axconfig::SMP;
+use axlockdep::spin::SpinNoIrq;
+use memory_addr::VirtAddr;
+
+use crate::cpu::{this_cpu_id, this_cpu_is_bsp};
+
+pub use crate::platform::irq::IPI_IRQ_NUM;
+
+/// The maximum number of pending calls on each CPU.
+const CALL_QUEUE_SIZE: usize = 16;
+
+#[derive(Clone, Copy)]
+struct IpiCall {
+    func: fn(usize),
+    arg: usize,
+    /// The number of unfinished calls the caller is waiting for, if any.
+    pending: Option<*const AtomicUsize>,
+}
+
+// The caller keeps `pending` alive until it drops to zero.
+unsafe impl Send for IpiCall {}
+
+struct CallQueue {
+    calls: [Option<IpiCall>; CALL_QUEUE_SIZE],
+    head: usize,
+    len: usize,
+    /// Whether the CPU can receive IPIs.
+    online: bool,
+}
+
+impl CallQueue {
+    const fn new() -> Self {
+        Self {
+            calls: [None; CALL_QUEUE_SIZE],
+            head: 0,
+            len: 0,
+            online: false,
+        }
+    }
+
+    fn push(&mut self, call: IpiCall) -> Result<(), IpiCall> {
+        if self.len == CALL_QUEUE_SIZE {
+            return Err(call);
+        }
+        self.calls[(self.head + self.len) % CALL_QUEUE_SIZE] = Some(call);
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<IpiCall> {
+        if self.len == 0 {
+            return None;
+        }
+        let call = self.calls[self.head].take();
+        self.head = (self.head + 1) % CALL_QUEUE_SIZE;
+        self.len -= 1;
+        call
+    }
+}
+
+static CALL_QUEUES: [SpinNoIrq<CallQueue>; SMP] = [QUEUE_REPEAT_VALUE; SMP];
+#[allow(clippy::declare_interior_mutable_const)]
+const QUEUE_REPEAT_VALUE: SpinNoIrq<CallQueue> = SpinNoIrq::new(CallQueue::new());
+
+/// Sends an IPI to the given CPU, without any function to call.
+///
+/// It can be used to wake up the CPU from [`wait_for_irqs`], or to make it
+/// check for rescheduling on return from the interrupt.
+///
+/// [`wait_for_irqs`]: crate::arch::wait_for_irqs
+pub fn send_ipi(cpu_id: usize) {
+    crate::platform::irq::send_ipi(cpu_id);
+}
+
+/// Whether the given CPU can receive IPIs.
+pub fn cpu_online(cpu_id: usize) -> bool {
+    cpu_id < SMP && CALL_QUEUES[cpu_id].lock().online
+}
+
+/// Calls `func(arg)` on the given CPU in its IPI handler.
+///
+/// If `cpu_id` is the current CPU, `func` is called directly with IRQs
+/// disabled. If `wait` is true, it returns after `func` has finished.
+///
+/// Returns `false` if the CPU can't receive IPIs.
+pub fn run_on_cpu(cpu_id: usize, func: fn(usize), arg: usize, wait: bool) -> bool {
+    // Stay on this CPU, or we may call ourselves via IPIs.
+    let _guard = kernel_guard::NoPreempt::new();
+    if cpu_id == this_cpu_id() {
+        let _irq_guard = kernel_guard::IrqSave::new();
+        func(arg);
+        return true;
+    }
+
+    let pending = AtomicUsize::new(1);
+    if !queue_call(cpu_id, func, arg, wait.then_some(&pending)) {
+        warn!("run_on_cpu: CPU {} is offline", cpu_id);
+        return false;
+    }
+    if wait {
+        wait_for(&pending);
+    }
+    true
+}
+
+/// Calls `func(arg)` on all other online CPUs in their IPI handlers.
+///
+/// If `wait` is true, it returns after `func` has finished on all of them.
+pub fn run_on_other_cpus(func: fn(usize), arg: usize, wait: bool) {
+    let _guard = kernel_guard::NoPreempt::new();
+    let this_cpu = this_cpu_id();
+    let pending = AtomicUsize::new(0);
+    for cpu_id in (0..SMP).filter(|&id| id != this_cpu) {
+        if wait {
+            pending.fetch_add(1, Ordering::Relaxed);
+        }
+        if !queue_call(cpu_id, func, arg, wait.then_some(&pending)) && wait {
+            pending.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+    if wait {
+        wait_for(&pending);
+    }
+}
+
+/// Flushes the TLB on all online CPUs, and returns after all of them are done.
+///
+/// If `vaddr` is [`None`], flushes the entire TLB. Otherwise, flushes the TLB
+/// entry that maps the given virtual address.
+pub fn flush_tlb_all_cpus(vaddr: Option<VirtAddr>) {
+    // `usize::MAX` is never page-aligned, so it can't be a valid `vaddr`.
+    let arg = vaddr.map_or(usize::MAX, VirtAddr::as_usize);
+    crate::arch::flush_tlb(vaddr);
+    run_on_other_cpus(flush_tlb_handler, arg, true);
+}
+
+fn flush_tlb_handler(arg: usize) {
+    let vaddr = (arg != usize::MAX).then(|| VirtAddr::from(arg));
+    crate::arch::flush_tlb(vaddr);
+}
+
+/// Puts a call into the queue of `cpu_id`, and interrupts it.
+///
+/// Returns `false` if the CPU is offline.
+fn queue_call(cpu_id: usize, func: fn(usize), arg: usize, pending: Option<&AtomicUsize>) -> bool {
+    let mut call = IpiCall {
+        func,
+        arg,
+        pending: pending.map(|p| p as *const _),
+    };
+    loop {
+        let mut queue = CALL_QUEUES[cpu_id].lock();
+        if !queue.online {
+            return false;
+        }
+        match queue.push(call) {
+            Ok(()) => break,
+            Err(c) => call = c,
+        }
+        drop(queue);
+        // The queue is full, make sure the target is draining it, and serve
+        // our own calls meanwhile in case it's waiting for us.
+        send_ipi(cpu_id);
+        handle_local_calls();
+        core::hint::spin_loop();
+    }
+    send_ipi(cpu_id);
+    true
+}
+
+/// Waits for all calls sharing the `pending` counter to finish.
+fn wait_for(pending: &AtomicUsize) {
+    while pending.load(Ordering::Acquire) != 0 {
+        // Two CPUs may wait for each other with IRQs disabled, so serve the
+        // calls to this CPU here to avoid deadlocks.
+        handle_local_calls();
+        core::hint::spin_loop();
+    }
+}
+
+fn take_call(cpu_id: usize) -> Option<IpiCall> {
+    CALL_QUEUES[cpu_id].lock().pop()
+}
+
+/// Calls all pending functions queued for the current CPU.
+fn handle_local_calls() {
+    let _guard = kernel_guard::IrqSave::new();
+    let cpu_id = this_cpu_id();
+    while let Some(call) = take_call(cpu_id) {
+        (call.func)(call.arg);
+        if let Some(pending) = call.pending {
+            unsafe { (*pending).fetch_sub(1, Ordering::Release) };
+        }
+    }
+}
+
+fn ipi_handler() {
+    trace!("IPI on CPU {}", this_cpu_id());
+    handle_local_calls();
+}
+
+/// Initializes IPI handling on the current CPU.
+///
+/// It registers the IPI handler when called on the primary CPU. It must be
+/// called before enabling IRQs on each CPU.
+pub fn init_percpu() {
+    if this_cpu_is_bsp() {
+        crate::irq::register_handler(IPI_IRQ_NUM, ipi_handler);
+    } else {
+        // IPIs may be banked per CPU in the interrupt controller.
+        crate::irq::set_enable(IPI_IRQ_NUM, true);
+    }
+    CALL_QUEUES[this_cpu_id()].lock().online = true;
+}
+
+/// Stops IPI handling on the current CPU before it goes offline.
+///
+/// Pending calls are called before it returns, and no calls can be queued for
+/// the CPU after that. It must be called with IRQs disabled.
+pub fn deinit_percpu() {
+    let cpu_id = this_cpu_id();
+    CALL_QUEUES[cpu_id].lock().online = false;
+    // Calls can't be queued after marking offline, so it drains the queue.
+    handle_local_calls();
+    crate::irq::set_enable(IPI_IRQ_NUM, false);
+}
@@ -15,6 +15,16 @@ pub type IrqHandler = handler_table::Handler;
 
 static IRQ_HANDLER_TABLE: HandlerTable<MAX_IRQ_COUNT> = HandlerTable::new();
 
+/// The nesting depth of [`handler_irq`] on each CPU.
+#[percpu::def_percpu]
+static IRQ_DEPTH: usize = 0;
+
+/// Returns `true` if the current CPU is running an IRQ handler.
+#[inline]
+pub fn in_irq() -> bool {
+    IRQ_DEPTH.read_current() > 0
+}
+
 /// Platform-independent IRQ dispatching.
 #[allow(dead_code)]
 pub(crate) fn dispatch_irq_common(irq_num: usize) {
@@ -46,7 +56,14 @@ pub(crate) fn register_handler_common(irq_num: usize, handler: IrqHandler) -> bo
 #[register_trap_handler(IRQ)]
 pub fn handler_irq(irq_num: usize) -> bool {
     let guard = kernel_guard::NoPreempt::new();
+    // IRQs are disabled in IRQ handlers.
+    unsafe { IRQ_DEPTH.write_current_raw(IRQ_DEPTH.read_current_raw() + 1) };
+    #[cfg(feature = "trace")]
+    axtrace::record(axtrace::TraceEvent::IrqEnter { irq: irq_num });
     dispatch_irq(irq_num);
+    #[cfg(feature = "trace")]
+    axtrace::record(axtrace::TraceEvent::IrqExit { irq: irq_num });
+    unsafe { IRQ_DEPTH.write_current_raw(IRQ_DEPTH.read_current_raw() - 1) };
     drop(guard); // rescheduling may occur when preemption is re-enabled.
     true
 }
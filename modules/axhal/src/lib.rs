@@ -19,7 +19,10 @@
 //! - `smp`: Enable SMP (symmetric multiprocessing) support.
 //! - `fp_simd`: Enable floating-point and SIMD support.
 //! - `paging`: Enable page table manipulation.
+//! - `uspace`: Enable user space support, i.e., entering user mode and
+//!   handling system calls.
 //! - `irq`: Enable interrupt handling support.
+//! - `trace`: Record IRQ entries and exits with [axtrace].
 //!
 //! [ArceOS]: https://github.com/arceos-org/arceos
 //! [cargo test]: https://doc.rust-lang.org/cargo/guide/tests.html
@@ -44,6 +47,7 @@ mod platform;
 pub mod trap;
 
 pub mod arch;
+pub mod backtrace;
 pub mod cpu;
 pub mod mem;
 pub mod time;
@@ -54,6 +58,9 @@ pub mod tls;
 #[cfg(feature = "irq")]
 pub mod irq;
 
+#[cfg(feature = "irq")]
+pub mod ipi;
+
 #[cfg(feature = "paging")]
 pub mod paging;
 
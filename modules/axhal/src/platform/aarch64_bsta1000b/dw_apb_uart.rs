@@ -1,8 +1,8 @@
 //! snps,dw-apb-uart serial driver
 
 use crate::mem::phys_to_virt;
+use axlockdep::spin::SpinNoIrq;
 use dw_apb_uart::DW8250;
-use kspin::SpinNoIrq;
 use memory_addr::PhysAddr;
 
 const UART_BASE: PhysAddr = pa!(axconfig::UART_PADDR);
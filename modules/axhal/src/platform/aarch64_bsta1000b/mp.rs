@@ -5,6 +5,10 @@ pub const MAX_HARTS: usize = 8;
 /// CPU HWID from cpu device tree nodes with "reg" property
 pub const CPU_HWID: [usize; MAX_HARTS] = [0x00, 0x100, 0x200, 0x300, 0x400, 0x500, 0x600, 0x700];
 
+/// Whether a CPU stopped by [`stop_this_cpu`] can be restarted by
+/// [`start_secondary_cpu`].
+pub const CPU_RESTARTABLE: bool = true;
+
 /// Starts the given secondary CPU with its boot stack.
 pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
     if cpu_id >= MAX_HARTS {
@@ -21,3 +25,14 @@ pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
         stack_top.as_usize(),
     );
 }
+
+/// Powers off the current (secondary) CPU through PSCI `CPU_OFF`. It can be
+/// restarted later by [`start_secondary_cpu`].
+pub fn stop_this_cpu() -> ! {
+    crate::arch::disable_irqs();
+    crate::platform::aarch64_common::psci::cpu_off();
+    error!("failed to power off CPU {}", crate::cpu::this_cpu_id());
+    loop {
+        crate::arch::halt();
+    }
+}
@@ -1,6 +1,6 @@
 use crate::{irq::IrqHandler, mem::phys_to_virt};
 use arm_gicv2::{translate_irq, GicCpuInterface, GicDistributor, InterruptType};
-use kspin::SpinNoIrq;
+use axlockdep::spin::SpinNoIrq;
 use memory_addr::PhysAddr;
 
 /// The maximum number of IRQs.
@@ -14,6 +14,9 @@ pub const TIMER_IRQ_NUM: usize = translate_irq(14, InterruptType::PPI).unwrap();
 /// Non-secure EL2 Physical Timer irq number.
 pub const TIMER_IRQ_NUM: usize = translate_irq(10, InterruptType::PPI).unwrap();
 
+/// The IRQ number of inter-processor interrupts (SGI 1).
+pub const IPI_IRQ_NUM: usize = translate_irq(1, InterruptType::SGI).unwrap();
+
 /// The UART IRQ number.
 pub const UART_IRQ_NUM: usize = translate_irq(axconfig::UART_IRQ, InterruptType::SPI).unwrap();
 
@@ -41,6 +44,11 @@ pub fn register_handler(irq_num: usize, handler: IrqHandler) -> bool {
     crate::irq::register_handler_common(irq_num, handler)
 }
 
+/// Sends an inter-processor interrupt to the given CPU.
+pub fn send_ipi(cpu_id: usize) {
+    GICD.lock().send_sgi(cpu_id, IPI_IRQ_NUM);
+}
+
 /// Fetches the IRQ number.
 pub fn fetch_irq() -> usize {
     GICC.iar() as usize
@@ -1,7 +1,7 @@
 //! PL011 UART.
 
 use arm_pl011::Pl011Uart;
-use kspin::SpinNoIrq;
+use axlockdep::spin::SpinNoIrq;
 use memory_addr::PhysAddr;
 
 use crate::mem::phys_to_virt;
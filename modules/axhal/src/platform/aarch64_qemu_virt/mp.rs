@@ -1,5 +1,9 @@
 use crate::mem::{virt_to_phys, PhysAddr};
 
+/// Whether a CPU stopped by [`stop_this_cpu`] can be restarted by
+/// [`start_secondary_cpu`].
+pub const CPU_RESTARTABLE: bool = true;
+
 /// Starts the given secondary CPU with its boot stack.
 pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
     extern "C" {
@@ -8,3 +12,14 @@ pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
     let entry = virt_to_phys(va!(_start_secondary as usize));
     crate::platform::aarch64_common::psci::cpu_on(cpu_id, entry.as_usize(), stack_top.as_usize());
 }
+
+/// Powers off the current (secondary) CPU through PSCI `CPU_OFF`. It can be
+/// restarted later by [`start_secondary_cpu`].
+pub fn stop_this_cpu() -> ! {
+    crate::arch::disable_irqs();
+    crate::platform::aarch64_common::psci::cpu_off();
+    error!("failed to power off CPU {}", crate::cpu::this_cpu_id());
+    loop {
+        crate::arch::halt();
+    }
+}
@@ -24,6 +24,12 @@ unsafe extern "C" fn modify_stack_and_start() {
 
 pub static CPU_SPIN_TABLE: [PhysAddr; 4] = [pa!(0xd8), pa!(0xe0), pa!(0xe8), pa!(0xf0)];
 
+/// Whether a CPU stopped by [`stop_this_cpu`] can be restarted by
+/// [`start_secondary_cpu`].
+///
+/// CPUs booted by the spin table can't be released again.
+pub const CPU_RESTARTABLE: bool = false;
+
 /// Starts the given secondary CPU with its boot stack.
 pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
     let entry_paddr = virt_to_phys(va!(modify_stack_and_start as usize)).as_usize();
@@ -40,3 +46,17 @@ pub fn start_secondary_cpu(cpu_id: usize, stack_top: PhysAddr) {
     }
     aarch64_cpu::asm::sev();
 }
+
+/// Stops the current (secondary) CPU.
+///
+/// CPUs are booted by the spin table, which can't be re-entered once they have
+/// been released, so the CPU halts forever and can't be restarted.
+pub fn stop_this_cpu() -> ! {
+    warn!(
+        "CPU {} can't be restarted after stopped",
+        crate::cpu::this_cpu_id()
+    );
+    loop {
+        crate::arch::halt();
+    }
+}
@@ -1,7 +1,7 @@
 //! Definitions for snps,dw-apb-uart serial driver.
 //! Uart snps,dw-apb-uart driver in Rust for BST A1000b FADA board.
 use crate::mem::phys_to_virt;
-use kspin::SpinNoIrq;
+use axlockdep::spin::SpinNoIrq;
 use memory_addr::PhysAddr;
 
 use tock_registers::{
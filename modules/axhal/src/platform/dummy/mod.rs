@@ -22,8 +22,18 @@ pub mod misc {
 
 #[cfg(feature = "smp")]
 pub mod mp {
+    /// Whether a CPU stopped by [`stop_this_cpu`] can be restarted by
+    /// [`start_secondary_cpu`].
+    pub const CPU_RESTARTABLE: bool = true;
+
     /// Starts the given secondary CPU with its boot stack.
     pub fn start_secondary_cpu(cpu_id: usize, stack_top: crate::mem::PhysAddr) {}
+
+    /// Stops the current (secondary) CPU, which can be restarted later by
+    /// [`start_secondary_cpu`].
+    pub fn stop_this_cpu() -> ! {
+        unimplemented!()
+    }
 }
 
 pub mod mem {
@@ -68,6 +78,9 @@ pub mod irq {
     /// The timer IRQ number.
     pub const TIMER_IRQ_NUM: usize = 0;
 
+    /// The IRQ number of inter-processor interrupts.
+    pub const IPI_IRQ_NUM: usize = 1;
+
     /// Enables or disables the given IRQ.
     pub fn set_enable(irq_num: usize, enabled: bool) {}
 
@@ -76,6 +89,9 @@ pub mod irq {
         false
     }
 
+    /// Sends an inter-processor interrupt to the given CPU.
+    pub fn send_ipi(cpu_id: usize) {}
+
     /// Dispatches the IRQ.
     ///
     /// This function is called by the common interrupt handler. It looks
@@ -1,5 +1,9 @@
 use crate::mem::{virt_to_phys, PhysAddr};
 
+/// Whether a CPU stopped by [`stop_this_cpu`] can be restarted by
+/// [`start_secondary_cpu`].
+pub const CPU_RESTARTABLE: bool = true;
+
 /// Starts the given secondary CPU with its boot stack.
 pub fn start_secondary_cpu(hartid: usize, stack_top: PhysAddr) {
     extern "C" {
@@ -12,3 +16,18 @@ pub fn start_secondary_cpu(hartid: usize, stack_top: PhysAddr) {
     let entry = virt_to_phys(va!(_start_secondary as usize));
     sbi_rt::hart_start(hartid, entry.as_usize(), stack_top.as_usize());
 }
+
+/// Stops the current (secondary) hart through SBI HSM `hart_stop`. It can be
+/// restarted later by [`start_secondary_cpu`].
+pub fn stop_this_cpu() -> ! {
+    crate::arch::disable_irqs();
+    let res = sbi_rt::hart_stop();
+    error!(
+        "failed to stop hart {}: {:?}",
+        crate::cpu::this_cpu_id(),
+        res
+    );
+    loop {
+        crate::arch::halt();
+    }
+}
@@ -1,25 +1,33 @@
 //! Description tables (per-CPU GDT, per-CPU ISS, IDT)
 
-use crate::arch::{GdtStruct, IdtStruct, TaskStateSegment};
+use crate::arch::{GdtStruct, IdtStruct, TSS};
 use lazyinit::LazyInit;
+use x86_64::VirtAddr;
+
+/// Size of the per-CPU stack for the double fault handler.
+const DOUBLE_FAULT_STACK_SIZE: usize = 0x4000;
 
 static IDT: LazyInit<IdtStruct> = LazyInit::new();
 
 #[percpu::def_percpu]
-static TSS: LazyInit<TaskStateSegment> = LazyInit::new();
+static GDT: LazyInit<GdtStruct> = LazyInit::new();
 
 #[percpu::def_percpu]
-static GDT: LazyInit<GdtStruct> = LazyInit::new();
+static DOUBLE_FAULT_STACK: [u8; DOUBLE_FAULT_STACK_SIZE] = [0; DOUBLE_FAULT_STACK_SIZE];
 
 fn init_percpu() {
     unsafe {
         IDT.load();
         let tss = TSS.current_ref_mut_raw();
         let gdt = GDT.current_ref_mut_raw();
-        tss.init_once(TaskStateSegment::new());
+        let df_stack_top = DOUBLE_FAULT_STACK.current_ptr() as usize + DOUBLE_FAULT_STACK_SIZE;
+        tss.interrupt_stack_table[IdtStruct::DOUBLE_FAULT_IST_INDEX as usize] =
+            VirtAddr::new(df_stack_top as u64);
         gdt.init_once(GdtStruct::new(tss));
         gdt.load();
         gdt.load_tss();
+        #[cfg(feature = "uspace")]
+        crate::arch::init_syscall();
     }
 }
 
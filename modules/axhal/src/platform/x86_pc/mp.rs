@@ -28,6 +28,10 @@ unsafe fn setup_startup_page(stack_top: PhysAddr) {
     start_page[U64_PER_PAGE - 1] = ap_entry32 as usize as _; // entry
 }
 
+/// Whether a CPU stopped by [`stop_this_cpu`] can be restarted by
+/// [`start_secondary_cpu`].
+pub const CPU_RESTARTABLE: bool = true;
+
 /// Starts the given secondary CPU with its boot stack.
 pub fn start_secondary_cpu(apic_id: usize, stack_top: PhysAddr) {
     unsafe { setup_startup_page(stack_top) };
@@ -43,3 +47,15 @@ pub fn start_secondary_cpu(apic_id: usize, stack_top: PhysAddr) {
     busy_wait(Duration::from_micros(200)); // 200us
     unsafe { lapic.send_sipi(START_PAGE_IDX, apic_id) };
 }
+
+/// Stops the current (secondary) CPU, which can be restarted later by
+/// [`start_secondary_cpu`].
+///
+/// There is no firmware interface to power off a CPU, so it halts with IRQs
+/// disabled, until the INIT IPI sent by [`start_secondary_cpu`] resets it.
+pub fn stop_this_cpu() -> ! {
+    crate::arch::disable_irqs();
+    loop {
+        crate::arch::halt();
+    }
+}
@@ -1,6 +1,6 @@
 //! Uart 16550.
 
-use kspin::SpinNoIrq;
+use axlockdep::spin::SpinNoIrq;
 use x86_64::instructions::port::{Port, PortReadOnly, PortWriteOnly};
 
 const UART_CLOCK_FACTOR: usize = 16;
@@ -4,6 +4,9 @@ use linkme::distributed_slice as def_trap_handler;
 use memory_addr::VirtAddr;
 use page_table_entry::MappingFlags;
 
+#[cfg(feature = "uspace")]
+use crate::arch::TrapFrame;
+
 pub use linkme::distributed_slice as register_trap_handler;
 
 /// A slice of IRQ handler functions.
@@ -14,6 +17,38 @@ pub static IRQ: [fn(usize) -> bool];
 #[def_trap_handler]
 pub static PAGE_FAULT: [fn(VirtAddr, MappingFlags, bool) -> bool];
 
+/// A slice of syscall handler functions.
+///
+/// A handler is called with the trap frame of the user task and the syscall
+/// number, and returns the value to be returned to user space. The arguments
+/// can be read from the trap frame by [`TrapFrame::arg0`] and so on.
+#[cfg(feature = "uspace")]
+#[def_trap_handler]
+pub static SYSCALL: [fn(&TrapFrame, usize) -> isize];
+
+/// An exception raised by user code that the kernel can't resolve.
+#[cfg(feature = "uspace")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserException {
+    /// An access to `vaddr` that is not resolved by the page fault handler.
+    PageFault(VirtAddr, MappingFlags),
+    /// An undefined or privileged instruction.
+    IllegalInstruction,
+    /// Any other exception, e.g., a general protection fault or a misaligned
+    /// access. The number is the architecture-specific exception code.
+    Other(usize),
+}
+
+/// A slice of handler functions of exceptions raised by user code.
+///
+/// A handler is called with IRQs enabled, and never returns, as the user code
+/// can't make progress. It usually terminates the current task, whose
+/// kernel stack holding the trap frame is then abandoned. If no handler is
+/// registered, the kernel panics.
+#[cfg(feature = "uspace")]
+#[def_trap_handler]
+pub static USER_EXCEPTION: [fn(&TrapFrame, UserException) -> !];
+
 #[allow(unused_macros)]
 macro_rules! handle_trap {
     ($trap:ident, $($args:tt)*) => {{
@@ -29,3 +64,42 @@ macro_rules! handle_trap {
         }
     }}
 }
+
+/// Calls the syscall handler with IRQs enabled, and returns its result.
+///
+/// Returns `-ENOSYS` if no handler is registered.
+#[cfg(feature = "uspace")]
+pub(crate) fn handle_syscall(tf: &TrapFrame, syscall_num: usize) -> isize {
+    const ENOSYS: isize = 38;
+
+    let mut iter = SYSCALL.iter();
+    let Some(func) = iter.next() else {
+        warn!("No registered handler for trap SYSCALL");
+        return -ENOSYS;
+    };
+    if iter.next().is_some() {
+        warn!("Multiple handlers for trap SYSCALL are not currently supported");
+    }
+    #[cfg(feature = "irq")]
+    crate::arch::enable_irqs();
+    let ret = func(tf, syscall_num);
+    #[cfg(feature = "irq")]
+    crate::arch::disable_irqs();
+    ret
+}
+
+/// Calls the user exception handler with IRQs enabled, or panics if no handler
+/// is registered.
+#[cfg(feature = "uspace")]
+pub(crate) fn handle_user_exception(tf: &TrapFrame, exception: UserException) -> ! {
+    let mut iter = USER_EXCEPTION.iter();
+    let Some(func) = iter.next() else {
+        panic!("Unhandled user exception {:x?}:\n{:#x?}", exception, tf);
+    };
+    if iter.next().is_some() {
+        warn!("Multiple handlers for trap USER_EXCEPTION are not currently supported");
+    }
+    #[cfg(feature = "irq")]
+    crate::arch::enable_irqs();
+    func(tf, exception)
+}
@@ -0,0 +1,310 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) lock dependency validator
+//! (lockdep).
+//!
+//! Every tracked lock belongs to a lock class, which is identified by the
+//! place where the lock is created and the type of the lock (see
+//! [`LockClass`]). So all locks created at the same place, e.g., the locks in
+//! all instances of a struct, share the same class, like in Linux.
+//!
+//! The locks held by each context are recorded in acquisition order, where a
+//! context is a task, or the IRQ handlers on a CPU. When a context acquires a
+//! lock of class `B` while holding a lock of class `A`, the dependency
+//! `A -> B` is added to a global graph. If `B -> ... -> A` is already in the
+//! graph, the two acquisition orders can deadlock with each other (e.g., ABBA
+//! deadlocks). Such an inversion is reported the first time it is observed,
+//! with the acquisition stacks of both orders, even if the deadlock does not
+//! actually happen. It also reports blocking locks acquired with IRQs
+//! disabled.
+//!
+//! The tracked locks are the spinlocks in [`spin`], which are used by the
+//! kernel modules instead of the ones in [`kspin`], and the sleeping locks
+//! built on [`acquire`] and [`release`] (e.g., `axsync::Mutex`).
+//!
+//! All data is kept in tables of fixed size, so lockdep never allocates
+//! memory, and it can track the locks of the memory allocator. If a table is
+//! full, lockdep turns itself off after a warning. The current context, the
+//! IRQ state and the backtraces are provided by the [`LockdepIf`] interface,
+//! which must be implemented in other crates.
+//!
+//! # Cargo Features
+//!
+//! - `enabled`: Track the locks. Otherwise, [`spin`] is [`kspin`] itself, and
+//!   nothing is tracked.
+
+#![cfg_attr(not(test), no_std)]
+
+#[cfg(feature = "enabled")]
+#[macro_use]
+extern crate log;
+
+#[cfg(not(feature = "enabled"))]
+pub use kspin as spin;
+
+#[cfg(feature = "enabled")]
+pub mod spin;
+
+#[cfg(feature = "enabled")]
+mod state;
+
+#[cfg(feature = "enabled")]
+pub use self::tracking::*;
+
+#[cfg(feature = "enabled")]
+mod tracking {
+    use core::panic::Location;
+    use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    use crate_interface::call_interface;
+    use kernel_guard::IrqSave;
+
+    use crate::state::{State, Trace};
+
+    /// The bit set in the context IDs of IRQ handlers, see [`irq_context`].
+    const IRQ_CONTEXT: u64 = 1 << 63;
+
+    static ENABLED: AtomicBool = AtomicBool::new(true);
+    static NUM_REPORTS: AtomicUsize = AtomicUsize::new(0);
+    /// The state is only accessed with IRQs disabled. Preemption is not
+    /// touched, since the scheduler itself takes tracked locks.
+    static STATE: kspin::BaseSpinLock<IrqSave, State> = kspin::BaseSpinLock::new(State::new());
+
+    /// Extern interfaces that must be implemented in other crates.
+    #[crate_interface::def_interface]
+    pub trait LockdepIf {
+        /// Returns the ID of the current context, which is the task ID, or
+        /// [`irq_context`] in IRQ handlers. Returns [`None`] if no context is
+        /// available yet (e.g., in early boot), then nothing is tracked.
+        fn current_context() -> Option<u64>;
+
+        /// Returns `true` if IRQs are enabled on the current CPU.
+        fn irqs_enabled() -> bool;
+
+        /// Fills `pcs` with the program counters of the caller's stack frames,
+        /// returns the number of frames filled.
+        fn capture_backtrace(pcs: &mut [usize]) -> usize;
+    }
+
+    /// Returns the context ID of the IRQ handlers on the CPU `cpu_id`, which
+    /// never conflicts with task IDs.
+    pub const fn irq_context(cpu_id: usize) -> u64 {
+        IRQ_CONTEXT | cpu_id as u64
+    }
+
+    pub(crate) fn context_is_irq(ctx: u64) -> bool {
+        ctx & IRQ_CONTEXT != 0
+    }
+
+    /// The class of a tracked lock, embedded in the lock.
+    ///
+    /// It records where the lock is created, which is the caller of
+    /// [`LockClass::new`], or the caller of the outermost function marked
+    /// `#[track_caller]`. Together with the type name of the lock passed to
+    /// [`acquire`], it identifies the class.
+    pub struct LockClass {
+        site: &'static Location<'static>,
+    }
+
+    impl LockClass {
+        /// Creates the class of a lock created at the caller.
+        #[track_caller]
+        pub const fn new() -> Self {
+            Self {
+                site: Location::caller(),
+            }
+        }
+
+        /// Returns where the locks of this class are created.
+        pub fn site(&self) -> &'static Location<'static> {
+            self.site
+        }
+    }
+
+    impl Default for LockClass {
+        #[track_caller]
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    fn current_context() -> Option<u64> {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return None;
+        }
+        call_interface!(LockdepIf::current_context)
+    }
+
+    fn report(report: Option<impl core::fmt::Display>) {
+        if let Some(report) = report {
+            NUM_REPORTS.fetch_add(1, Ordering::Relaxed);
+            error!("{}", report);
+        }
+    }
+
+    fn check_limit(res: Result<(), &'static str>) {
+        if let Err(limit) = res {
+            if ENABLED.swap(false, Ordering::Relaxed) {
+                warn!(
+                    "lockdep: {} exceeded, turning off the lock validator",
+                    limit
+                );
+            }
+        }
+    }
+
+    /// Validates the acquisition of a lock of the `class` and type `name` by
+    /// the current context, and records it as held. It's called before the
+    /// lock is acquired, so that the problems are reported before the deadlock
+    /// happens.
+    ///
+    /// `blocking` is `true` if the task may sleep on the lock.
+    #[inline(always)]
+    pub fn acquire(class: &LockClass, name: &'static str, blocking: bool) {
+        let Some(ctx) = current_context() else {
+            return;
+        };
+        let irqs_disabled = blocking && !call_interface!(LockdepIf::irqs_enabled);
+        let trace = Trace::capture();
+        let mut reports = (None, None);
+        let res = STATE
+            .lock()
+            .acquire(ctx, class.site, name, trace, irqs_disabled, &mut reports);
+        check_limit(res);
+        report(reports.0);
+        report(reports.1);
+    }
+
+    /// Records a lock of the `class` and type `name` as held by the current
+    /// context after it was acquired by a `try_lock`, which never deadlocks,
+    /// so no dependency is added.
+    #[inline(always)]
+    pub fn try_acquired(class: &LockClass, name: &'static str) {
+        let Some(ctx) = current_context() else {
+            return;
+        };
+        let trace = Trace::capture();
+        let res = STATE.lock().try_acquired(ctx, class.site, name, trace);
+        check_limit(res);
+    }
+
+    /// Records a lock of the `class` and type `name` as released by the
+    /// current context.
+    ///
+    /// Locks may be released in any order. Releasing a lock that is not
+    /// recorded (e.g., acquired before the context is available) is ignored.
+    pub fn release(class: &LockClass, name: &'static str) {
+        let Some(ctx) = current_context() else {
+            return;
+        };
+        STATE.lock().release(ctx, class.site, name);
+    }
+
+    /// Returns the number of problems reported by lockdep so far.
+    pub fn num_reports() -> usize {
+        NUM_REPORTS.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if lockdep is still on, i.e., none of its tables has
+    /// been full.
+    pub fn is_enabled() -> bool {
+        ENABLED.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn capture_backtrace(pcs: &mut [usize]) -> usize {
+        call_interface!(LockdepIf::capture_backtrace, pcs)
+    }
+}
+
+#[cfg(all(test, feature = "enabled"))]
+mod tests {
+    use core::sync::atomic::{AtomicU64, Ordering};
+
+    use super::*;
+
+    static CONTEXT: AtomicU64 = AtomicU64::new(1);
+
+    struct LockdepIfImpl;
+
+    #[crate_interface::impl_interface]
+    impl LockdepIf for LockdepIfImpl {
+        fn current_context() -> Option<u64> {
+            Some(CONTEXT.load(Ordering::Relaxed))
+        }
+
+        fn irqs_enabled() -> bool {
+            true
+        }
+
+        fn capture_backtrace(_pcs: &mut [usize]) -> usize {
+            0
+        }
+    }
+
+    fn new_lock() -> spin::SpinRaw<()> {
+        spin::SpinRaw::new(())
+    }
+
+    // All tests share the global graph, so they run in one test.
+    #[test]
+    fn test_lockdep() {
+        // Locks created at the same place share a class.
+        let (a1, a2) = (new_lock(), new_lock());
+        let b = spin::SpinNoPreempt::new(());
+        let c = spin::SpinRaw::new(());
+
+        let reports = num_reports();
+        {
+            let _a = a1.lock();
+            let _b = b.lock();
+        }
+        {
+            let _b = b.lock();
+            let _c = c.lock();
+        }
+        assert_eq!(num_reports(), reports);
+
+        // `c -> a` closes the cycle `a -> b -> c`, although `a2` has never
+        // been locked before.
+        {
+            let _c = c.lock();
+            let _a = a2.lock();
+        }
+        assert_eq!(num_reports(), reports + 1);
+        // Reported only once.
+        {
+            let _c = c.lock();
+            let _a = a1.lock();
+        }
+        assert_eq!(num_reports(), reports + 1);
+
+        // Nested locks of the same class are not reported.
+        {
+            let _a1 = a1.lock();
+            let _a2 = a2.lock();
+        }
+        assert_eq!(num_reports(), reports + 1);
+
+        // Contexts are tracked separately: locking `b` in an IRQ handler while
+        // the task holds `d` doesn't add `d -> b`.
+        let d = spin::SpinRaw::new(());
+        let guard = d.lock();
+        CONTEXT.store(irq_context(0), Ordering::Relaxed);
+        {
+            let _b = b.lock();
+        }
+        CONTEXT.store(1, Ordering::Relaxed);
+        drop(guard);
+        {
+            let _b = b.lock();
+            let _d = d.lock();
+        }
+        assert_eq!(num_reports(), reports + 1);
+
+        // A `try_lock` doesn't add dependencies, but the lock is held.
+        {
+            let _d = d.try_lock().unwrap();
+            let _b = b.lock();
+        }
+        assert_eq!(num_reports(), reports + 2);
+        assert!(is_enabled());
+    }
+}
@@ -0,0 +1,153 @@
+//! Spinlocks imported from the [`kspin`] crate, which are tracked by lockdep.
+//!
+//! They have the same interface as the ones in [`kspin`]. The class of a lock
+//! is decided by where [`BaseSpinLock::new`] is called, see [`LockClass`].
+
+use core::any::type_name;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+use kernel_guard::{BaseGuard, NoOp, NoPreempt, NoPreemptIrqSave};
+
+use crate::LockClass;
+
+/// A spin lock that disables kernel preemption while trying to lock, and
+/// re-enables it after unlocking.
+pub type SpinNoPreempt<T> = BaseSpinLock<NoPreempt, T>;
+/// A guard that provides mutable data access for [`SpinNoPreempt`].
+pub type SpinNoPreemptGuard<'a, T> = BaseSpinLockGuard<'a, NoPreempt, T>;
+
+/// A spin lock that disables kernel preemption and local IRQs while trying to
+/// lock, and re-enables it after unlocking.
+pub type SpinNoIrq<T> = BaseSpinLock<NoPreemptIrqSave, T>;
+/// A guard that provides mutable data access for [`SpinNoIrq`].
+pub type SpinNoIrqGuard<'a, T> = BaseSpinLockGuard<'a, NoPreemptIrqSave, T>;
+
+/// A raw spin lock that does nothing while trying to lock.
+pub type SpinRaw<T> = BaseSpinLock<NoOp, T>;
+/// A guard that provides mutable data access for [`SpinRaw`].
+pub type SpinRawGuard<'a, T> = BaseSpinLockGuard<'a, NoOp, T>;
+
+/// A [`kspin::BaseSpinLock`] tracked by lockdep.
+pub struct BaseSpinLock<G: BaseGuard, T: ?Sized> {
+    class: LockClass,
+    inner: kspin::BaseSpinLock<G, T>,
+}
+
+/// A guard that provides mutable data access.
+///
+/// When the guard falls out of scope it will release the lock.
+pub struct BaseSpinLockGuard<'a, G: BaseGuard, T: ?Sized + 'a> {
+    class: &'a LockClass,
+    inner: kspin::BaseSpinLockGuard<'a, G, T>,
+}
+
+impl<G: BaseGuard, T> BaseSpinLock<G, T> {
+    /// Creates a new [`BaseSpinLock`] wrapping the supplied data.
+    #[inline(always)]
+    #[track_caller]
+    pub const fn new(data: T) -> Self {
+        Self {
+            class: LockClass::new(),
+            inner: kspin::BaseSpinLock::new(data),
+        }
+    }
+
+    /// Consumes this [`BaseSpinLock`] and unwraps the underlying data.
+    #[inline(always)]
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
+
+impl<G: BaseGuard, T: ?Sized> BaseSpinLock<G, T> {
+    /// Locks the [`BaseSpinLock`] and returns a guard that permits access to
+    /// the inner data.
+    #[inline(always)]
+    pub fn lock(&self) -> BaseSpinLockGuard<G, T> {
+        crate::acquire(&self.class, type_name::<Self>(), false);
+        BaseSpinLockGuard {
+            class: &self.class,
+            inner: self.inner.lock(),
+        }
+    }
+
+    /// Returns `true` if the lock is currently held.
+    #[inline(always)]
+    pub fn is_locked(&self) -> bool {
+        self.inner.is_locked()
+    }
+
+    /// Force unlock this [`BaseSpinLock`].
+    ///
+    /// # Safety
+    ///
+    /// This is *extremely* unsafe if the lock is not held by the current
+    /// thread.
+    #[inline(always)]
+    pub unsafe fn force_unlock(&self) {
+        crate::release(&self.class, type_name::<Self>());
+        self.inner.force_unlock()
+    }
+
+    /// Try to lock this [`BaseSpinLock`], returning a lock guard if
+    /// successful.
+    #[inline(always)]
+    pub fn try_lock(&self) -> Option<BaseSpinLockGuard<G, T>> {
+        let inner = self.inner.try_lock()?;
+        crate::try_acquired(&self.class, type_name::<Self>());
+        Some(BaseSpinLockGuard {
+            class: &self.class,
+            inner,
+        })
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+}
+
+impl<G: BaseGuard, T: ?Sized + Default> Default for BaseSpinLock<G, T> {
+    #[inline(always)]
+    #[track_caller]
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<G: BaseGuard, T: ?Sized + fmt::Debug> fmt::Debug for BaseSpinLock<G, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.fmt(f)
+    }
+}
+
+impl<'a, G: BaseGuard, T: ?Sized> Deref for BaseSpinLockGuard<'a, G, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, G: BaseGuard, T: ?Sized> DerefMut for BaseSpinLockGuard<'a, G, T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<'a, G: BaseGuard, T: ?Sized + fmt::Debug> fmt::Debug for BaseSpinLockGuard<'a, G, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, G: BaseGuard, T: ?Sized> Drop for BaseSpinLockGuard<'a, G, T> {
+    /// The lock is released by the inner guard after this.
+    #[inline(always)]
+    fn drop(&mut self) {
+        crate::release(self.class, type_name::<BaseSpinLock<G, T>>());
+    }
+}
@@ -0,0 +1,482 @@
+//! The lock classes, the dependency graph and the held locks, in tables of
+//! fixed size.
+
+use core::fmt;
+use core::panic::Location;
+
+use crate::tracking::{capture_backtrace, context_is_irq};
+
+/// The maximum number of lock classes.
+const MAX_CLASSES: usize = 1024;
+/// The number of buckets in the hash table of classes.
+const CLASS_HASH_SIZE: usize = MAX_CLASSES * 2;
+/// The maximum number of dependencies in the graph.
+const MAX_DEPS: usize = 4096;
+/// The maximum number of contexts holding locks at the same time.
+const MAX_CONTEXTS: usize = 128;
+/// The maximum number of locks held by a context at the same time.
+const MAX_HELD: usize = 24;
+/// The maximum number of reported inversions.
+const MAX_INVERSIONS: usize = 64;
+/// The maximum number of frames recorded for each acquisition.
+const MAX_TRACE_DEPTH: usize = 8;
+
+/// The end of a linked list of indices.
+const NIL: u16 = u16::MAX;
+
+/// The program counters of an acquisition.
+#[derive(Clone, Copy)]
+pub(crate) struct Trace {
+    len: usize,
+    pcs: [usize; MAX_TRACE_DEPTH],
+}
+
+impl Trace {
+    const EMPTY: Self = Self {
+        len: 0,
+        pcs: [0; MAX_TRACE_DEPTH],
+    };
+
+    #[inline(always)]
+    pub fn capture() -> Self {
+        let mut trace = Self::EMPTY;
+        trace.len = capture_backtrace(&mut trace.pcs).min(MAX_TRACE_DEPTH);
+        trace
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.len == 0 {
+            return writeln!(f, "  <no backtrace>");
+        }
+        for (i, pc) in self.pcs[..self.len].iter().enumerate() {
+            writeln!(f, "  #{:<2} {:#x}", i, pc)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Class {
+    site: Option<&'static Location<'static>>,
+    name: &'static str,
+    /// The next class in the same hash bucket.
+    next_in_bucket: u16,
+    /// The first dependency from this class.
+    deps: u16,
+    reported_blocking: bool,
+}
+
+impl Class {
+    const EMPTY: Self = Self {
+        site: None,
+        name: "",
+        next_in_bucket: NIL,
+        deps: NIL,
+        reported_blocking: false,
+    };
+}
+
+/// The dependency `from -> to`, recorded when it is first observed.
+#[derive(Clone, Copy)]
+pub(crate) struct Dependency {
+    to: u16,
+    /// The next dependency from the same class.
+    next: u16,
+    /// Where `from` was acquired.
+    held: Trace,
+    /// Where `to` was acquired while holding `from`.
+    acquired: Trace,
+}
+
+impl Dependency {
+    const EMPTY: Self = Self {
+        to: NIL,
+        next: NIL,
+        held: Trace::EMPTY,
+        acquired: Trace::EMPTY,
+    };
+}
+
+#[derive(Clone, Copy)]
+struct HeldLock {
+    class: u16,
+    trace: Trace,
+}
+
+/// The locks held by a context, in acquisition order.
+struct Context {
+    id: Option<u64>,
+    depth: usize,
+    held: [HeldLock; MAX_HELD],
+}
+
+impl Context {
+    const EMPTY: Self = Self {
+        id: None,
+        depth: 0,
+        held: [HeldLock {
+            class: NIL,
+            trace: Trace::EMPTY,
+        }; MAX_HELD],
+    };
+}
+
+/// The class information printed in reports.
+#[derive(Clone, Copy)]
+pub(crate) struct ClassInfo {
+    id: u16,
+    site: Option<&'static Location<'static>>,
+    name: &'static str,
+}
+
+impl fmt::Display for ClassInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} ({}", self.id, self.name)?;
+        if let Some(site) = self.site {
+            write!(f, " created at {}", site)?;
+        }
+        write!(f, ")")
+    }
+}
+
+struct ContextInfo(u64);
+
+impl fmt::Display for ContextInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if context_is_irq(self.0) {
+            write!(f, "IRQ handler on CPU {}", self.0 as u32)
+        } else {
+            write!(f, "task {}", self.0)
+        }
+    }
+}
+
+/// A possible deadlock found by lockdep, which is printed after the state is
+/// unlocked.
+pub(crate) struct InversionReport {
+    ctx: u64,
+    class: ClassInfo,
+    trace: Trace,
+    held: ClassInfo,
+    held_trace: Trace,
+    next: ClassInfo,
+    dep: Dependency,
+}
+
+/// A blocking lock acquired with IRQs disabled, which is printed after the
+/// state is unlocked.
+pub(crate) struct BlockingReport {
+    ctx: u64,
+    class: ClassInfo,
+    trace: Trace,
+}
+
+impl fmt::Display for InversionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "lockdep: possible circular locking dependency detected")?;
+        writeln!(
+            f,
+            "{} is trying to acquire lock {} at:",
+            ContextInfo(self.ctx),
+            self.class
+        )?;
+        write!(f, "{}", self.trace)?;
+        writeln!(f, "while holding lock {}, acquired at:", self.held)?;
+        write!(f, "{}", self.held_trace)?;
+        writeln!(
+            f,
+            "but the reverse order #{} -> ... -> #{} was observed before, \
+            where lock {} was acquired at:",
+            self.class.id, self.held.id, self.next
+        )?;
+        write!(f, "{}", self.dep.acquired)?;
+        writeln!(f, "while holding lock #{}, acquired at:", self.class.id)?;
+        write!(f, "{}", self.dep.held)
+    }
+}
+
+impl fmt::Display for BlockingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "lockdep: blocking lock acquired with IRQs disabled")?;
+        writeln!(
+            f,
+            "{} is trying to acquire lock {} at:",
+            ContextInfo(self.ctx),
+            self.class
+        )?;
+        write!(f, "{}", self.trace)
+    }
+}
+
+pub(crate) struct State {
+    classes: [Class; MAX_CLASSES],
+    num_classes: usize,
+    buckets: [u16; CLASS_HASH_SIZE],
+    deps: [Dependency; MAX_DEPS],
+    num_deps: usize,
+    contexts: [Context; MAX_CONTEXTS],
+    inversions: [(u16, u16); MAX_INVERSIONS],
+    num_inversions: usize,
+    /// Scratch space of the graph search: the visited classes, and the queue
+    /// of classes with the first dependencies on their paths.
+    visited: [u64; MAX_CLASSES / 64],
+    queue: [(u16, u16); MAX_CLASSES],
+}
+
+impl State {
+    pub const fn new() -> Self {
+        Self {
+            classes: [Class::EMPTY; MAX_CLASSES],
+            num_classes: 0,
+            buckets: [NIL; CLASS_HASH_SIZE],
+            deps: [Dependency::EMPTY; MAX_DEPS],
+            num_deps: 0,
+            contexts: [Context::EMPTY; MAX_CONTEXTS],
+            inversions: [(NIL, NIL); MAX_INVERSIONS],
+            num_inversions: 0,
+            visited: [0; MAX_CLASSES / 64],
+            queue: [(NIL, NIL); MAX_CLASSES],
+        }
+    }
+
+    fn class_info(&self, id: u16) -> ClassInfo {
+        let class = &self.classes[id as usize];
+        ClassInfo {
+            id,
+            site: class.site,
+            name: class.name,
+        }
+    }
+
+    /// Returns the ID of the class, registers it if it's new.
+    fn class_id(
+        &mut self,
+        site: &'static Location<'static>,
+        name: &'static str,
+    ) -> Result<u16, &'static str> {
+        if let Some(id) = self.find_class(site, name) {
+            return Ok(id);
+        }
+        if self.num_classes == MAX_CLASSES {
+            return Err("MAX_CLASSES");
+        }
+        let id = self.num_classes as u16;
+        let bucket = &mut self.buckets[class_hash(site, name)];
+        self.classes[id as usize] = Class {
+            site: Some(site),
+            name,
+            next_in_bucket: *bucket,
+            ..Class::EMPTY
+        };
+        *bucket = id;
+        self.num_classes += 1;
+        Ok(id)
+    }
+
+    /// Returns the ID of the class if it's registered.
+    ///
+    /// The same call site may have several [`Location`]s in different codegen
+    /// units, so they are compared by value.
+    fn find_class(&self, site: &'static Location<'static>, name: &'static str) -> Option<u16> {
+        let mut id = self.buckets[class_hash(site, name)];
+        while id != NIL {
+            let class = &self.classes[id as usize];
+            if class.site == Some(site) && class.name == name {
+                return Some(id);
+            }
+            id = class.next_in_bucket;
+        }
+        None
+    }
+
+    /// Returns the dependency `from -> to`.
+    fn find_dep(&self, from: u16, to: u16) -> Option<&Dependency> {
+        let mut idx = self.classes[from as usize].deps;
+        while idx != NIL {
+            let dep = &self.deps[idx as usize];
+            if dep.to == to {
+                return Some(dep);
+            }
+            idx = dep.next;
+        }
+        None
+    }
+
+    fn add_dep(
+        &mut self,
+        from: u16,
+        to: u16,
+        held: Trace,
+        acquired: Trace,
+    ) -> Result<(), &'static str> {
+        if self.num_deps == MAX_DEPS {
+            return Err("MAX_DEPS");
+        }
+        let idx = self.num_deps;
+        self.deps[idx] = Dependency {
+            to,
+            next: self.classes[from as usize].deps,
+            held,
+            acquired,
+        };
+        self.classes[from as usize].deps = idx as u16;
+        self.num_deps += 1;
+        Ok(())
+    }
+
+    /// Finds a path `from -> ... -> to` in the graph by breadth-first search,
+    /// returns the first class on the path after `from`.
+    fn find_path(&mut self, from: u16, to: u16) -> Option<u16> {
+        self.visited.fill(0);
+        self.visited[from as usize / 64] |= 1 << (from % 64);
+        let (mut head, mut tail) = (0, 0);
+        let mut first = NIL;
+        let mut class = from;
+        loop {
+            let mut idx = self.classes[class as usize].deps;
+            while idx != NIL {
+                let dep = &self.deps[idx as usize];
+                let first = if class == from { dep.to } else { first };
+                if dep.to == to {
+                    return Some(first);
+                }
+                let (word, bit) = (dep.to as usize / 64, 1 << (dep.to % 64));
+                if self.visited[word] & bit == 0 {
+                    self.visited[word] |= bit;
+                    // Each class is queued at most once.
+                    self.queue[tail] = (dep.to, first);
+                    tail += 1;
+                }
+                idx = dep.next;
+            }
+            if head == tail {
+                return None;
+            }
+            (class, first) = self.queue[head];
+            head += 1;
+        }
+    }
+
+    fn context(&mut self, ctx: u64) -> Option<&mut Context> {
+        self.contexts.iter_mut().find(|c| c.id == Some(ctx))
+    }
+
+    /// Returns the context, or allocates one for it.
+    fn context_or_insert(&mut self, ctx: u64) -> Result<&mut Context, &'static str> {
+        let pos = match self.contexts.iter().position(|c| c.id == Some(ctx)) {
+            Some(pos) => pos,
+            None => self
+                .contexts
+                .iter()
+                .position(|c| c.id.is_none())
+                .ok_or("MAX_CONTEXTS")?,
+        };
+        let context = &mut self.contexts[pos];
+        context.id = Some(ctx);
+        Ok(context)
+    }
+
+    fn push_held(&mut self, ctx: u64, class: u16, trace: Trace) -> Result<(), &'static str> {
+        let context = self.context_or_insert(ctx)?;
+        if context.depth == MAX_HELD {
+            return Err("MAX_HELD");
+        }
+        context.held[context.depth] = HeldLock { class, trace };
+        context.depth += 1;
+        Ok(())
+    }
+
+    pub fn acquire(
+        &mut self,
+        ctx: u64,
+        site: &'static Location<'static>,
+        name: &'static str,
+        trace: Trace,
+        irqs_disabled: bool,
+        reports: &mut (Option<BlockingReport>, Option<InversionReport>),
+    ) -> Result<(), &'static str> {
+        let id = self.class_id(site, name)?;
+        if irqs_disabled && !self.classes[id as usize].reported_blocking {
+            self.classes[id as usize].reported_blocking = true;
+            reports.0 = Some(BlockingReport {
+                ctx,
+                class: self.class_info(id),
+                trace,
+            });
+        }
+
+        let pos = self.contexts.iter().position(|c| c.id == Some(ctx));
+        let depth = pos.map_or(0, |pos| self.contexts[pos].depth);
+        for i in 0..depth {
+            let h = self.contexts[pos.unwrap()].held[i];
+            // Recursive locking is detected by the lock itself.
+            if h.class == id || self.find_dep(h.class, id).is_some() {
+                continue;
+            }
+            if let Some(next) = self.find_path(id, h.class) {
+                if reports.1.is_none()
+                    && !self.inversions[..self.num_inversions].contains(&(h.class, id))
+                {
+                    if self.num_inversions == MAX_INVERSIONS {
+                        return Err("MAX_INVERSIONS");
+                    }
+                    self.inversions[self.num_inversions] = (h.class, id);
+                    self.num_inversions += 1;
+                    reports.1 = Some(InversionReport {
+                        ctx,
+                        class: self.class_info(id),
+                        trace,
+                        held: self.class_info(h.class),
+                        held_trace: h.trace,
+                        next: self.class_info(next),
+                        dep: *self.find_dep(id, next).unwrap(),
+                    });
+                }
+                continue;
+            }
+            self.add_dep(h.class, id, h.trace, trace)?;
+        }
+        self.push_held(ctx, id, trace)
+    }
+
+    pub fn try_acquired(
+        &mut self,
+        ctx: u64,
+        site: &'static Location<'static>,
+        name: &'static str,
+        trace: Trace,
+    ) -> Result<(), &'static str> {
+        let id = self.class_id(site, name)?;
+        self.push_held(ctx, id, trace)
+    }
+
+    pub fn release(&mut self, ctx: u64, site: &'static Location<'static>, name: &'static str) {
+        let Some(id) = self.find_class(site, name) else {
+            return;
+        };
+        let Some(context) = self.context(ctx) else {
+            return;
+        };
+        let depth = context.depth;
+        if let Some(pos) = context.held[..depth].iter().rposition(|h| h.class == id) {
+            context.held.copy_within(pos + 1..depth, pos);
+            context.depth -= 1;
+        }
+        if context.depth == 0 {
+            context.id = None;
+        }
+    }
+}
+
+fn class_hash(site: &Location, name: &str) -> usize {
+    let hash = (site.line() as usize)
+        .wrapping_mul(31)
+        .wrapping_add(site.column() as usize)
+        .wrapping_mul(31)
+        .wrapping_add(site.file().len())
+        .wrapping_mul(31)
+        .wrapping_add(name.len());
+    hash % CLASS_HASH_SIZE
+}
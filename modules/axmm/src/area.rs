@@ -0,0 +1,140 @@
+use core::fmt;
+
+use axhal::paging::{MappingFlags, PageTable};
+use memory_addr::{VirtAddr, VirtAddrRange};
+
+use crate::backend::Backend;
+use crate::tlb::TlbBatch;
+
+/// A memory area in an address space, i.e., a contiguous range of virtual
+/// pages with the same flags and backend.
+#[derive(Clone)]
+pub struct MemoryArea {
+    va_range: VirtAddrRange,
+    flags: MappingFlags,
+    backend: Backend,
+}
+
+impl MemoryArea {
+    pub(crate) fn new(start: VirtAddr, size: usize, flags: MappingFlags, backend: Backend) -> Self {
+        Self {
+            va_range: VirtAddrRange::from_start_size(start, size),
+            flags,
+            backend,
+        }
+    }
+
+    /// Returns the virtual address range of the area.
+    pub const fn va_range(&self) -> VirtAddrRange {
+        self.va_range
+    }
+
+    /// Returns the start address of the area.
+    pub const fn start(&self) -> VirtAddr {
+        self.va_range.start
+    }
+
+    /// Returns the end address of the area (exclusive).
+    pub const fn end(&self) -> VirtAddr {
+        self.va_range.end
+    }
+
+    /// Returns the size of the area in bytes.
+    pub fn size(&self) -> usize {
+        self.va_range.size()
+    }
+
+    /// Returns the mapping flags of the area.
+    pub const fn flags(&self) -> MappingFlags {
+        self.flags
+    }
+
+    /// Returns the backend of the area.
+    pub const fn backend(&self) -> &Backend {
+        &self.backend
+    }
+
+    pub(crate) fn map(&self, pt: &mut PageTable, tlb: &mut TlbBatch) -> bool {
+        self.backend
+            .map(self.start(), self.size(), self.flags, pt, tlb)
+    }
+
+    pub(crate) fn unmap(&self, pt: &mut PageTable, tlb: &mut TlbBatch) -> bool {
+        self.backend.unmap(self.start(), self.size(), pt, tlb)
+    }
+
+    pub(crate) fn protect(
+        &mut self,
+        new_flags: MappingFlags,
+        pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        if !self
+            .backend
+            .protect(self.start(), self.size(), new_flags, pt, tlb)
+        {
+            return false;
+        }
+        self.flags = new_flags;
+        true
+    }
+
+    pub(crate) fn clone_map(
+        &self,
+        pt: &mut PageTable,
+        new_pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        self.backend
+            .clone_map(self.start(), self.size(), self.flags, pt, new_pt, tlb)
+    }
+
+    pub(crate) fn handle_page_fault(
+        &self,
+        vaddr: VirtAddr,
+        pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        self.backend.handle_page_fault(vaddr, self.flags, pt, tlb)
+    }
+
+    /// Splits the area at `pos`, which must be page aligned and inside the
+    /// area. The area is shrunk to `[start, pos)`, and `[pos, end)` is
+    /// returned.
+    pub(crate) fn split(&mut self, pos: VirtAddr) -> Self {
+        debug_assert!(self.start() < pos && pos < self.end());
+        let right = Self {
+            va_range: VirtAddrRange::new(pos, self.end()),
+            flags: self.flags,
+            backend: self.backend.clone(),
+        };
+        self.va_range.end = pos;
+        right
+    }
+
+    /// Whether `next`, which starts at the end of this area, can be merged
+    /// into this area, i.e., they have the same flags and their backends map
+    /// the pages in the same way.
+    pub(crate) fn can_merge(&self, next: &Self) -> bool {
+        self.end() == next.start() && self.flags == next.flags && self.backend == next.backend
+    }
+
+    /// Merges `next` into this area, which must satisfy [`Self::can_merge`].
+    pub(crate) fn merge(&mut self, next: Self) {
+        debug_assert!(self.can_merge(&next));
+        self.va_range.end = next.end();
+    }
+}
+
+impl fmt::Debug for MemoryArea {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "[{:#x}, {:#x}) {:?} {:?}",
+            self.start(),
+            self.end(),
+            self.flags,
+            self.backend
+        )
+    }
+}
@@ -1,3 +1,6 @@
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::fmt;
 
 use axerrno::{ax_err, AxError, AxResult};
@@ -6,15 +9,42 @@ use axhal::{
     paging::{MappingFlags, PageTable},
 };
 use memory_addr::{
-    is_aligned_4k, pa, MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K,
+    is_aligned_4k, MemoryAddr, PageIter4K, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K,
 };
 
-use crate::paging_err_to_ax_err;
+use crate::area::MemoryArea;
+use crate::backend::{Backend, MappedFile};
+use crate::page_cache::DirtyPages;
+use crate::tlb::TlbBatch;
 
 /// The virtual memory address space.
+///
+/// It consists of non-overlapping memory areas, each of which is mapped by a
+/// [`Backend`]. Pages of the areas with lazy backends are mapped on page
+/// faults, which are routed to [`AddrSpace::handle_page_fault`].
+///
+/// Areas are split when only parts of them are unmapped or protected, and
+/// adjacent areas with the same flags and compatible backends are merged. All
+/// areas are listed when the address space is formatted with `{:#x?}`.
+///
+/// Pages of file mappings are mapped from the page caches of the files, and
+/// never read from the files on page faults. The dirty pages of shared file
+/// mappings are taken by [`AddrSpace::take_dirty_pages`] or
+/// [`AddrSpace::unmap_dirty`], and written back after releasing the lock.
+///
+/// When pages are unmapped or protected, made read-only by
+/// [`AddrSpace::clone_cow`], or copied on write, only the TLB of the current
+/// CPU is flushed. The flushes on the other CPUs are collected in a
+/// [`TlbBatch`], with the unmapped frames, which are only freed after them. The
+/// owner of the lock of the address space should take the batch with
+/// [`AddrSpace::take_tlb_batch`] and drop it after releasing the lock (see
+/// [`with_kernel_aspace`](crate::with_kernel_aspace)). Otherwise, they happen
+/// when the next batch is taken or the address space is dropped.
 pub struct AddrSpace {
     va_range: VirtAddrRange,
+    areas: BTreeMap<VirtAddr, MemoryArea>,
     pt: PageTable,
+    tlb: TlbBatch,
 }
 
 impl AddrSpace {
@@ -49,14 +79,176 @@ impl AddrSpace {
             .contains_range(VirtAddrRange::from_start_size(start, size))
     }
 
+    /// Clones the address space, e.g., for forking a process.
+    ///
+    /// Linear mappings and loaded pages of shared file mappings are mapped to
+    /// the same physical memory in the copy. The frames of allocation mappings
+    /// and private file mappings are shared copy-on-write: they are made
+    /// read-only in both address spaces, and the first write to a shared frame
+    /// from either one copies it (see [`AddrSpace::handle_page_fault`]). The
+    /// frames are reference counted, and freed when no address space maps
+    /// them. Pages that have not been populated stay lazy in both.
+    ///
+    /// Other CPUs running in this address space may still write to the shared
+    /// frames until their TLBs are flushed by the batch taken with
+    /// [`AddrSpace::take_tlb_batch`].
+    pub fn clone_cow(&mut self) -> AxResult<Self> {
+        let mut new_aspace = Self::new_empty(self.base(), self.size())?;
+        for area in self.areas.values() {
+            // Inserted first, so that it's unmapped on failure.
+            new_aspace.areas.insert(area.start(), area.clone());
+            if !area.clone_map(&mut self.pt, &mut new_aspace.pt, &mut self.tlb) {
+                return ax_err!(NoMemory, "failed to clone the area");
+            }
+        }
+        Ok(new_aspace)
+    }
+
+    /// Takes the TLB flushes on other CPUs that are pending since the last
+    /// call, and the frames to free after them (see [`TlbBatch`]).
+    ///
+    /// The returned batch flushes the TLBs of all CPUs when dropped, which
+    /// waits for them. So it must be dropped after the lock of the address
+    /// space is released, as other CPUs may be spinning on the lock with IRQs
+    /// disabled.
+    pub fn take_tlb_batch(&mut self) -> TlbBatch {
+        core::mem::take(&mut self.tlb)
+    }
+
+    /// Returns an iterator over the memory areas, in ascending order of
+    /// their addresses.
+    pub fn areas(&self) -> impl Iterator<Item = &MemoryArea> {
+        self.areas.values()
+    }
+
+    /// Returns the memory area that contains `vaddr`, if any.
+    pub fn find_area(&self, vaddr: VirtAddr) -> Option<&MemoryArea> {
+        self.areas
+            .range(..=vaddr)
+            .next_back()
+            .map(|(_, area)| area)
+            .filter(|area| area.end() > vaddr)
+    }
+
+    /// Finds the lowest free range of `size` bytes that starts at or above
+    /// `hint`, and is not mapped by any area. The range must be in `limit` and
+    /// the address space.
+    ///
+    /// Returns the start address of the range, or [`None`] if there is no such
+    /// range, or `size` is not page aligned.
+    pub fn find_free_area(
+        &self,
+        hint: VirtAddr,
+        size: usize,
+        limit: VirtAddrRange,
+    ) -> Option<VirtAddr> {
+        if !is_aligned_4k(size) {
+            return None;
+        }
+        let mut start = hint.max(limit.start).max(self.base()).align_up_4k();
+        let end = limit.end.min(self.end());
+        if let Some(area) = self.find_area(start) {
+            start = area.end();
+        }
+        for (&area_start, area) in self.areas.range(start..) {
+            if area_start.as_usize() - start.as_usize() >= size {
+                break;
+            }
+            start = area.end();
+        }
+        let range_end = start.as_usize().checked_add(size)?;
+        (range_end <= end.as_usize()).then_some(start)
+    }
+
     /// Creates a new empty address space.
     pub(crate) fn new_empty(base: VirtAddr, size: usize) -> AxResult<Self> {
         Ok(Self {
             va_range: VirtAddrRange::from_start_size(base, size),
+            areas: BTreeMap::new(),
             pt: PageTable::try_new().map_err(|_| AxError::NoMemory)?,
+            tlb: TlbBatch::new(),
         })
     }
 
+    /// Shares the page table entries of `other` into this page table, so that
+    /// the mappings of `other` are also valid in this address space. The two
+    /// address spaces must not overlap.
+    pub(crate) fn copy_mappings_from(&mut self, other: &AddrSpace) -> AxResult {
+        if self.va_range.overlaps(other.va_range) {
+            return ax_err!(InvalidInput, "address space overlap");
+        }
+        self.pt.copy_from(&other.pt, other.base(), other.size());
+        Ok(())
+    }
+
+    /// Returns whether the range `[start, start + size)` overlaps with any
+    /// existing area.
+    fn overlaps(&self, start: VirtAddr, size: usize) -> bool {
+        let end = start + size;
+        self.areas
+            .range(..end)
+            .next_back()
+            .is_some_and(|(_, area)| area.end() > start)
+    }
+
+    /// Splits the area containing `pos` at `pos`, if `pos` is inside it.
+    fn split_area_at(&mut self, pos: VirtAddr) {
+        let right = match self.areas.range_mut(..pos).next_back() {
+            Some((_, area)) if area.end() > pos => area.split(pos),
+            _ => return,
+        };
+        self.areas.insert(pos, right);
+    }
+
+    /// Merges the area starting at `pos` into the previous area, if they are
+    /// adjacent and can be merged.
+    fn merge_area_at(&mut self, pos: VirtAddr) {
+        let Some(next) = self.areas.get(&pos) else {
+            return;
+        };
+        match self.areas.range(..pos).next_back() {
+            Some((_, prev)) if prev.can_merge(next) => {}
+            _ => return,
+        }
+        let next = self.areas.remove(&pos).unwrap();
+        let (_, prev) = self.areas.range_mut(..pos).next_back().unwrap();
+        prev.merge(next);
+    }
+
+    /// Checks that `[start, start + size)` is in the address space, and is
+    /// page aligned.
+    fn validate_range(&self, start: VirtAddr, size: usize) -> AxResult {
+        if !self.contains_range(start, size) {
+            return ax_err!(InvalidInput, "address out of range");
+        }
+        if !start.is_aligned_4k() || !is_aligned_4k(size) {
+            return ax_err!(InvalidInput, "address not aligned");
+        }
+        Ok(())
+    }
+
+    /// Adds a new area `[start, start + size)` mapped by `backend`.
+    fn map_area(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        backend: Backend,
+    ) -> AxResult {
+        self.validate_range(start, size)?;
+        if self.overlaps(start, size) {
+            return ax_err!(AlreadyExists, "address already mapped");
+        }
+        let area = MemoryArea::new(start, size, flags, backend);
+        if !area.map(&mut self.pt, &mut self.tlb) {
+            return ax_err!(NoMemory, "failed to map the area");
+        }
+        self.areas.insert(start, area);
+        self.merge_area_at(start + size);
+        self.merge_area_at(start);
+        Ok(())
+    }
+
     /// Add a new linear mapping.
     ///
     /// The mapping is linear, i.e., `start_vaddr` is mapped to `start_paddr`,
@@ -64,8 +256,8 @@ impl AddrSpace {
     ///
     /// The `flags` parameter indicates the mapping permissions and attributes.
     ///
-    /// Returns an error if the address range is out of the address space or not
-    /// aligned.
+    /// Returns an error if the address range is out of the address space, not
+    /// aligned, or overlaps with existing mappings.
     pub fn map_linear(
         &mut self,
         start_vaddr: VirtAddr,
@@ -73,51 +265,209 @@ impl AddrSpace {
         size: usize,
         flags: MappingFlags,
     ) -> AxResult {
-        if !self.contains_range(start_vaddr, size) {
-            return ax_err!(InvalidInput, "address out of range");
-        }
-        if !start_vaddr.is_aligned_4k() || !start_paddr.is_aligned_4k() || !is_aligned_4k(size) {
+        if !start_paddr.is_aligned_4k() {
             return ax_err!(InvalidInput, "address not aligned");
         }
+        let offset = start_vaddr.as_usize().wrapping_sub(start_paddr.as_usize());
+        self.map_area(start_vaddr, size, flags, Backend::new_linear(offset))
+    }
 
-        let offset = start_vaddr.as_usize() - start_paddr.as_usize();
-        self.pt
-            .map_region(
-                start_vaddr,
-                |va| pa!(va.as_usize() - offset),
-                size,
-                flags,
-                false, // allow_huge
-                false, // flush_tlb_by_page
-            )
-            .map_err(paging_err_to_ax_err)?
-            .flush_all();
-        Ok(())
+    /// Add a new allocation mapping, whose physical frames are allocated from
+    /// the global allocator.
+    ///
+    /// If `populate` is `true`, all frames are allocated and mapped now.
+    /// Otherwise, the mapping is lazy: each frame is allocated and mapped on
+    /// the first access to its page, so untouched pages of a large sparse
+    /// mapping cost no memory. The frames are zeroed.
+    ///
+    /// Returns an error if the address range is out of the address space, not
+    /// aligned, or overlaps with existing mappings.
+    pub fn map_alloc(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        populate: bool,
+    ) -> AxResult {
+        self.map_area(start, size, flags, Backend::new_alloc(populate))
+    }
+
+    /// Add a new file mapping, which maps the data of `file` from `offset` at
+    /// `start`.
+    ///
+    /// The pages are mapped from the page cache of `file`, into which they
+    /// must have been loaded by [`PageCache::load`](crate::PageCache::load),
+    /// as page faults never read the file. Accesses to the pages that are not
+    /// cached, e.g., beyond the end of the file, fail. If `shared` is `true`,
+    /// the changes to the pages are visible to all mappings of the file, and
+    /// are written back to the file after [`AddrSpace::take_dirty_pages`].
+    /// Otherwise, the mapping is private, and the changes are never visible
+    /// in the file.
+    ///
+    /// Returns an error if the address range is out of the address space, not
+    /// aligned, or overlaps with existing mappings, or `offset` is not page
+    /// aligned.
+    pub fn map_file(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        file: Arc<dyn MappedFile>,
+        offset: u64,
+        shared: bool,
+    ) -> AxResult {
+        if !is_aligned_4k(offset as usize) {
+            return ax_err!(InvalidInput, "offset not aligned");
+        }
+        let backend = Backend::new_file(start, file, offset, shared);
+        self.map_area(start, size, flags, backend)
     }
 
     /// Removes mappings within the specified virtual address range.
     ///
+    /// Areas partially in the range are split, and only the pages in the
+    /// range are unmapped. Frames allocated by the allocation mappings are
+    /// freed after the TLB flushes on other CPUs, which are left in the pending
+    /// [`TlbBatch`]. Changes to shared file mappings stay dirty in the page
+    /// caches of the files, use [`AddrSpace::unmap_dirty`] to write them back.
+    ///
     /// Returns an error if the address range is out of the address space or not
     /// aligned.
     pub fn unmap(&mut self, start: VirtAddr, size: usize) -> AxResult {
-        if !self.contains_range(start, size) {
-            return ax_err!(InvalidInput, "address out of range");
+        self.unmap_dirty(start, size, &mut DirtyPages::new())
+    }
+
+    /// Removes mappings as [`AddrSpace::unmap`], and takes the dirty pages of
+    /// the shared file mappings in the range into `pages`, to be written back
+    /// by [`DirtyPages::write_back`] after releasing the lock of the address
+    /// space.
+    ///
+    /// The changes are kept in the page caches until they are written back,
+    /// so none of them is lost between unmapping and writing back. If an area
+    /// fails to be unmapped, it's kept, and the areas before it stay unmapped,
+    /// so `pages` must be written back even if it returns an error.
+    pub fn unmap_dirty(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        pages: &mut DirtyPages,
+    ) -> AxResult {
+        self.validate_range(start, size)?;
+        let end = start + size;
+        self.split_area_at(start);
+        self.split_area_at(end);
+        let starts: Vec<VirtAddr> = self.areas.range(start..end).map(|(&s, _)| s).collect();
+        for s in starts {
+            let area = &self.areas[&s];
+            // Only file mappings have dirty pages, and they never fail to be
+            // unmapped, so the pages taken are never left mapped.
+            area.backend()
+                .take_dirty_unmapped(area.start(), area.size(), &self.pt, pages);
+            if !area.unmap(&mut self.pt, &mut self.tlb) {
+                return ax_err!(BadState, "failed to unmap the area");
+            }
+            self.areas.remove(&s);
         }
-        if !start.is_aligned_4k() || !is_aligned_4k(size) {
-            return ax_err!(InvalidInput, "address not aligned");
+        Ok(())
+    }
+
+    /// Removes all mappings in the address space.
+    ///
+    /// The TLB flushes on other CPUs are left in the pending [`TlbBatch`], and
+    /// happen when the address space is dropped at the latest.
+    pub fn clear(&mut self) {
+        for (_, area) in core::mem::take(&mut self.areas) {
+            area.unmap(&mut self.pt, &mut self.tlb);
+        }
+    }
+
+    /// Takes the dirty pages of the shared file mappings in
+    /// `[start, start + size)`, to be written back by
+    /// [`DirtyPages::write_back`].
+    ///
+    /// The pages are made read-only, so that later writes mark them dirty
+    /// again. Until the TLBs of other CPUs are flushed by the batch taken with
+    /// [`AddrSpace::take_tlb_batch`], they may still write to the pages, so the
+    /// pages must be written back after that, which also releases the lock of
+    /// the address space first.
+    ///
+    /// Returns an error if the address range is out of the address space or
+    /// not aligned.
+    pub fn take_dirty_pages(&mut self, start: VirtAddr, size: usize) -> AxResult<DirtyPages> {
+        self.validate_range(start, size)?;
+        let end = start + size;
+        let first = self.find_area(start).map_or(start, |area| area.start());
+        let mut pages = DirtyPages::new();
+        for area in self.areas.range(first..end).map(|(_, area)| area) {
+            let area_start = area.start().max(start);
+            let area_end = area.end().min(end);
+            area.backend().take_dirty_pages(
+                area_start,
+                area_end - area_start,
+                &mut self.pt,
+                &mut self.tlb,
+                &mut pages,
+            );
+        }
+        Ok(pages)
+    }
+
+    /// Handles a page fault at `vaddr` with the access type `access_flags`.
+    ///
+    /// Returns `true` if the fault is resolved, i.e., `vaddr` is in an area
+    /// that permits the access, and its page is mapped now (for lazy areas),
+    /// or copied from a shared frame (for writes to copy-on-write pages).
+    /// Otherwise, the fault is caused by an invalid access.
+    ///
+    /// The TLB flushes on other CPUs are left in the pending [`TlbBatch`].
+    ///
+    /// It never blocks, so it can be called in trap context: pages of file
+    /// mappings are only mapped from the page caches of the files, and the
+    /// fault is not resolved if the page is not cached.
+    pub fn handle_page_fault(&mut self, vaddr: VirtAddr, access_flags: MappingFlags) -> bool {
+        if !self.va_range.contains(vaddr) {
+            return false;
+        }
+        match self.areas.range(..=vaddr).next_back() {
+            Some((_, area)) if area.end() > vaddr && area.flags().contains(access_flags) => {
+                area.handle_page_fault(vaddr, &mut self.pt, &mut self.tlb)
+            }
+            _ => false,
         }
+    }
 
+    /// Returns the physical address of the page at `vaddr` for an access of
+    /// `access_flags`, after resolving the page fault it would cause.
+    fn query_for_access(
+        &mut self,
+        vaddr: VirtAddr,
+        access_flags: MappingFlags,
+    ) -> AxResult<PhysAddr> {
+        match self.pt.query(vaddr) {
+            Ok((paddr, flags, _)) if flags.contains(access_flags) => return Ok(paddr),
+            _ => {}
+        }
+        if !self.handle_page_fault(vaddr, access_flags) {
+            return Err(AxError::BadAddress);
+        }
         self.pt
-            .unmap_region(start, size, true)
-            .map_err(paging_err_to_ax_err)?
-            .ignore();
-        Ok(())
+            .query(vaddr)
+            .map(|(paddr, _, _)| paddr)
+            .map_err(|_| AxError::BadAddress)
     }
 
     /// To process data in this area with the given function.
     ///
     /// Now it supports reading and writing data in the given interval.
-    fn process_area_data<F>(&self, start: VirtAddr, size: usize, mut f: F) -> AxResult
+    /// Pages that are not mapped yet or copy-on-write are resolved as by the
+    /// page fault handler.
+    fn process_area_data<F>(
+        &mut self,
+        start: VirtAddr,
+        size: usize,
+        access_flags: MappingFlags,
+        mut f: F,
+    ) -> AxResult
     where
         F: FnMut(VirtAddr, usize, usize),
     {
@@ -130,7 +480,7 @@ impl AddrSpace {
         for vaddr in PageIter4K::new(start.align_down_4k(), end_align_up)
             .expect("Failed to create page iterator")
         {
-            let (mut paddr, _, _) = self.pt.query(vaddr).map_err(|_| AxError::BadAddress)?;
+            let mut paddr = self.query_for_access(vaddr, access_flags)?;
 
             let mut copy_size = (size - cnt).min(PAGE_SIZE_4K);
 
@@ -150,44 +500,80 @@ impl AddrSpace {
 
     /// To read data from the address space.
     ///
+    /// Pages that are not populated yet are mapped as on a page fault, so it
+    /// takes `&mut self` to change the page table.
+    ///
     /// # Arguments
     ///
     /// * `start` - The start virtual address to read.
     /// * `buf` - The buffer to store the data.
-    pub fn read(&self, start: VirtAddr, buf: &mut [u8]) -> AxResult {
-        self.process_area_data(start, buf.len(), |src, offset, read_size| unsafe {
-            core::ptr::copy_nonoverlapping(src.as_ptr(), buf.as_mut_ptr().add(offset), read_size);
-        })
+    pub fn read(&mut self, start: VirtAddr, buf: &mut [u8]) -> AxResult {
+        self.process_area_data(
+            start,
+            buf.len(),
+            MappingFlags::READ,
+            |src, offset, read_size| unsafe {
+                core::ptr::copy_nonoverlapping(
+                    src.as_ptr(),
+                    buf.as_mut_ptr().add(offset),
+                    read_size,
+                );
+            },
+        )
     }
 
     /// To write data to the address space.
     ///
+    /// Writes go through the page table as user writes do: pages that are not
+    /// populated yet are mapped, and copy-on-write pages are copied first, so
+    /// that the address spaces sharing them don't see the data. This changes
+    /// the page table, hence `&mut self`. The TLB flushes on other CPUs are
+    /// left in the pending [`TlbBatch`] (see [`AddrSpace::take_tlb_batch`]).
+    ///
     /// # Arguments
     ///
     /// * `start_vaddr` - The start virtual address to write.
     /// * `buf` - The buffer to write to the address space.
-    pub fn write(&self, start: VirtAddr, buf: &[u8]) -> AxResult {
-        self.process_area_data(start, buf.len(), |dst, offset, write_size| unsafe {
-            core::ptr::copy_nonoverlapping(buf.as_ptr().add(offset), dst.as_mut_ptr(), write_size);
-        })
+    pub fn write(&mut self, start: VirtAddr, buf: &[u8]) -> AxResult {
+        self.process_area_data(
+            start,
+            buf.len(),
+            MappingFlags::WRITE,
+            |dst, offset, write_size| unsafe {
+                core::ptr::copy_nonoverlapping(
+                    buf.as_ptr().add(offset),
+                    dst.as_mut_ptr(),
+                    write_size,
+                );
+            },
+        )
     }
 
     /// Updates mapping within the specified virtual address range.
     ///
+    /// Areas partially in the range are split, so that only the pages in the
+    /// range get the new flags, including the ones mapped on demand later. The
+    /// TLB flushes on other CPUs are left in the pending [`TlbBatch`].
+    ///
     /// Returns an error if the address range is out of the address space or not
     /// aligned.
     pub fn protect(&mut self, start: VirtAddr, size: usize, flags: MappingFlags) -> AxResult {
-        if !self.contains_range(start, size) {
-            return ax_err!(InvalidInput, "address out of range");
+        self.validate_range(start, size)?;
+        let end = start + size;
+        self.split_area_at(start);
+        self.split_area_at(end);
+        let mut starts: Vec<VirtAddr> = Vec::new();
+        for (&s, area) in self.areas.range_mut(start..end) {
+            if !area.protect(flags, &mut self.pt, &mut self.tlb) {
+                return ax_err!(BadState, "failed to protect the area");
+            }
+            starts.push(s);
         }
-        if !start.is_aligned_4k() || !is_aligned_4k(size) {
-            return ax_err!(InvalidInput, "address not aligned");
+        // Merge from the end, so that the starts are still valid.
+        self.merge_area_at(end);
+        for s in starts.into_iter().rev() {
+            self.merge_area_at(s);
         }
-
-        self.pt
-            .protect_region(start, size, flags, true)
-            .map_err(paging_err_to_ax_err)?
-            .ignore();
         Ok(())
     }
 }
@@ -197,6 +583,13 @@ impl fmt::Debug for AddrSpace {
         f.debug_struct("AddrSpace")
             .field("va_range", &self.va_range)
             .field("page_table_root", &self.pt.root_paddr())
+            .field("areas", &self.areas.values())
             .finish()
     }
 }
+
+impl Drop for AddrSpace {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
@@ -0,0 +1,238 @@
+use axhal::mem::phys_to_virt;
+use axhal::paging::{MappingFlags, PageSize, PageTable};
+use memory_addr::{PageIter4K, PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use super::Backend;
+use crate::frame::{alloc_frame, dealloc_frame, frame_ref_count, share_frame};
+use crate::tlb::TlbBatch;
+
+impl Backend {
+    /// Creates a new allocation mapping backend.
+    pub const fn new_alloc(populate: bool) -> Self {
+        Self::Alloc { populate }
+    }
+
+    pub(super) fn map_alloc(
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+        populate: bool,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        debug!(
+            "map_alloc: [{:#x}, {:#x}) {:?} (populate={})",
+            start,
+            start + size,
+            flags,
+            populate
+        );
+        if !populate {
+            // Pages are mapped on demand by `handle_page_fault_alloc`.
+            return true;
+        }
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            let mapped = alloc_frame().is_some_and(|frame| {
+                match pt.map(addr, frame, PageSize::Size4K, flags) {
+                    Ok(tlb) => {
+                        tlb.ignore(); // not mapped before
+                        true
+                    }
+                    Err(_) => {
+                        dealloc_frame(frame);
+                        false
+                    }
+                }
+            });
+            if !mapped {
+                // Roll back the pages mapped so far.
+                Self::unmap_alloc(start, addr.as_usize() - start.as_usize(), pt, tlb);
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Unmaps the pages in `[start, start + size)`, and drops the references
+    /// to their frames.
+    ///
+    /// Other CPUs may still map the frames in their TLBs, so the references
+    /// are moved into `tlb`, and only dropped after their TLBs are flushed,
+    /// as [`Backend::copy_on_write`] does.
+    pub(super) fn unmap_alloc(
+        start: VirtAddr,
+        size: usize,
+        pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        debug!("unmap_alloc: [{:#x}, {:#x})", start, start + size);
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            // Pages that have never been accessed are not mapped.
+            if let Ok((frame, page_size, local)) = pt.unmap(addr) {
+                local.flush();
+                if page_size.is_huge() {
+                    tlb.add_page(addr);
+                } else {
+                    tlb.add_stale_frame(addr, frame);
+                }
+            }
+        }
+        true
+    }
+
+    /// Changes the flags of the mapped pages in `[start, start + size)`.
+    ///
+    /// Other CPUs may still access the pages with the old flags, e.g., write
+    /// to the pages made read-only, until their TLBs are flushed by `tlb`.
+    pub(super) fn protect_alloc(
+        start: VirtAddr,
+        size: usize,
+        new_flags: MappingFlags,
+        pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            if let Ok((frame, _, _)) = pt.query(addr) {
+                // Shared frames stay read-only until they are copied.
+                let flags = if frame_ref_count(frame) > 1 {
+                    new_flags - MappingFlags::WRITE
+                } else {
+                    new_flags
+                };
+                match pt.protect(addr, flags) {
+                    Ok((_, local)) => {
+                        local.flush();
+                        tlb.add_page(addr);
+                    }
+                    Err(_) => return false,
+                }
+            }
+        }
+        true
+    }
+
+    pub(super) fn handle_page_fault_alloc(
+        vaddr: VirtAddr,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+        populate: bool,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        let vaddr = vaddr.align_down_4k();
+        if let Ok((frame, flags, _)) = pt.query(vaddr) {
+            // The page is mapped, it can only be a write to a copy-on-write
+            // page, or the fault has been resolved by another CPU.
+            if flags.contains(MappingFlags::WRITE) || !orig_flags.contains(MappingFlags::WRITE) {
+                // Drop the stale entry that caused the fault, if any.
+                axhal::arch::flush_tlb(Some(vaddr));
+                return true;
+            }
+            return Self::copy_on_write(vaddr, frame, orig_flags, pt, tlb);
+        }
+        if populate {
+            return false; // Populated mappings should not trigger page faults.
+        }
+        let Some(frame) = alloc_frame() else {
+            return false;
+        };
+        // The page may be mapped by another CPU in the meantime, in which
+        // case it fails with `AlreadyMapped`.
+        match pt.map(vaddr, frame, PageSize::Size4K, orig_flags) {
+            Ok(local) => {
+                local.flush();
+                true
+            }
+            Err(_) => {
+                dealloc_frame(frame);
+                pt.query(vaddr).is_ok()
+            }
+        }
+    }
+
+    /// Gives the page at `vaddr` its own copy of the shared `frame`, and makes
+    /// it writable again.
+    ///
+    /// Other CPUs may still map `frame` at `vaddr` in their TLBs, so the
+    /// reference to `frame` is moved into `tlb`, and only dropped after their
+    /// TLBs are flushed. Until then, no other sharer can take over the frame
+    /// and write to it, which would be visible through the stale entries.
+    pub(super) fn copy_on_write(
+        vaddr: VirtAddr,
+        frame: PhysAddr,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        if frame_ref_count(frame) == 1 {
+            // The other sharers are gone, reuse the frame. Stale read-only
+            // entries on other CPUs cause spurious faults at most, which
+            // flush them.
+            return match pt.protect(vaddr, orig_flags) {
+                Ok((_, local)) => {
+                    local.flush();
+                    true
+                }
+                Err(_) => false,
+            };
+        }
+        let Some(new_frame) = alloc_frame() else {
+            return false;
+        };
+        unsafe {
+            core::ptr::copy_nonoverlapping(
+                phys_to_virt(frame).as_ptr(),
+                phys_to_virt(new_frame).as_mut_ptr(),
+                PAGE_SIZE_4K,
+            );
+        }
+        let remapped = pt.unmap(vaddr).is_ok_and(|(_, _, local)| {
+            local.flush();
+            pt.map(vaddr, new_frame, PageSize::Size4K, orig_flags)
+                .map(|local| local.flush())
+                .is_ok()
+        });
+        if !remapped {
+            dealloc_frame(new_frame);
+            return false;
+        }
+        tlb.add_stale_frame(vaddr, frame);
+        true
+    }
+
+    /// Shares the mapped pages in `[start, start + size)` of `pt` with
+    /// `new_pt` as copy-on-write pages, which are made read-only in both page
+    /// tables. Returns whether it succeeded.
+    ///
+    /// Other CPUs may still have writable entries of the pages in `pt` in
+    /// their TLBs, which are recorded in `tlb` to be flushed.
+    pub(super) fn clone_alloc(
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+        new_pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        let cow_flags = flags - MappingFlags::WRITE;
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            let Ok((frame, _, _)) = pt.query(addr) else {
+                continue; // not populated yet
+            };
+            if flags.contains(MappingFlags::WRITE) {
+                match pt.protect(addr, cow_flags) {
+                    Ok((_, local)) => {
+                        local.flush();
+                        tlb.add_page(addr);
+                    }
+                    Err(_) => return false,
+                }
+            }
+            match new_pt.map(addr, frame, PageSize::Size4K, cow_flags) {
+                Ok(local) => local.ignore(),
+                Err(_) => return false,
+            }
+            share_frame(frame);
+        }
+        true
+    }
+}
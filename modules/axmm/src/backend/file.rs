@@ -0,0 +1,313 @@
+use alloc::sync::Arc;
+use core::{fmt, ops::Deref};
+
+use axerrno::AxResult;
+use axhal::paging::{MappingFlags, PageSize, PageTable};
+use memory_addr::{PageIter4K, PhysAddr, VirtAddr};
+
+use super::Backend;
+use crate::frame::{dealloc_frame, share_frame};
+use crate::page_cache::{DirtyPages, PageCache};
+use crate::tlb::TlbBatch;
+
+/// A file that can be mapped into an address space by the file mapping
+/// backend.
+///
+/// Its pages are mapped from its [`PageCache`], into which they are loaded by
+/// [`PageCache::load`] before. The I/O methods may block, so they are only
+/// called by [`PageCache::load`] and [`DirtyPages::write_back`], and never in
+/// page faults or with the lock of an address space held.
+pub trait MappedFile: Send + Sync {
+    /// Reads the data at `offset` of the file into `buf`, returns the number
+    /// of bytes read, which is less than `buf.len()` at the end of the file.
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize>;
+
+    /// Writes `buf` to the file at `offset`, returns the number of bytes
+    /// written.
+    fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize>;
+
+    /// Returns the size of the file in bytes.
+    fn size(&self) -> AxResult<u64>;
+
+    /// Returns the cache of the pages of the file that are mapped.
+    ///
+    /// All mappings of the file must use the same cache, so that they are
+    /// coherent.
+    fn page_cache(&self) -> &PageCache;
+}
+
+/// A reference to a [`MappedFile`].
+///
+/// Two references are equal if they refer to the same file object.
+#[derive(Clone)]
+pub struct MappedFileRef(Arc<dyn MappedFile>);
+
+impl MappedFileRef {
+    fn as_ptr(&self) -> *const () {
+        Arc::as_ptr(&self.0) as *const ()
+    }
+}
+
+impl From<Arc<dyn MappedFile>> for MappedFileRef {
+    fn from(file: Arc<dyn MappedFile>) -> Self {
+        Self(file)
+    }
+}
+
+impl Deref for MappedFileRef {
+    type Target = dyn MappedFile;
+
+    fn deref(&self) -> &Self::Target {
+        &*self.0
+    }
+}
+
+impl PartialEq for MappedFileRef {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_ptr() == other.as_ptr()
+    }
+}
+
+impl Eq for MappedFileRef {}
+
+impl fmt::Debug for MappedFileRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MappedFile({:#x})", self.as_ptr() as usize)
+    }
+}
+
+/// Returns the offset in the file that is mapped at `vaddr`.
+fn file_offset(vaddr: VirtAddr, file_va_offset: usize) -> u64 {
+    vaddr.as_usize().wrapping_sub(file_va_offset) as u64
+}
+
+impl Backend {
+    /// Creates a new file mapping backend, which maps the file from `offset`
+    /// at the virtual address `start`.
+    pub fn new_file(start: VirtAddr, file: Arc<dyn MappedFile>, offset: u64, shared: bool) -> Self {
+        Self::File {
+            file: file.into(),
+            file_va_offset: start.as_usize().wrapping_sub(offset as usize),
+            shared,
+        }
+    }
+
+    /// Maps the cached pages of the file in `[start, start + size)`. They are
+    /// read-only, so that the first write to a page of a shared mapping marks
+    /// it dirty, and copies a page of a private mapping.
+    pub(super) fn map_file(
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+        file: &MappedFileRef,
+        file_va_offset: usize,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        let flags = flags - MappingFlags::WRITE;
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            let Some(frame) = file.page_cache().get(file_offset(addr, file_va_offset)) else {
+                continue; // beyond the end of the file
+            };
+            match pt.map(addr, frame, PageSize::Size4K, flags) {
+                Ok(tlb) => tlb.ignore(), // not mapped before
+                Err(_) => {
+                    dealloc_frame(frame);
+                    Self::unmap_alloc(start, addr.as_usize() - start.as_usize(), pt, tlb);
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    /// Takes the dirty pages of a shared file mapping in `[start, start + size)`
+    /// of `pt` into `pages`, to be written back.
+    ///
+    /// The pages are made read-only, so that the next write to them marks
+    /// them dirty again. Other CPUs may still write to them through stale TLB
+    /// entries, which are recorded in `tlb`, and must be flushed before the
+    /// pages are written back. Dirty pages that have been made read-only by
+    /// [`AddrSpace::protect`](crate::AddrSpace::protect) are taken as well.
+    pub(crate) fn take_dirty_pages(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+        pages: &mut DirtyPages,
+    ) {
+        let Self::File {
+            file,
+            file_va_offset,
+            shared: true,
+        } = self
+        else {
+            return;
+        };
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            let Ok((_, flags, _)) = pt.query(addr) else {
+                continue; // beyond the end of the file
+            };
+            if flags.contains(MappingFlags::WRITE) {
+                match pt.protect(addr, flags - MappingFlags::WRITE) {
+                    Ok((_, local)) => {
+                        local.flush();
+                        tlb.add_page(addr);
+                    }
+                    Err(_) => continue,
+                }
+            }
+            let offset = file_offset(addr, *file_va_offset);
+            if let Some(frame) = file.page_cache().take_dirty(offset, true) {
+                pages.push(file, offset, frame);
+            }
+        }
+    }
+
+    /// Takes the dirty pages of a shared file mapping in `[start, start + size)`
+    /// of `pt` into `pages`, to be written back, before the range is unmapped.
+    ///
+    /// It's done before, so that the references to the frames moved into the
+    /// [`TlbBatch`] by the unmapping are not taken for other mappings.
+    pub(crate) fn take_dirty_unmapped(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        pt: &PageTable,
+        pages: &mut DirtyPages,
+    ) {
+        let Self::File {
+            file,
+            file_va_offset,
+            shared: true,
+        } = self
+        else {
+            return;
+        };
+        let offset = file_offset(start, *file_va_offset);
+        let mapped = |offset: u64, frame: PhysAddr| {
+            let vaddr = VirtAddr::from((offset as usize).wrapping_add(*file_va_offset));
+            pt.query(vaddr).is_ok_and(|(paddr, _, _)| paddr == frame)
+        };
+        for (offset, frame) in file
+            .page_cache()
+            .take_dirty_unmapped(offset..offset + size as u64, mapped)
+        {
+            pages.push(file, offset, frame);
+        }
+    }
+
+    /// Changes the flags of the mapped pages of a shared file mapping. Pages
+    /// stay read-only until they are written, and marked dirty.
+    ///
+    /// Other CPUs may still write to the pages through stale TLB entries
+    /// without marking them dirty, until their TLBs are flushed by `tlb`. The
+    /// pages that are dirty already stay dirty, so these writes are written
+    /// back with them.
+    pub(super) fn protect_file_shared(
+        start: VirtAddr,
+        size: usize,
+        new_flags: MappingFlags,
+        pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            if pt.query(addr).is_ok() {
+                match pt.protect(addr, new_flags - MappingFlags::WRITE) {
+                    Ok((_, local)) => {
+                        local.flush();
+                        tlb.add_page(addr);
+                    }
+                    Err(_) => return false,
+                }
+            }
+        }
+        true
+    }
+
+    /// Maps the pages of a shared file mapping in `[start, start + size)` of
+    /// `pt` to the same frames in `new_pt`, so that the changes are visible to
+    /// both. They are read-only in `new_pt` until they are written.
+    pub(super) fn clone_file_shared(
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &PageTable,
+        new_pt: &mut PageTable,
+    ) -> bool {
+        let flags = flags - MappingFlags::WRITE;
+        for addr in PageIter4K::new(start, start + size).unwrap() {
+            let Ok((frame, _, _)) = pt.query(addr) else {
+                continue; // beyond the end of the file
+            };
+            match new_pt.map(addr, frame, PageSize::Size4K, flags) {
+                Ok(tlb) => tlb.ignore(),
+                Err(_) => return false,
+            }
+            share_frame(frame);
+        }
+        true
+    }
+
+    /// Handles a page fault in a file mapping.
+    ///
+    /// It never reads the file, as it's called in trap context: pages that
+    /// are not mapped are mapped from the page cache of the file, and the
+    /// fault is not resolved if they are not cached. Writes to read-only pages
+    /// mark the pages of shared mappings dirty, and copy the pages of private
+    /// mappings.
+    pub(super) fn handle_page_fault_file(
+        vaddr: VirtAddr,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+        file: &MappedFileRef,
+        file_va_offset: usize,
+        shared: bool,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        let vaddr = vaddr.align_down_4k();
+        let offset = file_offset(vaddr, file_va_offset);
+        if let Ok((frame, flags, _)) = pt.query(vaddr) {
+            if flags.contains(MappingFlags::WRITE) || !orig_flags.contains(MappingFlags::WRITE) {
+                // The fault has been resolved by another CPU, drop the stale
+                // entry that caused it, if any.
+                axhal::arch::flush_tlb(Some(vaddr));
+                return true;
+            }
+            if !shared {
+                return Self::copy_on_write(vaddr, frame, orig_flags, pt, tlb);
+            }
+            // The first write to a clean page. Stale read-only entries on
+            // other CPUs cause spurious faults at most, which flush them.
+            if !file.page_cache().mark_dirty(offset) {
+                return false;
+            }
+            return match pt.protect(vaddr, orig_flags) {
+                Ok((_, local)) => {
+                    local.flush();
+                    true
+                }
+                Err(_) => false,
+            };
+        }
+        let Some(frame) = file.page_cache().get(offset) else {
+            return false; // beyond the end of the file
+        };
+        match pt.map(
+            vaddr,
+            frame,
+            PageSize::Size4K,
+            orig_flags - MappingFlags::WRITE,
+        ) {
+            Ok(local) => {
+                local.flush();
+                true
+            }
+            Err(_) => {
+                dealloc_frame(frame);
+                false
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use axhal::paging::{MappingFlags, PageTable};
+use memory_addr::{pa, VirtAddr};
+
+use super::Backend;
+use crate::tlb::TlbBatch;
+
+impl Backend {
+    /// Creates a new linear mapping backend.
+    pub const fn new_linear(pa_va_offset: usize) -> Self {
+        Self::Linear { pa_va_offset }
+    }
+
+    pub(super) fn map_linear(
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+        pa_va_offset: usize,
+    ) -> bool {
+        let va_to_pa = |va: VirtAddr| pa!(va.as_usize() - pa_va_offset);
+        debug!(
+            "map_linear: [{:#x}, {:#x}) -> [{:#x}, {:#x}) {:?}",
+            start,
+            start + size,
+            va_to_pa(start),
+            va_to_pa(start + size),
+            flags
+        );
+        pt.map_region(
+            start, va_to_pa, size, flags, false, // allow_huge
+            false, // flush_tlb_by_page
+        )
+        .map(|tlb| tlb.flush_all())
+        .is_ok()
+    }
+
+    /// Unmaps the pages in `[start, start + size)`, which are recorded in
+    /// `tlb`. The frames are not owned by the mapping, so their owner must
+    /// only reuse them after the TLBs of all CPUs are flushed by `tlb`.
+    pub(super) fn unmap_linear(
+        start: VirtAddr,
+        size: usize,
+        pt: &mut PageTable,
+        _pa_va_offset: usize,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        debug!("unmap_linear: [{:#x}, {:#x})", start, start + size);
+        // The local TLB is flushed page by page.
+        let unmapped = pt
+            .unmap_region(start, size, true)
+            .map(|local| local.ignore())
+            .is_ok();
+        tlb.add_range(start, size);
+        unmapped
+    }
+
+    /// Changes the flags of the pages in `[start, start + size)`, which are
+    /// recorded in `tlb`.
+    pub(super) fn protect_linear(
+        start: VirtAddr,
+        size: usize,
+        new_flags: MappingFlags,
+        pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        // The local TLB is flushed page by page.
+        let protected = pt
+            .protect_region(start, size, new_flags, true)
+            .map(|local| local.ignore())
+            .is_ok();
+        tlb.add_range(start, size);
+        protected
+    }
+}
@@ -0,0 +1,191 @@
+//! Memory mapping backends.
+
+use axhal::paging::{MappingFlags, PageTable};
+use memory_addr::VirtAddr;
+
+use crate::tlb::TlbBatch;
+
+mod alloc;
+mod file;
+mod linear;
+
+pub use self::file::{MappedFile, MappedFileRef};
+
+/// A unified enum type for different memory mapping backends.
+///
+/// Each memory area in an [`AddrSpace`](crate::AddrSpace) has a backend,
+/// which decides how the pages of the area are mapped, and handles the page
+/// faults in the area.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Backend {
+    /// Linear mapping backend.
+    ///
+    /// The offset between the virtual address and the physical address is
+    /// constant, which is specified by `pa_va_offset`. For example, the
+    /// virtual address `vaddr` is mapped to the physical address
+    /// `vaddr - pa_va_offset`. All pages are mapped when the area is created.
+    Linear {
+        /// `vaddr - paddr`.
+        pa_va_offset: usize,
+    },
+    /// Allocation mapping backend.
+    ///
+    /// Physical frames are allocated from the global allocator, and are not
+    /// contiguous. If `populate` is `true`, all frames are allocated when the
+    /// area is created. Otherwise, each frame is allocated on the first access
+    /// to its page, in the page fault handler. Frames shared by cloned address
+    /// spaces are copied on write.
+    Alloc {
+        /// Whether to populate the physical frames when creating the mapping.
+        populate: bool,
+    },
+    /// File mapping backend.
+    ///
+    /// The pages are mapped from the [`PageCache`](crate::PageCache) of the
+    /// file, into which they must be loaded before, as page faults never read
+    /// the file. The virtual address `vaddr` maps the data at offset
+    /// `vaddr - file_va_offset` of the file.
+    ///
+    /// All shared mappings of the file map the same cached frames. Their pages
+    /// are read-only until the first write to them, which marks them dirty,
+    /// and the dirty pages are written back to the file by
+    /// [`DirtyPages::write_back`](crate::DirtyPages::write_back). Private
+    /// mappings never write to the file, and the cached frames are
+    /// copy-on-write in them.
+    File {
+        /// The mapped file.
+        file: MappedFileRef,
+        /// `vaddr - file_offset`.
+        file_va_offset: usize,
+        /// Whether the changes are shared with the file.
+        shared: bool,
+    },
+}
+
+impl Backend {
+    /// Maps the pages of the area `[start, start + size)`, returns whether it
+    /// succeeded. The pages unmapped on failure are recorded in `tlb`.
+    pub(crate) fn map(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        match *self {
+            Self::Linear { pa_va_offset } => Self::map_linear(start, size, flags, pt, pa_va_offset),
+            Self::Alloc { populate } => Self::map_alloc(start, size, flags, pt, populate, tlb),
+            Self::File {
+                ref file,
+                file_va_offset,
+                ..
+            } => Self::map_file(start, size, flags, pt, file, file_va_offset, tlb),
+        }
+    }
+
+    /// Unmaps the pages of the area `[start, start + size)`, and deallocates
+    /// the physical frames owned by the backend. Returns whether it succeeded.
+    ///
+    /// The unmapped pages are recorded in `tlb`, and the frames owned by the
+    /// backend are only freed after the TLBs of all CPUs are flushed by it.
+    pub(crate) fn unmap(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        match *self {
+            Self::Linear { pa_va_offset } => Self::unmap_linear(start, size, pt, pa_va_offset, tlb),
+            Self::Alloc { .. } => Self::unmap_alloc(start, size, pt, tlb),
+            // The frames are reference counted as those of the allocation
+            // mappings, and the page cache holds its own references.
+            Self::File { .. } => Self::unmap_alloc(start, size, pt, tlb),
+        }
+    }
+
+    /// Changes the flags of the mapped pages in `[start, start + size)`,
+    /// returns whether it succeeded. The changed pages are recorded in `tlb`.
+    pub(crate) fn protect(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        new_flags: MappingFlags,
+        pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        match *self {
+            Self::Linear { .. } => Self::protect_linear(start, size, new_flags, pt, tlb),
+            Self::Alloc { .. } | Self::File { shared: false, .. } => {
+                Self::protect_alloc(start, size, new_flags, pt, tlb)
+            }
+            Self::File { shared: true, .. } => {
+                Self::protect_file_shared(start, size, new_flags, pt, tlb)
+            }
+        }
+    }
+
+    /// Maps the area `[start, start + size)` of `pt` into `new_pt`, which
+    /// shares the same physical memory. Returns whether it succeeded.
+    ///
+    /// The frames of allocation mappings and private file mappings become
+    /// copy-on-write: they are read-only in both page tables, and a frame is
+    /// copied on the first write to it (see
+    /// [`AddrSpace::clone_cow`](crate::AddrSpace::clone_cow)). The pages made
+    /// read-only in `pt` are recorded in `tlb`.
+    pub(crate) fn clone_map(
+        &self,
+        start: VirtAddr,
+        size: usize,
+        flags: MappingFlags,
+        pt: &mut PageTable,
+        new_pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        match *self {
+            Self::Linear { pa_va_offset } => {
+                Self::map_linear(start, size, flags, new_pt, pa_va_offset)
+            }
+            Self::Alloc { .. } | Self::File { shared: false, .. } => {
+                Self::clone_alloc(start, size, flags, pt, new_pt, tlb)
+            }
+            Self::File { shared: true, .. } => {
+                Self::clone_file_shared(start, size, flags, pt, new_pt)
+            }
+        }
+    }
+
+    /// Handles a page fault at `vaddr` in the area with the flags
+    /// `orig_flags`, returns whether the page is mapped successfully.
+    ///
+    /// The replaced mappings of copy-on-write pages are recorded in `tlb`.
+    pub(crate) fn handle_page_fault(
+        &self,
+        vaddr: VirtAddr,
+        orig_flags: MappingFlags,
+        pt: &mut PageTable,
+        tlb: &mut TlbBatch,
+    ) -> bool {
+        match *self {
+            // Linear areas are always fully mapped.
+            Self::Linear { .. } => false,
+            Self::Alloc { populate } => {
+                Self::handle_page_fault_alloc(vaddr, orig_flags, pt, populate, tlb)
+            }
+            Self::File {
+                ref file,
+                file_va_offset,
+                shared,
+            } => Self::handle_page_fault_file(
+                vaddr,
+                orig_flags,
+                pt,
+                file,
+                file_va_offset,
+                shared,
+                tlb,
+            ),
+        }
+    }
+}
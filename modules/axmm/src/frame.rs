@@ -0,0 +1,46 @@
+//! Physical frames allocated for the allocation mappings and page caches, with
+//! reference counts for sharing between address spaces.
+//!
+//! Only the counts of shared frames are recorded, a frame that is absent from
+//! the table is owned by only one mapping.
+
+use alloc::collections::BTreeMap;
+
+use axalloc::global_allocator;
+use axhal::mem::{phys_to_virt, virt_to_phys};
+use axlockdep::spin::SpinNoIrq;
+use memory_addr::{PhysAddr, PAGE_SIZE_4K};
+
+/// Reference counts of the frames shared by more than one mapping.
+static SHARED_FRAMES: SpinNoIrq<BTreeMap<PhysAddr, usize>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Allocates a zeroed physical frame, with a reference count of 1.
+pub(crate) fn alloc_frame() -> Option<PhysAddr> {
+    let vaddr = global_allocator().alloc_pages(1, PAGE_SIZE_4K).ok()?;
+    unsafe { core::ptr::write_bytes(vaddr as *mut u8, 0, PAGE_SIZE_4K) };
+    Some(virt_to_phys(vaddr.into()))
+}
+
+/// Drops a reference to the frame, and deallocates it if it's the last one.
+pub(crate) fn dealloc_frame(frame: PhysAddr) {
+    let mut shared = SHARED_FRAMES.lock();
+    if let Some(count) = shared.get_mut(&frame) {
+        *count -= 1;
+        if *count == 1 {
+            shared.remove(&frame);
+        }
+        return;
+    }
+    drop(shared);
+    global_allocator().dealloc_pages(phys_to_virt(frame).as_usize(), 1);
+}
+
+/// Adds a reference to the frame, when it's shared by another mapping.
+pub(crate) fn share_frame(frame: PhysAddr) {
+    *SHARED_FRAMES.lock().entry(frame).or_insert(1) += 1;
+}
+
+/// Returns the number of mappings sharing the frame.
+pub(crate) fn frame_ref_count(frame: PhysAddr) -> usize {
+    SHARED_FRAMES.lock().get(&frame).copied().unwrap_or(1)
+}
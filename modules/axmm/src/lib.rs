@@ -1,34 +1,46 @@
 //! [ArceOS](https://github.com/arceos-org/arceos) memory management module.
+//!
+//! An [`AddrSpace`] is made of memory areas mapped by different [`Backend`]s:
+//! linear mappings of physical memory, mappings of frames allocated from the
+//! global allocator, which can be populated lazily on page faults, and shared
+//! copy-on-write by cloned address spaces, and mappings of [`MappedFile`]s,
+//! whose pages are mapped from their [`PageCache`]s.
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 #[macro_use]
 extern crate log;
 extern crate alloc;
 
+mod area;
 mod aspace;
+mod backend;
+mod frame;
+mod page_cache;
+mod tlb;
 
+#[cfg(test)]
+mod tests;
+
+pub use self::area::MemoryArea;
 pub use self::aspace::AddrSpace;
+pub use self::backend::{Backend, MappedFile, MappedFileRef};
+pub use self::page_cache::{DirtyPages, PageCache};
+pub use self::tlb::TlbBatch;
 
-use axerrno::{AxError, AxResult};
+use axerrno::AxResult;
 use axhal::mem::phys_to_virt;
-use axhal::paging::PagingError;
-use kspin::SpinNoIrq;
+use axhal::paging::MappingFlags;
+use axlockdep::spin::{SpinNoIrq, SpinNoIrqGuard};
 use lazyinit::LazyInit;
-use memory_addr::{va, PhysAddr};
+use memory_addr::{va, PhysAddr, VirtAddr};
 
 static KERNEL_ASPACE: LazyInit<SpinNoIrq<AddrSpace>> = LazyInit::new();
+static KERNEL_PAGE_TABLE_ROOT: LazyInit<PhysAddr> = LazyInit::new();
 
-fn paging_err_to_ax_err(err: PagingError) -> AxError {
-    warn!("Paging error: {:?}", err);
-    match err {
-        PagingError::NoMemory => AxError::NoMemory,
-        PagingError::NotAligned => AxError::InvalidInput,
-        PagingError::NotMapped => AxError::NotFound,
-        PagingError::AlreadyMapped => AxError::AlreadyExists,
-        PagingError::MappedToHugePage => AxError::InvalidInput,
-    }
-}
+/// How many times to try locking an address space for a page fault raised by
+/// kernel code, before giving up (see [`handle_user_page_fault`]).
+const FAULT_LOCK_TRIES: usize = 1 << 24;
 
 /// Creates a new address space for kernel itself.
 pub fn new_kernel_aspace() -> AxResult<AddrSpace> {
@@ -42,14 +54,122 @@ pub fn new_kernel_aspace() -> AxResult<AddrSpace> {
     Ok(aspace)
 }
 
+/// Creates a new address space for user code, which covers
+/// `[base, base + size)` and must not overlap with the kernel address space.
+///
+/// Except on AArch64, where the kernel has its own page table root, the
+/// mappings of the kernel address space are shared into the new page table,
+/// so that the kernel keeps running when it's active. Kernel mappings added
+/// later under new root entries are not visible in it.
+pub fn new_user_aspace(base: VirtAddr, size: usize) -> AxResult<AddrSpace> {
+    let mut aspace = AddrSpace::new_empty(base, size)?;
+    if !cfg!(target_arch = "aarch64") {
+        aspace.copy_mappings_from(&KERNEL_ASPACE.lock())?;
+    }
+    Ok(aspace)
+}
+
 /// Returns the globally unique kernel address space.
 pub fn kernel_aspace() -> &'static SpinNoIrq<AddrSpace> {
     &KERNEL_ASPACE
 }
 
+/// Runs `f` with the kernel address space locked, and flushes the TLBs of all
+/// CPUs for the changes made by `f` after releasing the lock (see
+/// [`AddrSpace::take_tlb_batch`]), so that no stale TLB entry is left on any
+/// CPU when it returns.
+///
+/// It waits for the other CPUs, so it must not be called with IRQs disabled.
+pub fn with_kernel_aspace<R>(f: impl FnOnce(&mut AddrSpace) -> R) -> R {
+    let mut aspace = KERNEL_ASPACE.lock();
+    let ret = f(&mut aspace);
+    let tlb = aspace.take_tlb_batch();
+    drop(aspace);
+    drop(tlb);
+    ret
+}
+
 /// Returns the root physical address of the kernel page table.
+///
+/// It never changes after initialization, and takes no lock.
 pub fn kernel_page_table_root() -> PhysAddr {
-    KERNEL_ASPACE.lock().page_table_root()
+    *KERNEL_PAGE_TABLE_ROOT
+}
+
+/// Handles a page fault at `vaddr` in the kernel address space, with the
+/// access type `access_flags`. Returns whether the fault is resolved (see
+/// [`AddrSpace::handle_page_fault`]).
+///
+/// It's called in trap context. Only kernel code can access the kernel
+/// address space, so the lock of the address space is handled as described
+/// in [`handle_user_page_fault`] for faults raised by kernel code. Files are
+/// never read here, so the faulting code may hold the locks of the mapped
+/// files (see [`PageCache`]).
+pub fn handle_kernel_page_fault(vaddr: VirtAddr, access_flags: MappingFlags) -> bool {
+    handle_page_fault_in(&KERNEL_ASPACE, vaddr, access_flags, false)
+}
+
+/// Handles a page fault at `vaddr` in the address space `aspace` of user
+/// code, with the access type `access_flags`. Returns whether the fault is
+/// resolved (see [`AddrSpace::handle_page_fault`]).
+///
+/// It's called in trap context. `is_user` tells whether the fault is raised
+/// by user code, or by kernel code accessing user memory. In the latter case,
+/// the faulting code may hold the lock of `aspace` itself, so the lock is
+/// only tried for a while (another CPU holds it briefly), and it panics if
+/// the lock can't be acquired, instead of deadlocking silently.
+pub fn handle_user_page_fault(
+    aspace: &SpinNoIrq<AddrSpace>,
+    vaddr: VirtAddr,
+    access_flags: MappingFlags,
+    is_user: bool,
+) -> bool {
+    handle_page_fault_in(aspace, vaddr, access_flags, is_user)
+}
+
+fn lock_for_fault(
+    aspace: &SpinNoIrq<AddrSpace>,
+    vaddr: VirtAddr,
+    is_user: bool,
+) -> SpinNoIrqGuard<'_, AddrSpace> {
+    if is_user {
+        // User code never holds kernel locks.
+        return aspace.lock();
+    }
+    for _ in 0..FAULT_LOCK_TRIES {
+        if let Some(guard) = aspace.try_lock() {
+            return guard;
+        }
+        core::hint::spin_loop();
+    }
+    panic!(
+        "page fault @ {:#x} with its address space locked, accessed with the lock held?",
+        vaddr
+    );
+}
+
+fn handle_page_fault_in(
+    aspace_lock: &SpinNoIrq<AddrSpace>,
+    vaddr: VirtAddr,
+    access_flags: MappingFlags,
+    is_user: bool,
+) -> bool {
+    let mut aspace = lock_for_fault(aspace_lock, vaddr, is_user);
+    let ok = aspace.handle_page_fault(vaddr, access_flags);
+    let tlb = aspace.take_tlb_batch();
+    drop(aspace);
+    drop(tlb); // flushes other CPUs without the lock held
+    ok
+}
+
+/// Writes the dirty pages of the shared file mappings in
+/// `[start, start + size)` of the kernel address space back to their files
+/// (see [`AddrSpace::take_dirty_pages`]).
+///
+/// The files are written after releasing the lock of the address space, and
+/// flushing the TLBs of other CPUs, which may still write to the pages.
+pub fn sync_kernel_aspace(start: VirtAddr, size: usize) -> AxResult {
+    with_kernel_aspace(|aspace| aspace.take_dirty_pages(start, size))?.write_back()
 }
 
 /// Initializes virtual memory management.
@@ -61,6 +181,7 @@ pub fn init_memory_management() {
 
     let kernel_aspace = new_kernel_aspace().expect("failed to initialize kernel address space");
     debug!("kernel address space init OK: {:#x?}", kernel_aspace);
+    KERNEL_PAGE_TABLE_ROOT.init_once(kernel_aspace.page_table_root());
     KERNEL_ASPACE.init_once(SpinNoIrq::new(kernel_aspace));
     unsafe { axhal::arch::write_page_table_root(kernel_page_table_root()) };
 }
@@ -0,0 +1,426 @@
+//! Page caches of mapped files.
+//!
+//! Page faults are handled in trap context, maybe with IRQs disabled, with
+//! spinlocks held, or with the lock of the mapped file itself held (e.g., by
+//! `write(fd, p, n)` with `p` in a mapping of `fd`), so they never read the
+//! file. Instead, the pages of a file are loaded into its [`PageCache`] by
+//! [`PageCache::load`] before they are mapped, and file mappings only map the
+//! cached frames. Unlike Linux, which reads them on demand, the whole mapped
+//! part of the file is thus read into memory when it's mapped, so the size of
+//! the mappings is bounded by the free memory (see [`PageCache::load`]).
+//!
+//! All mappings of a file share the cached frames, so the changes through a
+//! shared mapping are visible to the other shared mappings at once, and to
+//! private mappings until they copy the page on write. The owner of the cache
+//! keeps reads and writes of the file coherent with the mappings by
+//! [`PageCache::read`] and [`PageCache::write`].
+//!
+//! Pages of shared mappings are mapped read-only until the first write to
+//! them, which marks them dirty (see `Backend::handle_page_fault_file`). Only
+//! dirty pages are written back, and a page is marked clean again only when no
+//! writable mapping of it is left, so that the next write to it faults again.
+
+use alloc::collections::{btree_map::Entry, BTreeMap};
+use alloc::vec::Vec;
+use core::ops::Range;
+
+use axalloc::global_allocator;
+use axerrno::{AxError, AxResult};
+use axhal::mem::phys_to_virt;
+use axlockdep::spin::SpinNoIrq;
+use memory_addr::{PhysAddr, PAGE_SIZE_4K};
+
+use crate::backend::{MappedFile, MappedFileRef};
+use crate::frame::{alloc_frame, dealloc_frame, frame_ref_count, share_frame};
+
+struct CachedPage {
+    frame: PhysAddr,
+    dirty: bool,
+}
+
+/// The pages of a [`MappedFile`] that are mapped, keyed by their offsets in
+/// the file.
+///
+/// The cache holds a reference to each frame, and each page table entry that
+/// maps it holds another one. Pages stay cached while they are mapped or
+/// dirty, and are freed with the cache otherwise.
+pub struct PageCache {
+    pages: SpinNoIrq<BTreeMap<u64, CachedPage>>,
+}
+
+impl PageCache {
+    /// Creates an empty page cache.
+    pub const fn new() -> Self {
+        Self {
+            pages: SpinNoIrq::new(BTreeMap::new()),
+        }
+    }
+
+    /// Whether no page is cached.
+    pub fn is_empty(&self) -> bool {
+        self.pages.lock().is_empty()
+    }
+
+    /// Loads the pages of `file` in `[offset, offset + size)` that are not
+    /// cached yet, where `offset` is page aligned.
+    ///
+    /// Pages beyond the end of the file are not loaded, so accesses to them
+    /// through mappings fail, and the part of the last page beyond it is
+    /// zeroed.
+    ///
+    /// The pages to load must fit in half of the free memory, so that a large
+    /// mapping can't exhaust it, or it fails with [`AxError::NoMemory`] before
+    /// reading anything. If it fails to read a page, the pages it has loaded
+    /// are freed, unless they have been mapped meanwhile.
+    ///
+    /// It reads the file, which may block, so it must not be called in trap
+    /// context or with spinlocks held.
+    pub fn load(&self, file: &dyn MappedFile, offset: u64, size: usize) -> AxResult {
+        let end = offset.saturating_add(size as u64).min(file.size()?);
+        if end <= offset {
+            return Ok(());
+        }
+        let total = (end - offset).div_ceil(PAGE_SIZE_4K as u64) as usize;
+        let missing = total - self.pages.lock().range(offset..end).count();
+        if missing > global_allocator().available_pages() / 2 {
+            warn!(
+                "not enough memory to load {} pages of the mapped file",
+                missing
+            );
+            return Err(AxError::NoMemory);
+        }
+        let mut loaded = Vec::new();
+        let mut page = offset;
+        while page < end {
+            if !self.pages.lock().contains_key(&page) {
+                let frame = match read_page(file, page) {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        for page in loaded {
+                            self.evict_unmapped(page);
+                        }
+                        return Err(e);
+                    }
+                };
+                let mut pages = self.pages.lock();
+                match pages.entry(page) {
+                    Entry::Vacant(entry) => {
+                        entry.insert(CachedPage {
+                            frame,
+                            dirty: false,
+                        });
+                        loaded.push(page);
+                    }
+                    // Loaded by another task in the meantime.
+                    Entry::Occupied(_) => dealloc_frame(frame),
+                }
+            }
+            page += PAGE_SIZE_4K as u64;
+        }
+        Ok(())
+    }
+
+    /// Copies the cached data in `[offset, offset + buf.len())` into `buf`,
+    /// which has just been read from the file, as the cached pages may have
+    /// been changed by shared mappings.
+    pub fn read(&self, offset: u64, buf: &mut [u8]) {
+        for (page, frame) in self.overlapping(offset, buf.len()) {
+            let (buf_range, frame_offset) = overlap(offset, buf.len(), page);
+            let src = phys_to_virt(frame + frame_offset).as_ptr();
+            let dst = &mut buf[buf_range];
+            // The cache is not locked during the copy, as `buf` may be in a
+            // mapping of the same file, whose page faults lock the cache. For
+            // the same reason, `buf` may overlap with the page.
+            unsafe { core::ptr::copy(src, dst.as_mut_ptr(), dst.len()) };
+            dealloc_frame(frame);
+        }
+    }
+
+    /// Copies `buf`, which has just been written to the file at `offset`, into
+    /// the cached pages, so that the mappings of the file see it.
+    ///
+    /// It must be called with the file locked against the write-back of the
+    /// pages (i.e., [`MappedFile::write_at`]), so that older data is never
+    /// written back over `buf`.
+    pub fn write(&self, offset: u64, buf: &[u8]) {
+        for (page, frame) in self.overlapping(offset, buf.len()) {
+            let (buf_range, frame_offset) = overlap(offset, buf.len(), page);
+            let src = &buf[buf_range];
+            let dst = phys_to_virt(frame + frame_offset).as_mut_ptr();
+            // `buf` may be in a mapping of the same file (see `read`).
+            unsafe { core::ptr::copy(src.as_ptr(), dst, src.len()) };
+            dealloc_frame(frame);
+        }
+    }
+
+    /// Returns the cached pages overlapping `[offset, offset + len)`, with a
+    /// reference to each frame.
+    fn overlapping(&self, offset: u64, len: usize) -> Vec<(u64, PhysAddr)> {
+        if len == 0 {
+            return Vec::new();
+        }
+        let first = offset - offset % PAGE_SIZE_4K as u64;
+        let end = offset.saturating_add(len as u64);
+        let pages = self.pages.lock();
+        pages
+            .range(first..end)
+            .map(|(&page, cached)| {
+                share_frame(cached.frame);
+                (page, cached.frame)
+            })
+            .collect()
+    }
+
+    /// Returns the frame of the page at `offset`, with a reference taken for a
+    /// new mapping of it, if it's cached.
+    pub(crate) fn get(&self, offset: u64) -> Option<PhysAddr> {
+        let pages = self.pages.lock();
+        let frame = pages.get(&offset)?.frame;
+        share_frame(frame);
+        Some(frame)
+    }
+
+    /// Marks the page at `offset` dirty, on a write to it. Returns whether it's
+    /// cached.
+    pub(crate) fn mark_dirty(&self, offset: u64) -> bool {
+        match self.pages.lock().get_mut(&offset) {
+            Some(page) => {
+                page.dirty = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Takes the page at `offset` to be written back if it's dirty, with a
+    /// reference to its frame. `mapped` tells whether the caller maps it
+    /// read-only.
+    pub(crate) fn take_dirty(&self, offset: u64, mapped: bool) -> Option<PhysAddr> {
+        take_dirty(&mut self.pages.lock(), offset, mapped as usize, false)
+    }
+
+    /// Takes the dirty pages in `range` of the file to be written back, before
+    /// they are unmapped by the caller, and frees the clean pages that will no
+    /// longer be mapped.
+    ///
+    /// `mapped` tells whether the caller maps the page at an offset to the
+    /// given frame. These mappings are about to be removed, so they are not
+    /// counted as writers of the pages.
+    pub(crate) fn take_dirty_unmapped(
+        &self,
+        range: Range<u64>,
+        mapped: impl Fn(u64, PhysAddr) -> bool,
+    ) -> Vec<(u64, PhysAddr)> {
+        let mut pages = self.pages.lock();
+        let offsets: Vec<(u64, usize)> = pages
+            .range(range)
+            .map(|(&offset, page)| (offset, mapped(offset, page.frame) as usize))
+            .collect();
+        offsets
+            .into_iter()
+            .filter_map(|(offset, mappers)| {
+                take_dirty(&mut pages, offset, mappers, true).map(|frame| (offset, frame))
+            })
+            .collect()
+    }
+
+    /// Frees the page at `offset` if it's clean and no longer mapped, e.g.,
+    /// after it has been written back.
+    fn evict_unmapped(&self, offset: u64) {
+        let mut pages = self.pages.lock();
+        if let Entry::Occupied(entry) = pages.entry(offset) {
+            if !entry.get().dirty && frame_ref_count(entry.get().frame) == 1 {
+                dealloc_frame(entry.remove().frame);
+            }
+        }
+    }
+}
+
+impl Default for PageCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for PageCache {
+    fn drop(&mut self) {
+        for (offset, page) in core::mem::take(self.pages.get_mut()) {
+            if page.dirty {
+                warn!("dropping the dirty file page at {:#x}", offset);
+            }
+            dealloc_frame(page.frame);
+        }
+    }
+}
+
+/// Takes the dirty page at `offset` in `pages` (see [`PageCache::take_dirty`]).
+///
+/// The page is marked clean only if the caller's `mappers` entries are its
+/// only mappings, which are read-only or about to be removed, so that any later
+/// write to it marks it dirty again. Otherwise, it may still be written through
+/// other mappings, so it stays dirty, and is written back again next time. If
+/// `unmapping` is `true`, the caller's entries are about to be removed, and a
+/// clean page that is not mapped otherwise is freed (the frame itself lives on
+/// until the entries are removed and the TLBs are flushed).
+fn take_dirty(
+    pages: &mut BTreeMap<u64, CachedPage>,
+    offset: u64,
+    mappers: usize,
+    unmapping: bool,
+) -> Option<PhysAddr> {
+    let page = pages.get_mut(&offset)?;
+    // One reference is held by the cache itself.
+    let other_mappers = frame_ref_count(page.frame).saturating_sub(1 + mappers);
+    if !page.dirty {
+        if unmapping && other_mappers == 0 {
+            dealloc_frame(pages.remove(&offset).unwrap().frame);
+        }
+        return None;
+    }
+    if other_mappers == 0 {
+        page.dirty = false;
+    }
+    share_frame(page.frame);
+    Some(page.frame)
+}
+
+/// Returns the range in a buffer of `len` bytes at `offset` of the file, and
+/// the offset in the page at `page`, where they overlap.
+fn overlap(offset: u64, len: usize, page: u64) -> (Range<usize>, usize) {
+    let start = offset.max(page);
+    let end = (offset + len as u64).min(page + PAGE_SIZE_4K as u64);
+    let buf_start = (start - offset) as usize;
+    let buf_end = (end - offset) as usize;
+    (buf_start..buf_end, (start - page) as usize)
+}
+
+/// Allocates a frame and reads the page at `offset` of `file` into it.
+fn read_page(file: &dyn MappedFile, offset: u64) -> AxResult<PhysAddr> {
+    let frame = alloc_frame().ok_or(AxError::NoMemory)?;
+    let buf =
+        unsafe { core::slice::from_raw_parts_mut(phys_to_virt(frame).as_mut_ptr(), PAGE_SIZE_4K) };
+    let mut read = 0;
+    while read < PAGE_SIZE_4K {
+        match file.read_at(offset + read as u64, &mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) => {
+                dealloc_frame(frame);
+                return Err(e);
+            }
+        }
+    }
+    Ok(frame)
+}
+
+/// A page of a shared file mapping, i.e., a page of data at `offset` of the
+/// file.
+struct FilePage {
+    file: MappedFileRef,
+    offset: u64,
+}
+
+impl FilePage {
+    /// Writes the contents of `frame` back to the page, except the part
+    /// beyond the end of the file, which is never extended.
+    fn store(&self, frame: PhysAddr) -> AxResult {
+        let file_size = self.file.size()?;
+        if self.offset >= file_size {
+            return Ok(());
+        }
+        let len = (file_size - self.offset).min(PAGE_SIZE_4K as u64) as usize;
+        let buf = unsafe { core::slice::from_raw_parts(phys_to_virt(frame).as_ptr(), len) };
+        let mut written = 0;
+        while written < len {
+            match self
+                .file
+                .write_at(self.offset + written as u64, &buf[written..])?
+            {
+                0 => return Err(AxError::WriteZero),
+                n => written += n,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Dirty pages of shared file mappings, taken from the page caches of the
+/// files, to be written back by [`DirtyPages::write_back`].
+///
+/// They are taken with the lock of the address space held, and written back
+/// after it's released, as the I/O may block. The data stays in the cached
+/// frames meanwhile, so no write to them is lost. Pages that are dropped
+/// without being written back are marked dirty again.
+#[must_use]
+pub struct DirtyPages(Vec<(FilePage, PhysAddr)>);
+
+impl DirtyPages {
+    /// Creates an empty set of pages.
+    pub const fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    pub(crate) fn push(&mut self, file: &MappedFileRef, offset: u64, frame: PhysAddr) {
+        let page = FilePage {
+            file: file.clone(),
+            offset,
+        };
+        self.0.push((page, frame));
+    }
+
+    /// Returns the number of pages.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether there is no page.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Writes the pages back to their files.
+    ///
+    /// All pages are written even if some of them fail, and the first error
+    /// is returned. The pages that fail stay dirty in the page caches, and are
+    /// written back again with the next pages taken from them.
+    ///
+    /// It may block, so it must not be called in trap context or with
+    /// spinlocks held.
+    pub fn write_back(mut self) -> AxResult {
+        let mut res = Ok(());
+        for (page, frame) in core::mem::take(&mut self.0) {
+            let cache = page.file.page_cache();
+            match page.store(frame) {
+                Ok(()) => {
+                    dealloc_frame(frame);
+                    cache.evict_unmapped(page.offset);
+                }
+                Err(e) => {
+                    warn!(
+                        "failed to write back the file page at {:#x}: {:?}",
+                        page.offset, e
+                    );
+                    cache.mark_dirty(page.offset);
+                    dealloc_frame(frame);
+                    res = res.and(Err(e));
+                }
+            }
+        }
+        res
+    }
+}
+
+impl Default for DirtyPages {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for DirtyPages {
+    fn drop(&mut self) {
+        for (page, frame) in self.0.drain(..) {
+            page.file.page_cache().mark_dirty(page.offset);
+            dealloc_frame(frame);
+        }
+    }
+}
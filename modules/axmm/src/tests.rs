@@ -0,0 +1,501 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Once};
+
+use axalloc::global_allocator;
+use axerrno::AxResult;
+use axhal::mem::virt_to_phys;
+use axhal::paging::MappingFlags;
+use memory_addr::{va, PhysAddr, VirtAddr, VirtAddrRange, PAGE_SIZE_4K};
+
+use crate::frame::frame_ref_count;
+use crate::{AddrSpace, DirtyPages, MappedFile, PageCache};
+
+const HEAP_SIZE: usize = 16 * 1024 * 1024;
+
+#[repr(align(4096))]
+struct Heap([u8; HEAP_SIZE]);
+
+static mut HEAP: Heap = Heap([0; HEAP_SIZE]);
+static INIT: Once = Once::new();
+// Frame usage is checked in the tests, so they run one by one.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+const BASE: VirtAddr = va!(0x1000_0000);
+const SIZE: usize = 0x1000_0000;
+const RW: MappingFlags = MappingFlags::READ.union(MappingFlags::WRITE);
+
+fn init_heap() {
+    INIT.call_once(|| {
+        let heap = unsafe { core::ptr::addr_of_mut!(HEAP) } as usize;
+        axalloc::global_init(heap, HEAP_SIZE);
+    });
+}
+
+fn new_aspace() -> AddrSpace {
+    init_heap();
+    AddrSpace::new_empty(BASE, SIZE).unwrap()
+}
+
+fn mapping(aspace: &AddrSpace, vaddr: VirtAddr) -> Option<(PhysAddr, MappingFlags)> {
+    let (paddr, flags, _) = aspace.page_table().query(vaddr).ok()?;
+    Some((paddr, flags))
+}
+
+/// A file in memory, which counts the writes to it.
+struct MemFile {
+    data: Mutex<Vec<u8>>,
+    writes: AtomicUsize,
+    page_cache: PageCache,
+}
+
+impl MemFile {
+    fn new(data: &[u8]) -> Arc<Self> {
+        Arc::new(Self {
+            data: Mutex::new(data.to_vec()),
+            writes: AtomicUsize::new(0),
+            page_cache: PageCache::new(),
+        })
+    }
+
+    fn data(&self) -> Vec<u8> {
+        self.data.lock().unwrap().clone()
+    }
+
+    fn writes(&self) -> usize {
+        self.writes.load(Ordering::SeqCst)
+    }
+}
+
+impl MappedFile for MemFile {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> AxResult<usize> {
+        let data = self.data.lock().unwrap();
+        let start = (offset as usize).min(data.len());
+        let len = buf.len().min(data.len() - start);
+        buf[..len].copy_from_slice(&data[start..start + len]);
+        Ok(len)
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> AxResult<usize> {
+        self.writes.fetch_add(1, Ordering::SeqCst);
+        let mut data = self.data.lock().unwrap();
+        let end = offset as usize + buf.len();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[offset as usize..end].copy_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn size(&self) -> AxResult<u64> {
+        Ok(self.data.lock().unwrap().len() as u64)
+    }
+
+    fn page_cache(&self) -> &PageCache {
+        &self.page_cache
+    }
+}
+
+fn area_ranges(aspace: &AddrSpace) -> Vec<(usize, usize, MappingFlags)> {
+    aspace
+        .areas()
+        .map(|area| (area.start() - BASE, area.end() - BASE, area.flags()))
+        .collect()
+}
+
+#[test]
+fn test_linear() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+
+    let frame = global_allocator().alloc_pages(2, PAGE_SIZE_4K).unwrap();
+    let paddr = virt_to_phys(frame.into());
+    let start = BASE + 0x10_0000;
+    aspace
+        .map_linear(start, paddr, 2 * PAGE_SIZE_4K, RW)
+        .unwrap();
+    let (pa, flags, _) = aspace.page_table().query(start + PAGE_SIZE_4K).unwrap();
+    assert_eq!(pa, paddr + PAGE_SIZE_4K);
+    assert_eq!(flags, RW);
+
+    // Accesses go to the physical memory directly.
+    aspace.write(start + PAGE_SIZE_4K - 2, b"abcd").unwrap();
+    let data = unsafe { core::slice::from_raw_parts((frame + PAGE_SIZE_4K - 2) as *const u8, 4) };
+    assert_eq!(data, b"abcd");
+
+    // Linear areas are fully mapped, so faults in them are invalid accesses.
+    assert!(!aspace.handle_page_fault(start, MappingFlags::EXECUTE));
+    assert!(!aspace.handle_page_fault(start - PAGE_SIZE_4K, MappingFlags::READ));
+    // Unaligned or overlapping mappings are rejected.
+    assert!(aspace
+        .map_linear(start, paddr + 1, PAGE_SIZE_4K, RW)
+        .is_err());
+    assert!(aspace
+        .map_linear(start + PAGE_SIZE_4K, paddr, PAGE_SIZE_4K, RW)
+        .is_err());
+
+    aspace.unmap(start, 2 * PAGE_SIZE_4K).unwrap();
+    assert!(aspace.page_table().query(start).is_err());
+    // The frames are only reused after the TLBs of all CPUs are flushed.
+    assert!(!aspace.take_tlb_batch().is_empty());
+    global_allocator().dealloc_pages(frame, 2);
+}
+
+#[test]
+fn test_lazy_alloc() {
+    let _lock = SERIAL.lock();
+    init_heap();
+    let used_pages = global_allocator().used_pages();
+    let mut aspace = new_aspace();
+
+    // A large sparse mapping costs no frames until it's touched.
+    let start = BASE + 0x20_0000;
+    let size = 0x100_0000;
+    let before_map = global_allocator().used_pages();
+    aspace.map_alloc(start, size, RW, false).unwrap();
+    assert!(aspace.page_table().query(start).is_err());
+    assert_eq!(global_allocator().used_pages(), before_map);
+
+    // Only the faulting page is mapped, with the flags of the area.
+    assert!(aspace.handle_page_fault(start + 0x1234, MappingFlags::WRITE));
+    let (_, flags, _) = aspace.page_table().query(start + 0x1000).unwrap();
+    assert_eq!(flags, RW);
+    assert!(aspace.page_table().query(start).is_err());
+    assert!(aspace.page_table().query(start + 0x2000).is_err());
+    // Accesses not permitted by the area are not resolved.
+    assert!(!aspace.handle_page_fault(start, MappingFlags::EXECUTE));
+    assert!(aspace.page_table().query(start).is_err());
+
+    // Reads and writes populate the pages they touch, which are zeroed.
+    let addr = start + size - PAGE_SIZE_4K - 2;
+    let mut buf = [0xff; 4];
+    aspace.read(addr, &mut buf).unwrap();
+    assert_eq!(buf, [0; 4]);
+    aspace.write(addr, b"abcd").unwrap();
+    aspace.read(addr, &mut buf).unwrap();
+    assert_eq!(&buf, b"abcd");
+    // Page tables are allocated as well, so only count the data pages.
+    let mapped = (0..size / PAGE_SIZE_4K)
+        .filter(|i| aspace.page_table().query(start + i * PAGE_SIZE_4K).is_ok())
+        .count();
+    assert_eq!(mapped, 3);
+
+    // Populated mappings are mapped at once.
+    let start2 = start + size;
+    aspace
+        .map_alloc(start2, 4 * PAGE_SIZE_4K, RW, true)
+        .unwrap();
+    for i in 0..4 {
+        assert!(aspace.page_table().query(start2 + i * PAGE_SIZE_4K).is_ok());
+    }
+
+    // All frames are freed on unmapping, once the TLBs of all CPUs are
+    // flushed, and the page tables on dropping.
+    aspace.unmap(start, size + 4 * PAGE_SIZE_4K).unwrap();
+    assert!(aspace.page_table().query(start + 0x1000).is_err());
+    let unmapped = global_allocator().used_pages();
+    drop(aspace.take_tlb_batch());
+    assert_eq!(global_allocator().used_pages(), unmapped - 7);
+    drop(aspace);
+    assert_eq!(global_allocator().used_pages(), used_pages);
+}
+
+#[test]
+fn test_find_free_area() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+    let limit = VirtAddrRange::from_start_size(BASE, SIZE);
+
+    aspace.map_alloc(BASE + 0x1000, 0x1000, RW, false).unwrap();
+    aspace.map_alloc(BASE + 0x4000, 0x1000, RW, false).unwrap();
+    assert_eq!(aspace.find_free_area(BASE, 0x1000, limit), Some(BASE));
+    assert_eq!(
+        aspace.find_free_area(BASE, 0x2000, limit),
+        Some(BASE + 0x2000)
+    );
+    assert_eq!(
+        aspace.find_free_area(BASE, 0x3000, limit),
+        Some(BASE + 0x5000)
+    );
+    // A hint inside an area starts the search at its end.
+    assert_eq!(
+        aspace.find_free_area(BASE + 0x1000, 0x1000, limit),
+        Some(BASE + 0x2000)
+    );
+    // The range must fit in the limit.
+    let small = VirtAddrRange::from_start_size(BASE, 0x4000);
+    assert_eq!(aspace.find_free_area(BASE, 0x3000, small), None);
+    assert_eq!(
+        aspace.find_free_area(BASE, 0x2000, small),
+        Some(BASE + 0x2000)
+    );
+    assert_eq!(aspace.find_free_area(BASE, SIZE, limit), None);
+    // The size must be page aligned.
+    assert_eq!(aspace.find_free_area(BASE, 0x800, limit), None);
+}
+
+#[test]
+fn test_merge_split() {
+    let _lock = SERIAL.lock();
+    let mut aspace = new_aspace();
+
+    // Adjacent areas with the same flags and backend are merged.
+    aspace.map_alloc(BASE, 0x3000, RW, false).unwrap();
+    aspace.map_alloc(BASE + 0x3000, 0x2000, RW, false).unwrap();
+    assert_eq!(area_ranges(&aspace), [(0, 0x5000, RW)]);
+    // Different backends or flags are not merged.
+    aspace.map_alloc(BASE + 0x5000, 0x1000, RW, true).unwrap();
+    aspace
+        .map_alloc(BASE + 0x6000, 0x1000, MappingFlags::READ, false)
+        .unwrap();
+    assert_eq!(
+        area_ranges(&aspace),
+        [
+            (0, 0x5000, RW),
+            (0x5000, 0x6000, RW),
+            (0x6000, 0x7000, MappingFlags::READ)
+        ]
+    );
+    aspace.unmap(BASE + 0x5000, 0x2000).unwrap();
+
+    // Protecting a part of an area splits it.
+    assert!(aspace.handle_page_fault(BASE + 0x1000, MappingFlags::WRITE));
+    aspace
+        .protect(BASE + 0x1000, 0x1000, MappingFlags::READ)
+        .unwrap();
+    assert_eq!(
+        area_ranges(&aspace),
+        [
+            (0, 0x1000, RW),
+            (0x1000, 0x2000, MappingFlags::READ),
+            (0x2000, 0x5000, RW)
+        ]
+    );
+    let (_, flags, _) = aspace.page_table().query(BASE + 0x1000).unwrap();
+    assert_eq!(flags, MappingFlags::READ);
+    // Pages mapped later get the flags of their part.
+    assert!(!aspace.handle_page_fault(BASE + 0x1800, MappingFlags::WRITE));
+    assert!(aspace.handle_page_fault(BASE + 0x2000, MappingFlags::WRITE));
+
+    // Restoring the flags merges the parts again.
+    aspace.protect(BASE + 0x1000, 0x1000, RW).unwrap();
+    assert_eq!(area_ranges(&aspace), [(0, 0x5000, RW)]);
+
+    // Unmapping a part splits the area, and mapping it again merges them.
+    aspace.unmap(BASE + 0x1000, 0x2000).unwrap();
+    assert_eq!(
+        area_ranges(&aspace),
+        [(0, 0x1000, RW), (0x3000, 0x5000, RW)]
+    );
+    assert!(aspace.page_table().query(BASE + 0x1000).is_err());
+    assert!(aspace.page_table().query(BASE + 0x2000).is_err());
+    aspace.map_alloc(BASE + 0x1000, 0x2000, RW, false).unwrap();
+    assert_eq!(area_ranges(&aspace), [(0, 0x5000, RW)]);
+
+    // Ranges out of the address space or unaligned are rejected.
+    assert!(aspace.unmap(BASE - 0x1000, 0x2000).is_err());
+    assert!(aspace.protect(BASE + 0x800, 0x1000, RW).is_err());
+    assert!(aspace.map_alloc(BASE + 0x4000, 0x2000, RW, false).is_err());
+}
+
+#[test]
+fn test_clone_cow() {
+    let _lock = SERIAL.lock();
+    init_heap();
+    let used_pages = global_allocator().used_pages();
+    let mut parent = new_aspace();
+
+    let start = BASE + 0x30_0000;
+    parent
+        .map_alloc(start, 3 * PAGE_SIZE_4K, RW, false)
+        .unwrap();
+    parent.write(start, b"page0").unwrap();
+    parent.write(start + PAGE_SIZE_4K, b"page1").unwrap();
+    assert!(parent.take_tlb_batch().is_empty());
+
+    // Populated pages are shared read-only, and the others stay lazy.
+    let mut child = parent.clone_cow().unwrap();
+    assert!(!parent.take_tlb_batch().is_empty());
+    let (frame0, flags, _) = parent.page_table().query(start).unwrap();
+    assert_eq!(flags, MappingFlags::READ);
+    assert_eq!(mapping(&child, start), Some((frame0, flags)));
+    assert_eq!(frame_ref_count(frame0), 2);
+    assert!(parent.page_table().query(start + 2 * PAGE_SIZE_4K).is_err());
+    assert!(child.page_table().query(start + 2 * PAGE_SIZE_4K).is_err());
+    let mut buf = [0; 5];
+    child.read(start, &mut buf).unwrap();
+    assert_eq!(&buf, b"page0");
+
+    // The first write copies the frame. The reference to the old one is only
+    // dropped after the other CPUs flush their TLBs.
+    child.write(start, b"child").unwrap();
+    let (child_frame0, flags, _) = child.page_table().query(start).unwrap();
+    assert_ne!(child_frame0, frame0);
+    assert_eq!(flags, RW);
+    assert_eq!(frame_ref_count(frame0), 2);
+    drop(child.take_tlb_batch());
+    assert_eq!(frame_ref_count(frame0), 1);
+    parent.read(start, &mut buf).unwrap();
+    assert_eq!(&buf, b"page0");
+    child.read(start, &mut buf).unwrap();
+    assert_eq!(&buf, b"child");
+
+    // The last sharer takes the frame over without copying.
+    assert!(parent.handle_page_fault(start, MappingFlags::WRITE));
+    assert_eq!(mapping(&parent, start), Some((frame0, RW)));
+    assert!(parent.take_tlb_batch().is_empty());
+
+    // Lazy pages are allocated separately in each address space.
+    parent.write(start + 2 * PAGE_SIZE_4K, b"lazy").unwrap();
+    assert!(child.page_table().query(start + 2 * PAGE_SIZE_4K).is_err());
+
+    // Dropping a sharer drops its references, and the frames are freed with
+    // the last one.
+    let (frame1, _, _) = child.page_table().query(start + PAGE_SIZE_4K).unwrap();
+    assert_eq!(frame_ref_count(frame1), 2);
+    drop(child);
+    assert_eq!(frame_ref_count(frame1), 1);
+    parent.read(start + PAGE_SIZE_4K, &mut buf).unwrap();
+    assert_eq!(&buf, b"page1");
+    drop(parent);
+    assert_eq!(global_allocator().used_pages(), used_pages);
+}
+
+#[test]
+fn test_file_shared() {
+    let _lock = SERIAL.lock();
+    init_heap();
+    let used_pages = global_allocator().used_pages();
+    let mut aspace = new_aspace();
+
+    // Two pages and a half.
+    let mut data = vec![b'a'; 2 * PAGE_SIZE_4K + PAGE_SIZE_4K / 2];
+    data[PAGE_SIZE_4K] = b'b';
+    let file = MemFile::new(&data);
+    let size = 4 * PAGE_SIZE_4K;
+    file.page_cache().load(&*file, 0, size).unwrap();
+
+    // Both mappings map the cached frames, read-only until they are written.
+    let (a, b) = (BASE + 0x40_0000, BASE + 0x50_0000);
+    aspace.map_file(a, size, RW, file.clone(), 0, true).unwrap();
+    aspace.map_file(b, size, RW, file.clone(), 0, true).unwrap();
+    let (frame, flags, _) = aspace.page_table().query(a + PAGE_SIZE_4K).unwrap();
+    assert_eq!(flags, MappingFlags::READ);
+    assert_eq!(mapping(&aspace, b + PAGE_SIZE_4K), Some((frame, flags)));
+    // The last page is zeroed beyond the end of the file, and the pages after
+    // it can't be accessed, as faults never read the file.
+    let mut buf = [0xff; 2];
+    let last = a + 2 * PAGE_SIZE_4K + PAGE_SIZE_4K / 2 - 1;
+    aspace.read(last, &mut buf).unwrap();
+    assert_eq!(buf, [b'a', 0]);
+    assert!(!aspace.handle_page_fault(a + 3 * PAGE_SIZE_4K, MappingFlags::READ));
+
+    // Writes through a mapping are visible to the other one, and to the reads
+    // of the file through the cache.
+    aspace.write(a + PAGE_SIZE_4K, b"shared").unwrap();
+    assert_eq!(mapping(&aspace, a + PAGE_SIZE_4K), Some((frame, RW)));
+    let mut buf = [0; 6];
+    aspace.read(b + PAGE_SIZE_4K, &mut buf).unwrap();
+    assert_eq!(&buf, b"shared");
+    let mut buf = [0; 7];
+    file.read_at(PAGE_SIZE_4K as u64 - 1, &mut buf).unwrap();
+    assert_eq!(&buf, b"abaaaaa");
+    file.page_cache().read(PAGE_SIZE_4K as u64 - 1, &mut buf);
+    assert_eq!(&buf, b"ashared");
+    assert_eq!(file.writes(), 0);
+
+    // Only the dirty page is written back, and made read-only. It stays dirty,
+    // as it's also mapped by the other mapping.
+    let pages = aspace.take_dirty_pages(a, size).unwrap();
+    assert_eq!(pages.len(), 1);
+    assert_eq!(
+        mapping(&aspace, a + PAGE_SIZE_4K),
+        Some((frame, MappingFlags::READ))
+    );
+    drop(aspace.take_tlb_batch());
+    pages.write_back().unwrap();
+    assert_eq!(&file.data()[PAGE_SIZE_4K..PAGE_SIZE_4K + 6], b"shared");
+    assert_eq!(file.writes(), 1);
+    assert_eq!(aspace.take_dirty_pages(a, size).unwrap().len(), 1);
+
+    // Once it's mapped by one mapping only, it's clean until written again.
+    aspace.unmap(b, size).unwrap();
+    drop(aspace.take_tlb_batch());
+    aspace
+        .take_dirty_pages(a, size)
+        .unwrap()
+        .write_back()
+        .unwrap();
+    assert!(aspace.take_dirty_pages(a, size).unwrap().is_empty());
+    assert_eq!(file.writes(), 2);
+
+    // Dirty pages that are made read-only are still written back.
+    aspace.write(a, b"x").unwrap();
+    aspace.protect(a, size, MappingFlags::READ).unwrap();
+    assert_eq!(aspace.take_dirty_pages(a, size).unwrap().len(), 1);
+
+    // Unmapping takes the dirty pages, and the cached pages are freed once
+    // they are clean and unmapped.
+    let mut pages = DirtyPages::new();
+    aspace.unmap_dirty(a, size, &mut pages).unwrap();
+    assert_eq!(pages.len(), 1);
+    drop(aspace.take_tlb_batch());
+    pages.write_back().unwrap();
+    assert_eq!(file.data()[0], b'x');
+    assert_eq!(file.writes(), 3);
+    assert!(file.page_cache().is_empty());
+
+    drop(aspace);
+    drop(file);
+    assert_eq!(global_allocator().used_pages(), used_pages);
+}
+
+#[test]
+fn test_file_private() {
+    let _lock = SERIAL.lock();
+    init_heap();
+    let used_pages = global_allocator().used_pages();
+    let mut aspace = new_aspace();
+
+    let file = MemFile::new(&[b'a'; PAGE_SIZE_4K]);
+    file.page_cache().load(&*file, 0, PAGE_SIZE_4K).unwrap();
+    let start = BASE + 0x60_0000;
+    aspace
+        .map_file(start, PAGE_SIZE_4K, RW, file.clone(), 0, false)
+        .unwrap();
+    let (frame, flags, _) = aspace.page_table().query(start).unwrap();
+    assert_eq!(flags, MappingFlags::READ);
+
+    // Writes to the file are visible until the page is copied on write.
+    file.page_cache().write(0, b"bb");
+    let mut buf = [0; 3];
+    aspace.read(start, &mut buf).unwrap();
+    assert_eq!(&buf, b"bba");
+    aspace.write(start, b"private").unwrap();
+    let (new_frame, flags, _) = aspace.page_table().query(start).unwrap();
+    assert_ne!(new_frame, frame);
+    assert_eq!(flags, RW);
+    drop(aspace.take_tlb_batch());
+    let mut buf = [0; 7];
+    file.page_cache().read(0, &mut buf);
+    assert_eq!(&buf, b"bbaaaaa");
+
+    // Private mappings are never written back.
+    assert!(aspace
+        .take_dirty_pages(start, PAGE_SIZE_4K)
+        .unwrap()
+        .is_empty());
+    let mut pages = DirtyPages::new();
+    aspace.unmap_dirty(start, PAGE_SIZE_4K, &mut pages).unwrap();
+    assert!(pages.is_empty());
+    assert_eq!(file.writes(), 0);
+    assert_eq!(file.data(), [b'a'; PAGE_SIZE_4K]);
+
+    // Files are not loaded if they don't fit in half of the free memory.
+    let large = MemFile::new(&vec![0; HEAP_SIZE]);
+    assert!(large.page_cache().load(&*large, 0, HEAP_SIZE).is_err());
+    assert!(large.page_cache().is_empty());
+
+    drop(aspace);
+    drop(file);
+    assert_eq!(global_allocator().used_pages(), used_pages);
+}
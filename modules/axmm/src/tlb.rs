@@ -0,0 +1,105 @@
+//! Deferred TLB shootdown.
+//!
+//! An address space may be active on several CPUs, so after a page is
+//! unmapped, remapped, or its flags are changed, the other CPUs must flush
+//! their TLBs as well. Every such change of an address space is recorded. The flush
+//! waits for all of them, which deadlocks if another CPU is spinning on the
+//! (IRQ-disabling) lock of the address space at the same time. So the page
+//! table is changed under the lock, and the TLBs of all CPUs are flushed after
+//! the lock is released, by dropping the [`TlbBatch`] taken from the address
+//! space with [`AddrSpace::take_tlb_batch`](crate::AddrSpace::take_tlb_batch).
+//!
+//! Frames of allocation and file mappings that are unmapped meanwhile, by
+//! unmapping or copy-on-write, are only freed after the flush, so that a stale
+//! TLB entry never points to a frame that is reused, or that has become
+//! writable by another address space (as its reference count is not dropped).
+//! Linear mappings don't own their frames, so the owners of the frames must
+//! only reuse them after the flush (see
+//! [`with_kernel_aspace`](crate::with_kernel_aspace)).
+
+use alloc::vec::Vec;
+
+use memory_addr::{PhysAddr, VirtAddr, PAGE_SIZE_4K};
+
+use crate::frame::dealloc_frame;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum FlushRange {
+    #[default]
+    None,
+    Page(VirtAddr),
+    All,
+}
+
+/// TLB entries that may be stale on other CPUs, and the frames that they may
+/// still map.
+///
+/// The TLBs of all CPUs are flushed and the frames are freed when it's
+/// dropped, which must happen without holding the lock of the address space.
+#[derive(Debug, Default)]
+pub struct TlbBatch {
+    range: FlushRange,
+    frames: Vec<PhysAddr>,
+}
+
+impl TlbBatch {
+    /// Creates an empty batch.
+    pub const fn new() -> Self {
+        Self {
+            range: FlushRange::None,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Whether nothing needs to be flushed.
+    pub fn is_empty(&self) -> bool {
+        self.range == FlushRange::None
+    }
+
+    /// Records that the TLB entry of the page at `vaddr` may be stale.
+    pub(crate) fn add_page(&mut self, vaddr: VirtAddr) {
+        self.range = match self.range {
+            FlushRange::None => FlushRange::Page(vaddr),
+            FlushRange::Page(page) if page == vaddr => FlushRange::Page(vaddr),
+            _ => FlushRange::All,
+        };
+    }
+
+    /// Records that the TLB entries of the pages in `[start, start + size)`
+    /// may be stale.
+    pub(crate) fn add_range(&mut self, start: VirtAddr, size: usize) {
+        match size {
+            0 => {}
+            PAGE_SIZE_4K => self.add_page(start),
+            _ => self.range = FlushRange::All,
+        }
+    }
+
+    /// Records that the TLB entry of the page at `vaddr` may be stale, and
+    /// still map `frame`, whose reference is dropped after the flush.
+    pub(crate) fn add_stale_frame(&mut self, vaddr: VirtAddr, frame: PhysAddr) {
+        self.add_page(vaddr);
+        self.frames.push(frame);
+    }
+}
+
+impl Drop for TlbBatch {
+    fn drop(&mut self) {
+        match self.range {
+            FlushRange::None => {}
+            FlushRange::Page(vaddr) => flush_tlb_all_cpus(Some(vaddr)),
+            FlushRange::All => flush_tlb_all_cpus(None),
+        }
+        for frame in self.frames.drain(..) {
+            dealloc_frame(frame);
+        }
+    }
+}
+
+fn flush_tlb_all_cpus(vaddr: Option<VirtAddr>) {
+    // Other CPUs can only be reached by IPIs.
+    #[cfg(feature = "irq")]
+    axhal::ipi::flush_tlb_all_cpus(vaddr);
+    #[cfg(not(feature = "irq"))]
+    axhal::arch::flush_tlb(vaddr);
+}
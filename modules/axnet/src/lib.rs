@@ -14,6 +14,9 @@
 //!
 //! - `smoltcp`: Use [smoltcp] as the underlying network stack. This is enabled
 //!   by default.
+//! - `async`: Enable asynchronous socket operations (e.g.,
+//!   `TcpSocket::recv_async`), which are woken up by socket events. It
+//!   requires multitasking.
 //!
 //! [smoltcp]: https://github.com/smoltcp-rs/smoltcp
 
@@ -1,5 +1,7 @@
 use alloc::{boxed::Box, collections::VecDeque};
 use core::ops::{Deref, DerefMut};
+#[cfg(feature = "async")]
+use core::task::Waker;
 
 use axerrno::{ax_err, AxError, AxResult};
 use axsync::Mutex;
@@ -14,6 +16,10 @@ const PORT_NUM: usize = 65536;
 struct ListenTableEntry {
     listen_endpoint: IpListenEndpoint,
     syn_queue: VecDeque<SocketHandle>,
+    /// The waker of the asynchronous accept, which is registered in all
+    /// sockets in the SYN queue to be woken up once they are connected.
+    #[cfg(feature = "async")]
+    accept_waker: Option<Waker>,
 }
 
 impl ListenTableEntry {
@@ -21,6 +27,8 @@ impl ListenTableEntry {
         Self {
             listen_endpoint,
             syn_queue: VecDeque::with_capacity(LISTEN_QUEUE_SIZE),
+            #[cfg(feature = "async")]
+            accept_waker: None,
         }
     }
 
@@ -110,6 +118,18 @@ impl ListenTable {
         }
     }
 
+    #[cfg(feature = "async")]
+    pub fn register_accept_waker(&self, port: u16, waker: &Waker) {
+        if let Some(entry) = self.tcp[port as usize].lock().deref_mut() {
+            for &handle in &entry.syn_queue {
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    socket.register_recv_waker(waker)
+                });
+            }
+            entry.accept_waker = Some(waker.clone());
+        }
+    }
+
     pub fn incoming_tcp_packet(
         &self,
         src: IpEndpoint,
@@ -127,6 +147,10 @@ impl ListenTable {
                 return;
             }
             let mut socket = SocketSetWrapper::new_tcp_socket();
+            #[cfg(feature = "async")]
+            if let Some(waker) = &entry.accept_waker {
+                socket.register_recv_waker(waker);
+            }
             if socket.listen(entry.listen_endpoint).is_ok() {
                 let handle = sockets.add(socket);
                 debug!(
@@ -2,6 +2,8 @@ mod addr;
 mod bench;
 mod dns;
 mod listen_table;
+#[cfg(feature = "async")]
+mod poller;
 mod tcp;
 mod udp;
 
@@ -21,6 +23,8 @@ use smoltcp::time::Instant;
 use smoltcp::wire::{EthernetAddress, HardwareAddress, IpAddress, IpCidr};
 
 use self::listen_table::ListenTable;
+#[cfg(feature = "async")]
+use self::poller::poll_io;
 
 pub use self::dns::dns_query;
 pub use self::tcp::TcpSocket;
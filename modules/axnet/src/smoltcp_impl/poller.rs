@@ -0,0 +1,106 @@
+//! Wakeups of asynchronous socket operations.
+//!
+//! A pending operation registers its waker in the smoltcp socket, which wakes
+//! it up on the socket event (data received, buffer space freed, connection
+//! established or closed, etc.) when the interface is polled. NICs don't
+//! raise interrupts to us, so a poller polls the interfaces on behalf of the
+//! pending operations. It only runs while some registered wakers are not
+//! woken up yet, and sleeps in a wait queue otherwise.
+
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+use axerrno::{AxError, AxResult};
+use axtask::WaitQueue;
+
+use super::SOCKET_SET;
+
+/// The interval at which the receive queues of the NICs are checked while
+/// some operations are pending.
+const RX_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// The number of wakers registered in sockets that are not woken up yet.
+static PENDING_WAKERS: AtomicUsize = AtomicUsize::new(0);
+/// The poller waits here when there are no pending wakers.
+static POLLER_WQ: WaitQueue = WaitQueue::new();
+static POLLER_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// A waker registered in a socket, which counts itself in
+/// [`PENDING_WAKERS`] until it's woken up or replaced.
+struct SocketWaker {
+    waker: Waker,
+    done: AtomicBool,
+}
+
+impl SocketWaker {
+    fn new(waker: &Waker) -> Arc<Self> {
+        PENDING_WAKERS.fetch_add(1, Ordering::AcqRel);
+        Arc::new(Self {
+            waker: waker.clone(),
+            done: AtomicBool::new(false),
+        })
+    }
+
+    fn done(&self) {
+        if !self.done.swap(true, Ordering::AcqRel) {
+            PENDING_WAKERS.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+impl Wake for SocketWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.done();
+        self.waker.wake_by_ref();
+    }
+}
+
+impl Drop for SocketWaker {
+    fn drop(&mut self) {
+        self.done();
+    }
+}
+
+async fn poller_main() {
+    loop {
+        POLLER_WQ
+            .wait_until_async(|| PENDING_WAKERS.load(Ordering::Acquire) > 0)
+            .await;
+        SOCKET_SET.poll_interfaces();
+        axtask::future::sleep(RX_POLL_INTERVAL).await;
+    }
+}
+
+/// Tries an asynchronous socket operation `f`.
+///
+/// If it would block, `register` is called to register the waker in the
+/// socket, and `f` is tried again in case the socket became ready in between.
+pub(super) fn poll_io<T>(
+    cx: &mut Context<'_>,
+    mut f: impl FnMut() -> AxResult<T>,
+    register: impl FnOnce(&Waker),
+) -> Poll<AxResult<T>> {
+    SOCKET_SET.poll_interfaces();
+    match f() {
+        Err(AxError::WouldBlock) => {}
+        res => return Poll::Ready(res),
+    }
+    register(&Waker::from(SocketWaker::new(cx.waker())));
+    if !POLLER_STARTED.swap(true, Ordering::AcqRel) {
+        debug!("starting the net poller...");
+        // It's run by the executor, and waits for pending wakers there.
+        let _ = axtask::future::spawn(poller_main());
+    }
+    POLLER_WQ.notify_one(false);
+    match f() {
+        Err(AxError::WouldBlock) => Poll::Pending,
+        res => Poll::Ready(res),
+    }
+}
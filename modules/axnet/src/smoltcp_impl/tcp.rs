@@ -13,6 +13,12 @@ use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 use super::addr::{from_core_sockaddr, into_core_sockaddr, is_unspecified, UNSPECIFIED_ENDPOINT};
 use super::{SocketSetWrapper, ETH0, LISTEN_TABLE, SOCKET_SET};
 
+#[cfg(feature = "async")]
+use core::{
+    future::poll_fn,
+    task::{ready, Context, Poll},
+};
+
 // State transitions:
 // CLOSED -(connect)-> BUSY -> CONNECTING -> CONNECTED -(shutdown)-> BUSY -> CLOSED
 //       |
@@ -31,6 +37,12 @@ const STATE_LISTENING: u8 = 4;
 /// - [`bind`], [`listen`], and [`accept`] are for TCP servers.
 /// - Other methods are for both TCP clients and servers.
 ///
+/// With the `async` feature, asynchronous versions of [`accept`], [`recv`]
+/// and [`send`] are provided for futures, which are woken up when the socket
+/// becomes ready.
+///
+/// [`recv`]: TcpSocket::recv
+/// [`send`]: TcpSocket::send
 /// [`connect`]: TcpSocket::connect
 /// [`bind`]: TcpSocket::bind
 /// [`listen`]: TcpSocket::listen
@@ -229,11 +241,7 @@ impl TcpSocket {
 
         // SAFETY: `self.local_addr` should be initialized after `bind()`.
         let local_port = unsafe { self.local_addr.get().read().port };
-        self.block_on(|| {
-            let (handle, (local_addr, peer_addr)) = LISTEN_TABLE.accept(local_port)?;
-            debug!("TCP socket accepted a new connection {}", peer_addr);
-            Ok(TcpSocket::new_connected(handle, local_addr, peer_addr))
-        })
+        self.block_on(|| accept_impl(local_port))
     }
 
     /// Close the connection.
@@ -279,27 +287,7 @@ impl TcpSocket {
 
         // SAFETY: `self.handle` should be initialized in a connected socket.
         let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
-            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
-                if !socket.is_active() {
-                    // not open
-                    ax_err!(ConnectionRefused, "socket recv() failed")
-                } else if !socket.may_recv() {
-                    // connection closed
-                    Ok(0)
-                } else if socket.recv_queue() > 0 {
-                    // data available
-                    // TODO: use socket.recv(|buf| {...})
-                    let len = socket
-                        .recv_slice(buf)
-                        .map_err(|_| ax_err_type!(BadState, "socket recv() failed"))?;
-                    Ok(len)
-                } else {
-                    // no more data
-                    Err(AxError::WouldBlock)
-                }
-            })
-        })
+        self.block_on(|| recv_impl(handle, buf))
     }
 
     /// Transmits data in the given buffer.
@@ -312,24 +300,7 @@ impl TcpSocket {
 
         // SAFETY: `self.handle` should be initialized in a connected socket.
         let handle = unsafe { self.handle.get().read().unwrap() };
-        self.block_on(|| {
-            SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
-                if !socket.is_active() || !socket.may_send() {
-                    // closed by remote
-                    ax_err!(ConnectionReset, "socket send() failed")
-                } else if socket.can_send() {
-                    // connected, and the tx buffer is not full
-                    // TODO: use socket.send(|buf| {...})
-                    let len = socket
-                        .send_slice(buf)
-                        .map_err(|_| ax_err_type!(BadState, "socket send() failed"))?;
-                    Ok(len)
-                } else {
-                    // tx buffer is full
-                    Err(AxError::WouldBlock)
-                }
-            })
-        })
+        self.block_on(|| send_impl(handle, buf))
     }
 
     /// Whether the socket is readable or writable.
@@ -346,6 +317,117 @@ impl TcpSocket {
     }
 }
 
+/// Asynchronous methods
+#[cfg(feature = "async")]
+impl TcpSocket {
+    /// Polls to accept a new connection, for asynchronous tasks.
+    ///
+    /// If no connection is established yet, it returns [`Poll::Pending`], and
+    /// the waker in `cx` is woken up when a connection may be accepted.
+    pub fn poll_accept(&self, cx: &mut Context<'_>) -> Poll<AxResult<TcpSocket>> {
+        if !self.is_listening() {
+            return Poll::Ready(ax_err!(InvalidInput, "socket accept() failed: not listen"));
+        }
+
+        // SAFETY: `self.local_addr` should be initialized after `bind()`.
+        let local_port = unsafe { self.local_addr.get().read().port };
+        super::poll_io(
+            cx,
+            || accept_impl(local_port),
+            |waker| LISTEN_TABLE.register_accept_waker(local_port, waker),
+        )
+    }
+
+    /// Accepts a new connection asynchronously.
+    pub async fn accept_async(&self) -> AxResult<TcpSocket> {
+        poll_fn(|cx| self.poll_accept(cx)).await
+    }
+
+    /// Polls to receive data from the socket, for asynchronous tasks.
+    ///
+    /// If no data is available, it returns [`Poll::Pending`], and the waker in
+    /// `cx` is woken up when the socket may be readable. It's also pending
+    /// while the socket is connecting.
+    pub fn poll_recv(&self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<AxResult<usize>> {
+        ready!(self.poll_connecting(cx))?;
+        if !self.is_connected() {
+            return Poll::Ready(ax_err!(NotConnected, "socket recv() failed"));
+        }
+
+        // SAFETY: `self.handle` should be initialized in a connected socket.
+        let handle = unsafe { self.handle.get().read().unwrap() };
+        super::poll_io(
+            cx,
+            || recv_impl(handle, buf),
+            |waker| {
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    socket.register_recv_waker(waker)
+                })
+            },
+        )
+    }
+
+    /// Receives data from the socket asynchronously.
+    pub async fn recv_async(&self, buf: &mut [u8]) -> AxResult<usize> {
+        poll_fn(|cx| self.poll_recv(cx, buf)).await
+    }
+
+    /// Polls to transmit data in the given buffer, for asynchronous tasks.
+    ///
+    /// If the transmit buffer is full, it returns [`Poll::Pending`], and the
+    /// waker in `cx` is woken up when the socket may be writable. It's also
+    /// pending while the socket is connecting.
+    pub fn poll_send(&self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<AxResult<usize>> {
+        ready!(self.poll_connecting(cx))?;
+        if !self.is_connected() {
+            return Poll::Ready(ax_err!(NotConnected, "socket send() failed"));
+        }
+
+        // SAFETY: `self.handle` should be initialized in a connected socket.
+        let handle = unsafe { self.handle.get().read().unwrap() };
+        super::poll_io(
+            cx,
+            || send_impl(handle, buf),
+            |waker| {
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    socket.register_send_waker(waker)
+                })
+            },
+        )
+    }
+
+    /// Transmits data in the given buffer asynchronously.
+    pub async fn send_async(&self, buf: &[u8]) -> AxResult<usize> {
+        poll_fn(|cx| self.poll_send(cx, buf)).await
+    }
+
+    /// Polls until a connecting socket is connected or the connection fails.
+    fn poll_connecting(&self, cx: &mut Context<'_>) -> Poll<AxResult> {
+        if !self.is_connecting() {
+            return Poll::Ready(Ok(()));
+        }
+        // SAFETY: `self.handle` should be initialized in a connecting socket.
+        let handle = unsafe { self.handle.get().read().unwrap() };
+        super::poll_io(
+            cx,
+            || {
+                self.poll_connect()?;
+                if self.is_connecting() {
+                    Err(AxError::WouldBlock)
+                } else {
+                    Ok(())
+                }
+            },
+            // Wakers are woken up on state changes of the socket.
+            |waker| {
+                SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+                    socket.register_send_waker(waker)
+                })
+            },
+        )
+    }
+}
+
 /// Private methods
 impl TcpSocket {
     #[inline]
@@ -503,6 +585,53 @@ impl Drop for TcpSocket {
     }
 }
 
+fn accept_impl(local_port: u16) -> AxResult<TcpSocket> {
+    let (handle, (local_addr, peer_addr)) = LISTEN_TABLE.accept(local_port)?;
+    debug!("TCP socket accepted a new connection {}", peer_addr);
+    Ok(TcpSocket::new_connected(handle, local_addr, peer_addr))
+}
+
+fn recv_impl(handle: SocketHandle, buf: &mut [u8]) -> AxResult<usize> {
+    SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+        if !socket.is_active() {
+            // not open
+            ax_err!(ConnectionRefused, "socket recv() failed")
+        } else if !socket.may_recv() {
+            // connection closed
+            Ok(0)
+        } else if socket.recv_queue() > 0 {
+            // data available
+            // TODO: use socket.recv(|buf| {...})
+            let len = socket
+                .recv_slice(buf)
+                .map_err(|_| ax_err_type!(BadState, "socket recv() failed"))?;
+            Ok(len)
+        } else {
+            // no more data
+            Err(AxError::WouldBlock)
+        }
+    })
+}
+
+fn send_impl(handle: SocketHandle, buf: &[u8]) -> AxResult<usize> {
+    SOCKET_SET.with_socket_mut::<tcp::Socket, _, _>(handle, |socket| {
+        if !socket.is_active() || !socket.may_send() {
+            // closed by remote
+            ax_err!(ConnectionReset, "socket send() failed")
+        } else if socket.can_send() {
+            // connected, and the tx buffer is not full
+            // TODO: use socket.send(|buf| {...})
+            let len = socket
+                .send_slice(buf)
+                .map_err(|_| ax_err_type!(BadState, "socket send() failed"))?;
+            Ok(len)
+        } else {
+            // tx buffer is full
+            Err(AxError::WouldBlock)
+        }
+    })
+}
+
 fn get_ephemeral_port() -> AxResult<u16> {
     const PORT_START: u16 = 0xc000;
     const PORT_END: u16 = 0xffff;
@@ -13,6 +13,12 @@ use smoltcp::wire::{IpEndpoint, IpListenEndpoint};
 use super::addr::{from_core_sockaddr, into_core_sockaddr, is_unspecified, UNSPECIFIED_ENDPOINT};
 use super::{SocketSetWrapper, SOCKET_SET};
 
+#[cfg(feature = "async")]
+use core::{
+    future::poll_fn,
+    task::{Context, Poll},
+};
+
 /// A UDP socket that provides POSIX-like APIs.
 pub struct UdpSocket {
     handle: SocketHandle,
@@ -197,6 +203,81 @@ impl UdpSocket {
     }
 }
 
+/// Asynchronous methods
+#[cfg(feature = "async")]
+impl UdpSocket {
+    /// Polls to send data on the socket to the given address, for asynchronous
+    /// tasks.
+    ///
+    /// If the transmit buffer is full, it returns [`Poll::Pending`], and the
+    /// waker in `cx` is woken up when the socket may be writable.
+    pub fn poll_send_to(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+        remote_addr: SocketAddr,
+    ) -> Poll<AxResult<usize>> {
+        if remote_addr.port() == 0 || remote_addr.ip().is_unspecified() {
+            return Poll::Ready(ax_err!(
+                InvalidInput,
+                "socket send_to() failed: invalid address"
+            ));
+        }
+        if self.local_addr.read().is_none() {
+            return Poll::Ready(ax_err!(NotConnected, "socket send() failed"));
+        }
+        let remote_endpoint = from_core_sockaddr(remote_addr);
+        super::poll_io(
+            cx,
+            || self.try_send(buf, remote_endpoint),
+            |waker| {
+                SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+                    socket.register_send_waker(waker)
+                })
+            },
+        )
+    }
+
+    /// Sends data on the socket to the given address asynchronously.
+    pub async fn send_to_async(&self, buf: &[u8], remote_addr: SocketAddr) -> AxResult<usize> {
+        poll_fn(|cx| self.poll_send_to(cx, buf, remote_addr)).await
+    }
+
+    /// Polls to receive a single datagram message on the socket, for
+    /// asynchronous tasks.
+    ///
+    /// If no message is available, it returns [`Poll::Pending`], and the waker
+    /// in `cx` is woken up when the socket may be readable.
+    pub fn poll_recv_from(
+        &self,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<AxResult<(usize, SocketAddr)>> {
+        if self.local_addr.read().is_none() {
+            return Poll::Ready(ax_err!(NotConnected, "socket send() failed"));
+        }
+        super::poll_io(
+            cx,
+            || {
+                self.try_recv(|socket| match socket.recv_slice(buf) {
+                    Ok((len, meta)) => Ok((len, into_core_sockaddr(meta.endpoint))),
+                    Err(_) => ax_err!(BadState, "socket recv_from() failed"),
+                })
+            },
+            |waker| {
+                SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+                    socket.register_recv_waker(waker)
+                })
+            },
+        )
+    }
+
+    /// Receives a single datagram message on the socket asynchronously.
+    pub async fn recv_from_async(&self, buf: &mut [u8]) -> AxResult<(usize, SocketAddr)> {
+        poll_fn(|cx| self.poll_recv_from(cx, buf)).await
+    }
+}
+
 /// Private methods
 impl UdpSocket {
     fn remote_endpoint(&self) -> AxResult<IpEndpoint> {
@@ -211,23 +292,25 @@ impl UdpSocket {
             return ax_err!(NotConnected, "socket send() failed");
         }
 
-        self.block_on(|| {
-            SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
-                if socket.can_send() {
-                    socket
-                        .send_slice(buf, remote_endpoint)
-                        .map_err(|e| match e {
-                            SendError::BufferFull => AxError::WouldBlock,
-                            SendError::Unaddressable => {
-                                ax_err_type!(ConnectionRefused, "socket send() failed")
-                            }
-                        })?;
-                    Ok(buf.len())
-                } else {
-                    // tx buffer is full
-                    Err(AxError::WouldBlock)
-                }
-            })
+        self.block_on(|| self.try_send(buf, remote_endpoint))
+    }
+
+    fn try_send(&self, buf: &[u8], remote_endpoint: IpEndpoint) -> AxResult<usize> {
+        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+            if socket.can_send() {
+                socket
+                    .send_slice(buf, remote_endpoint)
+                    .map_err(|e| match e {
+                        SendError::BufferFull => AxError::WouldBlock,
+                        SendError::Unaddressable => {
+                            ax_err_type!(ConnectionRefused, "socket send() failed")
+                        }
+                    })?;
+                Ok(buf.len())
+            } else {
+                // tx buffer is full
+                Err(AxError::WouldBlock)
+            }
         })
     }
 
@@ -239,16 +322,21 @@ impl UdpSocket {
             return ax_err!(NotConnected, "socket send() failed");
         }
 
-        self.block_on(|| {
-            SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
-                if socket.can_recv() {
-                    // data available
-                    op(socket)
-                } else {
-                    // no more data
-                    Err(AxError::WouldBlock)
-                }
-            })
+        self.block_on(|| self.try_recv(&mut op))
+    }
+
+    fn try_recv<F, T>(&self, op: F) -> AxResult<T>
+    where
+        F: FnOnce(&mut udp::Socket) -> AxResult<T>,
+    {
+        SOCKET_SET.with_socket_mut::<udp::Socket, _, _>(self.handle, |socket| {
+            if socket.can_recv() {
+                // data available
+                op(socket)
+            } else {
+                // no more data
+                Err(AxError::WouldBlock)
+            }
         })
     }
 
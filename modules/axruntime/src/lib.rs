@@ -13,6 +13,7 @@
 //! - `fs`: Enable filesystem support.
 //! - `net`: Enable networking support.
 //! - `display`: Enable graphics support.
+//! - `trace`: Enable kernel event tracing.
 //!
 //! All the features are optional and disabled by default.
 
@@ -30,6 +31,8 @@ mod mp;
 
 #[cfg(feature = "smp")]
 pub use self::mp::rust_main_secondary;
+#[cfg(all(feature = "smp", feature = "multitask", feature = "irq"))]
+pub use self::mp::{offline_cpu, online_cpu};
 
 const LOGO: &str = r#"
        d8888                            .d88888b.   .d8888b.
@@ -83,6 +86,21 @@ impl axlog::LogIf for LogIfImpl {
     }
 }
 
+#[cfg(feature = "trace")]
+struct TraceIfImpl;
+
+#[cfg(feature = "trace")]
+#[crate_interface::impl_interface]
+impl axtrace::TraceIf for TraceIfImpl {
+    fn monotonic_time_nanos() -> u64 {
+        axhal::time::monotonic_time_nanos()
+    }
+
+    fn current_cpu_id() -> usize {
+        axhal::cpu::this_cpu_id()
+    }
+}
+
 use core::sync::atomic::{AtomicUsize, Ordering};
 
 static INITED_CPUS: AtomicUsize = AtomicUsize::new(0);
@@ -241,6 +259,9 @@ fn init_interrupt() {
     #[percpu::def_percpu]
     static NEXT_DEADLINE: u64 = 0;
 
+    // With the `tickless` feature of axtask, an idle CPU reprograms the timer
+    // for its next timer event only, and the periodic tick is restored here on
+    // the next timer IRQ.
     fn update_timer() {
         let now_ns = axhal::time::monotonic_time_nanos();
         // Safety: we have disabled preemption in IRQ handler.
@@ -258,6 +279,8 @@ fn init_interrupt() {
         axtask::on_timer_tick();
     });
 
+    axhal::ipi::init_percpu();
+
     // Enable IRQs before starting app
     axhal::arch::enable_irqs();
 }
@@ -268,3 +291,55 @@ fn init_tls() {
     unsafe { axhal::arch::write_thread_pointer(main_tls.tls_ptr() as usize) };
     core::mem::forget(main_tls);
 }
+
+/// The kernel page fault handler.
+///
+/// Faults in the user address space of the current task (see
+/// `axtask::TaskInner::set_user_aspace`) are resolved by
+/// [`axmm::handle_user_page_fault`], whether they are raised by the user code
+/// or by the kernel accessing user memory. Faults of kernel code in the lazily
+/// mapped areas of the kernel address space are resolved by
+/// [`axmm::handle_kernel_page_fault`], after checking for overflows of task
+/// stacks, which are reported by panicking. Stack guard pages are only used on
+/// x86_64 (see `axtask::check_stack_overflow`). Other faults are left to the
+/// architecture-specific handler.
+#[cfg(feature = "paging")]
+#[axhal::trap::register_trap_handler(axhal::trap::PAGE_FAULT)]
+fn handle_page_fault(
+    vaddr: axhal::mem::VirtAddr,
+    access_flags: axhal::paging::MappingFlags,
+    is_user: bool,
+) -> bool {
+    let in_kernel_aspace = (axconfig::KERNEL_ASPACE_BASE
+        ..axconfig::KERNEL_ASPACE_BASE + axconfig::KERNEL_ASPACE_SIZE)
+        .contains(&vaddr.as_usize());
+    #[cfg(feature = "multitask")]
+    if !in_kernel_aspace {
+        if let Some(aspace) = axtask::current_may_uninit().and_then(|t| t.user_aspace().cloned()) {
+            return axmm::handle_user_page_fault(&aspace, vaddr, access_flags, is_user);
+        }
+    }
+    if !is_user && in_kernel_aspace {
+        // Check the stack guard pages first, which takes no locks: on a stack
+        // overflow (reported via the double fault handler), the address space
+        // may be locked by the overflowed task.
+        #[cfg(all(feature = "multitask", target_arch = "x86_64"))]
+        axtask::check_stack_overflow(vaddr);
+        return axmm::handle_kernel_page_fault(vaddr, access_flags);
+    }
+    false
+}
+
+/// The handler of exceptions raised by user code, which terminates the current
+/// task with the exit code `-1`.
+#[cfg(all(feature = "uspace", feature = "multitask"))]
+#[axhal::trap::register_trap_handler(axhal::trap::USER_EXCEPTION)]
+fn handle_user_exception(tf: &axhal::arch::TrapFrame, exception: axhal::trap::UserException) -> ! {
+    warn!(
+        "Task {} killed by user exception {:x?}:\n{:#x?}",
+        axtask::current().id_name(),
+        exception,
+        tf
+    );
+    axtask::exit(-1)
+}
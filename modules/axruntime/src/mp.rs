@@ -1,23 +1,37 @@
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use axconfig::{SMP, TASK_STACK_SIZE};
-use axhal::mem::{virt_to_phys, VirtAddr};
+use axhal::mem::{virt_to_phys, PhysAddr, VirtAddr};
 
 #[link_section = ".bss.stack"]
 static mut SECONDARY_BOOT_STACK: [[u8; TASK_STACK_SIZE]; SMP - 1] = [[0; TASK_STACK_SIZE]; SMP - 1];
 
 static ENTERED_CPUS: AtomicUsize = AtomicUsize::new(1);
 
+static PRIMARY_CPU_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Whether each CPU has finished its first boot, so that it resumes the
+/// scheduler instead of initializing it when restarted.
+static BOOTED_CPUS: [AtomicBool; SMP] = [FLAG_REPEAT_VALUE; SMP];
+/// Whether each CPU has been taken offline by [`offline_cpu`].
+#[cfg(all(feature = "multitask", feature = "irq"))]
+static PARKED_CPUS: [AtomicBool; SMP] = [FLAG_REPEAT_VALUE; SMP];
+#[allow(clippy::declare_interior_mutable_const)]
+const FLAG_REPEAT_VALUE: AtomicBool = AtomicBool::new(false);
+
+fn boot_stack_top(logic_cpu_id: usize) -> PhysAddr {
+    virt_to_phys(VirtAddr::from(unsafe {
+        SECONDARY_BOOT_STACK[logic_cpu_id].as_ptr_range().end as usize
+    }))
+}
+
 pub fn start_secondary_cpus(primary_cpu_id: usize) {
+    PRIMARY_CPU_ID.store(primary_cpu_id, Ordering::Relaxed);
     let mut logic_cpu_id = 0;
     for i in 0..SMP {
         if i != primary_cpu_id {
-            let stack_top = virt_to_phys(VirtAddr::from(unsafe {
-                SECONDARY_BOOT_STACK[logic_cpu_id].as_ptr_range().end as usize
-            }));
-
             debug!("starting CPU {}...", i);
-            axhal::mp::start_secondary_cpu(i, stack_top);
+            axhal::mp::start_secondary_cpu(i, boot_stack_top(logic_cpu_id));
             logic_cpu_id += 1;
 
             while ENTERED_CPUS.load(Ordering::Acquire) <= logic_cpu_id {
@@ -32,6 +46,11 @@ pub fn start_secondary_cpus(primary_cpu_id: usize) {
 /// It is called from the bootstrapping code in [axhal].
 #[no_mangle]
 pub extern "C" fn rust_main_secondary(cpu_id: usize) -> ! {
+    #[cfg(all(feature = "multitask", feature = "irq"))]
+    if BOOTED_CPUS[cpu_id].load(Ordering::Acquire) {
+        restart_secondary(cpu_id);
+    }
+
     ENTERED_CPUS.fetch_add(1, Ordering::Relaxed);
     info!("Secondary CPU {:x} started.", cpu_id);
 
@@ -44,6 +63,7 @@ pub extern "C" fn rust_main_secondary(cpu_id: usize) -> ! {
     axtask::init_scheduler_secondary();
 
     info!("Secondary CPU {:x} init OK.", cpu_id);
+    BOOTED_CPUS[cpu_id].store(true, Ordering::Release);
     super::INITED_CPUS.fetch_add(1, Ordering::Relaxed);
 
     while !super::is_init_ok() {
@@ -51,7 +71,10 @@ pub extern "C" fn rust_main_secondary(cpu_id: usize) -> ! {
     }
 
     #[cfg(feature = "irq")]
-    axhal::arch::enable_irqs();
+    {
+        axhal::ipi::init_percpu();
+        axhal::arch::enable_irqs();
+    }
 
     #[cfg(all(feature = "tls", not(feature = "multitask")))]
     super::init_tls();
@@ -63,3 +86,77 @@ pub extern "C" fn rust_main_secondary(cpu_id: usize) -> ! {
         axhal::arch::wait_for_irqs();
     }
 }
+
+/// Brings a CPU taken offline by [`offline_cpu`] back to the scheduler.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+fn restart_secondary(cpu_id: usize) -> ! {
+    info!("Secondary CPU {:x} restarted.", cpu_id);
+
+    #[cfg(feature = "paging")]
+    axmm::init_memory_management_secondary();
+
+    axhal::platform_init_secondary();
+    axtask::init_scheduler_online();
+    axhal::ipi::init_percpu();
+    axhal::arch::enable_irqs();
+
+    info!("Secondary CPU {:x} is online.", cpu_id);
+    axtask::run_idle();
+}
+
+/// Takes the given secondary CPU offline, after migrating all its tasks and
+/// timer events to other CPUs.
+///
+/// Returns `false` if the CPU can't be taken offline, see
+/// [`axtask::offline_cpu`] for details.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub fn offline_cpu(cpu_id: usize) -> bool {
+    if !axtask::offline_cpu(cpu_id) {
+        return false;
+    }
+    PARKED_CPUS[cpu_id].store(true, Ordering::Release);
+    true
+}
+
+/// Restarts a secondary CPU taken offline by [`offline_cpu`], and waits until
+/// it's online.
+///
+/// Returns `false` if the CPU is not offline, the platform can't restart
+/// stopped CPUs (see [`axhal::mp::CPU_RESTARTABLE`]), or it fails to come
+/// online in time.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+pub fn online_cpu(cpu_id: usize) -> bool {
+    const ONLINE_TIMEOUT: core::time::Duration = core::time::Duration::from_secs(1);
+
+    if cpu_id >= SMP || !PARKED_CPUS[cpu_id].load(Ordering::Acquire) {
+        return false;
+    }
+    if !axhal::mp::CPU_RESTARTABLE {
+        warn!("CPU {} can't be restarted on this platform", cpu_id);
+        return false;
+    }
+    if !PARKED_CPUS[cpu_id].swap(false, Ordering::AcqRel) {
+        return false;
+    }
+    let primary_cpu_id = PRIMARY_CPU_ID.load(Ordering::Relaxed);
+    // Secondary boot stacks are indexed by CPU IDs, skipping the primary one.
+    let logic_cpu_id = if cpu_id < primary_cpu_id {
+        cpu_id
+    } else {
+        cpu_id - 1
+    };
+
+    debug!("restarting CPU {}...", cpu_id);
+    axhal::mp::start_secondary_cpu(cpu_id, boot_stack_top(logic_cpu_id));
+    let deadline = axhal::time::wall_time() + ONLINE_TIMEOUT;
+    while !axtask::cpu_online(cpu_id) {
+        if axhal::time::wall_time() >= deadline {
+            warn!("CPU {} failed to come online", cpu_id);
+            // It may still be parked, allow retrying.
+            PARKED_CPUS[cpu_id].store(true, Ordering::Release);
+            return false;
+        }
+        axtask::sleep(core::time::Duration::from_millis(1));
+    }
+    true
+}
@@ -0,0 +1,79 @@
+//! A sleeping barrier.
+
+use core::fmt;
+
+use crate::spin::SpinNoIrq;
+use axtask::WaitQueue;
+
+struct BarrierState {
+    count: usize,
+    generation: usize,
+}
+
+/// A barrier enables multiple tasks to synchronize the beginning of some
+/// computation, similar to
+/// [`std::sync::Barrier`](https://doc.rust-lang.org/std/sync/struct.Barrier.html).
+///
+/// Tasks calling [`Barrier::wait`] are blocked in a wait queue until all of
+/// them have arrived. The barrier can be reused afterwards.
+pub struct Barrier {
+    state: SpinNoIrq<BarrierState>,
+    wq: WaitQueue,
+    num_tasks: usize,
+}
+
+/// The result returned by [`Barrier::wait`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Returns `true` if the task is the "leader" of this round, i.e., the
+    /// last one that arrived. Exactly one task is the leader in each round.
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl Barrier {
+    /// Creates a new barrier that blocks a group of `n` tasks.
+    ///
+    /// A barrier with `n` of 0 behaves like one with `n` of 1, which never
+    /// blocks.
+    pub const fn new(n: usize) -> Self {
+        Self {
+            state: SpinNoIrq::new(BarrierState {
+                count: 0,
+                generation: 0,
+            }),
+            wq: WaitQueue::new(),
+            num_tasks: n,
+        }
+    }
+
+    /// Blocks the current task until all tasks have rendezvoused here.
+    pub fn wait(&self) -> BarrierWaitResult {
+        let mut state = self.state.lock();
+        let generation = state.generation;
+        state.count += 1;
+        if state.count < self.num_tasks {
+            drop(state);
+            self.wq
+                .wait_until(|| self.state.lock().generation != generation);
+            BarrierWaitResult(false)
+        } else {
+            state.count = 0;
+            state.generation = state.generation.wrapping_add(1);
+            drop(state);
+            self.wq.notify_all(true);
+            BarrierWaitResult(true)
+        }
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Barrier")
+            .field("num_tasks", &self.num_tasks)
+            .finish_non_exhaustive()
+    }
+}
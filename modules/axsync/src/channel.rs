@@ -0,0 +1,684 @@
+//! Multi-producer, multi-consumer channels for message passing.
+//!
+//! A channel is created by [`bounded`] or [`unbounded`], which returns a
+//! [`Sender`] and a [`Receiver`]. Both halves can be cloned to have multiple
+//! producers and consumers, and each message is received by only one of the
+//! receivers. Tasks blocked on sending or receiving sleep in wait queues.
+//!
+//! The channel is disconnected when all senders or all receivers are dropped,
+//! which is reported by the operations on the other side. A [`Select`] waits
+//! on several receivers at the same time.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt;
+#[cfg(feature = "irq")]
+use core::time::Duration;
+
+use crate::spin::SpinNoIrq;
+use axtask::WaitQueue;
+
+/// An error returned from [`Sender::send`], the message is returned back as
+/// the channel is disconnected.
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub struct SendError<T>(pub T);
+
+/// An error returned from [`Sender::try_send`].
+#[derive(PartialEq, Eq, Clone, Copy)]
+pub enum TrySendError<T> {
+    /// The channel is full, or no receiver is waiting on a zero-capacity
+    /// channel.
+    Full(T),
+    /// The channel is disconnected.
+    Disconnected(T),
+}
+
+/// An error returned from [`Receiver::recv`], as the channel is empty and
+/// disconnected.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub struct RecvError;
+
+/// An error returned from [`Receiver::try_recv`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum TryRecvError {
+    /// The channel is empty.
+    Empty,
+    /// The channel is empty and disconnected.
+    Disconnected,
+}
+
+/// An error returned from [`Receiver::recv_timeout`].
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum RecvTimeoutError {
+    /// No message is received before the timeout.
+    Timeout,
+    /// The channel is empty and disconnected.
+    Disconnected,
+}
+
+impl<T> fmt::Debug for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SendError { .. }")
+    }
+}
+
+impl<T> fmt::Debug for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full(..) => f.write_str("Full(..)"),
+            Self::Disconnected(..) => f.write_str("Disconnected(..)"),
+        }
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("sending on a disconnected channel")
+    }
+}
+
+impl<T> fmt::Display for TrySendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Full(..) => f.write_str("sending on a full channel"),
+            Self::Disconnected(..) => f.write_str("sending on a disconnected channel"),
+        }
+    }
+}
+
+impl fmt::Display for RecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("receiving on a disconnected channel")
+    }
+}
+
+impl fmt::Display for TryRecvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("receiving on an empty channel"),
+            Self::Disconnected => f.write_str("receiving on a disconnected channel"),
+        }
+    }
+}
+
+impl fmt::Display for RecvTimeoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => f.write_str("timed out waiting on a channel"),
+            Self::Disconnected => f.write_str("receiving on a disconnected channel"),
+        }
+    }
+}
+
+impl<T> From<SendError<T>> for TrySendError<T> {
+    fn from(err: SendError<T>) -> Self {
+        Self::Disconnected(err.0)
+    }
+}
+
+impl From<RecvError> for TryRecvError {
+    fn from(_: RecvError) -> Self {
+        Self::Disconnected
+    }
+}
+
+impl From<RecvError> for RecvTimeoutError {
+    fn from(_: RecvError) -> Self {
+        Self::Disconnected
+    }
+}
+
+struct State<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receivers: usize,
+    /// Number of receivers blocked in [`Receiver::recv`].
+    waiting_receivers: usize,
+    /// Number of messages received, for zero-capacity channels.
+    received: u64,
+    sent: u64,
+    /// Wait queues of the [`Select`]s waiting on the channel.
+    selects: Vec<Arc<WaitQueue>>,
+}
+
+struct Channel<T> {
+    state: SpinNoIrq<State<T>>,
+    /// The capacity, or `None` if unbounded.
+    cap: Option<usize>,
+    /// Receivers waiting for messages.
+    recv_wq: WaitQueue,
+    /// Senders waiting for free space, or for the receipt of their messages
+    /// on zero-capacity channels.
+    send_wq: WaitQueue,
+}
+
+impl<T> Channel<T> {
+    fn new(cap: Option<usize>) -> Self {
+        Self {
+            state: SpinNoIrq::new(State {
+                queue: VecDeque::new(),
+                senders: 1,
+                receivers: 1,
+                waiting_receivers: 0,
+                received: 0,
+                sent: 0,
+                selects: Vec::new(),
+            }),
+            cap,
+            recv_wq: WaitQueue::new(),
+            send_wq: WaitQueue::new(),
+        }
+    }
+
+    /// Whether a message can be queued, a zero-capacity channel holds at most
+    /// one message in flight.
+    fn has_space(&self, state: &State<T>) -> bool {
+        self.cap.map_or(true, |cap| state.queue.len() < cap.max(1))
+    }
+
+    fn can_send(&self) -> bool {
+        let state = self.state.lock();
+        state.receivers == 0 || self.has_space(&state)
+    }
+
+    fn can_recv(&self) -> bool {
+        let state = self.state.lock();
+        !state.queue.is_empty() || state.senders == 0
+    }
+
+    /// Queues the message, returns its sequence number.
+    fn push(&self, mut state: crate::spin::SpinNoIrqGuard<State<T>>, msg: T) -> u64 {
+        state.queue.push_back(msg);
+        state.sent += 1;
+        let seq = state.sent;
+        let selects = (!state.selects.is_empty()).then(|| state.selects.clone());
+        drop(state);
+        self.recv_wq.notify_one(true);
+        for wq in selects.into_iter().flatten() {
+            wq.notify_all(true);
+        }
+        seq
+    }
+
+    /// Waits until the message `seq` is received on a zero-capacity channel.
+    fn wait_received(&self, seq: u64) -> Result<(), SendError<T>> {
+        self.send_wq.wait_until(|| {
+            let state = self.state.lock();
+            state.received >= seq || state.receivers == 0
+        });
+        let mut state = self.state.lock();
+        if state.received < seq {
+            // Disconnected, nobody can take the message now.
+            return Err(SendError(state.queue.pop_back().unwrap()));
+        }
+        Ok(())
+    }
+
+    fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        loop {
+            let state = self.state.lock();
+            if state.receivers == 0 {
+                return Err(SendError(msg));
+            }
+            if self.has_space(&state) {
+                let seq = self.push(state, msg);
+                if self.cap == Some(0) {
+                    return self.wait_received(seq);
+                }
+                return Ok(());
+            }
+            drop(state);
+            self.send_wq.wait_until(|| self.can_send());
+        }
+    }
+
+    fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+        let state = self.state.lock();
+        if state.receivers == 0 {
+            return Err(TrySendError::Disconnected(msg));
+        }
+        let ready = if self.cap == Some(0) {
+            // Only hands the message over to a waiting receiver.
+            state.queue.len() < state.waiting_receivers
+        } else {
+            self.has_space(&state)
+        };
+        if !ready {
+            return Err(TrySendError::Full(msg));
+        }
+        self.push(state, msg);
+        Ok(())
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.state.lock();
+        match state.queue.pop_front() {
+            Some(msg) => {
+                state.received += 1;
+                drop(state);
+                if self.cap == Some(0) {
+                    self.send_wq.notify_all(true);
+                } else {
+                    self.send_wq.notify_one(true);
+                }
+                Ok(msg)
+            }
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn recv(&self) -> Result<T, RecvError> {
+        loop {
+            match self.try_recv() {
+                Ok(msg) => return Ok(msg),
+                Err(TryRecvError::Disconnected) => return Err(RecvError),
+                Err(TryRecvError::Empty) => {}
+            }
+            self.state.lock().waiting_receivers += 1;
+            self.recv_wq.wait_until(|| self.can_recv());
+            self.state.lock().waiting_receivers -= 1;
+        }
+    }
+
+    #[cfg(feature = "irq")]
+    fn recv_timeout(&self, dur: Duration) -> Result<T, RecvTimeoutError> {
+        let deadline = axhal::time::wall_time() + dur;
+        loop {
+            match self.try_recv() {
+                Ok(msg) => return Ok(msg),
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) => {}
+            }
+            let now = axhal::time::wall_time();
+            if now >= deadline {
+                return Err(RecvTimeoutError::Timeout);
+            }
+            self.state.lock().waiting_receivers += 1;
+            self.recv_wq
+                .wait_timeout_until(deadline - now, || self.can_recv());
+            self.state.lock().waiting_receivers -= 1;
+        }
+    }
+}
+
+/// The sending half of a channel.
+pub struct Sender<T> {
+    chan: Arc<Channel<T>>,
+}
+
+/// The receiving half of a channel.
+pub struct Receiver<T> {
+    chan: Arc<Channel<T>>,
+}
+
+/// Creates a channel that can hold at most `cap` messages.
+///
+/// Senders are blocked when the channel is full. If `cap` is 0, it's a
+/// rendezvous channel: each sender is blocked until its message is received.
+pub fn bounded<T>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Channel::new(Some(cap)));
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+/// Creates a channel of unbounded capacity, senders are never blocked.
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    let chan = Arc::new(Channel::new(None));
+    (Sender { chan: chan.clone() }, Receiver { chan })
+}
+
+impl<T> Sender<T> {
+    /// Sends a message, blocking the current task until there is space in the
+    /// channel (or the message is received, for zero-capacity channels).
+    ///
+    /// Returns the message back if all receivers are dropped.
+    pub fn send(&self, msg: T) -> Result<(), SendError<T>> {
+        self.chan.send(msg)
+    }
+
+    /// Tries to send a message without blocking.
+    ///
+    /// On a zero-capacity channel, it only succeeds if a receiver is waiting
+    /// for a message.
+    pub fn try_send(&self, msg: T) -> Result<(), TrySendError<T>> {
+        self.chan.try_send(msg)
+    }
+
+    /// Returns the capacity of the channel, or `None` if it's unbounded.
+    pub fn capacity(&self) -> Option<usize> {
+        self.chan.cap
+    }
+
+    /// Returns `true` if all receivers are dropped.
+    pub fn is_disconnected(&self) -> bool {
+        self.chan.state.lock().receivers == 0
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Receives a message, blocking the current task until there is one.
+    ///
+    /// Returns [`RecvError`] if the channel is empty and all senders are
+    /// dropped.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.chan.recv()
+    }
+
+    /// Tries to receive a message without blocking.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.chan.try_recv()
+    }
+
+    /// Receives a message, blocking the current task until there is one or
+    /// the given duration has elapsed.
+    #[cfg(feature = "irq")]
+    pub fn recv_timeout(&self, dur: Duration) -> Result<T, RecvTimeoutError> {
+        self.chan.recv_timeout(dur)
+    }
+
+    /// Returns the number of messages in the channel.
+    pub fn len(&self) -> usize {
+        self.chan.state.lock().queue.len()
+    }
+
+    /// Returns `true` if the channel is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if all senders are dropped.
+    pub fn is_disconnected(&self) -> bool {
+        self.chan.state.lock().senders == 0
+    }
+
+    /// Returns an iterator that blocks on receiving messages, until the
+    /// channel is disconnected.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { rx: self }
+    }
+
+    /// Returns an iterator over the messages currently in the channel,
+    /// without blocking.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { rx: self }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.chan.state.lock().senders += 1;
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        self.chan.state.lock().receivers += 1;
+        Self {
+            chan: self.chan.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut state = self.chan.state.lock();
+        state.senders -= 1;
+        if state.senders == 0 {
+            let selects = core::mem::take(&mut state.selects);
+            drop(state);
+            self.chan.recv_wq.notify_all(true);
+            for wq in selects {
+                wq.notify_all(true);
+            }
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        let mut state = self.chan.state.lock();
+        state.receivers -= 1;
+        if state.receivers == 0 {
+            drop(state);
+            self.chan.send_wq.notify_all(true);
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("Sender { .. }")
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad("Receiver { .. }")
+    }
+}
+
+/// An iterator over messages on a [`Receiver`], created by
+/// [`Receiver::iter`].
+pub struct Iter<'a, T> {
+    rx: &'a Receiver<T>,
+}
+
+/// An iterator over the messages currently in a [`Receiver`], created by
+/// [`Receiver::try_iter`].
+pub struct TryIter<'a, T> {
+    rx: &'a Receiver<T>,
+}
+
+/// An owning iterator over messages on a [`Receiver`].
+pub struct IntoIter<T> {
+    rx: Receiver<T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { rx: self }
+    }
+}
+
+/// A receiver that can be waited on by [`Select`], regardless of the type of
+/// messages.
+trait Selectable {
+    fn is_ready(&self) -> bool;
+    fn watch(&self, wq: &Arc<WaitQueue>);
+    fn unwatch(&self, wq: &Arc<WaitQueue>);
+}
+
+impl<T> Selectable for Receiver<T> {
+    fn is_ready(&self) -> bool {
+        self.chan.can_recv()
+    }
+
+    fn watch(&self, wq: &Arc<WaitQueue>) {
+        let mut state = self.chan.state.lock();
+        if state.senders != 0 {
+            state.selects.push(wq.clone());
+        }
+    }
+
+    fn unwatch(&self, wq: &Arc<WaitQueue>) {
+        self.chan
+            .state
+            .lock()
+            .selects
+            .retain(|w| !Arc::ptr_eq(w, wq));
+    }
+}
+
+/// Waits on several receivers until one of them is ready, i.e., it has a
+/// message or it's disconnected.
+///
+/// Receivers are added by [`Select::recv`], which returns their indices.
+/// Once [`Select::ready`] returns the index of a ready receiver, the message
+/// can be received with [`Receiver::try_recv`]. With multiple consumers,
+/// another receiver may take the message first, so `try_recv` may still
+/// return [`TryRecvError::Empty`].
+#[derive(Default)]
+pub struct Select<'a> {
+    receivers: Vec<&'a dyn Selectable>,
+}
+
+impl<'a> Select<'a> {
+    /// Creates an empty [`Select`].
+    pub fn new() -> Self {
+        Self {
+            receivers: Vec::new(),
+        }
+    }
+
+    /// Adds a receiver, returns its index.
+    pub fn recv<T>(&mut self, rx: &'a Receiver<T>) -> usize {
+        self.receivers.push(rx);
+        self.receivers.len() - 1
+    }
+
+    /// Returns the index of a ready receiver without blocking, if any.
+    pub fn try_ready(&self) -> Option<usize> {
+        self.receivers.iter().position(|rx| rx.is_ready())
+    }
+
+    fn wait(&self, _timeout: Option<axhal::time::TimeValue>) -> Option<usize> {
+        if let Some(index) = self.try_ready() {
+            return Some(index);
+        }
+        let wq = Arc::new(WaitQueue::new());
+        for rx in self.receivers.iter() {
+            rx.watch(&wq);
+        }
+        let ready = || self.receivers.iter().any(|rx| rx.is_ready());
+        #[cfg(feature = "irq")]
+        if let Some(deadline) = _timeout {
+            let now = axhal::time::wall_time();
+            if now < deadline {
+                wq.wait_timeout_until(deadline - now, ready);
+            }
+        } else {
+            wq.wait_until(ready);
+        }
+        #[cfg(not(feature = "irq"))]
+        wq.wait_until(ready);
+        for rx in self.receivers.iter() {
+            rx.unwatch(&wq);
+        }
+        self.try_ready()
+    }
+
+    /// Blocks the current task until one of the receivers is ready, returns
+    /// its index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no receiver is added.
+    pub fn ready(&self) -> usize {
+        assert!(!self.receivers.is_empty(), "no receiver to select");
+        loop {
+            if let Some(index) = self.wait(None) {
+                return index;
+            }
+        }
+    }
+
+    /// Like [`Select::ready`], but returns `None` if no receiver is ready in
+    /// the given duration.
+    #[cfg(feature = "irq")]
+    pub fn ready_timeout(&self, dur: Duration) -> Option<usize> {
+        self.wait(Some(axhal::time::wall_time() + dur))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bounded, unbounded, Select, TryRecvError};
+    use axtask as thread;
+    use std::sync::Once;
+
+    static INIT: Once = Once::new();
+
+    #[test]
+    fn producers_and_consumers() {
+        INIT.call_once(thread::init_scheduler);
+
+        const NUM_TASKS: usize = 4;
+        const NUM_ITERS: usize = 1_000;
+
+        for cap in [0, 1, 16] {
+            let (tx, rx) = bounded(cap);
+            let (sum_tx, sum_rx) = unbounded();
+            for _ in 0..NUM_TASKS {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    for i in 0..NUM_ITERS {
+                        tx.send(i).unwrap();
+                    }
+                });
+                let (rx, sum_tx) = (rx.clone(), sum_tx.clone());
+                thread::spawn(move || sum_tx.send(rx.iter().sum::<usize>()).unwrap());
+            }
+            drop((tx, rx, sum_tx));
+
+            let total: usize = sum_rx.iter().sum();
+            assert_eq!(total, NUM_TASKS * NUM_ITERS * (NUM_ITERS - 1) / 2);
+        }
+        println!("channel test OK");
+    }
+
+    #[test]
+    fn select() {
+        INIT.call_once(thread::init_scheduler);
+
+        let (tx1, rx1) = unbounded::<u32>();
+        let (tx2, rx2) = bounded::<&str>(0);
+        thread::spawn(move || {
+            tx2.send("hello").unwrap();
+            drop(tx1);
+        });
+
+        let mut sel = Select::new();
+        let i1 = sel.recv(&rx1);
+        let i2 = sel.recv(&rx2);
+        assert_eq!(sel.ready(), i2);
+        assert_eq!(rx2.try_recv(), Ok("hello"));
+        // `rx1` becomes ready when it's disconnected.
+        assert_eq!(sel.ready(), i1);
+        assert_eq!(rx1.try_recv(), Err(TryRecvError::Disconnected));
+    }
+}
@@ -0,0 +1,211 @@
+//! A sleeping condition variable.
+
+use core::fmt;
+use core::sync::atomic::{AtomicU32, Ordering};
+#[cfg(feature = "irq")]
+use core::time::Duration;
+
+use axtask::WaitQueue;
+
+use crate::MutexGuard;
+
+/// A type indicating whether a timed wait on a condition variable returned
+/// due to a time out or not.
+///
+/// It's returned by [`Condvar::wait_timeout`] and its variants.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct WaitTimeoutResult(bool);
+
+impl WaitTimeoutResult {
+    /// Returns `true` if the wait was known to have timed out.
+    pub fn timed_out(&self) -> bool {
+        self.0
+    }
+}
+
+/// A condition variable, similar to
+/// [`std::sync::Condvar`](https://doc.rust-lang.org/std/sync/struct.Condvar.html).
+///
+/// Waiting tasks are blocked in a wait queue until they are notified. A
+/// sequence number is increased on each notification, so a notification sent
+/// after a task has released the mutex is never lost.
+///
+/// Like the standard library, spurious wakeups are possible, the condition
+/// should always be checked in a loop (or by [`Condvar::wait_while`]).
+pub struct Condvar {
+    wq: WaitQueue,
+    seq: AtomicU32,
+}
+
+impl Condvar {
+    /// Creates a new condition variable.
+    pub const fn new() -> Self {
+        Self {
+            wq: WaitQueue::new(),
+            seq: AtomicU32::new(0),
+        }
+    }
+
+    fn wait_for_notify(&self, seq: u32) {
+        self.wq
+            .wait_until(|| self.seq.load(Ordering::Acquire) != seq);
+    }
+
+    #[cfg(feature = "irq")]
+    fn wait_for_notify_timeout(&self, seq: u32, dur: Duration) -> WaitTimeoutResult {
+        self.wq
+            .wait_timeout_until(dur, || self.seq.load(Ordering::Acquire) != seq);
+        // It's not a timeout if notified at the same time.
+        WaitTimeoutResult(self.seq.load(Ordering::Acquire) == seq)
+    }
+
+    /// Blocks the current task until this condition variable receives a
+    /// notification.
+    ///
+    /// The mutex is released while waiting, and re-acquired before returning.
+    pub fn wait<'a, T: ?Sized>(&self, mut guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        let seq = self.seq.load(Ordering::Acquire);
+        MutexGuard::unlocked(&mut guard, || self.wait_for_notify(seq));
+        guard
+    }
+
+    /// Blocks the current task while `condition` returns `true`, waiting for
+    /// notifications between the checks.
+    pub fn wait_while<'a, T: ?Sized, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        mut condition: F,
+    ) -> MutexGuard<'a, T>
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        while condition(&mut *guard) {
+            guard = self.wait(guard);
+        }
+        guard
+    }
+
+    /// Like [`Condvar::wait`], but gives up after the given duration.
+    #[cfg(feature = "irq")]
+    pub fn wait_timeout<'a, T: ?Sized>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        dur: Duration,
+    ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        let seq = self.seq.load(Ordering::Acquire);
+        let res = MutexGuard::unlocked(&mut guard, || self.wait_for_notify_timeout(seq, dur));
+        (guard, res)
+    }
+
+    /// Like [`Condvar::wait_while`], but gives up after the given duration.
+    ///
+    /// The result is timed out if the condition is still `true` at the end.
+    #[cfg(feature = "irq")]
+    pub fn wait_timeout_while<'a, T: ?Sized, F>(
+        &self,
+        mut guard: MutexGuard<'a, T>,
+        dur: Duration,
+        mut condition: F,
+    ) -> (MutexGuard<'a, T>, WaitTimeoutResult)
+    where
+        F: FnMut(&mut T) -> bool,
+    {
+        let deadline = axhal::time::wall_time() + dur;
+        loop {
+            if !condition(&mut *guard) {
+                return (guard, WaitTimeoutResult(false));
+            }
+            let now = axhal::time::wall_time();
+            if now >= deadline {
+                return (guard, WaitTimeoutResult(true));
+            }
+            guard = self.wait_timeout(guard, deadline - now).0;
+        }
+    }
+
+    /// Like [`Condvar::wait`], but for any lock: releases it by calling
+    /// `unlock`, and re-acquires it by calling `lock` after being notified.
+    ///
+    /// It's useful for locks other than [`Mutex`](crate::Mutex), e.g., the
+    /// mutexes of the POSIX API.
+    pub fn wait_with<R>(&self, unlock: impl FnOnce(), lock: impl FnOnce() -> R) -> R {
+        let seq = self.seq.load(Ordering::Acquire);
+        unlock();
+        self.wait_for_notify(seq);
+        lock()
+    }
+
+    /// Like [`Condvar::wait_with`], but gives up after the given duration.
+    #[cfg(feature = "irq")]
+    pub fn wait_timeout_with<R>(
+        &self,
+        dur: Duration,
+        unlock: impl FnOnce(),
+        lock: impl FnOnce() -> R,
+    ) -> (R, WaitTimeoutResult) {
+        let seq = self.seq.load(Ordering::Acquire);
+        unlock();
+        let res = self.wait_for_notify_timeout(seq, dur);
+        (lock(), res)
+    }
+
+    /// Wakes up one task blocked on this condition variable.
+    pub fn notify_one(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        self.wq.notify_one(true);
+    }
+
+    /// Wakes up all tasks blocked on this condition variable.
+    pub fn notify_all(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        self.wq.notify_all(true);
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Condvar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Condvar").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Condvar, Mutex};
+    use axtask as thread;
+    use std::sync::Once;
+
+    static INIT: Once = Once::new();
+
+    #[test]
+    fn producer_consumer() {
+        INIT.call_once(thread::init_scheduler);
+
+        const NUM_ITEMS: u32 = 1_000;
+        static QUEUE: Mutex<Option<u32>> = Mutex::new(None);
+        static NOT_EMPTY: Condvar = Condvar::new();
+        static NOT_FULL: Condvar = Condvar::new();
+
+        thread::spawn(|| {
+            for i in 1..=NUM_ITEMS {
+                let mut slot = NOT_FULL.wait_while(QUEUE.lock(), |slot| slot.is_some());
+                *slot = Some(i);
+                NOT_EMPTY.notify_one();
+            }
+        });
+
+        let mut sum = 0;
+        for _ in 0..NUM_ITEMS {
+            let mut slot = NOT_EMPTY.wait_while(QUEUE.lock(), |slot| slot.is_none());
+            sum += slot.take().unwrap();
+            NOT_FULL.notify_one();
+        }
+        assert_eq!(sum, NUM_ITEMS * (NUM_ITEMS + 1) / 2);
+        println!("Condvar test OK");
+    }
+}
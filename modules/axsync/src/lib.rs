@@ -3,26 +3,91 @@
 //! Currently supported primitives:
 //!
 //! - [`Mutex`]: A mutual exclusion primitive.
-//! - mod [`spin`]: spinlocks imported from the [`kspin`] crate.
+//! - [`PiMutex`]: A mutual exclusion primitive with priority inheritance
+//!   (`multitask` only).
+//! - [`Condvar`]: A condition variable (`multitask` only).
+//! - [`RwLock`]: A reader-writer lock with writer preference (`multitask`
+//!   only).
+//! - [`Semaphore`]: A counting semaphore (`multitask` only).
+//! - [`Barrier`]: A barrier to synchronize a group of tasks (`multitask` only).
+//! - [`Once`] and [`OnceLock`]: One-time initialization (`multitask` only).
+//! - mod [`channel`]: Bounded and unbounded MPMC channels (`multitask` only).
+//! - mod [`rcu`]: Read-copy-update, and the RCU-protected pointer
+//!   [`RcuCell`](rcu::RcuCell) (`alloc` only).
+//! - mod [`spin`]: spinlocks imported from the [`kspin`] crate, through
+//!   [`axlockdep`].
+//! - mod [`lockdep`]: the lock dependency validator (`lockdep` only).
 //!
 //! # Cargo Features
 //!
 //! - `multitask`: For use in the multi-threaded environments. If the feature is
 //!   not enabled, [`Mutex`] will be an alias of [`spin::SpinNoIrq`]. This
 //!   feature is enabled by default.
+//! - `alloc`: Enables the primitives that need dynamic memory allocation
+//!   (e.g., [`rcu`]). It is enabled by the `multitask` feature.
+//! - `irq`: Enables the timed waits (e.g., [`Condvar::wait_timeout`]), which
+//!   require timer interrupts.
+//! - `lockdep`: Track the acquisition order of [`Mutex`] and the spinlocks in
+//!   all modules, and report possible deadlocks (see [`lockdep`]). It also
+//!   enables the `multitask` feature if it is enabled.
 
 #![cfg_attr(not(test), no_std)]
 #![feature(doc_cfg)]
 
-pub use kspin as spin;
+#[cfg(feature = "alloc")]
+extern crate alloc;
 
+pub use axlockdep::spin;
+
+#[cfg(feature = "lockdep")]
+#[doc(cfg(feature = "lockdep"))]
+pub mod lockdep;
+
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub mod channel;
+#[cfg(feature = "alloc")]
+#[doc(cfg(feature = "alloc"))]
+pub mod rcu;
+
+#[cfg(feature = "multitask")]
+mod barrier;
+#[cfg(feature = "multitask")]
+mod condvar;
 #[cfg(feature = "multitask")]
 mod mutex;
+#[cfg(feature = "multitask")]
+mod once;
+#[cfg(feature = "multitask")]
+mod pi_mutex;
+#[cfg(feature = "multitask")]
+mod rwlock;
+#[cfg(feature = "multitask")]
+mod semaphore;
 
 #[cfg(feature = "multitask")]
 #[doc(cfg(feature = "multitask"))]
 pub use self::mutex::{Mutex, MutexGuard};
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::pi_mutex::{PiMutex, PiMutexGuard};
+
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::barrier::{Barrier, BarrierWaitResult};
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::condvar::{Condvar, WaitTimeoutResult};
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::once::{Once, OnceLock};
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::rwlock::{RawRwLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use self::semaphore::Semaphore;
 
 #[cfg(not(feature = "multitask"))]
 #[doc(cfg(not(feature = "multitask")))]
-pub use kspin::{SpinNoIrq as Mutex, SpinNoIrqGuard as MutexGuard};
+pub use self::spin::{SpinNoIrq as Mutex, SpinNoIrqGuard as MutexGuard};
@@ -0,0 +1,68 @@
+//! Lock dependency validator (lockdep).
+//!
+//! It's provided by [`axlockdep`], see there for how it works. [`Mutex`] and
+//! the spinlocks in all modules are tracked, where all locks created at the
+//! same place share a lock class. The locks acquired in IRQ handlers are
+//! tracked apart from the interrupted task. It also reports blocking locks
+//! (e.g., [`Mutex`]) acquired with IRQs disabled, which is only checked with
+//! the `irq` feature. Backtraces are only available if the kernel is compiled
+//! with frame pointers (see [`axhal::backtrace`]).
+//!
+//! [`Mutex`]: crate::Mutex
+
+pub(crate) use axlockdep::{acquire, release, try_acquired, LockClass};
+pub use axlockdep::{is_enabled, num_reports};
+
+#[cfg(test)]
+mod tests {
+    use crate::{lockdep, Mutex};
+    use axtask as thread;
+    use std::sync::Once;
+
+    static INIT: Once = Once::new();
+
+    #[test]
+    fn abba() {
+        INIT.call_once(thread::init_scheduler);
+
+        static A: Mutex<()> = Mutex::new(());
+        static B: Mutex<()> = Mutex::new(());
+
+        let reports = lockdep::num_reports();
+        {
+            let _a = A.lock();
+            let _b = B.lock();
+        }
+        assert_eq!(lockdep::num_reports(), reports);
+        {
+            let _b = B.lock();
+            let _a = A.lock();
+        }
+        assert_eq!(lockdep::num_reports(), reports + 1);
+        // Reported only once.
+        {
+            let _b = B.lock();
+            let _a = A.lock();
+        }
+        assert_eq!(lockdep::num_reports(), reports + 1);
+
+        // Locks created at the same place share a class, so the reverse order
+        // on other instances is reported as well.
+        fn new_pair() -> (Mutex<()>, Mutex<()>) {
+            (Mutex::new(()), Mutex::new(()))
+        }
+        let (c1, d1) = new_pair();
+        let (c2, d2) = new_pair();
+        {
+            let _c = c1.lock();
+            let _d = d1.lock();
+        }
+        {
+            let _d = d2.lock();
+            let _c = c2.lock();
+        }
+        assert_eq!(lockdep::num_reports(), reports + 2);
+        assert!(lockdep::is_enabled());
+        println!("lockdep test OK");
+    }
+}
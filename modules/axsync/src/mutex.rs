@@ -7,6 +7,9 @@ use core::sync::atomic::{AtomicU64, Ordering};
 
 use axtask::{current, WaitQueue};
 
+#[cfg(feature = "lockdep")]
+use crate::lockdep::{self, LockClass};
+
 /// A mutual exclusion primitive useful for protecting shared data, similar to
 /// [`std::sync::Mutex`](https://doc.rust-lang.org/std/sync/struct.Mutex.html).
 ///
@@ -16,6 +19,8 @@ use axtask::{current, WaitQueue};
 pub struct Mutex<T: ?Sized> {
     wq: WaitQueue,
     owner_id: AtomicU64,
+    #[cfg(feature = "lockdep")]
+    class: LockClass,
     data: UnsafeCell<T>,
 }
 
@@ -34,10 +39,13 @@ unsafe impl<T: ?Sized + Send> Send for Mutex<T> {}
 impl<T> Mutex<T> {
     /// Creates a new [`Mutex`] wrapping the supplied data.
     #[inline(always)]
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub const fn new(data: T) -> Self {
         Self {
             wq: WaitQueue::new(),
             owner_id: AtomicU64::new(0),
+            #[cfg(feature = "lockdep")]
+            class: LockClass::new(),
             data: UnsafeCell::new(data),
         }
     }
@@ -69,6 +77,17 @@ impl<T: ?Sized> Mutex<T> {
     /// The returned value may be dereferenced for data access
     /// and the lock will be dropped when the guard falls out of scope.
     pub fn lock(&self) -> MutexGuard<T> {
+        self.acquire();
+        MutexGuard {
+            lock: self,
+            data: unsafe { &mut *self.data.get() },
+        }
+    }
+
+    /// Acquires the lock without creating a guard.
+    fn acquire(&self) {
+        #[cfg(feature = "lockdep")]
+        lockdep::acquire(&self.class, core::any::type_name::<Self>(), true);
         let current_id = current().id().as_u64();
         loop {
             // Can fail to lock even if the spinlock is not locked. May be more efficient than `try_lock`
@@ -92,10 +111,6 @@ impl<T: ?Sized> Mutex<T> {
                 }
             }
         }
-        MutexGuard {
-            lock: self,
-            data: unsafe { &mut *self.data.get() },
-        }
     }
 
     /// Try to lock this [`Mutex`], returning a lock guard if successful.
@@ -109,6 +124,8 @@ impl<T: ?Sized> Mutex<T> {
             .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
             .is_ok()
         {
+            #[cfg(feature = "lockdep")]
+            lockdep::try_acquired(&self.class, core::any::type_name::<Self>());
             Some(MutexGuard {
                 lock: self,
                 data: unsafe { &mut *self.data.get() },
@@ -133,6 +150,8 @@ impl<T: ?Sized> Mutex<T> {
             "{} tried to release mutex it doesn't own",
             current().id_name()
         );
+        #[cfg(feature = "lockdep")]
+        lockdep::release(&self.class, core::any::type_name::<Self>());
         self.wq.notify_one(true);
     }
 
@@ -151,6 +170,7 @@ impl<T: ?Sized> Mutex<T> {
 
 impl<T: ?Sized + Default> Default for Mutex<T> {
     #[inline(always)]
+    #[cfg_attr(feature = "lockdep", track_caller)]
     fn default() -> Self {
         Self::new(Default::default())
     }
@@ -167,6 +187,19 @@ impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
     }
 }
 
+impl<'a, T: ?Sized> MutexGuard<'a, T> {
+    /// Releases the lock, runs `f`, and acquires the lock again.
+    ///
+    /// It's an associated function so that it does not conflict with the
+    /// methods of `T`.
+    pub(crate) fn unlocked<R>(guard: &mut Self, f: impl FnOnce() -> R) -> R {
+        unsafe { guard.lock.force_unlock() };
+        let ret = f();
+        guard.lock.acquire();
+        ret
+    }
+}
+
 impl<'a, T: ?Sized> Deref for MutexGuard<'a, T> {
     type Target = T;
     #[inline(always)]
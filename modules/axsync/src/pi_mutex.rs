@@ -0,0 +1,277 @@
+//! A sleeping mutex with priority inheritance.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use axtask::{current, pi_acquired, pi_block_on, pi_release, pi_unblock, WaitQueue};
+
+/// A [`Mutex`](crate::Mutex) with priority inheritance.
+///
+/// While a task is blocked on the mutex, the owner of the mutex inherits the
+/// scheduling policy and priority of the task if they are higher, so that a
+/// preempted low-priority owner can not block a high-priority task for an
+/// unbounded time. The inherited priority is passed along the chain of owners
+/// if the owner is blocked on another [`PiMutex`], and it's restored when the
+/// owner unlocks the mutex.
+///
+/// Priority inheritance needs a scheduler with priorities (`sched_rt` or
+/// `sched_cfs`), see [`axtask::pi_supported`]. With other schedulers, it works
+/// as an ordinary [`Mutex`](crate::Mutex).
+pub struct PiMutex<T: ?Sized> {
+    wq: WaitQueue,
+    owner_id: AtomicU64,
+    data: UnsafeCell<T>,
+}
+
+/// A guard that provides mutable data access.
+///
+/// When the guard falls out of scope it will release the lock.
+pub struct PiMutexGuard<'a, T: ?Sized + 'a> {
+    lock: &'a PiMutex<T>,
+    data: *mut T,
+}
+
+// Same unsafe impls as `std::sync::Mutex`
+unsafe impl<T: ?Sized + Send> Sync for PiMutex<T> {}
+unsafe impl<T: ?Sized + Send> Send for PiMutex<T> {}
+
+impl<T> PiMutex<T> {
+    /// Creates a new [`PiMutex`] wrapping the supplied data.
+    #[inline(always)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            wq: WaitQueue::new(),
+            owner_id: AtomicU64::new(0),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes this [`PiMutex`] and unwraps the underlying data.
+    #[inline(always)]
+    pub fn into_inner(self) -> T {
+        // We know statically that there are no outstanding references to
+        // `self` so there's no need to lock.
+        let PiMutex { data, .. } = self;
+        data.into_inner()
+    }
+}
+
+impl<T: ?Sized> PiMutex<T> {
+    /// Returns `true` if the lock is currently held.
+    ///
+    /// # Safety
+    ///
+    /// This function provides no synchronization guarantees and so its result should be considered 'out of date'
+    /// the instant it is called. Do not use it for synchronization purposes. However, it may be useful as a heuristic.
+    #[inline(always)]
+    pub fn is_locked(&self) -> bool {
+        self.owner_id.load(Ordering::Relaxed) != 0
+    }
+
+    /// The ID of this mutex used for priority inheritance.
+    #[inline(always)]
+    fn lock_id(&self) -> usize {
+        self as *const Self as *const () as usize
+    }
+
+    /// Locks the [`PiMutex`] and returns a guard that permits access to the inner data.
+    ///
+    /// The returned value may be dereferenced for data access
+    /// and the lock will be dropped when the guard falls out of scope.
+    pub fn lock(&self) -> PiMutexGuard<T> {
+        let current_id = current().id().as_u64();
+        loop {
+            // Can fail to lock even if the spinlock is not locked. May be more efficient than `try_lock`
+            // when called in a loop.
+            match self.owner_id.compare_exchange_weak(
+                0,
+                current_id,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => break,
+                Err(owner_id) => {
+                    assert_ne!(
+                        owner_id,
+                        current_id,
+                        "{} tried to acquire mutex it already owns.",
+                        current().id_name()
+                    );
+                    // Lend our priority to the owner while waiting, until the
+                    // lock looks unlocked before retrying
+                    pi_block_on(self.lock_id());
+                    self.wq.wait_until(|| !self.is_locked());
+                    pi_unblock(self.lock_id());
+                }
+            }
+        }
+        pi_acquired(self.lock_id());
+        PiMutexGuard {
+            lock: self,
+            data: unsafe { &mut *self.data.get() },
+        }
+    }
+
+    /// Try to lock this [`PiMutex`], returning a lock guard if successful.
+    #[inline(always)]
+    pub fn try_lock(&self) -> Option<PiMutexGuard<T>> {
+        let current_id = current().id().as_u64();
+        // The reason for using a strong compare_exchange is explained here:
+        // https://github.com/Amanieu/parking_lot/pull/207#issuecomment-575869107
+        if self
+            .owner_id
+            .compare_exchange(0, current_id, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            pi_acquired(self.lock_id());
+            Some(PiMutexGuard {
+                lock: self,
+                data: unsafe { &mut *self.data.get() },
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Force unlock the [`PiMutex`].
+    ///
+    /// # Safety
+    ///
+    /// This is *extremely* unsafe if the lock is not held by the current
+    /// thread. However, this can be useful in some instances for exposing
+    /// the lock to FFI that doesn’t know how to deal with RAII.
+    pub unsafe fn force_unlock(&self) {
+        let owner_id = self.owner_id.swap(0, Ordering::Release);
+        assert_eq!(
+            owner_id,
+            current().id().as_u64(),
+            "{} tried to release mutex it doesn't own",
+            current().id_name()
+        );
+        self.wq.notify_one(true);
+        // Restore our own priority after the waiter is woken up, or it may
+        // not get a chance to run before us.
+        pi_release(self.lock_id());
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the [`PiMutex`] mutably, and a mutable reference is guaranteed to be exclusive in
+    /// Rust, no actual locking needs to take place -- the mutable borrow statically guarantees no locks exist. As
+    /// such, this is a 'zero-cost' operation.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut T {
+        // We know statically that there are no other references to `self`, so
+        // there's no need to lock the inner mutex.
+        unsafe { &mut *self.data.get() }
+    }
+}
+
+impl<T: ?Sized + Default> Default for PiMutex<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for PiMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => write!(f, "PiMutex {{ data: ")
+                .and_then(|()| (*guard).fmt(f))
+                .and_then(|()| write!(f, "}}")),
+            None => write!(f, "PiMutex {{ <locked> }}"),
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for PiMutexGuard<'a, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        // We know statically that only we are referencing data
+        unsafe { &*self.data }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for PiMutexGuard<'a, T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        // We know statically that only we are referencing data
+        unsafe { &mut *self.data }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for PiMutexGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized> Drop for PiMutexGuard<'a, T> {
+    /// The dropping of the [`PiMutexGuard`] will release the lock it was created from.
+    fn drop(&mut self) {
+        unsafe { self.lock.force_unlock() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::PiMutex;
+    use axtask as thread;
+    use std::sync::Once;
+
+    static INIT: Once = Once::new();
+
+    fn may_interrupt() {
+        // simulate interrupts
+        if rand::random::<u32>() % 3 == 0 {
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn nested_locks() {
+        INIT.call_once(thread::init_scheduler);
+
+        const NUM_TASKS: u32 = 10;
+        const NUM_ITERS: u32 = 1_000;
+        static OUTER: PiMutex<u32> = PiMutex::new(0);
+        static INNER: PiMutex<u32> = PiMutex::new(0);
+
+        fn inc(delta: u32) {
+            for _ in 0..NUM_ITERS {
+                let mut outer = OUTER.lock();
+                may_interrupt();
+                let mut inner = INNER.lock();
+                *outer += delta;
+                *inner += delta;
+                may_interrupt();
+                drop(inner);
+                drop(outer);
+                may_interrupt();
+            }
+        }
+
+        for _ in 0..NUM_TASKS {
+            thread::spawn(|| inc(1));
+            thread::spawn(|| inc(2));
+        }
+
+        println!("spawn OK");
+        loop {
+            let val = INNER.lock();
+            if *val == NUM_ITERS * NUM_TASKS * 3 {
+                break;
+            }
+            may_interrupt();
+            drop(val);
+            may_interrupt();
+        }
+
+        assert_eq!(*OUTER.lock(), NUM_ITERS * NUM_TASKS * 3);
+        println!("PiMutex test OK");
+    }
+}
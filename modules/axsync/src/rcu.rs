@@ -0,0 +1,272 @@
+//! Read-copy-update (RCU) for read-mostly data.
+//!
+//! Readers access the data in read-side critical sections without taking
+//! any lock, while writers publish a new copy of the data, and reclaim the
+//! old one after all readers that may see it have finished. The grace periods
+//! are detected by [`axtask`] (see [`synchronize_rcu`]).
+//!
+//! Without the `multitask` feature, there is only one task running on one
+//! CPU, so a grace period has elapsed once the read-side critical sections
+//! of the task have finished. The ones in IRQ handlers always finish before
+//! returning to the task.
+
+use alloc::boxed::Box;
+use core::fmt;
+use core::sync::atomic::{AtomicPtr, Ordering};
+
+use crate::Mutex;
+
+#[cfg(feature = "multitask")]
+#[doc(cfg(feature = "multitask"))]
+pub use axtask::{call_rcu, rcu_barrier, rcu_read_lock, synchronize_rcu, RcuReadGuard};
+
+#[cfg(not(feature = "multitask"))]
+pub use self::single::{call_rcu, rcu_barrier, rcu_read_lock, synchronize_rcu, RcuReadGuard};
+
+#[cfg(not(feature = "multitask"))]
+mod single {
+    use alloc::boxed::Box;
+    use alloc::vec::Vec;
+    use core::marker::PhantomData;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::spin::SpinNoIrq;
+
+    /// Number of nested read-side critical sections, including the ones in
+    /// IRQ handlers.
+    static NESTING: AtomicUsize = AtomicUsize::new(0);
+    static CALLBACKS: SpinNoIrq<Vec<Box<dyn FnOnce() + Send>>> = SpinNoIrq::new(Vec::new());
+
+    /// A guard of an RCU read-side critical section, created by
+    /// [`rcu_read_lock`].
+    pub struct RcuReadGuard {
+        _not_send: PhantomData<*const ()>,
+    }
+
+    /// Enters an RCU read-side critical section.
+    pub fn rcu_read_lock() -> RcuReadGuard {
+        NESTING.fetch_add(1, Ordering::SeqCst);
+        RcuReadGuard {
+            _not_send: PhantomData,
+        }
+    }
+
+    impl Drop for RcuReadGuard {
+        fn drop(&mut self) {
+            if NESTING.fetch_sub(1, Ordering::SeqCst) == 1 {
+                rcu_barrier();
+            }
+        }
+    }
+
+    /// Waits until all pre-existing RCU read-side critical sections have
+    /// finished, which returns immediately.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it's called in a read-side critical section.
+    pub fn synchronize_rcu() {
+        assert_eq!(
+            NESTING.load(Ordering::SeqCst),
+            0,
+            "synchronize_rcu in an RCU read-side critical section"
+        );
+    }
+
+    /// Calls `f` after a grace period, i.e., immediately, or when the current
+    /// read-side critical section ends.
+    pub fn call_rcu<F>(f: F)
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if NESTING.load(Ordering::SeqCst) == 0 {
+            f();
+        } else {
+            CALLBACKS.lock().push(Box::new(f));
+        }
+    }
+
+    /// Calls the callbacks queued by [`call_rcu`] if not in a read-side
+    /// critical section.
+    pub fn rcu_barrier() {
+        loop {
+            if NESTING.load(Ordering::SeqCst) != 0 {
+                return;
+            }
+            let callbacks = core::mem::take(&mut *CALLBACKS.lock());
+            if callbacks.is_empty() {
+                return;
+            }
+            callbacks.into_iter().for_each(|f| f());
+        }
+    }
+}
+
+/// A pointer to data protected by RCU.
+///
+/// The data is read by [`RcuCell::read`] in a read-side critical section,
+/// which never blocks. It's updated by copying the current data and replacing
+/// it as a whole, the old data is dropped after a grace period by
+/// [`call_rcu`]. Writers are serialized by a [`Mutex`].
+pub struct RcuCell<T: Send + Sync + 'static> {
+    ptr: AtomicPtr<T>,
+    writer: Mutex<()>,
+}
+
+impl<T: Send + Sync + 'static> RcuCell<T> {
+    /// Creates a new [`RcuCell`] with the given data.
+    pub fn new(data: T) -> Self {
+        Self {
+            ptr: AtomicPtr::new(Box::into_raw(Box::new(data))),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Returns a reference to the current data, which is valid until the
+    /// read-side critical section ends.
+    pub fn read<'a>(&'a self, _guard: &'a RcuReadGuard) -> &'a T {
+        unsafe { &*self.ptr.load(Ordering::Acquire) }
+    }
+
+    /// Replaces the data, and drops the old data after a grace period.
+    pub fn replace(&self, data: T) {
+        let _writer = self.writer.lock();
+        self.publish(data);
+    }
+
+    /// Updates the data by calling `f` on a copy of the current data, and
+    /// publishes the copy. Returns the result of `f`.
+    ///
+    /// Readers see either the old or the new data as a whole, never the
+    /// changes in progress.
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R
+    where
+        T: Clone,
+    {
+        let _writer = self.writer.lock();
+        // The data is not reclaimed while the writer lock is held.
+        let mut data = unsafe { &*self.ptr.load(Ordering::Acquire) }.clone();
+        let ret = f(&mut data);
+        self.publish(data);
+        ret
+    }
+
+    /// Like [`RcuCell::update`], but waits for a grace period and drops the
+    /// old data before returning, so that the resources it holds (e.g., the
+    /// last references to other objects) are released synchronously.
+    ///
+    /// If all updates of the cell are made by this method, no old data
+    /// outlives the update that replaced it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if it's called in a read-side critical section, since it waits
+    /// for a grace period (see [`synchronize_rcu`]).
+    pub fn update_sync<R>(&self, f: impl FnOnce(&mut T) -> R) -> R
+    where
+        T: Clone,
+    {
+        let _writer = self.writer.lock();
+        let old = self.ptr.load(Ordering::Acquire);
+        let mut data = unsafe { &*old }.clone();
+        let ret = f(&mut data);
+        self.ptr
+            .store(Box::into_raw(Box::new(data)), Ordering::Release);
+        synchronize_rcu();
+        drop(unsafe { Box::from_raw(old) });
+        ret
+    }
+
+    fn publish(&self, data: T) {
+        let old = self
+            .ptr
+            .swap(Box::into_raw(Box::new(data)), Ordering::AcqRel) as usize;
+        call_rcu(move || drop(unsafe { Box::from_raw(old as *mut T) }));
+    }
+}
+
+impl<T: Send + Sync + Default + 'static> Default for RcuCell<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: Send + Sync + 'static> Drop for RcuCell<T> {
+    fn drop(&mut self) {
+        // No reader can see the data of a dropped cell.
+        drop(unsafe { Box::from_raw(*self.ptr.get_mut()) });
+    }
+}
+
+impl<T: Send + Sync + fmt::Debug + 'static> fmt::Debug for RcuCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let guard = rcu_read_lock();
+        f.debug_struct("RcuCell")
+            .field("data", self.read(&guard))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rcu_barrier, rcu_read_lock, RcuCell};
+    use axtask as thread;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Once};
+
+    static INIT: Once = Once::new();
+
+    #[test]
+    fn copy_update() {
+        INIT.call_once(thread::init_scheduler);
+
+        let cell = RcuCell::new(vec![1, 2, 3]);
+        let guard = rcu_read_lock();
+        let old = cell.read(&guard);
+        assert_eq!(cell.update(|v| v.pop()), Some(3));
+        // The old copy is still valid in the critical section.
+        assert_eq!(old, &[1, 2, 3]);
+        assert_eq!(cell.read(&guard), &[1, 2]);
+        drop(guard);
+
+        cell.replace(vec![]);
+        rcu_barrier();
+        assert!(cell.read(&rcu_read_lock()).is_empty());
+        println!("rcu test OK");
+    }
+
+    #[test]
+    fn update_sync() {
+        INIT.call_once(thread::init_scheduler);
+
+        let cell = Arc::new(RcuCell::new(Vec::new()));
+        let item = Arc::new(0);
+        cell.update_sync(|v| v.push(item.clone()));
+
+        // A concurrent reader keeps seeing either the old or the new copy.
+        let stop = Arc::new(AtomicBool::new(false));
+        let reader = thread::spawn({
+            let (cell, stop) = (cell.clone(), stop.clone());
+            move || {
+                while !stop.load(Ordering::Acquire) {
+                    let guard = rcu_read_lock();
+                    assert!(cell.read(&guard).len() <= 1);
+                    drop(guard);
+                    thread::yield_now();
+                }
+            }
+        });
+
+        for _ in 0..100 {
+            let removed = cell.update_sync(|v| v.pop());
+            assert!(removed.is_some());
+            drop(removed);
+            // No old copy holds the item after the update returns.
+            assert_eq!(Arc::strong_count(&item), 1);
+            cell.update_sync(|v| v.push(item.clone()));
+        }
+        stop.store(true, Ordering::Release);
+        reader.join();
+        println!("rcu update_sync test OK");
+    }
+}
@@ -0,0 +1,341 @@
+//! A sleeping reader-writer lock with writer preference.
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axtask::WaitQueue;
+
+/// The bit of the state indicating that the lock is held by a writer, the
+/// other bits are the number of readers.
+const WRITER: usize = 1 << (usize::BITS - 1);
+
+/// The raw reader-writer lock behind [`RwLock`], which does not protect any
+/// data.
+///
+/// Writers are preferred: new readers are blocked as long as a writer is
+/// waiting, so that writers are not starved by a stream of readers. As a
+/// result, acquiring a read lock recursively may deadlock.
+pub struct RawRwLock {
+    state: AtomicUsize,
+    /// Number of writers waiting for the lock.
+    waiting_writers: AtomicUsize,
+    readers_wq: WaitQueue,
+    writers_wq: WaitQueue,
+}
+
+impl RawRwLock {
+    /// Creates a new unlocked reader-writer lock.
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(0),
+            waiting_writers: AtomicUsize::new(0),
+            readers_wq: WaitQueue::new(),
+            writers_wq: WaitQueue::new(),
+        }
+    }
+
+    fn can_read(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER == 0
+            && self.waiting_writers.load(Ordering::Relaxed) == 0
+    }
+
+    /// Acquires a read lock, blocking the current task until it's available.
+    pub fn read(&self) {
+        while !self.try_read() {
+            self.readers_wq.wait_until(|| self.can_read());
+        }
+    }
+
+    /// Tries to acquire a read lock, returns whether it succeeded.
+    pub fn try_read(&self) -> bool {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            if state & WRITER != 0 || self.waiting_writers.load(Ordering::Relaxed) != 0 {
+                return false;
+            }
+            assert!(state + 1 < WRITER, "too many readers");
+            match self.state.compare_exchange_weak(
+                state,
+                state + 1,
+                Ordering::Acquire,
+                Ordering::Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    /// Acquires the write lock, blocking the current task until it's
+    /// available.
+    pub fn write(&self) {
+        if self.try_write() {
+            return;
+        }
+        self.waiting_writers.fetch_add(1, Ordering::Relaxed);
+        while !self.try_write() {
+            self.writers_wq
+                .wait_until(|| self.state.load(Ordering::Relaxed) == 0);
+        }
+        self.waiting_writers.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Tries to acquire the write lock, returns whether it succeeded.
+    pub fn try_write(&self) -> bool {
+        self.state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    /// Releases a read lock.
+    ///
+    /// # Safety
+    ///
+    /// The current task must hold a read lock.
+    pub unsafe fn read_unlock(&self) {
+        let state = self.state.fetch_sub(1, Ordering::Release);
+        debug_assert!(state & WRITER == 0 && state != 0);
+        if state == 1 {
+            // The last reader wakes up a writer.
+            self.writers_wq.notify_one(true);
+        }
+    }
+
+    /// Releases the write lock.
+    ///
+    /// # Safety
+    ///
+    /// The current task must hold the write lock.
+    pub unsafe fn write_unlock(&self) {
+        let state = self.state.swap(0, Ordering::Release);
+        debug_assert_eq!(state, WRITER);
+        if self.waiting_writers.load(Ordering::Relaxed) != 0 {
+            self.writers_wq.notify_one(true);
+        } else {
+            self.readers_wq.notify_all(true);
+        }
+    }
+
+    /// Returns `true` if the lock is held by a writer.
+    pub fn is_write_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) & WRITER != 0
+    }
+
+    /// Returns `true` if the lock is held by readers or a writer.
+    ///
+    /// Like [`Mutex::is_locked`](crate::Mutex::is_locked), the result can only
+    /// be used as a heuristic.
+    pub fn is_locked(&self) -> bool {
+        self.state.load(Ordering::Relaxed) != 0
+    }
+}
+
+impl Default for RawRwLock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A reader-writer lock, similar to
+/// [`std::sync::RwLock`](https://doc.rust-lang.org/std/sync/struct.RwLock.html).
+///
+/// It allows multiple readers or at most one writer at any point in time.
+/// Blocked tasks sleep in wait queues, and writers are preferred over readers
+/// (see [`RawRwLock`]).
+pub struct RwLock<T: ?Sized> {
+    raw: RawRwLock,
+    data: UnsafeCell<T>,
+}
+
+/// A guard that provides shared data access.
+///
+/// When the guard falls out of scope it will release the read lock.
+pub struct RwLockReadGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+/// A guard that provides mutable data access.
+///
+/// When the guard falls out of scope it will release the write lock.
+pub struct RwLockWriteGuard<'a, T: ?Sized + 'a> {
+    lock: &'a RwLock<T>,
+}
+
+// Same unsafe impls as `std::sync::RwLock`
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> {}
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> {}
+
+impl<T> RwLock<T> {
+    /// Creates a new [`RwLock`] wrapping the supplied data.
+    #[inline(always)]
+    pub const fn new(data: T) -> Self {
+        Self {
+            raw: RawRwLock::new(),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    /// Consumes this [`RwLock`] and unwraps the underlying data.
+    #[inline(always)]
+    pub fn into_inner(self) -> T {
+        self.data.into_inner()
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    /// Locks this [`RwLock`] with shared read access, blocking the current
+    /// task until it can be acquired.
+    pub fn read(&self) -> RwLockReadGuard<T> {
+        self.raw.read();
+        RwLockReadGuard { lock: self }
+    }
+
+    /// Tries to lock this [`RwLock`] with shared read access, returning a
+    /// guard if successful.
+    pub fn try_read(&self) -> Option<RwLockReadGuard<T>> {
+        self.raw.try_read().then(|| RwLockReadGuard { lock: self })
+    }
+
+    /// Locks this [`RwLock`] with exclusive write access, blocking the current
+    /// task until it can be acquired.
+    pub fn write(&self) -> RwLockWriteGuard<T> {
+        self.raw.write();
+        RwLockWriteGuard { lock: self }
+    }
+
+    /// Tries to lock this [`RwLock`] with exclusive write access, returning a
+    /// guard if successful.
+    pub fn try_write(&self) -> Option<RwLockWriteGuard<T>> {
+        self.raw
+            .try_write()
+            .then(|| RwLockWriteGuard { lock: self })
+    }
+
+    /// Returns `true` if the lock is currently held.
+    #[inline(always)]
+    pub fn is_locked(&self) -> bool {
+        self.raw.is_locked()
+    }
+
+    /// Returns a mutable reference to the underlying data.
+    ///
+    /// Since this call borrows the [`RwLock`] mutably, no actual locking needs
+    /// to take place.
+    #[inline(always)]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: ?Sized + Default> Default for RwLock<T> {
+    #[inline(always)]
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_read() {
+            Some(guard) => write!(f, "RwLock {{ data: ")
+                .and_then(|()| (*guard).fmt(f))
+                .and_then(|()| write!(f, "}}")),
+            None => write!(f, "RwLock {{ <locked> }}"),
+        }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for RwLockWriteGuard<'a, T> {
+    #[inline(always)]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.data.get() }
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for RwLockReadGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized + fmt::Debug> fmt::Debug for RwLockWriteGuard<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe { self.lock.raw.read_unlock() }
+    }
+}
+
+impl<'a, T: ?Sized> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe { self.lock.raw.write_unlock() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::RwLock;
+    use axtask as thread;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Once;
+
+    static INIT: Once = Once::new();
+
+    #[test]
+    fn readers_and_writers() {
+        INIT.call_once(thread::init_scheduler);
+
+        const NUM_TASKS: u32 = 10;
+        const NUM_ITERS: u32 = 1_000;
+        static LOCK: RwLock<(u32, u32)> = RwLock::new((0, 0));
+        static FINISHED: AtomicU32 = AtomicU32::new(0);
+
+        for _ in 0..NUM_TASKS {
+            thread::spawn(|| {
+                for _ in 0..NUM_ITERS {
+                    let mut pair = LOCK.write();
+                    pair.0 += 1;
+                    thread::yield_now();
+                    pair.1 += 1;
+                }
+                FINISHED.fetch_add(1, Ordering::Release);
+            });
+            thread::spawn(|| {
+                for _ in 0..NUM_ITERS {
+                    let pair = LOCK.read();
+                    thread::yield_now();
+                    assert_eq!(pair.0, pair.1);
+                }
+                FINISHED.fetch_add(1, Ordering::Release);
+            });
+        }
+
+        while FINISHED.load(Ordering::Acquire) < NUM_TASKS * 2 {
+            thread::yield_now();
+        }
+        assert_eq!(*LOCK.read(), (NUM_TASKS * NUM_ITERS, NUM_TASKS * NUM_ITERS));
+        println!("RwLock test OK");
+    }
+}
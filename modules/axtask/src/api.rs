@@ -7,11 +7,43 @@ use kernel_guard::NoPreemptIrqSave;
 pub(crate) use crate::run_queue::{current_run_queue, select_run_queue};
 
 #[doc(cfg(feature = "multitask"))]
-pub use crate::task::{CurrentTask, TaskId, TaskInner};
+pub use crate::cancel::{
+    cancel_task, current_cancel_pending, pop_cleanup_handler, push_cleanup_handler,
+    set_current_cancelable, test_cancel, Cancelled, CANCELED_EXIT_CODE,
+};
+#[cfg(feature = "irq")]
+#[doc(cfg(all(feature = "multitask", feature = "irq")))]
+pub use crate::futex::futex_wait_timeout;
+#[doc(cfg(feature = "multitask"))]
+pub use crate::futex::{futex_requeue, futex_wait, futex_wake, FutexError};
+#[cfg(all(feature = "smp", feature = "irq"))]
+#[doc(cfg(all(feature = "multitask", feature = "smp", feature = "irq")))]
+pub use crate::hotplug::{cpu_online, offline_cpu};
+#[doc(cfg(feature = "multitask"))]
+pub use crate::pi::{pi_acquired, pi_block_on, pi_release, pi_supported, pi_unblock};
+#[doc(cfg(feature = "multitask"))]
+pub use crate::rcu::{call_rcu, rcu_barrier, rcu_read_lock, synchronize_rcu, RcuReadGuard};
+#[doc(cfg(feature = "multitask"))]
+pub use crate::registry::{all_tasks, get_task, TaskInfo};
+#[doc(cfg(feature = "multitask"))]
+pub use crate::sched::{DeadlineParams, SchedPolicy, MAX_RT_PRIORITY, MIN_RT_PRIORITY};
+#[cfg(all(feature = "paging", target_arch = "x86_64"))]
+#[doc(cfg(all(feature = "multitask", feature = "paging", target_arch = "x86_64")))]
+pub use crate::stack::check_stack_overflow;
+#[doc(cfg(feature = "multitask"))]
+pub use crate::stats::{busy_cpu_time, TaskStats};
+#[doc(cfg(feature = "multitask"))]
+pub use crate::task::{CurrentTask, TaskId, TaskInner, TaskState};
 #[doc(cfg(feature = "multitask"))]
 pub use crate::task_ext::{TaskExtMut, TaskExtRef};
+#[cfg(feature = "irq")]
+#[doc(cfg(all(feature = "multitask", feature = "irq")))]
+pub use crate::timers::Timer;
 #[doc(cfg(feature = "multitask"))]
-pub use crate::wait_queue::WaitQueue;
+pub use crate::wait_queue::{WaitQueue, WaitUntil};
+#[cfg(feature = "watchdog")]
+#[doc(cfg(feature = "watchdog"))]
+pub use crate::watchdog::{set_watchdog_config, watchdog_config, WatchdogConfig};
 
 /// The reference type of a task.
 pub type AxTaskRef = Arc<AxTask>;
@@ -20,17 +52,20 @@ pub type AxTaskRef = Arc<AxTask>;
 pub type AxCpuMask = cpumask::CpuMask<{ axconfig::SMP }>;
 
 cfg_if::cfg_if! {
-    if #[cfg(feature = "sched_rr")] {
-        const MAX_TIME_SLICE: usize = 5;
-        pub(crate) type AxTask = scheduler::RRTask<TaskInner, MAX_TIME_SLICE>;
-        pub(crate) type Scheduler = scheduler::RRScheduler<TaskInner, MAX_TIME_SLICE>;
+    if #[cfg(feature = "sched_rt")] {
+        pub(crate) type AxTask = scheduler::CFSTask<TaskInner>;
+        pub(crate) type Scheduler = crate::sched_rt::RtCfsScheduler;
+    } else if #[cfg(feature = "sched_rr")] {
+        const MAX_TIME_SLICE: isize = 5;
+        pub(crate) type AxTask = scheduler::FifoTask<TaskInner>;
+        pub(crate) type Scheduler = crate::sched_queue::RRScheduler<MAX_TIME_SLICE>;
     } else if #[cfg(feature = "sched_cfs")] {
         pub(crate) type AxTask = scheduler::CFSTask<TaskInner>;
         pub(crate) type Scheduler = scheduler::CFScheduler<TaskInner>;
     } else {
         // If no scheduler features are set, use FIFO as the default.
         pub(crate) type AxTask = scheduler::FifoTask<TaskInner>;
-        pub(crate) type Scheduler = scheduler::FifoScheduler<TaskInner>;
+        pub(crate) type Scheduler = crate::sched_queue::FifoScheduler;
     }
 }
 
@@ -53,6 +88,38 @@ impl kernel_guard::KernelGuardIf for KernelGuardIfImpl {
     }
 }
 
+#[cfg(feature = "lockdep")]
+struct LockdepIfImpl;
+
+#[cfg(feature = "lockdep")]
+#[crate_interface::impl_interface]
+impl axlockdep::LockdepIf for LockdepIfImpl {
+    fn current_context() -> Option<u64> {
+        let curr = current_may_uninit()?;
+        if axhal::irq::in_irq() {
+            Some(axlockdep::irq_context(axhal::cpu::this_cpu_id()))
+        } else {
+            Some(curr.id().as_u64())
+        }
+    }
+
+    fn irqs_enabled() -> bool {
+        // IRQs are never enabled without the `irq` feature, nothing to check.
+        !cfg!(feature = "irq") || axhal::arch::irqs_enabled()
+    }
+
+    fn capture_backtrace(pcs: &mut [usize]) -> usize {
+        let stack = current_may_uninit()
+            .and_then(|curr| curr.kernel_stack_range())
+            .unwrap_or(0..0);
+        let frames = axhal::backtrace::Backtrace::current(stack);
+        pcs.iter_mut()
+            .zip(frames)
+            .map(|(slot, pc)| *slot = pc)
+            .count()
+    }
+}
+
 /// Gets the current task, or returns [`None`] if the current task is not
 /// initialized.
 pub fn current_may_uninit() -> Option<CurrentTask> {
@@ -75,8 +142,15 @@ pub fn init_scheduler() {
     crate::run_queue::init();
     #[cfg(feature = "irq")]
     crate::timers::init();
+    #[cfg(feature = "watchdog")]
+    crate::watchdog::init();
 
     info!("  use {} scheduler.", Scheduler::scheduler_name());
+    #[cfg(feature = "smp")]
+    info!(
+        "  use {} task placement.",
+        <crate::load_balance::Placement as crate::load_balance::PlacementPolicy>::name()
+    );
 }
 
 /// Initializes the task scheduler for secondary CPUs.
@@ -86,6 +160,17 @@ pub fn init_scheduler_secondary() {
     crate::timers::init();
 }
 
+/// Resumes the task scheduler on a secondary CPU that comes back online after
+/// [`offline_cpu`].
+///
+/// It must be called on the boot stack of the CPU, from which the idle task
+/// continues to run by [`run_idle`].
+#[cfg(all(feature = "smp", feature = "irq"))]
+#[doc(cfg(all(feature = "smp", feature = "irq")))]
+pub fn init_scheduler_online() {
+    crate::run_queue::reinit_secondary();
+}
+
 /// Handles periodic timer ticks for the task manager.
 ///
 /// For example, advance scheduler states, checks timed events, etc.
@@ -93,7 +178,11 @@ pub fn init_scheduler_secondary() {
 #[doc(cfg(feature = "irq"))]
 pub fn on_timer_tick() {
     use kernel_guard::NoOp;
+    #[cfg(feature = "tickless")]
+    crate::timers::tick_restarted();
     crate::timers::check_events();
+    #[cfg(all(feature = "watchdog", feature = "preempt"))]
+    crate::watchdog::check_soft_lockup();
     // Since irq and preemption are both disabled here,
     // we can get current run queue with the default `kernel_guard::NoOp`.
     current_run_queue::<NoOp>().scheduler_timer_tick();
@@ -102,6 +191,13 @@ pub fn on_timer_tick() {
 /// Adds the given task to the run queue, returns the task reference.
 pub fn spawn_task(task: TaskInner) -> AxTaskRef {
     let task_ref = task.into_arc();
+    // Without the real-time classes, priorities are kept by the scheduler,
+    // apply the one set by `TaskInner::set_sched_policy`.
+    #[cfg(not(feature = "sched_rt"))]
+    if task_ref.sched_priority() != 0 {
+        let prio = task_ref.sched_priority();
+        crate::run_queue::set_task_sched_params(&task_ref, SchedPolicy::Normal, prio);
+    }
     select_run_queue::<NoPreemptIrqSave>(&task_ref).add_task(task_ref.clone());
     task_ref
 }
@@ -133,7 +229,8 @@ where
 ///
 /// The range of the priority is dependent on the underlying scheduler. For
 /// example, in the [CFS] scheduler, the priority is the nice value, ranging from
-/// -20 to 19.
+/// -20 to 19. If the current task has a real-time policy, the priority ranges
+/// from [`MIN_RT_PRIORITY`] to [`MAX_RT_PRIORITY`].
 ///
 /// Returns `true` if the priority is set successfully.
 ///
@@ -142,13 +239,70 @@ pub fn set_priority(prio: isize) -> bool {
     current_run_queue::<NoPreemptIrqSave>().set_current_priority(prio)
 }
 
+/// Set the scheduling policy and priority for current task.
+///
+/// Real-time policies are only supported with the `sched_rt` feature. For
+/// [`SchedPolicy::Normal`], it's the same as [`set_priority`].
+///
+/// Returns `true` if the policy and priority are set successfully.
+pub fn set_current_sched_policy(policy: SchedPolicy, prio: isize) -> bool {
+    current_run_queue::<NoPreemptIrqSave>().set_current_sched_policy(policy, prio)
+}
+
+/// Set the scheduling policy and priority for the given task, which may be the
+/// current task, or any other spawned task.
+///
+/// It takes effect at once: a task ready in a run queue is re-queued with the
+/// new parameters, and may preempt the current task.
+///
+/// Returns `true` if the policy and priority are set successfully, see
+/// [`set_current_sched_policy`].
+pub fn set_task_sched_policy(task: &AxTaskRef, policy: SchedPolicy, prio: isize) -> bool {
+    if task.id() == crate::current().id() {
+        return set_current_sched_policy(policy, prio);
+    }
+    if !policy.is_supported() || !policy.is_valid_priority(prio) {
+        return false;
+    }
+    if let Some(ok) = crate::pi::set_boosted_base(task, Some(policy), prio) {
+        return ok;
+    }
+    crate::run_queue::set_task_sched_params(task, policy, prio)
+}
+
+/// Set the [`SchedPolicy::Deadline`] policy for current task, with the given
+/// runtime, deadline and period.
+///
+/// Deadline tasks are only supported with the `sched_edf` feature. The task is
+/// bound to the current CPU, and it's rejected if the deadline tasks on the
+/// current CPU can not be scheduled (i.e., their total bandwidth exceeds 1).
+///
+/// Returns `true` if the task is admitted.
+pub fn set_current_deadline(params: DeadlineParams) -> bool {
+    current_run_queue::<NoPreemptIrqSave>().set_current_deadline(params)
+}
+
+/// Current task completes its job of the current period, and sleeps until
+/// the next period.
+///
+/// It returns immediately if the current task is not a deadline task.
+pub fn wait_next_period() {
+    #[cfg(feature = "sched_edf")]
+    current_run_queue::<NoPreemptIrqSave>().wait_next_period();
+}
+
 /// Set the affinity for the current task.
 /// [`AxCpuMask`] is used to specify the CPU affinity.
-/// Returns `true` if the affinity is set successfully.
+/// Returns `true` if the affinity is set successfully, or `false` if no CPU
+/// in the mask is online, or the current task is a deadline task, which is
+/// bound to the CPU where its bandwidth is reserved.
 ///
 /// TODO: support set the affinity for other tasks.
 pub fn set_current_affinity(cpumask: AxCpuMask) -> bool {
-    if cpumask.is_empty() {
+    if cpumask.is_empty() || current().sched_policy().is_deadline() {
+        false
+    } else if !any_cpu_online(cpumask) {
+        warn!("set_current_affinity: no online CPU in {:?}", cpumask);
         false
     } else {
         let mut rq = current_run_queue::<NoPreemptIrqSave>();
@@ -162,6 +316,18 @@ pub fn set_current_affinity(cpumask: AxCpuMask) -> bool {
     }
 }
 
+fn any_cpu_online(cpumask: AxCpuMask) -> bool {
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    {
+        (0..axconfig::SMP).any(|cpu_id| cpumask.get(cpu_id) && crate::hotplug::cpu_online(cpu_id))
+    }
+    #[cfg(not(all(feature = "smp", feature = "irq")))]
+    {
+        let _ = cpumask;
+        true
+    }
+}
+
 /// Current task gives up the CPU time voluntarily, and switches to another
 /// ready task.
 pub fn yield_now() {
@@ -185,19 +351,68 @@ pub fn sleep_until(deadline: axhal::time::TimeValue) {
     axhal::time::busy_wait_until(deadline);
 }
 
+/// Like [`sleep`], but it's a cancellation point: returns [`Cancelled`] as
+/// soon as the current task is cancelled by [`cancel_task`].
+pub fn sleep_cancellable(dur: core::time::Duration) -> Result<(), Cancelled> {
+    sleep_until_cancellable(axhal::time::wall_time() + dur)
+}
+
+/// Like [`sleep_until`], but it's a cancellation point: returns [`Cancelled`]
+/// as soon as the current task is cancelled by [`cancel_task`].
+///
+/// If the feature `irq` is not enabled, the cancellation is only checked
+/// before and after the busy-wait.
+pub fn sleep_until_cancellable(deadline: axhal::time::TimeValue) -> Result<(), Cancelled> {
+    #[cfg(feature = "irq")]
+    {
+        current_run_queue::<NoPreemptIrqSave>().sleep_until_cancellable(deadline)
+    }
+    #[cfg(not(feature = "irq"))]
+    {
+        if !current_cancel_pending() {
+            axhal::time::busy_wait_until(deadline);
+        }
+        if current_cancel_pending() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+}
+
 /// Exits the current task.
+///
+/// The cleanup handlers registered by [`push_cleanup_handler`] are called
+/// before exiting.
 pub fn exit(exit_code: i32) -> ! {
+    crate::cancel::run_cleanup_handlers();
     current_run_queue::<NoPreemptIrqSave>().exit_current(exit_code)
 }
 
 /// The idle task routine.
 ///
-/// It runs an infinite loop that keeps calling [`yield_now()`].
+/// It runs an infinite loop that keeps calling [`yield_now()`], and waits for
+/// IRQs in between if there are no ready tasks. With the `tickless` feature,
+/// the periodic tick is stopped before waiting. With the `smp` and `irq`
+/// features, the CPU is parked here when it's taken offline by
+/// [`offline_cpu`].
 pub fn run_idle() -> ! {
     loop {
         yield_now();
-        debug!("idle task: waiting for IRQs...");
+        #[cfg(all(feature = "smp", feature = "irq"))]
+        crate::hotplug::park_if_going_offline();
         #[cfg(feature = "irq")]
-        axhal::arch::wait_for_irqs();
+        {
+            // Check for ready tasks and wait with IRQs disabled in between, so
+            // that a task woken up by an IRQ doesn't wait until the next one.
+            // The idle task can't be preempted then, `NoOp` is enough.
+            axhal::arch::disable_irqs();
+            if current_run_queue::<kernel_guard::NoOp>().idle_can_wait() {
+                debug!("idle task: waiting for IRQs...");
+                axhal::arch::enable_irqs_and_wait();
+            } else {
+                axhal::arch::enable_irqs();
+            }
+        }
     }
 }
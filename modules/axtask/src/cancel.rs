@@ -0,0 +1,175 @@
+//! Task cancellation.
+//!
+//! A task can be requested to terminate by other tasks with [`cancel_task`].
+//! The request is deferred: it only takes effect when the target task reaches
+//! a cancellation point, i.e., calls [`test_cancel`], or blocks in one of the
+//! cancellable waits such as [`WaitQueue::wait_cancellable`] and
+//! [`sleep_cancellable`], which return [`Cancelled`] once the request arrives.
+//! Ordinary waits (e.g., for locks) are not interrupted, though they may see
+//! a spurious wakeup in a rare race (see [`cancel_task`]).
+//!
+//! Before a task exits, the cleanup handlers it registered with
+//! [`push_cleanup_handler`] are called in the reverse order.
+//!
+//! [`WaitQueue::wait_cancellable`]: crate::WaitQueue::wait_cancellable
+//! [`sleep_cancellable`]: crate::sleep_cancellable
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{fence, AtomicBool, Ordering};
+
+use axlockdep::spin::SpinNoIrq;
+use kernel_guard::NoPreemptIrqSave;
+
+use crate::task::TaskState;
+use crate::{current, select_run_queue, AxTaskRef};
+
+/// The exit code of tasks terminated by cancellation, as returned by
+/// [`TaskInner::join`](crate::TaskInner::join).
+pub const CANCELED_EXIT_CODE: i32 = i32::MIN;
+
+/// The error returned by cancellable waits if the current task has been
+/// cancelled.
+///
+/// The caller is expected to clean up and exit, e.g., by [`test_cancel`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Cancelled;
+
+type CleanupHandler = Box<dyn FnOnce() + Send>;
+
+/// Cancellation states of a task.
+pub(crate) struct CancelState {
+    /// A cancellation has been requested.
+    pending: AtomicBool,
+    /// Cancellation requests are held pending rather than acted upon.
+    disabled: AtomicBool,
+    /// The task is blocked (or going to block) in a cancellable wait.
+    in_wait: AtomicBool,
+    /// Cleanup handlers pushed by the task itself.
+    cleanup_handlers: SpinNoIrq<Vec<CleanupHandler>>,
+}
+
+impl CancelState {
+    pub const fn new() -> Self {
+        Self {
+            pending: AtomicBool::new(false),
+            disabled: AtomicBool::new(false),
+            in_wait: AtomicBool::new(false),
+            cleanup_handlers: SpinNoIrq::new(Vec::new()),
+        }
+    }
+
+    /// Whether a cancellation has been requested and is not disabled.
+    pub fn is_pending(&self) -> bool {
+        self.pending.load(Ordering::SeqCst) && !self.disabled.load(Ordering::SeqCst)
+    }
+
+    /// Marks the task is entering or leaving a cancellable wait.
+    ///
+    /// When entering, it must be called after the task is marked as
+    /// `Blocked`, and [`CancelState::is_pending`] must be checked afterwards.
+    pub fn set_in_wait(&self, in_wait: bool) {
+        self.in_wait.store(in_wait, Ordering::SeqCst);
+        // Pairs with the fence in `cancel_task()`: either the waiter sees the
+        // request, or the canceller sees the waiter blocked and wakes it up.
+        fence(Ordering::SeqCst);
+    }
+
+    fn take_cleanup_handler(&self) -> Option<CleanupHandler> {
+        self.cleanup_handlers.lock().pop()
+    }
+}
+
+/// Requests the cancellation of the given task.
+///
+/// The request is only recorded: the task acts upon it at its next
+/// cancellation point where cancellation is enabled. If the task is blocked in
+/// a cancellable wait with cancellation enabled, it is also woken up, and the
+/// wait returns [`Cancelled`]. Tasks blocked in other waits are not woken up,
+/// and re-enabling cancellation does not wake up the task either.
+///
+/// If the task leaves its cancellable wait on its own while being woken up
+/// here, the wakeup may hit the next wait it blocks in, which must tolerate a
+/// spurious wakeup.
+///
+/// Returns `false` if the task has already exited. A task that is exiting
+/// concurrently may still return `true`, and the request has no effect.
+pub fn cancel_task(task: &AxTaskRef) -> bool {
+    if task.state() == TaskState::Exited {
+        return false;
+    }
+    let cancel = task.cancel_state();
+    cancel.pending.store(true, Ordering::SeqCst);
+    fence(Ordering::SeqCst);
+    if cancel.in_wait.load(Ordering::SeqCst) && cancel.is_pending() {
+        debug!("task cancel: wake up {}", task.id_name());
+        // It may cause a spurious wakeup if the task has just left the
+        // cancellable wait and blocked again, which all waits tolerate.
+        select_run_queue::<NoPreemptIrqSave>(task).unblock_task(task.clone(), true);
+    }
+    true
+}
+
+/// Enables or disables cancellation for the current task.
+///
+/// Requests arrived while cancellation is disabled are held pending until it's
+/// enabled again. Returns whether cancellation was enabled before.
+pub fn set_current_cancelable(enabled: bool) -> bool {
+    !current()
+        .cancel_state()
+        .disabled
+        .swap(!enabled, Ordering::SeqCst)
+}
+
+/// Whether a cancellation of the current task has been requested and can be
+/// acted upon.
+pub fn current_cancel_pending() -> bool {
+    current().cancel_state().is_pending()
+}
+
+/// A cancellation point: exits the current task with [`CANCELED_EXIT_CODE`]
+/// if its cancellation has been requested and is enabled.
+pub fn test_cancel() {
+    if current_cancel_pending() {
+        debug!("task cancelled: {}", current().id_name());
+        crate::exit(CANCELED_EXIT_CODE);
+    }
+}
+
+/// Registers a cleanup handler for the current task, which is called when the
+/// task exits, unless it's removed by [`pop_cleanup_handler`] before.
+pub fn push_cleanup_handler<F>(f: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    current()
+        .cancel_state()
+        .cleanup_handlers
+        .lock()
+        .push(Box::new(f));
+}
+
+/// Removes the most recently registered cleanup handler of the current task,
+/// and calls it if `execute` is true.
+///
+/// Returns `false` if there are no cleanup handlers.
+pub fn pop_cleanup_handler(execute: bool) -> bool {
+    match current().cancel_state().take_cleanup_handler() {
+        Some(f) => {
+            if execute {
+                f();
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// Calls all cleanup handlers of the current task in the reverse order of
+/// registration. Handlers pushed during the process are called as well.
+pub(crate) fn run_cleanup_handlers() {
+    let curr = current();
+    while let Some(f) = curr.cancel_state().take_cleanup_handler() {
+        f();
+    }
+}
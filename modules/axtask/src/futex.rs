@@ -0,0 +1,198 @@
+//! Futexes: wait queues keyed by the addresses of atomic integers.
+//!
+//! A futex does not need any kernel object: tasks waiting on different
+//! addresses share a fixed number of hashed buckets, and each waiting task
+//! records the address it's waiting on. So synchronization primitives can be
+//! built on plain integers, with the fast path entirely in atomic operations.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use axhal::time::TimeValue;
+use kernel_guard::NoPreemptIrqSave;
+
+use crate::wait_queue::{unblock_one_task, WaitQueueGuard};
+use crate::{current_run_queue, CurrentTask, WaitQueue};
+
+const NUM_BUCKETS_SHIFT: u32 = 6;
+const NUM_BUCKETS: usize = 1 << NUM_BUCKETS_SHIFT;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_BUCKET: WaitQueue = WaitQueue::new();
+static BUCKETS: [WaitQueue; NUM_BUCKETS] = [EMPTY_BUCKET; NUM_BUCKETS];
+
+/// The error returned by futex operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FutexError {
+    /// The value of the futex is not the expected one.
+    WouldBlock,
+    /// The timeout elapsed before the task was woken up.
+    TimedOut,
+}
+
+fn key_of(futex: &AtomicU32) -> usize {
+    futex as *const AtomicU32 as usize
+}
+
+fn bucket(key: usize) -> &'static WaitQueue {
+    // Fibonacci hashing.
+    let hash = (key as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> (u64::BITS - NUM_BUCKETS_SHIFT);
+    &BUCKETS[hash as usize]
+}
+
+/// Wakes up at most `count` tasks waiting on `key` in the bucket.
+fn wake_locked(wq: &mut WaitQueueGuard, key: usize, count: usize) -> usize {
+    let mut woken = 0;
+    let mut i = 0;
+    while woken < count && i < wq.len() {
+        if wq[i].as_task().is_some_and(|t| t.futex_key() == key) {
+            let task = wq.remove(i).unwrap().as_task().unwrap().clone();
+            unblock_one_task(task, true);
+            woken += 1;
+        } else {
+            i += 1;
+        }
+    }
+    woken
+}
+
+/// Blocks the current task on the futex if its value is `expected`, until
+/// it's woken up by [`futex_wake`] or [`futex_requeue`], or the optional
+/// deadline is reached.
+fn wait(
+    futex: &AtomicU32,
+    expected: u32,
+    #[cfg_attr(not(feature = "irq"), allow(unused_variables))] deadline: Option<TimeValue>,
+) -> Result<(), FutexError> {
+    let curr = crate::current();
+    let key = key_of(futex);
+    {
+        let mut rq = current_run_queue::<NoPreemptIrqSave>();
+        let wq = bucket(key).lock();
+        // Checked with the bucket locked, so that a wakeup after changing the
+        // value is not missed.
+        if futex.load(Ordering::SeqCst) != expected {
+            return Err(FutexError::WouldBlock);
+        }
+        curr.set_futex_key(key);
+        #[cfg(feature = "irq")]
+        if let Some(deadline) = deadline {
+            crate::timers::set_alarm_wakeup(deadline, curr.clone());
+        }
+        rq.blocked_resched(wq);
+    }
+    let woken = finish_wait(&curr);
+    // Always try to remove the task from the timer list.
+    #[cfg(feature = "irq")]
+    if deadline.is_some() {
+        curr.timer_ticket_expired();
+    }
+    if woken {
+        Ok(())
+    } else {
+        Err(FutexError::TimedOut)
+    }
+}
+
+/// Removes the current task from its bucket if it's not woken up by a futex
+/// wakeup (i.e., timed out), returns whether it's woken up.
+fn finish_wait(curr: &CurrentTask) -> bool {
+    loop {
+        // The task may be requeued to another bucket concurrently, check the
+        // key again with the bucket locked.
+        let key = curr.futex_key();
+        let mut wq = bucket(key).lock();
+        if curr.futex_key() != key {
+            continue;
+        }
+        curr.set_futex_key(0);
+        if curr.in_wait_queue() {
+            wq.retain(|w| !w.is_task(curr.as_task_ref()));
+            curr.set_in_wait_queue(false);
+            return false;
+        }
+        return true;
+    }
+}
+
+/// Blocks the current task if the value of `futex` is `expected`, until it's
+/// woken up by [`futex_wake`] on the same futex.
+///
+/// Returns [`FutexError::WouldBlock`] immediately if the value is not
+/// `expected`. The comparison and the blocking are atomic with respect to
+/// [`futex_wake`], so the wakeup by another task after it changes the value
+/// is never missed.
+pub fn futex_wait(futex: &AtomicU32, expected: u32) -> Result<(), FutexError> {
+    wait(futex, expected, None)
+}
+
+/// Like [`futex_wait`], but returns [`FutexError::TimedOut`] if the task is
+/// not woken up in the given duration.
+#[cfg(feature = "irq")]
+pub fn futex_wait_timeout(
+    futex: &AtomicU32,
+    expected: u32,
+    dur: core::time::Duration,
+) -> Result<(), FutexError> {
+    wait(futex, expected, Some(axhal::time::wall_time() + dur))
+}
+
+/// Wakes up at most `count` tasks waiting on `futex`, returns the number of
+/// tasks woken up.
+pub fn futex_wake(futex: &AtomicU32, count: usize) -> usize {
+    let key = key_of(futex);
+    wake_locked(&mut bucket(key).lock(), key, count)
+}
+
+/// Wakes up at most `wake_count` tasks waiting on `futex`, and moves at most
+/// `requeue_count` of the remaining waiters to wait on `target` instead.
+///
+/// If `expected` is given, the value of `futex` is checked first, and
+/// [`FutexError::WouldBlock`] is returned if it's different. Returns the
+/// number of tasks woken up and requeued.
+///
+/// It avoids the thundering herd when a condition variable is broadcasted:
+/// only one waiter is woken up, and the others are moved to wait on the
+/// mutex.
+pub fn futex_requeue(
+    futex: &AtomicU32,
+    expected: Option<u32>,
+    wake_count: usize,
+    target: &AtomicU32,
+    requeue_count: usize,
+) -> Result<usize, FutexError> {
+    let (key, target_key) = (key_of(futex), key_of(target));
+    let (src, dst) = (bucket(key), bucket(target_key));
+    let same_bucket = core::ptr::eq(src, dst);
+
+    // Lock the two buckets in the order of their addresses.
+    let (mut src_wq, mut dst_wq) = if same_bucket {
+        (src.lock(), None)
+    } else if (src as *const WaitQueue) < (dst as *const WaitQueue) {
+        let src_wq = src.lock();
+        (src_wq, Some(dst.lock()))
+    } else {
+        let dst_wq = dst.lock();
+        (src.lock(), Some(dst_wq))
+    };
+    if expected.is_some_and(|v| futex.load(Ordering::SeqCst) != v) {
+        return Err(FutexError::WouldBlock);
+    }
+
+    let woken = wake_locked(&mut src_wq, key, wake_count);
+    let mut requeued = 0;
+    let mut i = 0;
+    while requeued < requeue_count && i < src_wq.len() {
+        let Some(task) = src_wq[i].as_task().filter(|t| t.futex_key() == key) else {
+            i += 1;
+            continue;
+        };
+        task.set_futex_key(target_key);
+        if let Some(dst_wq) = dst_wq.as_mut() {
+            dst_wq.push_back(src_wq.remove(i).unwrap());
+        } else {
+            i += 1;
+        }
+        requeued += 1;
+    }
+    Ok(woken + requeued)
+}
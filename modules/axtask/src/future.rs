@@ -0,0 +1,379 @@
+//! Asynchronous tasks and the executor to run them.
+//!
+//! Futures spawned by [`spawn`] are polled by a pool of worker tasks, one per
+//! CPU, which are created when the first future is spawned. A worker polls
+//! ready futures one after another, and blocks when there are none, so a large
+//! number of futures can share a few kernel stacks. [`block_on`] runs a future
+//! on the current task instead.
+//!
+//! A pending future is polled again after its [`Waker`] is woken up. Wakers
+//! can be registered in a [`WaitQueue`] by [`WaitQueue::register_waker`] (or
+//! [`WaitQueue::wait_until_async`]), in the timer list by [`sleep`], or passed
+//! to any other source of events.
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::cell::UnsafeCell;
+use core::future::Future;
+use core::pin::{pin, Pin};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
+
+use axhal::time::{wall_time, TimeValue};
+use axlockdep::spin::SpinNoIrq;
+
+use crate::WaitQueue;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send + 'static>>;
+
+// States of an asynchronous task.
+/// Waiting to be woken up.
+const STATE_IDLE: u8 = 0;
+/// In the ready queue of the executor.
+const STATE_SCHEDULED: u8 = 1;
+/// Being polled by a worker.
+const STATE_RUNNING: u8 = 2;
+/// Woken up while being polled, needs to be polled again.
+const STATE_NOTIFIED: u8 = 3;
+/// The future has completed.
+const STATE_DONE: u8 = 4;
+
+struct AsyncTask {
+    state: AtomicU8,
+    /// Only accessed by the worker that changed the state to `RUNNING`.
+    future: UnsafeCell<Option<BoxFuture>>,
+}
+
+unsafe impl Sync for AsyncTask {}
+
+impl AsyncTask {
+    /// Polls the future once, and puts the task back into the ready queue if
+    /// it's woken up meanwhile.
+    fn run(self: Arc<Self>) {
+        self.state.store(STATE_RUNNING, Ordering::Release);
+        let waker = Waker::from(self.clone());
+        let mut cx = Context::from_waker(&waker);
+        // Safety: we are the only one that accesses the future.
+        let future = unsafe { &mut *self.future.get() };
+        let Some(fut) = future.as_mut() else {
+            return;
+        };
+        if fut.as_mut().poll(&mut cx).is_ready() {
+            *future = None;
+            self.state.store(STATE_DONE, Ordering::Release);
+            return;
+        }
+        if self
+            .state
+            .compare_exchange(
+                STATE_RUNNING,
+                STATE_IDLE,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_err()
+        {
+            // Woken up while being polled.
+            self.state.store(STATE_SCHEDULED, Ordering::Release);
+            EXECUTOR.push(self);
+        }
+    }
+}
+
+impl Wake for AsyncTask {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let mut state = self.state.load(Ordering::Acquire);
+        loop {
+            let new_state = match state {
+                STATE_IDLE => STATE_SCHEDULED,
+                STATE_RUNNING => STATE_NOTIFIED,
+                // Already scheduled or done.
+                _ => return,
+            };
+            match self.state.compare_exchange_weak(
+                state,
+                new_state,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    if new_state == STATE_SCHEDULED {
+                        EXECUTOR.push(self.clone());
+                    }
+                    return;
+                }
+                Err(s) => state = s,
+            }
+        }
+    }
+}
+
+struct Executor {
+    ready: SpinNoIrq<VecDeque<Arc<AsyncTask>>>,
+    /// Idle workers wait here.
+    wq: WaitQueue,
+    workers_started: AtomicBool,
+}
+
+static EXECUTOR: Executor = Executor {
+    ready: SpinNoIrq::new(VecDeque::new()),
+    wq: WaitQueue::new(),
+    workers_started: AtomicBool::new(false),
+};
+
+impl Executor {
+    fn push(&self, task: Arc<AsyncTask>) {
+        self.ready.lock().push_back(task);
+        self.wq.notify_one(false);
+    }
+
+    fn pop(&self) -> Arc<AsyncTask> {
+        loop {
+            if let Some(task) = self.ready.lock().pop_front() {
+                return task;
+            }
+            self.wq.wait_until(|| !self.ready.lock().is_empty());
+        }
+    }
+
+    fn start_workers(&self) {
+        if self.workers_started.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        for i in 0..axconfig::SMP {
+            let _worker = crate::spawn_raw(
+                worker_main,
+                format!("async-worker{}", i),
+                axconfig::TASK_STACK_SIZE,
+            );
+            // Workers wait for ready futures indefinitely.
+            #[cfg(feature = "watchdog")]
+            _worker.set_hung_task_check(false);
+        }
+    }
+}
+
+fn worker_main() {
+    loop {
+        EXECUTOR.pop().run();
+    }
+}
+
+struct JoinState<T> {
+    output: SpinNoIrq<Option<T>>,
+    done: AtomicBool,
+    /// Joiners wait here.
+    wq: WaitQueue,
+}
+
+/// A handle to join an asynchronous task spawned by [`spawn`].
+///
+/// It's a future that resolves to the output of the task. The task keeps
+/// running if the handle is dropped.
+pub struct JoinHandle<T> {
+    state: Arc<JoinState<T>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Whether the task has finished.
+    pub fn is_finished(&self) -> bool {
+        self.state.done.load(Ordering::Acquire)
+    }
+
+    /// Blocks the current task until the asynchronous task finishes, and
+    /// returns its output.
+    pub fn join(self) -> T {
+        self.state.wq.wait_until(|| self.is_finished());
+        self.take_output()
+    }
+
+    fn take_output(&self) -> T {
+        self.state
+            .output
+            .lock()
+            .take()
+            .expect("`JoinHandle` polled after completion")
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        if !self.is_finished() {
+            self.state.wq.register_waker(cx.waker());
+            // It may have finished before the waker was registered.
+            if !self.is_finished() {
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(self.take_output())
+    }
+}
+
+/// Spawns an asynchronous task to run the given future on the executor.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let state = Arc::new(JoinState {
+        output: SpinNoIrq::new(None),
+        done: AtomicBool::new(false),
+        wq: WaitQueue::new(),
+    });
+    let join_state = state.clone();
+    let task = Arc::new(AsyncTask {
+        state: AtomicU8::new(STATE_SCHEDULED),
+        future: UnsafeCell::new(Some(Box::pin(async move {
+            let output = future.await;
+            *join_state.output.lock() = Some(output);
+            join_state.done.store(true, Ordering::Release);
+            join_state.wq.notify_all(false);
+        }))),
+    });
+    EXECUTOR.start_workers();
+    EXECUTOR.push(task);
+    JoinHandle { state }
+}
+
+struct BlockOnWaker {
+    notified: AtomicBool,
+    wq: WaitQueue,
+}
+
+impl Wake for BlockOnWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.notified.store(true, Ordering::Release);
+        self.wq.notify_one(true);
+    }
+}
+
+/// Runs the given future on the current task, blocks it until the future
+/// completes, and returns the output.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let inner = Arc::new(BlockOnWaker {
+        notified: AtomicBool::new(false),
+        wq: WaitQueue::new(),
+    });
+    let waker = Waker::from(inner.clone());
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        inner
+            .wq
+            .wait_until(|| inner.notified.swap(false, Ordering::AcqRel));
+    }
+}
+
+/// A future that completes at a deadline, returned by [`sleep`] and
+/// [`sleep_until`].
+///
+/// If the feature `irq` is not enabled, it's polled repeatedly until the
+/// deadline.
+pub struct Sleep {
+    deadline: TimeValue,
+    /// The waker set in the timer list.
+    #[cfg(feature = "irq")]
+    waker: Option<Waker>,
+}
+
+impl Sleep {
+    /// Returns the deadline (in wall time).
+    pub fn deadline(&self) -> TimeValue {
+        self.deadline
+    }
+
+    /// Whether the deadline has been reached.
+    pub fn is_elapsed(&self) -> bool {
+        wall_time() >= self.deadline
+    }
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        if this.is_elapsed() {
+            return Poll::Ready(());
+        }
+        // Set a new timer event if it's polled by another task.
+        #[cfg(feature = "irq")]
+        if !this
+            .waker
+            .as_ref()
+            .is_some_and(|waker| waker.will_wake(cx.waker()))
+        {
+            crate::timers::set_alarm_waker(this.deadline, cx.waker().clone());
+            this.waker = Some(cx.waker().clone());
+        }
+        #[cfg(not(feature = "irq"))]
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Returns a future that completes after the given duration.
+pub fn sleep(dur: Duration) -> Sleep {
+    sleep_until(wall_time() + dur)
+}
+
+/// Returns a future that completes at the given deadline (in wall time).
+pub fn sleep_until(deadline: TimeValue) -> Sleep {
+    Sleep {
+        deadline,
+        #[cfg(feature = "irq")]
+        waker: None,
+    }
+}
+
+/// The error returned by [`Timeout`] if the deadline has been reached before
+/// the future completes.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Elapsed;
+
+/// A future that runs another future with a time limit, returned by
+/// [`timeout`].
+pub struct Timeout<F> {
+    future: F,
+    sleep: Sleep,
+}
+
+impl<F: Future> Future for Timeout<F> {
+    type Output = Result<F::Output, Elapsed>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // Safety: `future` is never moved, and `Sleep` is `Unpin`.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+        if let Poll::Ready(output) = future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        Pin::new(&mut this.sleep).poll(cx).map(|_| Err(Elapsed))
+    }
+}
+
+/// Runs the given future until it completes, or returns [`Elapsed`] after the
+/// given duration.
+pub fn timeout<F: Future>(dur: Duration, future: F) -> Timeout<F> {
+    Timeout {
+        future,
+        sleep: sleep(dur),
+    }
+}
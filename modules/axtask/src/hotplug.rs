@@ -0,0 +1,169 @@
+//! CPU hotplug: taking secondary CPUs offline and back online at runtime.
+//!
+//! [`offline_cpu`] stops placing tasks on the CPU and kicks it. Once the CPU
+//! switches to its idle task, the idle task migrates all ready tasks, timer
+//! events and exited tasks to other CPUs, and parks the CPU by
+//! [`axhal::mp::stop_this_cpu`]. Tasks are only migrated to CPUs allowed by
+//! their affinity, so a CPU can't be taken offline if some task can run on no
+//! other online CPU.
+//!
+//! An offline CPU is restarted by the runtime with
+//! [`axhal::mp::start_secondary_cpu`], which then calls
+//! [`init_scheduler_online`](crate::init_scheduler_online) on it.
+//!
+//! The primary CPU can't be taken offline.
+
+use core::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axconfig::SMP;
+use axhal::cpu::this_cpu_id;
+
+use crate::{AxCpuMask, AxTaskRef};
+
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum CpuState {
+    /// Not booted yet, or parked.
+    Offline = 0,
+    /// Running tasks.
+    Online = 1,
+    /// Migrating tasks away before being parked.
+    GoingOffline = 2,
+}
+
+static CPU_STATES: [AtomicU8; SMP] = [STATE_REPEAT_VALUE; SMP];
+#[allow(clippy::declare_interior_mutable_const)]
+const STATE_REPEAT_VALUE: AtomicU8 = AtomicU8::new(CpuState::Offline as u8);
+
+static PRIMARY_CPU_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Serializes hotplug operations, so that the affinity checks of concurrent
+/// operations don't rely on each other's CPU.
+static HOTPLUG_BUSY: AtomicBool = AtomicBool::new(false);
+
+fn cpu_state(cpu_id: usize) -> CpuState {
+    match CPU_STATES[cpu_id].load(Ordering::Acquire) {
+        0 => CpuState::Offline,
+        1 => CpuState::Online,
+        _ => CpuState::GoingOffline,
+    }
+}
+
+fn set_cpu_state(cpu_id: usize, state: CpuState) {
+    CPU_STATES[cpu_id].store(state as u8, Ordering::Release);
+}
+
+/// Whether the given CPU is online, i.e., tasks can be placed on it.
+pub fn cpu_online(cpu_id: usize) -> bool {
+    cpu_id < SMP && cpu_state(cpu_id) == CpuState::Online
+}
+
+/// Whether the given CPU is migrating tasks away to go offline.
+#[inline]
+pub(crate) fn going_offline(cpu_id: usize) -> bool {
+    cpu_state(cpu_id) == CpuState::GoingOffline
+}
+
+/// Marks the CPU as online after its scheduler is initialized.
+pub(crate) fn set_online(cpu_id: usize, is_primary: bool) {
+    if is_primary {
+        PRIMARY_CPU_ID.store(cpu_id, Ordering::Relaxed);
+    }
+    set_cpu_state(cpu_id, CpuState::Online);
+}
+
+/// Whether any CPU in `cpumask` other than `except` is online.
+fn has_other_online_cpu(cpumask: AxCpuMask, except: usize) -> bool {
+    (0..SMP).any(|cpu_id| cpu_id != except && cpumask.get(cpu_id) && cpu_online(cpu_id))
+}
+
+/// Finds a live task that can run on `cpu_id` but no other online CPU.
+fn find_pinned_task(cpu_id: usize) -> Option<AxTaskRef> {
+    crate::all_tasks().into_iter().find(|task| {
+        let cpumask = task.cpumask();
+        // The idle and GC tasks of each CPU stay on it.
+        !task.is_idle()
+            && !crate::run_queue::is_gc_task(task, cpu_id)
+            && task.state() != crate::TaskState::Exited
+            && cpumask.get(cpu_id)
+            && !has_other_online_cpu(cpumask, cpu_id)
+    })
+}
+
+/// Takes the given secondary CPU offline.
+///
+/// It returns after all tasks and timer events have been migrated away and the
+/// CPU is parked. Without the `preempt` feature, the task running on the CPU
+/// has to yield or block first.
+///
+/// Returns `false` if the CPU is the primary CPU or not online, or some task
+/// can't run on any other online CPU according to its affinity.
+pub fn offline_cpu(cpu_id: usize) -> bool {
+    if !cpu_online(cpu_id) || cpu_id == PRIMARY_CPU_ID.load(Ordering::Relaxed) {
+        return false;
+    }
+    while HOTPLUG_BUSY.swap(true, Ordering::Acquire) {
+        crate::yield_now();
+    }
+    let res = do_offline_cpu(cpu_id);
+    HOTPLUG_BUSY.store(false, Ordering::Release);
+    res
+}
+
+fn do_offline_cpu(cpu_id: usize) -> bool {
+    if CPU_STATES[cpu_id]
+        .compare_exchange(
+            CpuState::Online as u8,
+            CpuState::GoingOffline as u8,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        )
+        .is_err()
+    {
+        return false;
+    }
+    // No task can be pinned to this CPU since now, check the existing ones.
+    if let Some(task) = find_pinned_task(cpu_id) {
+        warn!(
+            "CPU {} can't go offline: task {} can't run on other CPUs",
+            cpu_id,
+            task.id_name()
+        );
+        set_cpu_state(cpu_id, CpuState::Online);
+        return false;
+    }
+
+    info!("CPU {} is going offline...", cpu_id);
+    crate::run_queue::stop_run_queue(cpu_id);
+    while cpu_state(cpu_id) != CpuState::Offline {
+        // The kick may arrive before the idle task checks for going offline
+        // and waits for IRQs, kick it again.
+        axhal::ipi::send_ipi(cpu_id);
+        crate::sleep(Duration::from_millis(1));
+    }
+    info!("CPU {} is offline", cpu_id);
+    true
+}
+
+/// Migrates everything away from the current CPU and parks it, if it's going
+/// offline.
+///
+/// It's called by the idle task with IRQs enabled.
+pub(crate) fn park_if_going_offline() {
+    let cpu_id = this_cpu_id();
+    if !going_offline(cpu_id) {
+        return;
+    }
+    // Not by a guard, there is no way back.
+    axhal::arch::disable_irqs();
+    debug!("CPU {} parking...", cpu_id);
+    let target = (0..SMP)
+        .find(|&id| id != cpu_id && cpu_online(id))
+        .unwrap_or(PRIMARY_CPU_ID.load(Ordering::Relaxed));
+    crate::run_queue::migrate_tasks_away(target);
+    crate::timers::migrate_events(target);
+    axhal::ipi::deinit_percpu();
+    set_cpu_state(cpu_id, CpuState::Offline);
+    axhal::mp::stop_this_cpu();
+}
@@ -4,6 +4,11 @@
 //! creation, scheduling, sleeping, termination, etc. The scheduler algorithm
 //! is configurable by cargo features.
 //!
+//! With the `multitask` feature, asynchronous tasks (futures) can also be run
+//! by the executor in the [`future`] module, and woken up by wait queues and
+//! timers. The grace periods of read-copy-update (RCU) are also detected from the
+//! context switches, see [`synchronize_rcu`] and [`call_rcu`].
+//!
 //! # Cargo Features
 //!
 //! - `multitask`: Enable multi-task support. If it's enabled, complex task
@@ -13,6 +18,25 @@
 //!    APIs can be used, such as [`sleep`], [`sleep_until`], and
 //!    [`WaitQueue::wait_timeout`].
 //! - `preempt`: Enable preemptive scheduling.
+//! - `smp`: Enable SMP (symmetric multiprocessing) support. Each CPU has its
+//!   own run queue, tasks are placed on the least loaded run queue allowed by
+//!   their CPU affinity, and idle CPUs steal ready tasks from the busiest ones.
+//!   With the `irq` feature, remote CPUs are kicked by IPIs when tasks are
+//!   woken up on their run queues, and secondary CPUs can be taken offline
+//!   and back online at runtime (see [`offline_cpu`]).
+//! - `lb_round_robin`: Place new tasks on run queues in round-robin order
+//!   instead of by load. It only takes effect with the `smp` feature.
+//! - `watchdog`: Detect CPUs that have not scheduled for a long time with
+//!   preemption disabled (soft lockups, only with preemptive schedulers), and
+//!   tasks blocked in wait queues for a long time (hung tasks). They are
+//!   reported with backtraces (see [`WatchdogConfig`]). It also enables the
+//!   `multitask` and `irq` features if it is enabled.
+//! - `lockdep`: Track the acquisition order of the spinlocks in all modules
+//!   with [axlockdep], and report possible deadlocks. The locks held in IRQ
+//!   handlers are tracked apart from the interrupted task. It also enables
+//!   the `multitask` feature if it is enabled.
+//! - `trace`: Record context switches, task wakeups, and timer expiries with
+//!   [axtrace]. It also enables the `multitask` feature if it is enabled.
 //! - `sched_fifo`: Use the [FIFO cooperative scheduler][1]. It also enables the
 //!   `multitask` feature if it is enabled. This feature is enabled by default,
 //!   and it can be overriden by other scheduler features.
@@ -20,6 +44,24 @@
 //!   the `multitask` and `preempt` features if it is enabled.
 //! - `sched_cfs`: Use the [Completely Fair Scheduler][3]. It also enables the
 //!   the `multitask` and `preempt` features if it is enabled.
+//! - `sched_rt`: Use a real-time scheduling class on top of the [CFS][3].
+//!   Tasks with the [`SchedPolicy::Fifo`] or [`SchedPolicy::RoundRobin`]
+//!   policies always run before normal tasks, in the order of priorities. It
+//!   also enables the `multitask` and `preempt` features if it is enabled.
+//! - `sched_edf`: Add an earliest-deadline-first class on top of the real-time
+//!   class of `sched_rt`, for periodic tasks with the [`SchedPolicy::Deadline`]
+//!   policy. It also enables the `sched_rt` and `irq` features if it is
+//!   enabled.
+//! - `tickless`: Stop the periodic timer tick on idle CPUs, which only wake up
+//!   for the next timer event. The tick is restarted once a task becomes
+//!   runnable on the CPU. It also enables the `multitask` and `irq` features
+//!   if it is enabled.
+//! - `paging`: Map task stacks in a dedicated virtual memory region with an
+//!   unmapped guard page below each one on x86_64, so that stack overflows are
+//!   caught by the double fault handler (see `check_stack_overflow`).
+//!   Otherwise, a canary word at the bottom of each stack is checked at each
+//!   context switch. Tasks can also run user code in their own address spaces
+//!   (see [`TaskInner::set_user_aspace`]).
 //!
 //! [1]: scheduler::FifoScheduler
 //! [2]: scheduler::RRScheduler
@@ -44,13 +86,38 @@ cfg_if::cfg_if! {
 
         #[macro_use]
         mod run_queue;
+        mod cancel;
+        mod futex;
+        mod pi;
+        mod rcu;
+        mod registry;
+        mod sched;
+        mod stack;
+        mod stats;
         mod task;
         mod task_ext;
         mod api;
         mod wait_queue;
 
+        pub mod future;
+
         #[cfg(feature = "irq")]
         mod timers;
+        #[cfg(feature = "smp")]
+        mod load_balance;
+        #[cfg(all(feature = "smp", feature = "irq"))]
+        mod hotplug;
+        #[cfg(not(any(
+            feature = "sched_rt",
+            all(feature = "sched_cfs", not(feature = "sched_rr"))
+        )))]
+        mod sched_queue;
+        #[cfg(feature = "sched_rt")]
+        mod sched_rt;
+        #[cfg(feature = "sched_edf")]
+        mod sched_edf;
+        #[cfg(feature = "watchdog")]
+        mod watchdog;
 
         #[doc(cfg(feature = "multitask"))]
         pub use self::api::*;
@@ -0,0 +1,202 @@
+//! Load balancing across per-CPU run queues.
+//!
+//! Two mechanisms keep the run queues balanced on SMP systems:
+//!
+//! - **Placement**: when a task is spawned, woken up or migrated, a
+//!   [`PlacementPolicy`] chooses the run queue it is put into. The policy is
+//!   selected at compile time (see [`Placement`]).
+//! - **Work stealing**: when a CPU runs out of ready tasks, it pulls one from
+//!   the busiest peer run queue before falling back to its idle task (see
+//!   `AxRunQueue::steal_task`).
+//!
+//! Both of them respect the CPU affinity ([`AxCpuMask`]) of each task.
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use axhal::cpu::this_cpu_id;
+
+use crate::run_queue::run_queue_load;
+use crate::AxCpuMask;
+
+/// A policy to choose the run queue for a task from its CPU affinity mask.
+pub(crate) trait PlacementPolicy {
+    /// Returns the name of the placement policy.
+    fn name() -> &'static str;
+
+    /// Selects a CPU from `cpumask` to put the task on.
+    ///
+    /// The `cpumask` is guaranteed to be non-empty.
+    fn select_cpu(cpumask: AxCpuMask) -> usize;
+}
+
+/// Picks online CPUs from the affinity mask in round-robin order, regardless
+/// of how busy each run queue is.
+pub(crate) struct RoundRobinPlacement;
+
+/// Picks the CPU with the least runnable tasks in the affinity mask.
+///
+/// Ties are broken in round-robin order, so that a burst of spawned tasks is
+/// spread over all idle CPUs. An idle CPU is always preferred to a busy one,
+/// even if its tick is stopped, since it's kicked by an IPI once a task is
+/// put on it (see `AxRunQueueRef::notify_ready`).
+pub(crate) struct LeastLoadedPlacement;
+
+// The modulo operation is safe here because `axconfig::SMP` is always greater than 1 with "smp" enabled.
+#[allow(clippy::modulo_one)]
+#[inline]
+fn next_start_index() -> usize {
+    static RUN_QUEUE_INDEX: AtomicUsize = AtomicUsize::new(0);
+    RUN_QUEUE_INDEX.fetch_add(1, Ordering::Relaxed) % axconfig::SMP
+}
+
+impl PlacementPolicy for RoundRobinPlacement {
+    fn name() -> &'static str {
+        "round-robin"
+    }
+
+    fn select_cpu(cpumask: AxCpuMask) -> usize {
+        let online = |cpu_id| run_queue_load(cpu_id).is_some();
+        if let Some(cpu_id) =
+            round_robin_cpu(next_start_index, |cpu_id| cpumask.get(cpu_id), online)
+        {
+            return cpu_id;
+        }
+        // None of the allowed CPUs is online, break the affinity rather than
+        // leaving the task on an offline CPU forever.
+        let cpu_id =
+            round_robin_cpu(next_start_index, |_| true, online).unwrap_or_else(this_cpu_id);
+        warn!(
+            "no online CPU in {:?}, placing the task on CPU {}",
+            cpumask, cpu_id
+        );
+        cpu_id
+    }
+}
+
+impl PlacementPolicy for LeastLoadedPlacement {
+    fn name() -> &'static str {
+        "least-loaded"
+    }
+
+    fn select_cpu(cpumask: AxCpuMask) -> usize {
+        let start = next_start_index();
+        match least_loaded_cpu(start, |cpu_id| cpumask.get(cpu_id), run_queue_load) {
+            Some(cpu_id) => cpu_id,
+            // None of the allowed CPUs is online, there is no load to compare.
+            None => RoundRobinPlacement::select_cpu(cpumask),
+        }
+    }
+}
+
+/// Returns the first allowed and online CPU, trying the indexes returned by
+/// `next_index` in turn, at most `axconfig::SMP` times.
+fn round_robin_cpu(
+    mut next_index: impl FnMut() -> usize,
+    allowed: impl Fn(usize) -> bool,
+    online: impl Fn(usize) -> bool,
+) -> Option<usize> {
+    (0..axconfig::SMP)
+        .map(|_| next_index())
+        .find(|&cpu_id| allowed(cpu_id) && online(cpu_id))
+}
+
+/// Returns the allowed CPU with the least load, scanning from `start` in
+/// round-robin order. `load` returns [`None`] for CPUs that can't accept tasks,
+/// e.g., their run queues are not initialized yet or they are offline.
+#[allow(clippy::modulo_one)]
+fn least_loaded_cpu(
+    start: usize,
+    allowed: impl Fn(usize) -> bool,
+    load: impl Fn(usize) -> Option<usize>,
+) -> Option<usize> {
+    let mut best: Option<(usize, usize)> = None;
+    for i in 0..axconfig::SMP {
+        let cpu_id = (start + i) % axconfig::SMP;
+        if !allowed(cpu_id) {
+            continue;
+        }
+        let Some(load) = load(cpu_id) else {
+            continue;
+        };
+        if best.map_or(true, |(_, min_load)| load < min_load) {
+            best = Some((cpu_id, load));
+            if load == 0 {
+                // Can not do better than an idle CPU.
+                break;
+            }
+        }
+    }
+    best.map(|(cpu_id, _)| cpu_id)
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(feature = "lb_round_robin")] {
+        /// The placement policy in use, selected by cargo features.
+        pub(crate) type Placement = RoundRobinPlacement;
+    } else {
+        /// The placement policy in use, selected by cargo features.
+        pub(crate) type Placement = LeastLoadedPlacement;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::modulo_one)]
+    fn test_round_robin_cpu() {
+        const SMP: usize = axconfig::SMP;
+        let mut index = 0;
+        let mut next_index = || {
+            index += 1;
+            (index - 1) % SMP
+        };
+        let all = |_| true;
+        let picked = (0..SMP)
+            .map(|_| round_robin_cpu(&mut next_index, all, all).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(picked, (0..SMP).collect::<Vec<_>>());
+
+        // Offline CPUs are skipped, even if they are allowed.
+        let last = SMP - 1;
+        assert_eq!(
+            round_robin_cpu(&mut next_index, all, |cpu_id| cpu_id == last),
+            Some(last)
+        );
+        assert_eq!(round_robin_cpu(&mut next_index, all, |_| false), None);
+        assert_eq!(
+            round_robin_cpu(
+                &mut next_index,
+                |cpu_id| cpu_id != last,
+                |cpu_id| cpu_id == last
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_least_loaded_cpu() {
+        const SMP: usize = axconfig::SMP;
+        let all = |_| true;
+        // Loads of CPUs: 3, 2, 1, 0, 3, 2, ...
+        let load = |cpu_id: usize| Some(3 - cpu_id % 4);
+        for start in 0..SMP {
+            let best = least_loaded_cpu(start, all, load).unwrap();
+            assert_eq!(load(best), (0..SMP).filter_map(load).min());
+        }
+        // Ties are broken by the start index.
+        assert_eq!(least_loaded_cpu(SMP - 1, all, |_| Some(1)), Some(SMP - 1));
+        // Offline CPUs and CPUs not allowed are skipped.
+        assert_eq!(least_loaded_cpu(0, all, |_| None), None);
+        assert_eq!(least_loaded_cpu(0, |_| false, load), None);
+        assert_eq!(
+            least_loaded_cpu(0, |cpu_id| cpu_id == SMP - 1, load),
+            Some(SMP - 1)
+        );
+        assert_eq!(
+            least_loaded_cpu(0, all, |cpu_id| (cpu_id == 0).then_some(5)),
+            Some(0)
+        );
+    }
+}
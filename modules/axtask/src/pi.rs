@@ -0,0 +1,273 @@
+//! Priority inheritance for blocking locks.
+//!
+//! When a task blocks on a priority-inheriting lock, the owner of the lock
+//! inherits the scheduling policy and priority of the task if they are higher
+//! than its own. If the owner itself is blocked on another such lock, the
+//! inherited parameters are passed along the chain of owners. When the owner
+//! releases the lock, it falls back to the highest parameters inherited through
+//! the other locks it still holds, or its own ones.
+//!
+//! Locks are identified by unique IDs chosen by the lock implementations, e.g.,
+//! their addresses.
+//!
+//! Priority inheritance requires a scheduler with priorities, see
+//! [`pi_supported`]. Otherwise, the functions here do nothing.
+
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use axlockdep::spin::SpinNoIrq;
+
+use crate::run_queue::set_task_sched_params;
+use crate::sched::{SchedPolicy, MAX_RT_PRIORITY};
+use crate::{current, AxTaskRef, TaskInner};
+
+/// The maximum length of a chain of owners that inherited parameters are
+/// passed along, to bound the time spent with the lock held.
+const MAX_CHAIN_DEPTH: usize = 16;
+
+static PI_STATE: SpinNoIrq<PiState> = SpinNoIrq::new(PiState::new());
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+struct SchedParams {
+    policy: SchedPolicy,
+    priority: isize,
+}
+
+impl SchedParams {
+    fn of(task: &TaskInner) -> Self {
+        Self {
+            policy: task.sched_policy(),
+            priority: task.sched_priority(),
+        }
+    }
+
+    /// A larger rank means a higher priority.
+    fn rank(&self) -> isize {
+        match self.policy {
+            // The nice value, a lower one means a higher priority.
+            SchedPolicy::Normal => -self.priority,
+            SchedPolicy::Fifo | SchedPolicy::RoundRobin => 100 + self.priority,
+            SchedPolicy::Deadline => 200,
+        }
+    }
+
+    /// Returns the parameters inherited by the lock owner.
+    ///
+    /// The bandwidth of deadline tasks is reserved per task, so the owner
+    /// inherits the highest real-time priority instead.
+    fn inherited(self) -> Self {
+        if self.policy.is_deadline() {
+            Self {
+                policy: SchedPolicy::Fifo,
+                priority: MAX_RT_PRIORITY,
+            }
+        } else {
+            self
+        }
+    }
+}
+
+struct PiLock {
+    owner: Option<AxTaskRef>,
+    waiters: Vec<AxTaskRef>,
+}
+
+struct PiState {
+    /// Locks that have an owner or waiters, indexed by lock IDs.
+    locks: BTreeMap<usize, PiLock>,
+    /// The lock that each waiter is blocked on, indexed by task IDs.
+    waiting_for: BTreeMap<u64, usize>,
+    /// Own parameters of the tasks that have inherited others', indexed by
+    /// task IDs.
+    boosted: BTreeMap<u64, SchedParams>,
+}
+
+impl PiState {
+    const fn new() -> Self {
+        Self {
+            locks: BTreeMap::new(),
+            waiting_for: BTreeMap::new(),
+            boosted: BTreeMap::new(),
+        }
+    }
+
+    fn lock_entry(&mut self, lock_id: usize) -> &mut PiLock {
+        self.locks.entry(lock_id).or_insert_with(|| PiLock {
+            owner: None,
+            waiters: Vec::new(),
+        })
+    }
+
+    fn remove_if_unused(&mut self, lock_id: usize) {
+        if let Some(lock) = self.locks.get(&lock_id) {
+            if lock.owner.is_none() && lock.waiters.is_empty() {
+                self.locks.remove(&lock_id);
+            }
+        }
+    }
+
+    /// Recomputes the parameters of `task` from the waiters of the locks it
+    /// owns, and passes the change along the chain of owners.
+    fn update(&mut self, task: &AxTaskRef) {
+        let mut task = task.clone();
+        for _ in 0..MAX_CHAIN_DEPTH {
+            let id = task.id().as_u64();
+            let base = match self.boosted.get(&id) {
+                Some(base) => *base,
+                None => SchedParams::of(&task),
+            };
+            let top = self
+                .locks
+                .values()
+                .filter(|lock| lock.owner.as_ref().is_some_and(|o| Arc::ptr_eq(o, &task)))
+                .flat_map(|lock| lock.waiters.iter())
+                .map(|waiter| SchedParams::of(waiter).inherited())
+                .max_by_key(SchedParams::rank);
+            let target = match top {
+                Some(top) if top.rank() > base.rank() => {
+                    self.boosted.insert(id, base);
+                    top
+                }
+                _ => {
+                    self.boosted.remove(&id);
+                    base
+                }
+            };
+            if target == SchedParams::of(&task) {
+                break;
+            }
+            debug!(
+                "priority inheritance: {} {:?} -> {:?}",
+                task.id_name(),
+                SchedParams::of(&task),
+                target
+            );
+            set_task_sched_params(&task, target.policy, target.priority);
+
+            // Pass the change to the owner of the lock that the task is blocked on.
+            let next = self
+                .waiting_for
+                .get(&id)
+                .and_then(|lock_id| self.locks.get(lock_id))
+                .and_then(|lock| lock.owner.clone());
+            match next {
+                Some(next) => task = next,
+                None => break,
+            }
+        }
+    }
+}
+
+/// Whether priority inheritance is supported by the scheduler, i.e., the
+/// `sched_rt` or `sched_cfs` scheduler is used.
+///
+/// The FIFO and round-robin (`sched_rr`) schedulers have no priorities to
+/// inherit, so priority-inheriting locks work as ordinary locks with them.
+pub const fn pi_supported() -> bool {
+    cfg!(any(
+        feature = "sched_rt",
+        all(feature = "sched_cfs", not(feature = "sched_rr"))
+    ))
+}
+
+/// Sets the own policy (or keeps it if `policy` is [`None`]) and priority of
+/// `task`, if it has inherited others' parameters.
+///
+/// Returns [`None`] if the task has not inherited any parameters, otherwise
+/// returns whether the parameters are valid.
+pub(crate) fn set_boosted_base(
+    task: &AxTaskRef,
+    policy: Option<SchedPolicy>,
+    prio: isize,
+) -> Option<bool> {
+    let mut state = PI_STATE.lock();
+    let base = state.boosted.get_mut(&task.id().as_u64())?;
+    let policy = policy.unwrap_or(base.policy);
+    if !policy.is_supported() || !policy.is_valid_priority(prio) {
+        return Some(false);
+    }
+    *base = SchedParams {
+        policy,
+        priority: prio,
+    };
+    state.update(task);
+    Some(true)
+}
+
+/// Called by the current task before it blocks on the priority-inheriting
+/// lock `lock_id`, so that the owner of the lock inherits its priority.
+///
+/// It must be paired with a [`pi_unblock`] after the task stops waiting.
+pub fn pi_block_on(lock_id: usize) {
+    if !pi_supported() {
+        return;
+    }
+    let curr = current();
+    let mut state = PI_STATE.lock();
+    let lock = state.lock_entry(lock_id);
+    lock.waiters.push(curr.as_task_ref().clone());
+    let owner = lock.owner.clone();
+    state.waiting_for.insert(curr.id().as_u64(), lock_id);
+    if let Some(owner) = owner {
+        state.update(&owner);
+    }
+}
+
+/// Called by the current task after it stops waiting for the
+/// priority-inheriting lock `lock_id`, no matter whether it acquired the
+/// lock.
+pub fn pi_unblock(lock_id: usize) {
+    if !pi_supported() {
+        return;
+    }
+    let curr = current();
+    let mut state = PI_STATE.lock();
+    state.waiting_for.remove(&curr.id().as_u64());
+    let Some(lock) = state.locks.get_mut(&lock_id) else {
+        return;
+    };
+    lock.waiters
+        .retain(|waiter| !Arc::ptr_eq(waiter, curr.as_task_ref()));
+    let owner = lock.owner.clone();
+    state.remove_if_unused(lock_id);
+    if let Some(owner) = owner {
+        state.update(&owner);
+    }
+}
+
+/// Called by the current task after it acquires the priority-inheriting lock
+/// `lock_id`, it inherits the priorities of the tasks waiting for the lock.
+pub fn pi_acquired(lock_id: usize) {
+    if !pi_supported() {
+        return;
+    }
+    let curr = current();
+    let mut state = PI_STATE.lock();
+    state.lock_entry(lock_id).owner = Some(curr.as_task_ref().clone());
+    state.update(curr.as_task_ref());
+}
+
+/// Called by the current task after it releases the priority-inheriting lock
+/// `lock_id`, it no longer inherits the priorities through the lock.
+pub fn pi_release(lock_id: usize) {
+    if !pi_supported() {
+        return;
+    }
+    let curr = current();
+    let mut state = PI_STATE.lock();
+    let Some(lock) = state.locks.get_mut(&lock_id) else {
+        return;
+    };
+    // The lock may have been acquired by a waiter woken up by us.
+    if lock
+        .owner
+        .as_ref()
+        .is_some_and(|owner| Arc::ptr_eq(owner, curr.as_task_ref()))
+    {
+        lock.owner = None;
+    }
+    state.remove_if_unused(lock_id);
+    state.update(curr.as_task_ref());
+}
@@ -0,0 +1,164 @@
+//! Read-copy-update (RCU) grace period detection.
+//!
+//! Readers enter read-side critical sections by [`rcu_read_lock`], which
+//! disables preemption and must not block. Each CPU counts the read-side
+//! critical sections it's in, and the context switches it has done.
+//!
+//! A CPU has passed a quiescent state once it's observed outside of any
+//! read-side critical section, or it has switched context. A grace period
+//! ends when all CPUs have passed a quiescent state since it began, so all
+//! readers that may hold references to the old data have finished.
+//! [`synchronize_rcu`] waits for a grace period, and [`call_rcu`] defers a
+//! callback until the end of one, which is run by the `rcu` task.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use axhal::cpu::this_cpu_id;
+use axlockdep::spin::SpinNoIrq;
+use kernel_guard::{BaseGuard, NoPreempt};
+
+use crate::WaitQueue;
+
+struct RcuCpu {
+    /// Number of nested read-side critical sections on this CPU, including
+    /// the ones in IRQ handlers.
+    nesting: AtomicUsize,
+    /// Number of context switches on this CPU.
+    switches: AtomicU64,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const RCU_CPU_INIT: RcuCpu = RcuCpu {
+    nesting: AtomicUsize::new(0),
+    switches: AtomicU64::new(0),
+};
+
+static RCU_CPUS: [RcuCpu; axconfig::SMP] = [RCU_CPU_INIT; axconfig::SMP];
+
+type Callback = Box<dyn FnOnce() + Send>;
+
+static CALLBACKS: SpinNoIrq<Vec<Callback>> = SpinNoIrq::new(Vec::new());
+static CALLBACKS_QUEUED: AtomicU64 = AtomicU64::new(0);
+static CALLBACKS_DONE: AtomicU64 = AtomicU64::new(0);
+static CALLBACK_WQ: WaitQueue = WaitQueue::new();
+static BARRIER_WQ: WaitQueue = WaitQueue::new();
+static RCU_TASK_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// A guard of an RCU read-side critical section, created by
+/// [`rcu_read_lock`].
+///
+/// The critical section ends when the guard is dropped. Preemption is
+/// disabled in the meantime, and the task must not block or yield.
+pub struct RcuReadGuard {
+    cpu_id: usize,
+    // Must be dropped on the same CPU.
+    _not_send: PhantomData<*const ()>,
+}
+
+/// Enters an RCU read-side critical section.
+///
+/// The data protected by RCU and read in the critical section will not be
+/// reclaimed until the returned guard is dropped. Critical sections can be
+/// nested.
+pub fn rcu_read_lock() -> RcuReadGuard {
+    NoPreempt::acquire();
+    let cpu_id = this_cpu_id();
+    RCU_CPUS[cpu_id].nesting.fetch_add(1, Ordering::SeqCst);
+    RcuReadGuard {
+        cpu_id,
+        _not_send: PhantomData,
+    }
+}
+
+impl Drop for RcuReadGuard {
+    fn drop(&mut self) {
+        RCU_CPUS[self.cpu_id].nesting.fetch_sub(1, Ordering::SeqCst);
+        NoPreempt::release(());
+    }
+}
+
+/// Reports a quiescent state of the CPU at a context switch.
+///
+/// # Panics
+///
+/// Panics if the CPU is in a read-side critical section, i.e., the previous
+/// task blocks or yields in it.
+pub(crate) fn note_context_switch(cpu_id: usize) {
+    let cpu = &RCU_CPUS[cpu_id];
+    assert_eq!(
+        cpu.nesting.load(Ordering::Relaxed),
+        0,
+        "context switch in an RCU read-side critical section"
+    );
+    cpu.switches.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Waits until all pre-existing RCU read-side critical sections have
+/// finished, i.e., a grace period has elapsed.
+///
+/// The current task yields while waiting.
+///
+/// # Panics
+///
+/// Panics if it's called in a read-side critical section, which would never
+/// return.
+pub fn synchronize_rcu() {
+    let snapshot: [u64; axconfig::SMP] = {
+        let _guard = NoPreempt::new();
+        assert_eq!(
+            RCU_CPUS[this_cpu_id()].nesting.load(Ordering::SeqCst),
+            0,
+            "synchronize_rcu in an RCU read-side critical section"
+        );
+        core::array::from_fn(|i| RCU_CPUS[i].switches.load(Ordering::SeqCst))
+    };
+    for (cpu, switches) in RCU_CPUS.iter().zip(snapshot) {
+        while cpu.nesting.load(Ordering::SeqCst) != 0
+            && cpu.switches.load(Ordering::SeqCst) == switches
+        {
+            crate::yield_now();
+        }
+    }
+}
+
+/// Queues `f` to be called after a grace period, without blocking.
+///
+/// The callbacks are called in the `rcu` task, which is spawned on the first
+/// call.
+pub fn call_rcu<F>(f: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    if !RCU_TASK_STARTED.swap(true, Ordering::AcqRel) {
+        let _task = crate::spawn_raw(rcu_task_main, "rcu".into(), axconfig::TASK_STACK_SIZE);
+        // It waits for callbacks indefinitely.
+        #[cfg(feature = "watchdog")]
+        _task.set_hung_task_check(false);
+    }
+    CALLBACKS.lock().push(Box::new(f));
+    CALLBACKS_QUEUED.fetch_add(1, Ordering::Release);
+    CALLBACK_WQ.notify_one(false);
+}
+
+/// Waits until all callbacks queued by [`call_rcu`] before have been called.
+pub fn rcu_barrier() {
+    let target = CALLBACKS_QUEUED.load(Ordering::Acquire);
+    BARRIER_WQ.wait_until(|| CALLBACKS_DONE.load(Ordering::Acquire) >= target);
+}
+
+fn rcu_task_main() {
+    loop {
+        CALLBACK_WQ.wait_until(|| !CALLBACKS.lock().is_empty());
+        let callbacks = core::mem::take(&mut *CALLBACKS.lock());
+        synchronize_rcu();
+        let n = callbacks.len() as u64;
+        for f in callbacks {
+            f();
+        }
+        CALLBACKS_DONE.fetch_add(n, Ordering::Release);
+        BARRIER_WQ.notify_all(false);
+    }
+}
@@ -0,0 +1,80 @@
+//! A global registry of all live tasks, for enumeration and introspection.
+//!
+//! Tasks are registered when they are created, and removed when they are
+//! dropped. Exited tasks stay in the registry until their last references are
+//! released.
+
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Weak;
+use alloc::vec::Vec;
+
+use axlockdep::spin::SpinNoIrq;
+
+use crate::task::TaskState;
+use crate::{AxCpuMask, AxTask, AxTaskRef, SchedPolicy, TaskId, TaskInner};
+
+static TASKS: SpinNoIrq<BTreeMap<u64, Weak<AxTask>>> = SpinNoIrq::new(BTreeMap::new());
+
+/// Information about a task, returned by [`TaskInner::info`].
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    /// The task ID.
+    pub id: TaskId,
+    /// The task name.
+    pub name: String,
+    /// The current state.
+    pub state: TaskState,
+    /// The ID of the CPU on which the task is running or ran most recently, or
+    /// [`None`] if the task has never run.
+    pub cpu: Option<usize>,
+    /// The CPU affinity mask.
+    pub cpumask: AxCpuMask,
+    /// The scheduling policy.
+    pub policy: SchedPolicy,
+    /// The scheduling priority, whose meaning depends on the policy.
+    pub priority: isize,
+    /// Size of the kernel stack, or 0 if the task runs on the boot stack.
+    pub stack_size: usize,
+    /// The maximum number of bytes ever used in the kernel stack.
+    pub stack_used: usize,
+}
+
+impl TaskInner {
+    /// Returns a snapshot of the information about the task.
+    pub fn info(&self) -> TaskInfo {
+        let (stack_size, stack_used) = self.kernel_stack_usage();
+        TaskInfo {
+            id: self.id(),
+            name: String::from(self.name()),
+            state: self.state(),
+            cpu: self.stats_counters().last_cpu(),
+            cpumask: self.cpumask(),
+            policy: self.sched_policy(),
+            priority: self.sched_priority(),
+            stack_size,
+            stack_used,
+        }
+    }
+}
+
+pub(crate) fn register(task: &AxTaskRef) {
+    TASKS
+        .lock()
+        .insert(task.id().as_u64(), AxTaskRef::downgrade(task));
+}
+
+pub(crate) fn unregister(id: TaskId) {
+    TASKS.lock().remove(&id.as_u64());
+}
+
+/// Returns the task with the given ID, or [`None`] if it does not exist or has
+/// been dropped.
+pub fn get_task(id: TaskId) -> Option<AxTaskRef> {
+    TASKS.lock().get(&id.as_u64()).and_then(Weak::upgrade)
+}
+
+/// Returns all live tasks, ordered by task IDs.
+pub fn all_tasks() -> Vec<AxTaskRef> {
+    TASKS.lock().values().filter_map(Weak::upgrade).collect()
+}
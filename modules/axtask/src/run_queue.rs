@@ -1,17 +1,27 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
 use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
+use axlockdep::spin::SpinRaw;
 use kernel_guard::{BaseGuard, NoOp};
-use kspin::SpinRaw;
 use lazyinit::LazyInit;
 use scheduler::BaseScheduler;
 
 use axhal::cpu::this_cpu_id;
 
+#[cfg(feature = "smp")]
+use core::sync::atomic::AtomicBool;
+
+use crate::cancel::Cancelled;
+#[cfg(feature = "smp")]
+use crate::load_balance::{Placement, PlacementPolicy};
+#[cfg(feature = "smp")]
+use crate::sched::StealTask;
+use crate::sched::{DeadlineParams, SchedPolicy};
 use crate::task::{CurrentTask, TaskState};
-use crate::wait_queue::WaitQueueGuard;
-use crate::{AxCpuMask, AxTaskRef, Scheduler, TaskInner, WaitQueue};
+use crate::wait_queue::{WaitQueueGuard, Waiter};
+use crate::{AxCpuMask, AxTaskRef, Scheduler, TaskId, TaskInner, WaitQueue};
 
 macro_rules! percpu_static {
     ($($name:ident: $ty:ty = $init:expr),* $(,)?) => {
@@ -42,6 +52,16 @@ static mut RUN_QUEUES: [MaybeUninit<&'static mut AxRunQueue>; axconfig::SMP] =
     [ARRAY_REPEAT_VALUE; axconfig::SMP];
 const ARRAY_REPEAT_VALUE: MaybeUninit<&'static mut AxRunQueue> = MaybeUninit::uninit();
 
+/// Whether the run queue of each CPU has been initialized in [`RUN_QUEUES`].
+///
+/// Secondary CPUs initialize their run queues later than the primary CPU, the
+/// load balancer must not touch their run queues before that.
+#[cfg(feature = "smp")]
+static RUN_QUEUE_READY: [AtomicBool; axconfig::SMP] = [READY_REPEAT_VALUE; axconfig::SMP];
+#[cfg(feature = "smp")]
+#[allow(clippy::declare_interior_mutable_const)]
+const READY_REPEAT_VALUE: AtomicBool = AtomicBool::new(false);
+
 /// Returns a reference to the current run queue in [`CurrentRunQueueRef`].
 ///
 /// ## Safety
@@ -68,7 +88,8 @@ pub(crate) fn current_run_queue<G: BaseGuard>() -> CurrentRunQueueRef<'static, G
 /// Selects the run queue index based on a CPU set bitmap and load balancing.
 ///
 /// This function filters the available run queues based on the provided `cpumask` and
-/// selects the run queue index for the next task. The selection is based on a round-robin algorithm.
+/// selects the run queue index for the next task. The selection is delegated to the
+/// [`PlacementPolicy`] chosen by cargo features (see [`Placement`]).
 ///
 /// ## Arguments
 ///
@@ -83,22 +104,10 @@ pub(crate) fn current_run_queue<G: BaseGuard>() -> CurrentRunQueueRef<'static, G
 /// This function will panic if `cpu_mask` is empty, indicating that there are no available CPUs for task execution.
 ///
 #[cfg(feature = "smp")]
-// The modulo operation is safe here because `axconfig::SMP` is always greater than 1 with "smp" enabled.
-#[allow(clippy::modulo_one)]
 #[inline]
 fn select_run_queue_index(cpumask: AxCpuMask) -> usize {
-    use core::sync::atomic::{AtomicUsize, Ordering};
-    static RUN_QUEUE_INDEX: AtomicUsize = AtomicUsize::new(0);
-
     assert!(!cpumask.is_empty(), "No available CPU for task execution");
-
-    // Round-robin selection of the run queue index.
-    loop {
-        let index = RUN_QUEUE_INDEX.fetch_add(1, Ordering::SeqCst) % axconfig::SMP;
-        if cpumask.get(index) {
-            return index;
-        }
-    }
+    Placement::select_cpu(cpumask)
 }
 
 /// Retrieves a `'static` reference to the run queue corresponding to the given index.
@@ -124,6 +133,21 @@ fn get_run_queue(index: usize) -> &'static mut AxRunQueue {
     unsafe { RUN_QUEUES[index].assume_init_mut() }
 }
 
+/// Returns the number of runnable tasks on the run queue of the given CPU,
+/// including the running one (except the idle task).
+///
+/// It is used by the load balancer, the result may be out of date the instant
+/// it is returned. Returns [`None`] if the run queue is not initialized yet.
+#[cfg(feature = "smp")]
+#[inline]
+pub(crate) fn run_queue_load(cpu_id: usize) -> Option<usize> {
+    if RUN_QUEUE_READY[cpu_id].load(Ordering::Acquire) {
+        Some(get_run_queue(cpu_id).load())
+    } else {
+        None
+    }
+}
+
 /// Selects the appropriate run queue for the provided task.
 ///
 /// * In a single-core system, this function always returns a reference to the global run queue.
@@ -137,11 +161,6 @@ fn get_run_queue(index: usize) -> &'static mut AxRunQueue {
 ///
 /// * [`AxRunQueueRef`] - a static reference to the selected [`AxRunQueue`] (current or remote).
 ///
-/// ## TODO
-///
-/// 1. Implement better load balancing across CPUs for more efficient task distribution.
-/// 2. Use a more generic load balancing algorithm that can be customized or replaced.
-///
 #[inline]
 pub(crate) fn select_run_queue<G: BaseGuard>(task: &AxTaskRef) -> AxRunQueueRef<'static, G> {
     let irq_state = G::acquire();
@@ -167,6 +186,71 @@ pub(crate) fn select_run_queue<G: BaseGuard>(task: &AxTaskRef) -> AxRunQueueRef<
     }
 }
 
+/// Changes the scheduling policy and priority of a task, which may be ready in
+/// any run queue, running or blocked. It's used by priority inheritance and
+/// [`set_task_sched_policy`](crate::set_task_sched_policy).
+///
+/// A ready task is re-queued with the new parameters if the scheduler has
+/// multiple classes. Otherwise, only its priority in the scheduler is changed.
+///
+/// Returns whether the scheduler accepts the priority.
+pub(crate) fn set_task_sched_params(task: &AxTaskRef, policy: SchedPolicy, prio: isize) -> bool {
+    let _guard = kernel_guard::NoPreemptIrqSave::new();
+
+    // The task may move to another queue of the scheduler.
+    #[cfg(feature = "sched_rt")]
+    {
+        #[cfg(feature = "smp")]
+        let run_queues = (0..axconfig::SMP)
+            .filter(|&cpu_id| RUN_QUEUE_READY[cpu_id].load(Ordering::Acquire))
+            .map(get_run_queue);
+        #[cfg(not(feature = "smp"))]
+        let run_queues = core::iter::once(unsafe { RUN_QUEUE.current_ref_mut_raw() });
+        for rq in run_queues {
+            let mut scheduler = rq.scheduler.lock();
+            if let Some(task) = scheduler.remove_task(task) {
+                let ok = apply_sched_params(&mut scheduler, &task, policy, prio);
+                let preempt = rq.cpu_id == this_cpu_id()
+                    && task.sched_attr().preempts(crate::current().sched_attr());
+                scheduler.add_task(task);
+                if preempt {
+                    crate::current().set_preempt_pending(true);
+                }
+                return ok;
+            }
+        }
+    }
+
+    // The task is not ready, the new parameters take effect the next time it's
+    // put into a run queue.
+    let rq = unsafe { RUN_QUEUE.current_ref_mut_raw() };
+    let ok = apply_sched_params(&mut rq.scheduler.lock(), task, policy, prio);
+    #[cfg(feature = "preempt")]
+    if task.id() == crate::current().id() {
+        // Let other tasks compete with the current task again.
+        task.set_preempt_pending(true);
+    }
+    ok
+}
+
+fn apply_sched_params(
+    scheduler: &mut Scheduler,
+    task: &AxTaskRef,
+    policy: SchedPolicy,
+    prio: isize,
+) -> bool {
+    #[cfg(feature = "sched_rt")]
+    task.sched_attr().set_policy(policy, prio);
+    #[cfg(not(feature = "sched_rt"))]
+    let _ = policy;
+    if scheduler.set_priority(task, prio) {
+        task.sched_attr().set_priority(prio);
+        true
+    } else {
+        false
+    }
+}
+
 /// [`AxRunQueue`] represents a run queue for global system or a specific CPU.
 pub(crate) struct AxRunQueue {
     /// The ID of the CPU this run queue is associated with.
@@ -175,6 +259,17 @@ pub(crate) struct AxRunQueue {
     /// Since irq and preempt are preserved by the kernel guard hold by `AxRunQueueRef`,
     /// we just use a simple raw spin lock here.
     scheduler: SpinRaw<Scheduler>,
+    /// The number of ready tasks in the scheduler.
+    nr_ready: AtomicUsize,
+    /// Whether this CPU is running its idle task.
+    #[cfg(feature = "smp")]
+    running_idle: AtomicBool,
+    /// Whether this CPU has been kicked and hasn't handled the IPI yet.
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    kick_pending: AtomicBool,
+    /// The ID of the GC task, which stays on this CPU when it goes offline.
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    gc_task_id: TaskId,
 }
 
 /// A reference to the run queue with specific guard.
@@ -223,12 +318,25 @@ impl<'a, G: BaseGuard> AxRunQueueRef<'a, G> {
             self.inner.cpu_id
         );
         assert!(task.is_ready());
-        self.inner.scheduler.lock().add_task(task);
+        #[cfg(feature = "sched_rt")]
+        let preempt = self.should_preempt_current(&task);
+        #[cfg(not(feature = "sched_rt"))]
+        let preempt = false;
+        let mut scheduler = self.inner.scheduler.lock();
+        scheduler.add_task(task);
+        // Update the counter while holding the lock, to keep it consistent with the scheduler.
+        self.inner.nr_ready.fetch_add(1, Ordering::Relaxed);
+        drop(scheduler);
+        self.notify_ready(preempt);
     }
 
     /// Unblock one task by inserting it into the run queue.
     pub fn unblock_task(&mut self, task: AxTaskRef, resched: bool) {
         let task_id_name = task.id_name();
+        #[cfg(feature = "trace")]
+        let task_id = task.id().as_u64();
+        #[cfg(feature = "sched_rt")]
+        let resched = resched || self.should_preempt_current(&task);
         // Try to change the state of the task from `Blocked` to `Ready`,
         // if successful, the task will be put into this run queue,
         // otherwise, the task is already unblocked by other cores.
@@ -239,18 +347,66 @@ impl<'a, G: BaseGuard> AxRunQueueRef<'a, G> {
             // Since now, the task to be unblocked is in the `Ready` state.
             let cpu_id = self.inner.cpu_id;
             debug!("task unblock: {} on run_queue {}", task_id_name, cpu_id);
-            // Note: when the task is unblocked on another CPU's run queue,
-            // we just ingiore the `resched` flag.
-            if resched && cpu_id == this_cpu_id() {
+            #[cfg(feature = "trace")]
+            axtrace::record(axtrace::TraceEvent::Wakeup {
+                task: task_id,
+                cpu: cpu_id,
+            });
+            self.notify_ready(resched);
+        }
+    }
+
+    /// Notifies the CPU of this run queue that a task has become ready on it,
+    /// which should preempt the current task there if `resched`.
+    ///
+    /// A remote CPU is kicked by an IPI, so that it reschedules even if it's
+    /// idle and waiting for IRQs.
+    fn notify_ready(&self, resched: bool) {
+        if self.inner.cpu_id == this_cpu_id() {
+            if resched {
                 #[cfg(feature = "preempt")]
                 crate::current().set_preempt_pending(true);
             }
+        } else {
+            #[cfg(all(feature = "smp", feature = "irq"))]
+            self.inner.kick_if_needed(resched);
+        }
+    }
+
+    /// Whether the real-time `task` should preempt the current task of this run
+    /// queue once it's ready.
+    ///
+    /// It can't be checked for remote run queues, where all real-time tasks are
+    /// considered preempting, and the remote CPU decides once it's kicked.
+    #[cfg(feature = "sched_rt")]
+    fn should_preempt_current(&self, task: &AxTaskRef) -> bool {
+        if self.inner.cpu_id == this_cpu_id() {
+            task.sched_attr().preempts(crate::current().sched_attr())
+        } else {
+            task.sched_policy() != SchedPolicy::Normal
         }
     }
 }
 
 /// Core functions of run queue.
 impl<'a, G: BaseGuard> CurrentRunQueueRef<'a, G> {
+    /// Returns whether the idle task can wait for IRQs, i.e., there are no
+    /// ready tasks on the current CPU.
+    ///
+    /// It's called by the idle task with IRQs disabled, which stay disabled
+    /// until it waits, so that tasks woken up by IRQs in between are not
+    /// missed. With the `tickless` feature, the periodic tick is stopped if it
+    /// can wait, and restarted once the CPU switches to another task.
+    #[cfg(feature = "irq")]
+    pub fn idle_can_wait(&self) -> bool {
+        if !self.current_task.is_idle() || self.inner.nr_ready.load(Ordering::Relaxed) != 0 {
+            return false;
+        }
+        #[cfg(feature = "tickless")]
+        crate::timers::stop_tick();
+        true
+    }
+
     #[cfg(feature = "irq")]
     pub fn scheduler_timer_tick(&mut self) {
         let curr = &self.current_task;
@@ -271,7 +427,7 @@ impl<'a, G: BaseGuard> CurrentRunQueueRef<'a, G> {
         self.inner
             .put_task_with_state(curr.clone(), TaskState::Running, false);
 
-        self.inner.resched();
+        self.inner.resched(false);
     }
 
     /// Migrate the current task to a new run queue matching its CPU affinity and reschedule.
@@ -286,7 +442,7 @@ impl<'a, G: BaseGuard> CurrentRunQueueRef<'a, G> {
             .inner
             .put_task_with_state(curr.clone(), TaskState::Running, false);
 
-        self.inner.resched();
+        self.inner.resched(false);
     }
 
     /// Preempts the current task and reschedules.
@@ -317,14 +473,43 @@ impl<'a, G: BaseGuard> CurrentRunQueueRef<'a, G> {
             can_preempt
         );
         if can_preempt {
+            #[cfg(feature = "sched_edf")]
+            if self.throttle_current() {
+                return;
+            }
             self.inner
                 .put_task_with_state(curr.clone(), TaskState::Running, true);
-            self.inner.resched();
+            self.inner.resched(true);
         } else {
             curr.set_preempt_pending(true);
         }
     }
 
+    /// Throttles the current task until its next period, if it's a deadline
+    /// task that has used up its runtime budget.
+    ///
+    /// Returns `true` if the current task is throttled and it has been
+    /// rescheduled.
+    #[cfg(feature = "sched_edf")]
+    fn throttle_current(&mut self) -> bool {
+        let curr = &self.current_task;
+        let dl = curr.sched_attr().dl();
+        if !curr.sched_policy().is_deadline() || !dl.is_exhausted() {
+            return false;
+        }
+        // The job can not complete before its deadline.
+        if dl.record_miss() {
+            warn!("deadline miss: {} overran its runtime", curr.id_name());
+        }
+        debug!("task throttle: {}", curr.id_name());
+        dl.end_job();
+        let next_period = crate::sched_edf::wall_time_at(dl.next_period_ns());
+        crate::timers::set_alarm_wakeup(next_period, curr.clone());
+        curr.set_state(TaskState::Blocked);
+        self.inner.resched(true);
+        true
+    }
+
     /// Exit the current task with the specified exit code.
     /// This function will never return.
     pub fn exit_current(&mut self, exit_code: i32) -> ! {
@@ -341,6 +526,8 @@ impl<'a, G: BaseGuard> CurrentRunQueueRef<'a, G> {
             axhal::misc::terminate();
         } else {
             curr.set_state(TaskState::Exited);
+            #[cfg(feature = "sched_edf")]
+            curr.sched_attr().dl().release_bandwidth();
 
             // Notify the joiner task.
             curr.notify_exit(exit_code);
@@ -355,7 +542,7 @@ impl<'a, G: BaseGuard> CurrentRunQueueRef<'a, G> {
             }
 
             // Schedule to next task.
-            self.inner.resched();
+            self.inner.resched(false);
         }
         unreachable!("task exited!");
     }
@@ -367,7 +554,27 @@ impl<'a, G: BaseGuard> CurrentRunQueueRef<'a, G> {
     ///     2. The caller must ensure that the current task is in the running state.
     ///     3. The caller must ensure that the current task is not the idle task.
     ///     4. The lock of the wait queue will be released explicitly after current task is pushed into it.
-    pub fn blocked_resched(&mut self, mut wq_guard: WaitQueueGuard) {
+    pub fn blocked_resched(&mut self, wq_guard: WaitQueueGuard) {
+        self.push_blocked(wq_guard);
+        debug!("task block: {}", self.current_task.id_name());
+        self.inner.resched(false);
+    }
+
+    /// Like [`CurrentRunQueueRef::blocked_resched`], but the task is woken up
+    /// by cancellation as well.
+    ///
+    /// The task is left in the wait queue if it's woken up by cancellation.
+    pub fn cancellable_blocked_resched(
+        &mut self,
+        wq_guard: WaitQueueGuard,
+    ) -> Result<(), Cancelled> {
+        self.push_blocked(wq_guard);
+        debug!("task block (cancellable): {}", self.current_task.id_name());
+        self.cancellable_resched()
+    }
+
+    /// Marks the current task as `Blocked` and adds it to the wait queue.
+    fn push_blocked(&mut self, mut wq_guard: WaitQueueGuard) {
         let curr = &self.current_task;
         assert!(curr.is_running());
         assert!(!curr.is_idle());
@@ -382,16 +589,35 @@ impl<'a, G: BaseGuard> CurrentRunQueueRef<'a, G> {
         curr.set_state(TaskState::Blocked);
         curr.set_in_wait_queue(true);
 
-        wq_guard.push_back(curr.clone());
+        wq_guard.push_back(Waiter::Task(curr.clone()));
         // Drop the lock of wait queue explictly.
         drop(wq_guard);
 
         // Current task's state has been changed to `Blocked` and added to the wait queue.
         // Note that the state may have been set as `Ready` in `unblock_task()`,
         // see `unblock_task()` for details.
+    }
 
-        debug!("task block: {}", curr.id_name());
-        self.inner.resched();
+    /// Switches out the current task that has been marked as `Blocked`, unless
+    /// it has a pending cancellation.
+    fn cancellable_resched(&mut self) -> Result<(), Cancelled> {
+        let curr = &self.current_task;
+        let cancel = curr.cancel_state();
+        cancel.set_in_wait(true);
+        if cancel.is_pending() && curr.transition_state(TaskState::Blocked, TaskState::Running) {
+            // Not woken up yet, no need to switch out.
+            cancel.set_in_wait(false);
+            return Err(Cancelled);
+        }
+        // Otherwise, the task may have been set as `Ready` in `unblock_task()`
+        // already, it must be switched out to leave the run queue.
+        self.inner.resched(false);
+        cancel.set_in_wait(false);
+        if cancel.is_pending() {
+            Err(Cancelled)
+        } else {
+            Ok(())
+        }
     }
 
     #[cfg(feature = "irq")]
@@ -405,15 +631,128 @@ impl<'a, G: BaseGuard> CurrentRunQueueRef<'a, G> {
         if now < deadline {
             crate::timers::set_alarm_wakeup(deadline, curr.clone());
             curr.set_state(TaskState::Blocked);
-            self.inner.resched();
+            self.inner.resched(false);
+        }
+    }
+
+    #[cfg(feature = "irq")]
+    pub fn sleep_until_cancellable(
+        &mut self,
+        deadline: axhal::time::TimeValue,
+    ) -> Result<(), Cancelled> {
+        let curr = &self.current_task;
+        debug!(
+            "task sleep (cancellable): {}, deadline={:?}",
+            curr.id_name(),
+            deadline
+        );
+        assert!(curr.is_running());
+        assert!(!curr.is_idle());
+
+        if curr.cancel_state().is_pending() {
+            return Err(Cancelled);
+        }
+        let now = axhal::time::wall_time();
+        if now < deadline {
+            crate::timers::set_alarm_wakeup(deadline, curr.clone());
+            curr.set_state(TaskState::Blocked);
+            let res = self.cancellable_resched();
+            // The timer event may be still in the timer list if woken up by
+            // cancellation, make it ignored.
+            self.current_task.timer_ticket_expired();
+            return res;
         }
+        Ok(())
     }
 
     pub fn set_current_priority(&mut self, prio: isize) -> bool {
-        self.inner
-            .scheduler
-            .lock()
-            .set_priority(self.current_task.as_task_ref(), prio)
+        let curr = self.current_task.as_task_ref();
+        // Only set its own priority if it has inherited others'.
+        if let Some(ok) = crate::pi::set_boosted_base(curr, None, prio) {
+            return ok;
+        }
+        if self.inner.scheduler.lock().set_priority(curr, prio) {
+            curr.sched_attr().set_priority(prio);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn set_current_deadline(&mut self, params: DeadlineParams) -> bool {
+        #[cfg(feature = "sched_edf")]
+        {
+            // Reschedule at once like `set_current_sched_policy`.
+            let curr = &self.current_task;
+            let _scheduler = self.inner.scheduler.lock();
+            if !curr.admit_deadline(params, AxCpuMask::one_shot(self.inner.cpu_id)) {
+                return false;
+            }
+            // It's running, its budget is consumed from now on.
+            let now = axhal::time::monotonic_time_nanos();
+            curr.sched_attr().dl().start_running(now);
+            curr.set_preempt_pending(true);
+            true
+        }
+        #[cfg(not(feature = "sched_edf"))]
+        {
+            let _ = params;
+            false
+        }
+    }
+
+    /// Completes the current job of the current deadline task, and sleeps
+    /// until the next period.
+    #[cfg(feature = "sched_edf")]
+    pub fn wait_next_period(&mut self) {
+        let curr = &self.current_task;
+        if !curr.sched_policy().is_deadline() {
+            return;
+        }
+        let dl = curr.sched_attr().dl();
+        let now = axhal::time::monotonic_time_nanos();
+        if dl.check_miss(now) {
+            warn!("deadline miss: {}", curr.id_name());
+        }
+        let next_period = dl.next_period_ns();
+        if now < next_period {
+            debug!("task wait for next period: {}", curr.id_name());
+            dl.end_job();
+            let deadline = crate::sched_edf::wall_time_at(next_period);
+            crate::timers::set_alarm_wakeup(deadline, curr.clone());
+            curr.set_state(TaskState::Blocked);
+            self.inner.resched(false);
+        } else {
+            // Already late for the next period, start it at once.
+            dl.start_new_job(now);
+        }
+    }
+
+    pub fn set_current_sched_policy(&mut self, policy: SchedPolicy, prio: isize) -> bool {
+        #[cfg(feature = "sched_rt")]
+        {
+            let curr = &self.current_task;
+            if let Some(ok) = crate::pi::set_boosted_base(curr.as_task_ref(), Some(policy), prio) {
+                return ok;
+            }
+            // The current task is not in the scheduler, the new policy takes
+            // effect when it's put back. Reschedule at once, so that it
+            // competes with other tasks under the new policy.
+            let _scheduler = self.inner.scheduler.lock();
+            if !curr.set_sched_policy(policy, prio) {
+                return false;
+            }
+            curr.set_preempt_pending(true);
+            true
+        }
+        #[cfg(not(feature = "sched_rt"))]
+        {
+            if policy.is_realtime() {
+                false
+            } else {
+                self.set_current_priority(prio)
+            }
+        }
     }
 }
 
@@ -424,15 +763,55 @@ impl AxRunQueue {
         let gc_task = TaskInner::new(gc_entry, "gc".into(), axconfig::TASK_STACK_SIZE).into_arc();
         // gc task should be pinned to the current CPU.
         gc_task.set_cpumask(AxCpuMask::one_shot(cpu_id));
+        // It waits for exited tasks indefinitely.
+        #[cfg(feature = "watchdog")]
+        gc_task.set_hung_task_check(false);
 
+        #[cfg(all(feature = "smp", feature = "irq"))]
+        let gc_task_id = gc_task.id();
         let mut scheduler = Scheduler::new();
         scheduler.add_task(gc_task);
         Self {
             cpu_id,
             scheduler: SpinRaw::new(scheduler),
+            nr_ready: AtomicUsize::new(1),
+            #[cfg(feature = "smp")]
+            running_idle: AtomicBool::new(false),
+            #[cfg(all(feature = "smp", feature = "irq"))]
+            kick_pending: AtomicBool::new(false),
+            #[cfg(all(feature = "smp", feature = "irq"))]
+            gc_task_id,
         }
     }
 
+    /// Interrupts the remote CPU of this run queue to make it reschedule, if
+    /// `resched` or it's running the idle task, which may be waiting for IRQs
+    /// with its tick stopped.
+    ///
+    /// Kicks are coalesced until the CPU handles the IPI. A missed kick of an
+    /// idle CPU only delays the new task until its next tick, since the tick
+    /// is not stopped while there are ready tasks.
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    fn kick_if_needed(&self, resched: bool) {
+        let idle = self.running_idle.load(Ordering::Relaxed);
+        if (resched || idle)
+            && axhal::ipi::cpu_online(self.cpu_id)
+            && !self.kick_pending.swap(true, Ordering::AcqRel)
+        {
+            trace!("kick CPU {}", self.cpu_id);
+            axhal::ipi::run_on_cpu(self.cpu_id, handle_kick, 0, false);
+        }
+    }
+
+    /// Returns the number of runnable tasks on this run queue, including the
+    /// running one (except the idle task).
+    #[cfg(feature = "smp")]
+    #[inline]
+    fn load(&self) -> usize {
+        let running = !self.running_idle.load(Ordering::Relaxed) as usize;
+        self.nr_ready.load(Ordering::Relaxed) + running
+    }
+
     /// Puts target task into current run queue with `Ready` state
     /// if its state matches `current_state` (except idle task).
     ///
@@ -449,8 +828,14 @@ impl AxRunQueue {
         // If the task's state matches `current_state`, set its state to `Ready` and
         // put it back to the run queue (except idle task).
         if task.transition_state(current_state, TaskState::Ready) && !task.is_idle() {
+            if current_state == TaskState::Blocked {
+                task.stats_counters()
+                    .on_unblock(axhal::time::monotonic_time_nanos());
+            }
             // TODO: priority
-            self.scheduler.lock().put_prev_task(task, preempt);
+            let mut scheduler = self.scheduler.lock();
+            scheduler.put_prev_task(task, preempt);
+            self.nr_ready.fetch_add(1, Ordering::Relaxed);
             true
         } else {
             false
@@ -459,25 +844,91 @@ impl AxRunQueue {
 
     /// Core reschedule subroutine.
     /// Pick the next task to run and switch to it.
-    fn resched(&mut self) {
-        let next = self
-            .scheduler
-            .lock()
-            .pick_next_task()
-            .unwrap_or_else(|| unsafe {
-                // Safety: IRQs must be disabled at this time.
-                IDLE_TASK.current_ref_raw().get_unchecked().clone()
-            });
+    ///
+    /// `preempt` indicates whether the current task is preempted (an
+    /// involuntary context switch), which is recorded in its statistics.
+    fn resched(&mut self, preempt: bool) {
+        let next = self.pick_next_task().unwrap_or_else(|| unsafe {
+            // Safety: IRQs must be disabled at this time.
+            IDLE_TASK.current_ref_raw().get_unchecked().clone()
+        });
         assert!(
             next.is_ready(),
             "next {} is not ready: {:?}",
             next.id_name(),
             next.state()
         );
-        self.switch_to(crate::current(), next);
+        self.switch_to(crate::current(), next, preempt);
+    }
+
+    /// Picks the next ready task from this run queue.
+    ///
+    /// If this run queue is empty, tries to steal one from the busiest peer
+    /// run queue (SMP only). Returns [`None`] if there is nothing to run.
+    fn pick_next_task(&mut self) -> Option<AxTaskRef> {
+        #[cfg(all(feature = "smp", feature = "irq"))]
+        if crate::hotplug::going_offline(self.cpu_id) {
+            // Switch to the idle task, which migrates the ready tasks away.
+            return None;
+        }
+        let mut scheduler = self.scheduler.lock();
+        if let Some(next) = scheduler.pick_next_task() {
+            self.nr_ready.fetch_sub(1, Ordering::Relaxed);
+            return Some(next);
+        }
+        drop(scheduler);
+        #[cfg(feature = "smp")]
+        if let Some(task) = self.steal_task() {
+            // Put the stolen task into our own scheduler first, so that its
+            // scheduling states (e.g., the virtual runtime in CFS) are adjusted
+            // to this run queue.
+            let mut scheduler = self.scheduler.lock();
+            scheduler.add_task(task);
+            return scheduler.pick_next_task();
+        }
+        None
     }
 
-    fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef) {
+    /// Steals a ready task from the busiest peer run queue, whose CPU affinity
+    /// allows it to run on this CPU.
+    ///
+    /// Returns [`None`] if no peer run queue has a task to spare.
+    #[cfg(feature = "smp")]
+    fn steal_task(&mut self) -> Option<AxTaskRef> {
+        let busiest = (0..axconfig::SMP)
+            .filter(|&cpu_id| {
+                cpu_id != self.cpu_id && RUN_QUEUE_READY[cpu_id].load(Ordering::Acquire)
+            })
+            .map(|cpu_id| {
+                let nr_ready = get_run_queue(cpu_id).nr_ready.load(Ordering::Relaxed);
+                (cpu_id, nr_ready)
+            })
+            .filter(|&(_, nr_ready)| nr_ready > 0)
+            .max_by_key(|&(_, nr_ready)| nr_ready)?
+            .0;
+        let task = get_run_queue(busiest).pull_task_for(self.cpu_id)?;
+        debug!(
+            "task steal: {} from run_queue {} to run_queue {}",
+            task.id_name(),
+            busiest,
+            self.cpu_id
+        );
+        Some(task)
+    }
+
+    /// Removes the first ready task that is allowed to run on the CPU `cpu_id`
+    /// from this run queue, and returns it.
+    #[cfg(feature = "smp")]
+    fn pull_task_for(&self, cpu_id: usize) -> Option<AxTaskRef> {
+        let mut scheduler = self.scheduler.lock();
+        let stolen = scheduler.steal_task(|task| task.cpumask().get(cpu_id));
+        if stolen.is_some() {
+            self.nr_ready.fetch_sub(1, Ordering::Relaxed);
+        }
+        stolen
+    }
+
+    fn switch_to(&mut self, prev_task: CurrentTask, next_task: AxTaskRef, preempt: bool) {
         // Make sure that IRQs are disabled by kernel guard or other means.
         #[cfg(all(not(test), feature = "irq"))] // Note: irq is faked under unit tests.
         assert!(
@@ -492,9 +943,40 @@ impl AxRunQueue {
         #[cfg(feature = "preempt")]
         next_task.set_preempt_pending(false);
         next_task.set_state(TaskState::Running);
+        #[cfg(feature = "smp")]
+        self.running_idle
+            .store(next_task.is_idle(), Ordering::Relaxed);
+        #[cfg(feature = "watchdog")]
+        crate::watchdog::touch(axhal::time::monotonic_time_nanos());
+        crate::rcu::note_context_switch(self.cpu_id);
         if prev_task.ptr_eq(&next_task) {
             return;
         }
+        #[cfg(not(all(feature = "paging", target_arch = "x86_64")))]
+        prev_task.check_stack_canary();
+        #[cfg(feature = "tickless")]
+        if prev_task.is_idle() {
+            crate::timers::restart_tick();
+        }
+
+        // Update the CPU time and scheduling statistics of both tasks.
+        let now = axhal::time::monotonic_time_nanos();
+        let run_ns = prev_task.stats_counters().on_switch_out(now, preempt);
+        // Charge the deadline task if it's not put back to the run queue,
+        // e.g., it blocks, so that it's not charged when woken up later.
+        #[cfg(feature = "sched_edf")]
+        if prev_task.sched_policy().is_deadline() {
+            prev_task.sched_attr().dl().stop_running(now);
+        }
+        if !prev_task.is_idle() {
+            crate::stats::account_busy_time(run_ns);
+        }
+        next_task.stats_counters().on_switch_in(now, self.cpu_id);
+        #[cfg(feature = "trace")]
+        axtrace::record(axtrace::TraceEvent::ContextSwitch {
+            prev: prev_task.id().as_u64(),
+            next: next_task.id().as_u64(),
+        });
 
         // Task must be scheduled atomically, wait for next task's scheduling process to complete.
         // If the owning (remote) CPU is still in the middle of schedule() with
@@ -512,6 +994,9 @@ impl AxRunQueue {
             core::hint::spin_loop();
         }
 
+        #[cfg(feature = "paging")]
+        switch_page_table(prev_task.as_task_ref(), &next_task);
+
         // Claim the task as running, we do this before switching to it
         // such that any running task will have this set.
         #[cfg(feature = "smp")]
@@ -542,6 +1027,37 @@ impl AxRunQueue {
     }
 }
 
+/// Handles a kick from another CPU in the IPI handler: clears the pending flag
+/// and reschedules on return from the interrupt.
+#[cfg(all(feature = "smp", feature = "irq"))]
+fn handle_kick(_: usize) {
+    get_run_queue(this_cpu_id())
+        .kick_pending
+        .store(false, Ordering::Release);
+    #[cfg(feature = "preempt")]
+    crate::current().set_preempt_pending(true);
+}
+
+/// Activates the page table of the user address space of `next_task`, or the
+/// kernel page table if it has none.
+#[cfg(feature = "paging")]
+fn switch_page_table(prev_task: &AxTaskRef, next_task: &AxTaskRef) {
+    let next_root = next_task.page_table_root();
+    if prev_task.page_table_root() == next_root {
+        return;
+    }
+    // On AArch64, the kernel is mapped by its own root, and user address
+    // spaces are in the lower half.
+    #[cfg(target_arch = "aarch64")]
+    unsafe {
+        axhal::arch::write_page_table_root0(next_root)
+    };
+    #[cfg(not(target_arch = "aarch64"))]
+    unsafe {
+        axhal::arch::write_page_table_root(next_root)
+    };
+}
+
 fn gc_entry() {
     loop {
         // Drop all exited tasks and recycle resources.
@@ -590,6 +1106,10 @@ pub(crate) fn init() {
     unsafe {
         RUN_QUEUES[cpu_id].write(RUN_QUEUE.current_ref_mut_raw());
     }
+    #[cfg(feature = "smp")]
+    RUN_QUEUE_READY[cpu_id].store(true, Ordering::Release);
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    crate::hotplug::set_online(cpu_id, true);
 }
 
 pub(crate) fn init_secondary() {
@@ -609,4 +1129,96 @@ pub(crate) fn init_secondary() {
     unsafe {
         RUN_QUEUES[cpu_id].write(RUN_QUEUE.current_ref_mut_raw());
     }
+    #[cfg(feature = "smp")]
+    RUN_QUEUE_READY[cpu_id].store(true, Ordering::Release);
+    #[cfg(all(feature = "smp", feature = "irq"))]
+    crate::hotplug::set_online(cpu_id, false);
+}
+
+/// Resumes scheduling on a secondary CPU that comes back online.
+///
+/// The CPU restarts on its boot stack, which is the stack of its idle task, so
+/// the idle task is still the current task.
+#[cfg(all(feature = "smp", feature = "irq"))]
+pub(crate) fn reinit_secondary() {
+    let cpu_id = this_cpu_id();
+    let idle_task = unsafe { IDLE_TASK.current_ref_raw().get_unchecked() };
+    // The register holding the current task pointer may have been lost, the
+    // reference counted by it is still held.
+    unsafe { axhal::cpu::set_current_task_ptr(Arc::as_ptr(idle_task)) };
+    RUN_QUEUE_READY[cpu_id].store(true, Ordering::Release);
+    crate::hotplug::set_online(cpu_id, false);
+}
+
+/// Whether `task` is the GC task of CPU `cpu_id`.
+#[cfg(all(feature = "smp", feature = "irq"))]
+pub(crate) fn is_gc_task(task: &AxTaskRef, cpu_id: usize) -> bool {
+    get_run_queue(cpu_id).gc_task_id == task.id()
+}
+
+/// Stops placing tasks on the run queue of `cpu_id`, and kicks the CPU to
+/// switch to its idle task, which then calls [`migrate_tasks_away`].
+#[cfg(all(feature = "smp", feature = "irq"))]
+pub(crate) fn stop_run_queue(cpu_id: usize) {
+    RUN_QUEUE_READY[cpu_id].store(false, Ordering::Release);
+    // Other CPUs may be putting tasks into the run queue, with IRQs disabled.
+    // Once they have handled an IPI, they must have seen it's not ready.
+    axhal::ipi::run_on_other_cpus(|_| {}, 0, true);
+    get_run_queue(cpu_id).kick_if_needed(true);
+}
+
+/// Migrates all ready tasks of the current CPU, which is going offline, to
+/// other CPUs allowed by their affinity, except the GC task. The exited tasks
+/// are handed over to the GC task of CPU `target`.
+///
+/// It must be called by the idle task with IRQs disabled.
+#[cfg(all(feature = "smp", feature = "irq"))]
+pub(crate) fn migrate_tasks_away(target: usize) {
+    let rq = get_run_queue(this_cpu_id());
+    let mut scheduler = rq.scheduler.lock();
+    let mut tasks = alloc::vec::Vec::with_capacity(rq.nr_ready.load(Ordering::Relaxed));
+    while let Some(task) = scheduler.pick_next_task() {
+        tasks.push(task);
+    }
+    rq.nr_ready.store(0, Ordering::Relaxed);
+    if let Some(index) = tasks.iter().position(|task| task.id() == rq.gc_task_id) {
+        scheduler.add_task(tasks.remove(index));
+        rq.nr_ready.store(1, Ordering::Relaxed);
+    }
+    drop(scheduler);
+
+    for task in tasks {
+        let cpumask = task.cpumask();
+        if !(0..axconfig::SMP).any(|cpu_id| cpumask.get(cpu_id) && crate::cpu_online(cpu_id)) {
+            // It's checked by `offline_cpu()`, but the task may have been
+            // spawned with its affinity set to offline CPUs.
+            warn!(
+                "task {} has no online CPU to run, reset its affinity",
+                task.id_name()
+            );
+            task.set_cpumask(AxCpuMask::full());
+        }
+        debug!("task migrate: {} off CPU {}", task.id_name(), rq.cpu_id);
+        select_run_queue::<NoOp>(&task).add_task(task);
+    }
+
+    let exited_tasks = unsafe { core::mem::take(EXITED_TASKS.current_ref_mut_raw()) };
+    if !exited_tasks.is_empty() {
+        let arg = alloc::boxed::Box::into_raw(alloc::boxed::Box::new(exited_tasks)) as usize;
+        if !axhal::ipi::run_on_cpu(target, receive_exited_tasks, arg, true) {
+            // The target has just gone offline, let them be dropped later.
+            let exited_tasks = unsafe { alloc::boxed::Box::from_raw(arg as *mut VecDeque<_>) };
+            unsafe { EXITED_TASKS.current_ref_mut_raw().extend(*exited_tasks) };
+        }
+    }
+}
+
+#[cfg(all(feature = "smp", feature = "irq"))]
+fn receive_exited_tasks(arg: usize) {
+    let exited_tasks = unsafe { alloc::boxed::Box::from_raw(arg as *mut VecDeque<AxTaskRef>) };
+    // Safety: IRQs are disabled in IPI handlers.
+    unsafe {
+        EXITED_TASKS.current_ref_mut_raw().extend(*exited_tasks);
+        WAIT_FOR_EXIT.current_ref_mut_raw().notify_one(false);
+    }
 }
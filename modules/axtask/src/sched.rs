@@ -0,0 +1,304 @@
+//! Scheduling policies and per-task scheduling attributes.
+
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicIsize, AtomicU8, Ordering};
+use core::time::Duration;
+
+use scheduler::BaseScheduler;
+
+use crate::AxTaskRef;
+
+#[cfg(feature = "sched_rt")]
+use core::sync::atomic::{AtomicBool, AtomicUsize};
+
+/// The lowest priority of real-time tasks.
+pub const MIN_RT_PRIORITY: isize = 1;
+/// The highest priority of real-time tasks.
+pub const MAX_RT_PRIORITY: isize = 99;
+
+/// Scheduling policy of a task.
+#[repr(u8)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SchedPolicy {
+    /// The default time-sharing policy, scheduled by the scheduler selected by
+    /// cargo features (e.g., the CFS). The meaning of its priority depends on
+    /// that scheduler, e.g., the nice value in CFS.
+    Normal = 0,
+    /// The real-time first-in first-out policy. A task runs until it blocks,
+    /// yields, or is preempted by a real-time task with a higher priority.
+    ///
+    /// The priority ranges from [`MIN_RT_PRIORITY`] to [`MAX_RT_PRIORITY`], a
+    /// larger value means a higher priority.
+    Fifo = 1,
+    /// The real-time round-robin policy. It's the same as [`SchedPolicy::Fifo`],
+    /// except that tasks with the same priority share the CPU in time slices.
+    RoundRobin = 2,
+    /// The earliest-deadline-first policy for periodic tasks, see
+    /// [`DeadlineParams`]. Deadline tasks run before all the other tasks, and
+    /// the one with the earliest deadline runs first.
+    ///
+    /// It's set by [`set_current_deadline`](crate::set_current_deadline) or
+    /// [`TaskInner::set_deadline_params`](crate::TaskInner::set_deadline_params),
+    /// the priority is not used.
+    Deadline = 3,
+}
+
+/// Parameters of a periodic task with the [`SchedPolicy::Deadline`] policy.
+///
+/// In every `period`, the task is guaranteed to run for `runtime` before the
+/// relative `deadline`, and it's throttled until the next period if it runs
+/// longer than `runtime`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct DeadlineParams {
+    /// The CPU time budget in each period.
+    pub runtime: Duration,
+    /// The deadline relative to the start of each period.
+    pub deadline: Duration,
+    /// The length of each period.
+    pub period: Duration,
+}
+
+impl DeadlineParams {
+    /// Whether `0 < runtime <= deadline <= period` holds.
+    pub fn is_valid(&self) -> bool {
+        !self.runtime.is_zero() && self.runtime <= self.deadline && self.deadline <= self.period
+    }
+}
+
+impl SchedPolicy {
+    /// Whether this is a real-time policy.
+    pub const fn is_realtime(self) -> bool {
+        matches!(self, Self::Fifo | Self::RoundRobin)
+    }
+
+    /// Whether this is the deadline policy.
+    pub const fn is_deadline(self) -> bool {
+        matches!(self, Self::Deadline)
+    }
+
+    /// Whether the policy is supported by the enabled scheduler features.
+    pub const fn is_supported(self) -> bool {
+        match self {
+            Self::Normal => true,
+            Self::Fifo | Self::RoundRobin => cfg!(feature = "sched_rt"),
+            Self::Deadline => cfg!(feature = "sched_edf"),
+        }
+    }
+
+    /// Whether the policy is scheduled by another class than `other`.
+    #[cfg(feature = "sched_rt")]
+    const fn class_differs(self, other: Self) -> bool {
+        self.is_realtime() != other.is_realtime() || self.is_deadline() != other.is_deadline()
+    }
+
+    /// Whether `prio` is a valid priority of this policy.
+    ///
+    /// For [`SchedPolicy::Normal`], the range is checked only if it is known
+    /// without the scheduler, i.e., the nice value of the CFS in the `sched_rt`
+    /// scheduler.
+    pub(crate) const fn is_valid_priority(self, prio: isize) -> bool {
+        if self.is_realtime() {
+            MIN_RT_PRIORITY <= prio && prio <= MAX_RT_PRIORITY
+        } else if self.is_deadline() {
+            // Use `DeadlineParams` instead.
+            false
+        } else {
+            cfg!(not(feature = "sched_rt")) || (-20 <= prio && prio <= 19)
+        }
+    }
+}
+
+impl From<u8> for SchedPolicy {
+    #[inline]
+    fn from(policy: u8) -> Self {
+        match policy {
+            0 => Self::Normal,
+            1 => Self::Fifo,
+            2 => Self::RoundRobin,
+            3 => Self::Deadline,
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Scheduling attributes of a task.
+pub(crate) struct SchedAttr {
+    policy: AtomicU8,
+    priority: AtomicIsize,
+    /// Remaining time slice (in ticks) of a [`SchedPolicy::RoundRobin`] task,
+    /// or of any task with the round-robin scheduler (`sched_rr`).
+    #[cfg(any(feature = "sched_rt", feature = "sched_rr"))]
+    rt_time_slice: AtomicIsize,
+    /// The priority with which the task is queued in the real-time run queue,
+    /// or 0 if it's not in that queue.
+    #[cfg(feature = "sched_rt")]
+    queued_rt_prio: AtomicUsize,
+    /// Whether the policy has been changed between the real-time class and
+    /// the normal class since the task was queued last time.
+    #[cfg(feature = "sched_rt")]
+    class_changed: AtomicBool,
+    /// States of a [`SchedPolicy::Deadline`] task.
+    #[cfg(feature = "sched_edf")]
+    dl: crate::sched_edf::DeadlineState,
+}
+
+impl SchedAttr {
+    pub const fn new() -> Self {
+        Self {
+            policy: AtomicU8::new(SchedPolicy::Normal as u8),
+            priority: AtomicIsize::new(0),
+            #[cfg(any(feature = "sched_rt", feature = "sched_rr"))]
+            rt_time_slice: AtomicIsize::new(0),
+            #[cfg(feature = "sched_rt")]
+            queued_rt_prio: AtomicUsize::new(0),
+            #[cfg(feature = "sched_rt")]
+            class_changed: AtomicBool::new(false),
+            #[cfg(feature = "sched_edf")]
+            dl: crate::sched_edf::DeadlineState::new(),
+        }
+    }
+
+    #[inline]
+    pub fn policy(&self) -> SchedPolicy {
+        self.policy.load(Ordering::Acquire).into()
+    }
+
+    #[inline]
+    pub fn priority(&self) -> isize {
+        self.priority.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn set_priority(&self, prio: isize) {
+        self.priority.store(prio, Ordering::Release)
+    }
+
+    /// Sets both the policy and the priority, the caller must have checked
+    /// that they are valid.
+    pub fn set_policy(&self, policy: SchedPolicy, prio: isize) {
+        let old_policy: SchedPolicy = self.policy.swap(policy as u8, Ordering::AcqRel).into();
+        self.set_priority(prio);
+        #[cfg(feature = "sched_rt")]
+        if old_policy.class_differs(policy) {
+            self.class_changed.store(true, Ordering::Release);
+        }
+        #[cfg(feature = "sched_edf")]
+        if old_policy.is_deadline() && !policy.is_deadline() {
+            self.dl.release_bandwidth();
+        }
+        #[cfg(not(feature = "sched_rt"))]
+        let _ = old_policy;
+    }
+
+    /// Returns the deadline states of the task.
+    #[cfg(feature = "sched_edf")]
+    #[inline]
+    pub fn dl(&self) -> &crate::sched_edf::DeadlineState {
+        &self.dl
+    }
+}
+
+#[cfg(any(feature = "sched_rt", feature = "sched_rr"))]
+impl SchedAttr {
+    #[inline]
+    pub fn rt_time_slice(&self) -> isize {
+        self.rt_time_slice.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn set_rt_time_slice(&self, slice: isize) {
+        self.rt_time_slice.store(slice, Ordering::Release)
+    }
+
+    /// Consumes one tick of the time slice, returns the remaining ticks.
+    #[inline]
+    pub fn tick_rt_time_slice(&self) -> isize {
+        self.rt_time_slice.fetch_sub(1, Ordering::AcqRel) - 1
+    }
+}
+
+#[cfg(feature = "sched_rt")]
+impl SchedAttr {
+    #[inline]
+    pub fn queued_rt_prio(&self) -> usize {
+        self.queued_rt_prio.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn set_queued_rt_prio(&self, prio: usize) {
+        self.queued_rt_prio.store(prio, Ordering::Release)
+    }
+
+    /// Whether a task with these attributes should preempt a running task with
+    /// the `other` attributes.
+    #[inline]
+    pub fn preempts(&self, other: &SchedAttr) -> bool {
+        let (policy, other_policy) = (self.policy(), other.policy());
+        #[cfg(feature = "sched_edf")]
+        if policy.is_deadline() {
+            return !other_policy.is_deadline() || self.dl.earlier_than(&other.dl);
+        } else if other_policy.is_deadline() {
+            return false;
+        }
+        policy.is_realtime() && (!other_policy.is_realtime() || self.priority() > other.priority())
+    }
+
+    /// Returns whether the scheduling class has changed, and clears the flag.
+    #[inline]
+    pub fn take_class_changed(&self) -> bool {
+        self.class_changed.swap(false, Ordering::AcqRel)
+    }
+}
+
+/// Load balancing operations of schedulers.
+///
+/// The [`BaseScheduler`] trait can only pick the next task to run, this adds
+/// stealing a ready task that is allowed to run on another CPU.
+#[cfg_attr(not(feature = "smp"), allow(dead_code))]
+pub(crate) trait StealTask: BaseScheduler<SchedItem = AxTaskRef> + Sized {
+    /// Removes the first ready task, in the order they would be picked, for
+    /// which `allowed` returns `true`, and returns it. The other tasks keep
+    /// their order.
+    ///
+    /// By default, the tasks before it are picked out and put back (see
+    /// [`steal_by_picking`]), which only keeps the order of schedulers whose
+    /// queues are ordered by keys, e.g., the CFS.
+    fn steal_task<F>(&mut self, allowed: F) -> Option<AxTaskRef>
+    where
+        F: Fn(&AxTaskRef) -> bool,
+    {
+        steal_by_picking(self, allowed)
+    }
+}
+
+/// Steals a task by picking out tasks until one is allowed, for schedulers
+/// that can't be searched.
+///
+/// Only the skipped tasks are put back. They get their positions back only if
+/// the queue is ordered by keys (e.g., the virtual runtime in the CFS), so
+/// schedulers with plain queues must remove tasks in place instead.
+#[cfg_attr(not(feature = "smp"), allow(dead_code))]
+pub(crate) fn steal_by_picking<S, F>(scheduler: &mut S, allowed: F) -> Option<S::SchedItem>
+where
+    S: BaseScheduler,
+    F: Fn(&S::SchedItem) -> bool,
+{
+    let mut skipped = Vec::new();
+    let stolen = loop {
+        match scheduler.pick_next_task() {
+            Some(task) if allowed(&task) => break Some(task),
+            Some(task) => skipped.push(task),
+            None => break None,
+        }
+    };
+    for task in skipped {
+        scheduler.put_prev_task(task, false);
+    }
+    stolen
+}
+
+#[cfg(all(
+    feature = "sched_cfs",
+    not(any(feature = "sched_rr", feature = "sched_rt"))
+))]
+impl StealTask for crate::Scheduler {}
@@ -0,0 +1,509 @@
+//! The earliest-deadline-first (EDF) class of the multi-class scheduler.
+//!
+//! Deadline tasks are periodic tasks described by [`DeadlineParams`]. They are
+//! partitioned among CPUs: a deadline task is bound to one CPU, and it's
+//! admitted only if the total bandwidth (`runtime / deadline`) of the deadline
+//! tasks on that CPU does not exceed 1.
+//!
+//! Each task has a budget of `runtime` for each job (period), which is consumed
+//! while it runs. When it's exhausted, the task is throttled until the next
+//! period, by sleeping on the per-CPU timer list. The next job starts when the
+//! task is woken up at the next period, either after throttled or after
+//! [`wait_next_period`](crate::wait_next_period). When a task is woken up for
+//! other reasons, it continues the current job only if the remaining budget
+//! fits in the remaining time before the deadline, otherwise a new job starts
+//! (the rule of the constant bandwidth server).
+
+use alloc::collections::BTreeMap;
+use core::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axhal::time::{monotonic_time_nanos, wall_time, TimeValue};
+
+use crate::sched::DeadlineParams;
+use crate::{AxCpuMask, AxTaskRef};
+
+/// Bandwidths are fixed-point numbers with `BW_SHIFT` fractional bits.
+const BW_SHIFT: u32 = 20;
+/// The bandwidth of a whole CPU.
+const BW_UNIT: u64 = 1 << BW_SHIFT;
+
+/// The total bandwidth reserved by the deadline tasks on each CPU.
+static CPU_BANDWIDTH: [AtomicU64; axconfig::SMP] = [BW_REPEAT_VALUE; axconfig::SMP];
+#[allow(clippy::declare_interior_mutable_const)]
+const BW_REPEAT_VALUE: AtomicU64 = AtomicU64::new(0);
+
+fn bandwidth_of(params: &DeadlineParams) -> u64 {
+    // It never exceeds `BW_UNIT` since `runtime <= deadline`.
+    ((params.runtime.as_nanos() << BW_SHIFT) / params.deadline.as_nanos()) as u64
+}
+
+/// Converts a monotonic timestamp (in nanoseconds) to the wall time, which is
+/// used by the timer list.
+pub(crate) fn wall_time_at(mono_ns: u64) -> TimeValue {
+    let now = monotonic_time_nanos();
+    wall_time() + Duration::from_nanos(mono_ns.saturating_sub(now))
+}
+
+/// Parameters and runtime states of a deadline task.
+///
+/// All timestamps are monotonic time in nanoseconds.
+pub(crate) struct DeadlineState {
+    runtime_ns: AtomicU64,
+    deadline_ns: AtomicU64,
+    period_ns: AtomicU64,
+    /// The bandwidth reserved by the task, or 0 if it has not been admitted.
+    bandwidth: AtomicU64,
+    /// The CPU on which the bandwidth is reserved.
+    cpu: AtomicUsize,
+    /// The absolute deadline of the current job.
+    abs_deadline_ns: AtomicU64,
+    /// The start of the next period.
+    next_period_ns: AtomicU64,
+    /// The remaining budget of the current job.
+    runtime_left_ns: AtomicI64,
+    /// When the budget was charged last time.
+    exec_start_ns: AtomicU64,
+    /// Whether the task is running, i.e., its budget is being consumed.
+    running: AtomicBool,
+    /// Whether the current job has ended, and the next one starts at the
+    /// next period.
+    job_ended: AtomicBool,
+    /// Whether the current job has missed its deadline.
+    missed: AtomicBool,
+    nr_misses: AtomicU64,
+    /// Whether the task is in a [`DlRunQueue`].
+    queued: AtomicBool,
+}
+
+impl DeadlineState {
+    pub const fn new() -> Self {
+        Self {
+            runtime_ns: AtomicU64::new(0),
+            deadline_ns: AtomicU64::new(0),
+            period_ns: AtomicU64::new(0),
+            bandwidth: AtomicU64::new(0),
+            cpu: AtomicUsize::new(0),
+            abs_deadline_ns: AtomicU64::new(0),
+            next_period_ns: AtomicU64::new(0),
+            runtime_left_ns: AtomicI64::new(0),
+            exec_start_ns: AtomicU64::new(0),
+            running: AtomicBool::new(false),
+            job_ended: AtomicBool::new(false),
+            missed: AtomicBool::new(false),
+            nr_misses: AtomicU64::new(0),
+            queued: AtomicBool::new(false),
+        }
+    }
+
+    /// Performs the admission test for the new parameters on the CPUs in
+    /// `cpumask`, and reserves the bandwidth on the first CPU where it passes.
+    /// The bandwidth previously reserved by the task is replaced.
+    ///
+    /// On success, a new job is started, and the CPU that the task must be
+    /// bound to is returned. Returns [`None`] if the parameters are invalid or
+    /// the task set of every CPU would not be schedulable.
+    pub fn reserve(&self, params: &DeadlineParams, cpumask: AxCpuMask) -> Option<usize> {
+        if !params.is_valid() {
+            return None;
+        }
+        let old_bw = self.bandwidth.load(Ordering::Acquire);
+        let old_cpu = self.cpu.load(Ordering::Acquire);
+        let new_bw = bandwidth_of(params);
+        let cpu = (0..axconfig::SMP)
+            .filter(|&cpu| cpumask.get(cpu))
+            .find(|&cpu| {
+                // Replace the old bandwidth if it's reserved on the same CPU.
+                let old_bw = if cpu == old_cpu { old_bw } else { 0 };
+                CPU_BANDWIDTH[cpu]
+                    .fetch_update(Ordering::AcqRel, Ordering::Acquire, |total| {
+                        let total = total - old_bw + new_bw;
+                        (total <= BW_UNIT).then_some(total)
+                    })
+                    .is_ok()
+            })?;
+        if cpu != old_cpu {
+            CPU_BANDWIDTH[old_cpu].fetch_sub(old_bw, Ordering::AcqRel);
+        }
+        self.bandwidth.store(new_bw, Ordering::Release);
+        self.cpu.store(cpu, Ordering::Release);
+        self.runtime_ns
+            .store(params.runtime.as_nanos() as u64, Ordering::Release);
+        self.deadline_ns
+            .store(params.deadline.as_nanos() as u64, Ordering::Release);
+        self.period_ns
+            .store(params.period.as_nanos() as u64, Ordering::Release);
+
+        self.start_new_job(monotonic_time_nanos());
+        Some(cpu)
+    }
+
+    /// Releases the bandwidth reserved by the task, e.g., when it exits or
+    /// leaves the deadline policy.
+    pub fn release_bandwidth(&self) {
+        let bw = self.bandwidth.swap(0, Ordering::AcqRel);
+        CPU_BANDWIDTH[self.cpu.load(Ordering::Acquire)].fetch_sub(bw, Ordering::AcqRel);
+    }
+
+    pub fn params(&self) -> DeadlineParams {
+        DeadlineParams {
+            runtime: Duration::from_nanos(self.runtime_ns.load(Ordering::Acquire)),
+            deadline: Duration::from_nanos(self.deadline_ns.load(Ordering::Acquire)),
+            period: Duration::from_nanos(self.period_ns.load(Ordering::Acquire)),
+        }
+    }
+
+    #[inline]
+    pub fn abs_deadline_ns(&self) -> u64 {
+        self.abs_deadline_ns.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn next_period_ns(&self) -> u64 {
+        self.next_period_ns.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub fn nr_misses(&self) -> u64 {
+        self.nr_misses.load(Ordering::Acquire)
+    }
+
+    /// Whether the absolute deadline of this task is earlier than `other`.
+    #[inline]
+    pub fn earlier_than(&self, other: &DeadlineState) -> bool {
+        self.abs_deadline_ns() < other.abs_deadline_ns()
+    }
+
+    /// Whether the budget of the current job has been used up.
+    #[inline]
+    pub fn is_exhausted(&self) -> bool {
+        self.runtime_left_ns.load(Ordering::Acquire) <= 0
+    }
+
+    /// Starts a new job at `now`, with a full budget.
+    pub fn start_new_job(&self, now: u64) {
+        self.job_ended.store(false, Ordering::Release);
+        let deadline_ns = self.deadline_ns.load(Ordering::Acquire);
+        let period_ns = self.period_ns.load(Ordering::Acquire);
+        let runtime_ns = self.runtime_ns.load(Ordering::Acquire);
+        self.abs_deadline_ns
+            .store(now + deadline_ns, Ordering::Release);
+        self.next_period_ns
+            .store(now + period_ns, Ordering::Release);
+        self.runtime_left_ns
+            .store(runtime_ns as i64, Ordering::Release);
+        self.missed.store(false, Ordering::Release);
+    }
+
+    /// Ends the current job, e.g., it has completed or used up its budget.
+    /// The next job starts when the task is woken up at the next period.
+    pub fn end_job(&self) {
+        self.job_ended.store(true, Ordering::Release);
+    }
+
+    /// Postpones the deadline of the current job by periods, and refills the
+    /// budget by `runtime` for each period, until the budget is available.
+    fn replenish(&self) {
+        let runtime_ns = self.runtime_ns.load(Ordering::Acquire);
+        let period_ns = self.period_ns.load(Ordering::Acquire);
+        let mut runtime_left = self.runtime_left_ns.load(Ordering::Acquire);
+        let mut periods = 0u64;
+        while runtime_left <= 0 {
+            runtime_left += runtime_ns as i64;
+            periods += 1;
+        }
+        self.abs_deadline_ns
+            .fetch_add(periods * period_ns, Ordering::AcqRel);
+        self.next_period_ns
+            .fetch_add(periods * period_ns, Ordering::AcqRel);
+        self.runtime_left_ns.store(runtime_left, Ordering::Release);
+    }
+
+    /// Called when the task is put into a run queue not being preempted,
+    /// e.g., it's woken up or it yields.
+    ///
+    /// If the current job has ended, the next one starts at the next period
+    /// (or now if it has passed). If the deadline has passed, a new job starts
+    /// now. If the budget has been used up, the deadline is postponed with the
+    /// budget replenished. Otherwise, the current job continues only if running
+    /// the remaining budget before the deadline does not exceed the reserved
+    /// bandwidth, or a new job starts.
+    fn on_enqueue(&self, now: u64) {
+        if self.job_ended.load(Ordering::Acquire) {
+            // The timer may fire a little earlier than the monotonic time of
+            // the next period, keep the periods aligned.
+            self.start_new_job(now.max(self.next_period_ns()));
+            return;
+        }
+        let abs_deadline_ns = self.abs_deadline_ns();
+        if now >= abs_deadline_ns {
+            self.start_new_job(now);
+            return;
+        }
+        if self.is_exhausted() {
+            self.replenish();
+            return;
+        }
+        let runtime_left = self.runtime_left_ns.load(Ordering::Acquire) as u128;
+        let runtime_ns = self.runtime_ns.load(Ordering::Acquire) as u128;
+        let deadline_ns = self.deadline_ns.load(Ordering::Acquire) as u128;
+        // runtime_left / (abs_deadline - now) > runtime / deadline
+        if runtime_left * deadline_ns > (abs_deadline_ns - now) as u128 * runtime_ns {
+            self.start_new_job(now);
+        }
+    }
+
+    /// Called when the task starts running, its budget is consumed since then.
+    #[inline]
+    pub fn start_running(&self, now: u64) {
+        self.exec_start_ns.store(now, Ordering::Release);
+        self.running.store(true, Ordering::Release);
+    }
+
+    /// Called when the task stops running, e.g., it's preempted or switched
+    /// out to block. Charges the CPU time since the last charge to the budget.
+    ///
+    /// Returns `false` if it was not running.
+    pub fn stop_running(&self, now: u64) -> bool {
+        if !self.running.swap(false, Ordering::AcqRel) {
+            return false;
+        }
+        self.update_runtime(now);
+        true
+    }
+
+    /// Charges the CPU time since the last charge to the budget.
+    fn update_runtime(&self, now: u64) {
+        let exec_start_ns = self.exec_start_ns.swap(now, Ordering::AcqRel);
+        let delta = now.saturating_sub(exec_start_ns) as i64;
+        self.runtime_left_ns.fetch_sub(delta, Ordering::AcqRel);
+    }
+
+    /// Records a deadline miss of the current job, returns `false` if it has
+    /// been recorded before.
+    pub fn record_miss(&self) -> bool {
+        if self.missed.swap(true, Ordering::AcqRel) {
+            false
+        } else {
+            self.nr_misses.fetch_add(1, Ordering::AcqRel);
+            true
+        }
+    }
+
+    /// Records a deadline miss if the deadline of the current job has passed.
+    pub fn check_miss(&self, now: u64) -> bool {
+        now > self.abs_deadline_ns() && self.record_miss()
+    }
+}
+
+/// Ready deadline tasks, sorted by their absolute deadlines.
+pub(crate) struct DlRunQueue {
+    tasks: BTreeMap<(u64, u64), AxTaskRef>,
+}
+
+impl DlRunQueue {
+    pub fn new() -> Self {
+        Self {
+            tasks: BTreeMap::new(),
+        }
+    }
+
+    fn key(task: &AxTaskRef) -> (u64, u64) {
+        (task.sched_attr().dl().abs_deadline_ns(), task.id().as_u64())
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Returns the earliest absolute deadline of queued tasks.
+    pub fn earliest_deadline(&self) -> Option<u64> {
+        self.tasks
+            .first_key_value()
+            .map(|((deadline, _), _)| *deadline)
+    }
+
+    /// Puts a task into this queue. `preempt` indicates whether the task is
+    /// preempted, which continues its current job if it was running.
+    ///
+    /// A task being woken up is not running, since its time has been charged
+    /// when it was switched out, so it's never regarded as preempted.
+    pub fn enqueue(&mut self, task: AxTaskRef, preempt: bool) {
+        let dl = task.sched_attr().dl();
+        let now = monotonic_time_nanos();
+        let was_running = dl.stop_running(now);
+        if !(preempt && was_running) {
+            dl.on_enqueue(now);
+        }
+        dl.queued.store(true, Ordering::Release);
+        self.tasks.insert(Self::key(&task), task);
+    }
+
+    pub fn pop_earliest(&mut self) -> Option<AxTaskRef> {
+        let (_, task) = self.tasks.pop_first()?;
+        let dl = task.sched_attr().dl();
+        dl.queued.store(false, Ordering::Release);
+        dl.start_running(monotonic_time_nanos());
+        Some(task)
+    }
+
+    pub fn remove(&mut self, task: &AxTaskRef) -> Option<AxTaskRef> {
+        let dl = task.sched_attr().dl();
+        if !dl.queued.load(Ordering::Acquire) {
+            return None;
+        }
+        let task = self.tasks.remove(&Self::key(task))?;
+        dl.queued.store(false, Ordering::Release);
+        Some(task)
+    }
+
+    /// Charges the running deadline task on a timer tick, returns `true` if it
+    /// should be rescheduled: its budget is used up, or another task has an
+    /// earlier deadline.
+    pub fn task_tick(&self, current: &AxTaskRef) -> bool {
+        let dl = current.sched_attr().dl();
+        let now = monotonic_time_nanos();
+        dl.update_runtime(now);
+        if dl.check_miss(now) {
+            warn!("deadline miss: {}", current.id_name());
+        }
+        dl.is_exhausted()
+            || self
+                .earliest_deadline()
+                .is_some_and(|deadline| deadline < dl.abs_deadline_ns())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    /// Serializes the tests, which reserve bandwidth on the same CPU.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    const MS: u64 = 1_000_000;
+
+    fn params(runtime_ms: u64, deadline_ms: u64, period_ms: u64) -> DeadlineParams {
+        DeadlineParams {
+            runtime: Duration::from_millis(runtime_ms),
+            deadline: Duration::from_millis(deadline_ms),
+            period: Duration::from_millis(period_ms),
+        }
+    }
+
+    fn runtime_left(dl: &DeadlineState) -> i64 {
+        dl.runtime_left_ns.load(Ordering::Acquire)
+    }
+
+    #[test]
+    fn test_admission() {
+        let _lock = SERIAL.lock();
+        let (a, b) = (DeadlineState::new(), DeadlineState::new());
+        assert_eq!(a.reserve(&params(0, 10, 10), AxCpuMask::full()), None);
+        assert_eq!(a.reserve(&params(6, 5, 10), AxCpuMask::full()), None);
+
+        // Each CPU is full with 6/10 + 6/10.
+        let full = (0..axconfig::SMP)
+            .map(|_| {
+                let dl = DeadlineState::new();
+                assert!(dl.reserve(&params(6, 10, 10), AxCpuMask::full()).is_some());
+                dl
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(a.reserve(&params(6, 10, 10), AxCpuMask::full()), None);
+        assert_eq!(
+            a.reserve(&params(4, 10, 20), AxCpuMask::one_shot(0)),
+            Some(0)
+        );
+        // Replacing its own bandwidth on the same CPU.
+        assert_eq!(
+            a.reserve(&params(3, 10, 10), AxCpuMask::one_shot(0)),
+            Some(0)
+        );
+        assert_eq!(b.reserve(&params(2, 10, 10), AxCpuMask::one_shot(0)), None);
+
+        a.release_bandwidth();
+        assert_eq!(
+            b.reserve(&params(2, 10, 10), AxCpuMask::one_shot(0)),
+            Some(0)
+        );
+        b.release_bandwidth();
+        full.iter().for_each(DeadlineState::release_bandwidth);
+    }
+
+    #[test]
+    fn test_runtime_accounting() {
+        let _lock = SERIAL.lock();
+        let dl = DeadlineState::new();
+        assert!(dl.reserve(&params(2, 5, 10), AxCpuMask::full()).is_some());
+        dl.start_new_job(0);
+        assert_eq!(dl.abs_deadline_ns(), 5 * MS);
+        assert_eq!(dl.next_period_ns(), 10 * MS);
+
+        dl.start_running(0);
+        dl.update_runtime(MS);
+        assert_eq!(runtime_left(&dl), MS as i64);
+        // Preempted, then picked again after 1ms.
+        assert!(dl.stop_running(MS + MS / 2));
+        dl.start_running(2 * MS + MS / 2);
+        // Switched out to block, the sleep time is not charged.
+        assert!(dl.stop_running(3 * MS));
+        assert_eq!(runtime_left(&dl), 0);
+        assert!(!dl.stop_running(4 * MS));
+        assert_eq!(runtime_left(&dl), 0);
+        assert!(dl.is_exhausted());
+
+        // Woken up before the deadline without budget, the deadline is
+        // postponed by a period.
+        dl.on_enqueue(4 * MS);
+        assert!(!dl.is_exhausted());
+        assert_eq!(runtime_left(&dl), 2 * MS as i64);
+        assert_eq!(dl.abs_deadline_ns(), 15 * MS);
+        assert_eq!(dl.next_period_ns(), 20 * MS);
+        dl.release_bandwidth();
+    }
+
+    #[test]
+    fn test_deadline_and_period() {
+        let _lock = SERIAL.lock();
+        let dl = DeadlineState::new();
+        assert!(dl.reserve(&params(2, 5, 10), AxCpuMask::full()).is_some());
+        dl.start_new_job(0);
+
+        // Woken up with the remaining budget fitting in the remaining time.
+        dl.start_running(0);
+        assert!(dl.stop_running(MS));
+        dl.on_enqueue(2 * MS);
+        assert_eq!(dl.abs_deadline_ns(), 5 * MS);
+        assert_eq!(runtime_left(&dl), MS as i64);
+        // Woken up with too much budget left, a new job starts.
+        dl.on_enqueue(4 * MS);
+        assert_eq!(dl.abs_deadline_ns(), 9 * MS);
+        assert_eq!(dl.next_period_ns(), 14 * MS);
+
+        // Missing the deadline is recorded once for each job.
+        assert!(!dl.check_miss(9 * MS));
+        assert!(dl.check_miss(10 * MS));
+        assert!(!dl.check_miss(11 * MS));
+        assert_eq!(dl.nr_misses(), 1);
+        // Woken up after the deadline, a new job starts now.
+        dl.on_enqueue(12 * MS);
+        assert_eq!(dl.abs_deadline_ns(), 17 * MS);
+        assert_eq!(runtime_left(&dl), 2 * MS as i64);
+
+        // The job is completed, and the task is woken up a little early for
+        // the next period: the next job starts at the period.
+        dl.start_running(12 * MS);
+        assert!(dl.stop_running(13 * MS));
+        dl.end_job();
+        dl.on_enqueue(22 * MS - 1);
+        assert_eq!(dl.abs_deadline_ns(), 27 * MS);
+        assert_eq!(dl.next_period_ns(), 32 * MS);
+        assert_eq!(runtime_left(&dl), 2 * MS as i64);
+        assert!(!dl.check_miss(23 * MS));
+        dl.release_bandwidth();
+    }
+}
@@ -0,0 +1,169 @@
+//! Schedulers with a single ready queue: the cooperative FIFO scheduler and
+//! the preemptive round-robin scheduler.
+//!
+//! They work like those in the `scheduler` crate, except that the ready queue
+//! can be searched, so that a task stolen by another CPU is removed in place,
+//! and the other tasks keep their order.
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+use scheduler::{BaseScheduler, FifoTask};
+
+use crate::sched::StealTask;
+use crate::task::TaskInner;
+
+type SchedItem = Arc<FifoTask<TaskInner>>;
+
+/// Removes `task` from `queue` in place.
+fn remove_from(queue: &mut VecDeque<SchedItem>, task: &SchedItem) -> Option<SchedItem> {
+    queue
+        .iter()
+        .position(|t| Arc::ptr_eq(t, task))
+        .and_then(|index| queue.remove(index))
+}
+
+/// Removes the first task in `queue` for which `allowed` returns `true`.
+fn steal_from<F>(queue: &mut VecDeque<SchedItem>, allowed: F) -> Option<SchedItem>
+where
+    F: Fn(&SchedItem) -> bool,
+{
+    let index = queue.iter().position(allowed)?;
+    queue.remove(index)
+}
+
+/// The cooperative FIFO scheduler. Tasks run in the order they become ready,
+/// and the running task is never preempted.
+#[cfg(not(feature = "sched_rr"))]
+pub(crate) struct FifoScheduler {
+    ready_queue: VecDeque<SchedItem>,
+}
+
+#[cfg(not(feature = "sched_rr"))]
+impl FifoScheduler {
+    /// Creates a new empty scheduler.
+    pub const fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+
+    /// Returns the name of the scheduler.
+    pub fn scheduler_name() -> &'static str {
+        "FIFO"
+    }
+}
+
+#[cfg(not(feature = "sched_rr"))]
+impl BaseScheduler for FifoScheduler {
+    type SchedItem = SchedItem;
+
+    fn init(&mut self) {}
+
+    fn add_task(&mut self, task: Self::SchedItem) {
+        self.ready_queue.push_back(task);
+    }
+
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem> {
+        remove_from(&mut self.ready_queue, task)
+    }
+
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
+        self.ready_queue.pop_front()
+    }
+
+    fn put_prev_task(&mut self, prev: Self::SchedItem, _preempt: bool) {
+        self.ready_queue.push_back(prev);
+    }
+
+    fn task_tick(&mut self, _current: &Self::SchedItem) -> bool {
+        false
+    }
+
+    fn set_priority(&mut self, _task: &Self::SchedItem, _prio: isize) -> bool {
+        false
+    }
+}
+
+#[cfg(not(feature = "sched_rr"))]
+impl StealTask for FifoScheduler {
+    fn steal_task<F>(&mut self, allowed: F) -> Option<SchedItem>
+    where
+        F: Fn(&SchedItem) -> bool,
+    {
+        steal_from(&mut self.ready_queue, allowed)
+    }
+}
+
+/// The preemptive round-robin scheduler. The running task is preempted after
+/// running for `TIME_SLICE` ticks, and put back to the end of the queue.
+///
+/// The remaining time slice is kept in the scheduling attributes of tasks.
+#[cfg(feature = "sched_rr")]
+pub(crate) struct RRScheduler<const TIME_SLICE: isize> {
+    ready_queue: VecDeque<SchedItem>,
+}
+
+#[cfg(feature = "sched_rr")]
+impl<const TIME_SLICE: isize> RRScheduler<TIME_SLICE> {
+    /// Creates a new empty scheduler.
+    pub const fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+
+    /// Returns the name of the scheduler.
+    pub fn scheduler_name() -> &'static str {
+        "Round-robin"
+    }
+}
+
+#[cfg(feature = "sched_rr")]
+impl<const TIME_SLICE: isize> BaseScheduler for RRScheduler<TIME_SLICE> {
+    type SchedItem = SchedItem;
+
+    fn init(&mut self) {}
+
+    fn add_task(&mut self, task: Self::SchedItem) {
+        task.sched_attr().set_rt_time_slice(TIME_SLICE);
+        self.ready_queue.push_back(task);
+    }
+
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem> {
+        remove_from(&mut self.ready_queue, task)
+    }
+
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
+        self.ready_queue.pop_front()
+    }
+
+    fn put_prev_task(&mut self, prev: Self::SchedItem, preempt: bool) {
+        // A preempted task runs the rest of its time slice first.
+        let attr = prev.sched_attr();
+        if preempt && attr.rt_time_slice() > 0 {
+            self.ready_queue.push_front(prev);
+        } else {
+            attr.set_rt_time_slice(TIME_SLICE);
+            self.ready_queue.push_back(prev);
+        }
+    }
+
+    fn task_tick(&mut self, current: &Self::SchedItem) -> bool {
+        current.sched_attr().tick_rt_time_slice() <= 0
+    }
+
+    fn set_priority(&mut self, _task: &Self::SchedItem, _prio: isize) -> bool {
+        false
+    }
+}
+
+#[cfg(feature = "sched_rr")]
+impl<const TIME_SLICE: isize> StealTask for RRScheduler<TIME_SLICE> {
+    fn steal_task<F>(&mut self, allowed: F) -> Option<SchedItem>
+    where
+        F: Fn(&SchedItem) -> bool,
+    {
+        steal_from(&mut self.ready_queue, allowed)
+    }
+}
@@ -0,0 +1,303 @@
+//! A multi-class scheduler: a real-time class on top of the CFS.
+//!
+//! Real-time tasks ([`SchedPolicy::Fifo`] and [`SchedPolicy::RoundRobin`])
+//! always run before normal tasks, which are scheduled by the CFS. Among
+//! real-time tasks, the one with the highest priority runs first.
+//!
+//! With the `sched_edf` feature, there is also a deadline class on top of the
+//! real-time class, see [`crate::sched_edf`].
+
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+use scheduler::{BaseScheduler, CFSTask, CFScheduler};
+
+use crate::sched::{steal_by_picking, SchedPolicy, StealTask, MAX_RT_PRIORITY};
+#[cfg(feature = "sched_edf")]
+use crate::sched_edf::DlRunQueue;
+use crate::task::TaskInner;
+
+/// The time slice (in ticks) of [`SchedPolicy::RoundRobin`] tasks.
+const RT_TIME_SLICE: isize = 5;
+
+const NUM_RT_PRIO: usize = MAX_RT_PRIORITY as usize + 1;
+
+type SchedItem = Arc<CFSTask<TaskInner>>;
+
+/// Ready queues of real-time tasks, one for each priority.
+struct RtRunQueue {
+    queues: [VecDeque<SchedItem>; NUM_RT_PRIO],
+    /// Bit `i` is set if `queues[i]` is not empty.
+    bitmap: u128,
+}
+
+impl RtRunQueue {
+    fn new() -> Self {
+        Self {
+            queues: core::array::from_fn(|_| VecDeque::new()),
+            bitmap: 0,
+        }
+    }
+
+    /// Returns the highest priority of queued tasks, or 0 if it's empty.
+    fn highest_prio(&self) -> usize {
+        if self.bitmap == 0 {
+            0
+        } else {
+            127 - self.bitmap.leading_zeros() as usize
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bitmap == 0
+    }
+
+    fn enqueue(&mut self, task: SchedItem, front: bool) {
+        let prio = task.sched_attr().priority() as usize;
+        task.sched_attr().set_queued_rt_prio(prio);
+        if front {
+            self.queues[prio].push_front(task);
+        } else {
+            self.queues[prio].push_back(task);
+        }
+        self.bitmap |= 1 << prio;
+    }
+
+    fn pop_highest(&mut self) -> Option<SchedItem> {
+        if self.is_empty() {
+            return None;
+        }
+        let prio = self.highest_prio();
+        let task = self.queues[prio].pop_front();
+        self.update_bitmap(prio);
+        if let Some(task) = &task {
+            task.sched_attr().set_queued_rt_prio(0);
+        }
+        task
+    }
+
+    fn remove(&mut self, task: &SchedItem) -> Option<SchedItem> {
+        let prio = task.sched_attr().queued_rt_prio();
+        let queue = &mut self.queues[prio];
+        let task = queue
+            .iter()
+            .position(|t| Arc::ptr_eq(t, task))
+            .and_then(|index| queue.remove(index));
+        self.update_bitmap(prio);
+        if let Some(task) = &task {
+            task.sched_attr().set_queued_rt_prio(0);
+        }
+        task
+    }
+
+    /// Removes the first task for which `allowed` returns `true`, from the
+    /// highest priority.
+    #[cfg_attr(not(feature = "smp"), allow(dead_code))]
+    fn steal<F>(&mut self, allowed: F) -> Option<SchedItem>
+    where
+        F: Fn(&SchedItem) -> bool,
+    {
+        for prio in (1..NUM_RT_PRIO).rev() {
+            if self.bitmap & (1 << prio) == 0 {
+                continue;
+            }
+            let queue = &mut self.queues[prio];
+            if let Some(index) = queue.iter().position(&allowed) {
+                let task = queue.remove(index);
+                self.update_bitmap(prio);
+                if let Some(task) = &task {
+                    task.sched_attr().set_queued_rt_prio(0);
+                }
+                return task;
+            }
+        }
+        None
+    }
+
+    fn update_bitmap(&mut self, prio: usize) {
+        if self.queues[prio].is_empty() {
+            self.bitmap &= !(1 << prio);
+        }
+    }
+}
+
+/// A scheduler with a real-time class and a normal class (the CFS), and
+/// optionally a deadline class.
+pub(crate) struct RtCfsScheduler {
+    #[cfg(feature = "sched_edf")]
+    dl: DlRunQueue,
+    rt: RtRunQueue,
+    cfs: CFScheduler<TaskInner>,
+}
+
+impl RtCfsScheduler {
+    /// Creates a new empty scheduler.
+    pub fn new() -> Self {
+        Self {
+            #[cfg(feature = "sched_edf")]
+            dl: DlRunQueue::new(),
+            rt: RtRunQueue::new(),
+            cfs: CFScheduler::new(),
+        }
+    }
+
+    /// Returns the name of the scheduler.
+    pub fn scheduler_name() -> &'static str {
+        if cfg!(feature = "sched_edf") {
+            "DL+RT+CFS"
+        } else {
+            "RT+CFS"
+        }
+    }
+
+    /// Whether there are ready tasks in the classes above the normal class.
+    fn has_rt_or_dl(&self) -> bool {
+        #[cfg(feature = "sched_edf")]
+        if !self.dl.is_empty() {
+            return true;
+        }
+        !self.rt.is_empty()
+    }
+
+    /// Whether there are ready tasks in the deadline class.
+    fn has_dl(&self) -> bool {
+        #[cfg(feature = "sched_edf")]
+        return !self.dl.is_empty();
+        #[cfg(not(feature = "sched_edf"))]
+        false
+    }
+
+    fn enqueue_rt(&mut self, task: SchedItem, front: bool) {
+        let attr = task.sched_attr();
+        if attr.take_class_changed() || !front {
+            attr.set_rt_time_slice(RT_TIME_SLICE);
+        }
+        self.rt.enqueue(task, front);
+    }
+
+    /// Puts a task into the CFS. If it's new to the CFS, e.g., it changed its
+    /// class from real-time to normal, it's added as a new task.
+    fn enqueue_cfs(&mut self, task: SchedItem, preempt: bool, is_new: bool) {
+        // Apply the nice value saved in the attributes, it may be changed by
+        // `TaskInner::set_sched_policy` when the task was not in this scheduler.
+        self.cfs.set_priority(&task, task.sched_attr().priority());
+        if is_new {
+            self.cfs.add_task(task);
+        } else {
+            self.cfs.put_prev_task(task, preempt);
+        }
+    }
+}
+
+impl BaseScheduler for RtCfsScheduler {
+    type SchedItem = SchedItem;
+
+    fn init(&mut self) {
+        self.cfs.init();
+    }
+
+    fn add_task(&mut self, task: Self::SchedItem) {
+        let attr = task.sched_attr();
+        attr.take_class_changed();
+        match attr.policy() {
+            SchedPolicy::Normal => self.enqueue_cfs(task, false, true),
+            SchedPolicy::Fifo | SchedPolicy::RoundRobin => {
+                attr.set_rt_time_slice(RT_TIME_SLICE);
+                self.rt.enqueue(task, false);
+            }
+            #[cfg(feature = "sched_edf")]
+            SchedPolicy::Deadline => self.dl.enqueue(task, false),
+            #[cfg(not(feature = "sched_edf"))]
+            SchedPolicy::Deadline => unreachable!(),
+        }
+    }
+
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem> {
+        #[cfg(feature = "sched_edf")]
+        if let Some(task) = self.dl.remove(task) {
+            return Some(task);
+        }
+        if task.sched_attr().queued_rt_prio() != 0 {
+            self.rt.remove(task)
+        } else {
+            self.cfs.remove_task(task)
+        }
+    }
+
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
+        #[cfg(feature = "sched_edf")]
+        if let Some(task) = self.dl.pop_earliest() {
+            return Some(task);
+        }
+        self.rt.pop_highest().or_else(|| self.cfs.pick_next_task())
+    }
+
+    fn put_prev_task(&mut self, prev: Self::SchedItem, preempt: bool) {
+        let attr = prev.sched_attr();
+        match attr.policy() {
+            SchedPolicy::Normal => {
+                // The virtual runtime did not advance while it was a real-time
+                // task, reset it as a new task.
+                let is_new = attr.take_class_changed();
+                self.enqueue_cfs(prev, preempt, is_new);
+            }
+            // A preempted FIFO task stays at the head of its priority queue.
+            SchedPolicy::Fifo => self.enqueue_rt(prev, preempt),
+            // A preempted RR task runs the rest of its time slice first.
+            SchedPolicy::RoundRobin => {
+                let front = preempt && attr.rt_time_slice() > 0;
+                self.enqueue_rt(prev, front);
+            }
+            #[cfg(feature = "sched_edf")]
+            SchedPolicy::Deadline => {
+                attr.take_class_changed();
+                self.dl.enqueue(prev, preempt);
+            }
+            #[cfg(not(feature = "sched_edf"))]
+            SchedPolicy::Deadline => unreachable!(),
+        }
+    }
+
+    fn task_tick(&mut self, current: &Self::SchedItem) -> bool {
+        let attr = current.sched_attr();
+        let higher_ready = self.rt.highest_prio() as isize > attr.priority();
+        match attr.policy() {
+            SchedPolicy::Normal => self.cfs.task_tick(current) || self.has_rt_or_dl(),
+            SchedPolicy::Fifo => higher_ready || self.has_dl(),
+            SchedPolicy::RoundRobin => {
+                attr.tick_rt_time_slice() <= 0 || higher_ready || self.has_dl()
+            }
+            #[cfg(feature = "sched_edf")]
+            SchedPolicy::Deadline => self.dl.task_tick(current),
+            #[cfg(not(feature = "sched_edf"))]
+            SchedPolicy::Deadline => unreachable!(),
+        }
+    }
+
+    fn set_priority(&mut self, task: &Self::SchedItem, prio: isize) -> bool {
+        let policy = task.sched_attr().policy();
+        // Always fails for deadline tasks.
+        if !policy.is_valid_priority(prio) {
+            return false;
+        }
+        if !policy.is_realtime() {
+            self.cfs.set_priority(task, prio);
+        }
+        task.sched_attr().set_priority(prio);
+        true
+    }
+}
+
+impl StealTask for RtCfsScheduler {
+    /// Real-time tasks are searched in their queues without being picked out,
+    /// and deadline tasks are never stolen, as their bandwidth is reserved on
+    /// their CPUs.
+    fn steal_task<F>(&mut self, allowed: F) -> Option<SchedItem>
+    where
+        F: Fn(&SchedItem) -> bool,
+    {
+        self.rt
+            .steal(&allowed)
+            .or_else(|| steal_by_picking(&mut self.cfs, allowed))
+    }
+}
@@ -0,0 +1,274 @@
+//! Kernel stacks of tasks, with stack overflow detection.
+//!
+//! With the `paging` feature on x86_64, stacks are mapped in a dedicated
+//! virtual memory region at the top of the kernel address space, each with an
+//! unmapped guard page below it. A stack overflow then causes a page fault on
+//! the guard page. The page fault can't be handled on the overflowed stack, so
+//! it escalates to a double fault, which is handled on a separate per-CPU
+//! stack and recognized by [`check_stack_overflow`]. Other architectures have
+//! no separate exception stacks, so guard pages are not used there.
+//!
+//! Otherwise, stacks are allocated from the heap with a canary word at the
+//! bottom, which is checked each time the task is switched out.
+//!
+//! Stacks are zeroed when allocated, so that their maximum usage can be
+//! estimated by finding the lowest non-zero word.
+
+cfg_if::cfg_if! {
+    if #[cfg(all(feature = "paging", target_arch = "x86_64"))] {
+        pub(crate) use self::guarded::TaskStack;
+        pub use self::guarded::check_stack_overflow;
+    } else {
+        pub(crate) use self::canary::TaskStack;
+    }
+}
+
+/// Returns the maximum number of bytes ever used in the zeroed stack
+/// `[bottom, bottom + size)`.
+///
+/// It may be underestimated if the deepest words used are zeros.
+fn max_stack_usage(bottom: usize, size: usize) -> usize {
+    const WORD_SIZE: usize = core::mem::size_of::<u64>();
+    // Skip the lowest word, which is the canary if any.
+    let lowest = (WORD_SIZE..size)
+        .step_by(WORD_SIZE)
+        .find(|&off| unsafe { ((bottom + off) as *const u64).read_volatile() } != 0)
+        .unwrap_or(size);
+    size - lowest
+}
+
+#[cfg(all(feature = "paging", target_arch = "x86_64"))]
+mod guarded {
+    use alloc::collections::BTreeMap;
+    use alloc::vec::Vec;
+
+    use axhal::mem::{virt_to_phys, PAGE_SIZE_4K};
+    use axhal::paging::MappingFlags;
+    use axlockdep::spin::SpinNoIrq;
+    use memory_addr::{va, VirtAddr};
+
+    use crate::TaskInner;
+
+    /// Size of the unmapped guard region below each stack.
+    const GUARD_SIZE: usize = PAGE_SIZE_4K;
+
+    /// Size of the virtual memory region for task stacks, which is at the top
+    /// of the kernel address space.
+    const STACK_REGION_SIZE: usize = 1 << 32; // 4G
+
+    static STACKS: SpinNoIrq<StackRegion> = SpinNoIrq::new(StackRegion::new());
+
+    struct StackRegion {
+        /// The lowest address that has not been used, or 0 if uninitialized.
+        next: usize,
+        /// Freed stacks, grouped by sizes.
+        ///
+        /// They are kept mapped and reused, to avoid TLB shootdowns on other
+        /// CPUs each time a task exits.
+        free: BTreeMap<usize, Vec<usize>>,
+    }
+
+    impl StackRegion {
+        const fn new() -> Self {
+            Self {
+                next: 0,
+                free: BTreeMap::new(),
+            }
+        }
+
+        /// Allocates a stack of `size` bytes, returns its bottom address.
+        fn alloc(&mut self, size: usize) -> Option<usize> {
+            if let Some(bottom) = self.free.get_mut(&size).and_then(Vec::pop) {
+                return Some(bottom);
+            }
+
+            let region_end = axconfig::KERNEL_ASPACE_BASE + axconfig::KERNEL_ASPACE_SIZE;
+            if self.next == 0 {
+                self.next = region_end - STACK_REGION_SIZE;
+            }
+            let bottom = self.next + GUARD_SIZE;
+            if bottom + size > region_end {
+                warn!("task stack region is exhausted");
+                return None;
+            }
+
+            let allocator = axalloc::global_allocator();
+            let num_pages = size / PAGE_SIZE_4K;
+            let pages = allocator.alloc_pages(num_pages, PAGE_SIZE_4K).ok()?;
+            let res = axmm::kernel_aspace().lock().map_linear(
+                va!(bottom),
+                virt_to_phys(va!(pages)),
+                size,
+                MappingFlags::READ | MappingFlags::WRITE,
+            );
+            if res.is_err() {
+                allocator.dealloc_pages(pages, num_pages);
+                return None;
+            }
+            self.next = bottom + size;
+            Some(bottom)
+        }
+    }
+
+    /// A task stack with a guard page below it.
+    pub(crate) struct TaskStack {
+        bottom: usize,
+        size: usize,
+    }
+
+    impl TaskStack {
+        pub fn alloc(size: usize, _owner: &TaskInner) -> Self {
+            let bottom = STACKS
+                .lock()
+                .alloc(size)
+                .expect("failed to allocate task stack");
+            // Freed stacks are reused, and new pages are not zeroed either.
+            unsafe { core::ptr::write_bytes(bottom as *mut u8, 0, size) };
+            Self { bottom, size }
+        }
+
+        pub const fn top(&self) -> VirtAddr {
+            VirtAddr::from_usize(self.bottom + self.size)
+        }
+
+        pub const fn size(&self) -> usize {
+            self.size
+        }
+
+        pub fn max_used(&self) -> usize {
+            super::max_stack_usage(self.bottom, self.size)
+        }
+    }
+
+    impl Drop for TaskStack {
+        fn drop(&mut self) {
+            let mut stacks = STACKS.lock();
+            stacks.free.entry(self.size).or_default().push(self.bottom);
+        }
+    }
+
+    /// Whether `vaddr` is in the guard page below the stack at `bottom`.
+    pub(super) fn in_guard_page(bottom: usize, vaddr: usize) -> bool {
+        bottom - GUARD_SIZE <= vaddr && vaddr < bottom
+    }
+
+    /// Checks whether a page fault at `vaddr` hits the guard page of the stack
+    /// of the current task, and panics with "stack overflow in Task(id, name)"
+    /// if so.
+    ///
+    /// It is intended to be called by the kernel page fault handler before it
+    /// takes any locks, and returns normally if the fault is not caused by a
+    /// stack overflow. It takes no locks and allocates no memory, as the fault
+    /// may occur anywhere. Only available on x86_64, the only architecture
+    /// with stack guard pages.
+    pub fn check_stack_overflow(vaddr: VirtAddr) {
+        // Only the current task can overflow its own stack.
+        let Some(curr) = crate::current_may_uninit() else {
+            return;
+        };
+        if let Some(stack) = curr.kernel_stack_range() {
+            if in_guard_page(stack.start, vaddr.as_usize()) {
+                panic!(
+                    "stack overflow in Task({}, {:?})",
+                    curr.id().as_u64(),
+                    curr.name()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(not(all(feature = "paging", target_arch = "x86_64")))]
+mod canary {
+    use core::{alloc::Layout, ptr::NonNull};
+
+    use memory_addr::VirtAddr;
+
+    use crate::TaskInner;
+
+    /// The magic word at the bottom of each stack.
+    const STACK_CANARY: u64 = 0xdead_beef_cafe_babe;
+
+    /// A task stack allocated from the heap, with a canary word at the bottom.
+    pub(crate) struct TaskStack {
+        ptr: NonNull<u8>,
+        layout: Layout,
+    }
+
+    impl TaskStack {
+        pub fn alloc(size: usize, _owner: &TaskInner) -> Self {
+            let layout = Layout::from_size_align(size, 16).unwrap();
+            let ptr = NonNull::new(unsafe { alloc::alloc::alloc_zeroed(layout) }).unwrap();
+            unsafe { (ptr.as_ptr() as *mut u64).write(STACK_CANARY) };
+            Self { ptr, layout }
+        }
+
+        pub const fn top(&self) -> VirtAddr {
+            unsafe { core::mem::transmute(self.ptr.as_ptr().add(self.layout.size())) }
+        }
+
+        pub const fn size(&self) -> usize {
+            self.layout.size()
+        }
+
+        pub fn max_used(&self) -> usize {
+            super::max_stack_usage(self.ptr.as_ptr() as usize, self.layout.size())
+        }
+
+        /// Whether the canary word is intact, otherwise the stack has
+        /// overflowed.
+        pub fn canary_intact(&self) -> bool {
+            unsafe { (self.ptr.as_ptr() as *const u64).read() == STACK_CANARY }
+        }
+    }
+
+    impl Drop for TaskStack {
+        fn drop(&mut self) {
+            unsafe { alloc::alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_max_stack_usage() {
+        let mut stack = [0u64; 64];
+        let (bottom, size) = (stack.as_ptr() as usize, core::mem::size_of_val(&stack));
+        assert_eq!(max_stack_usage(bottom, size), 0);
+        // The lowest word is the canary.
+        stack[0] = u64::MAX;
+        assert_eq!(max_stack_usage(bottom, size), 0);
+        stack[60] = 1;
+        assert_eq!(max_stack_usage(bottom, size), 4 * 8);
+        stack[1] = 1;
+        assert_eq!(max_stack_usage(bottom, size), 63 * 8);
+    }
+
+    #[cfg(not(all(feature = "paging", target_arch = "x86_64")))]
+    #[test]
+    fn test_stack_canary() {
+        use crate::TaskInner;
+
+        let owner = TaskInner::new(|| {}, "canary".into(), 0x1000);
+        let stack = TaskStack::alloc(0x1000, &owner);
+        assert!(stack.canary_intact());
+        assert_eq!(stack.max_used(), 0);
+        let bottom = stack.top().as_usize() - stack.size();
+        unsafe { (bottom as *mut u64).write(0) };
+        assert!(!stack.canary_intact());
+    }
+
+    #[cfg(all(feature = "paging", target_arch = "x86_64"))]
+    #[test]
+    fn test_guard_page() {
+        use super::guarded::in_guard_page;
+        const PAGE: usize = 0x1000;
+        assert!(in_guard_page(0x10 * PAGE, 0x10 * PAGE - 1));
+        assert!(in_guard_page(0x10 * PAGE, 0xf * PAGE));
+        assert!(!in_guard_page(0x10 * PAGE, 0x10 * PAGE));
+        assert!(!in_guard_page(0x10 * PAGE, 0xf * PAGE - 1));
+    }
+}
@@ -0,0 +1,158 @@
+//! Per-task CPU time and scheduling statistics.
+
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::time::Duration;
+
+use axhal::time::monotonic_time_nanos;
+
+use crate::task::TaskState;
+
+/// Total CPU time (in nanoseconds) consumed by all non-idle tasks, excluding
+/// the time slices that are still in progress.
+static BUSY_CPU_TIME_NANOS: AtomicU64 = AtomicU64::new(0);
+
+/// Marks `last_cpu` as never run.
+const NO_CPU: usize = usize::MAX;
+
+/// A snapshot of the CPU time and scheduling statistics of a task.
+///
+/// Returned by [`TaskInner::stats`](crate::TaskInner::stats).
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct TaskStats {
+    /// Total time the task has been running on CPUs.
+    pub cpu_time: Duration,
+    /// Total time the task has been ready to run, but waiting in run queues.
+    pub ready_time: Duration,
+    /// Total time the task has been blocked (in wait queues or sleeping).
+    pub blocked_time: Duration,
+    /// Number of context switches that the task gave up the CPU voluntarily,
+    /// e.g., by yielding, blocking or exiting.
+    pub nr_voluntary_switches: u64,
+    /// Number of context switches that the task was preempted.
+    pub nr_involuntary_switches: u64,
+    /// The ID of the CPU on which the task ran most recently, or [`None`] if
+    /// the task has never run.
+    pub last_cpu: Option<usize>,
+}
+
+/// Scheduling counters of a task, updated by the run queue on every state
+/// change.
+///
+/// The time spent in the current state is accumulated lazily: it's added to
+/// the corresponding counter when the state changes next time.
+pub(crate) struct TaskStatsCounters {
+    cpu_time_ns: AtomicU64,
+    ready_time_ns: AtomicU64,
+    blocked_time_ns: AtomicU64,
+    nr_switches: AtomicU64,
+    nr_preempted: AtomicU64,
+    last_cpu: AtomicUsize,
+    /// Timestamp (in nanoseconds) of the last state change.
+    last_update_ns: AtomicU64,
+}
+
+impl TaskStatsCounters {
+    /// Creates zeroed counters, the current state is considered to be entered
+    /// just now.
+    pub fn new() -> Self {
+        Self {
+            cpu_time_ns: AtomicU64::new(0),
+            ready_time_ns: AtomicU64::new(0),
+            blocked_time_ns: AtomicU64::new(0),
+            nr_switches: AtomicU64::new(0),
+            nr_preempted: AtomicU64::new(0),
+            last_cpu: AtomicUsize::new(NO_CPU),
+            last_update_ns: AtomicU64::new(monotonic_time_nanos()),
+        }
+    }
+
+    /// Returns the time elapsed since the last state change, and starts a new
+    /// period at `now`.
+    fn new_period(&self, now: u64) -> u64 {
+        now.saturating_sub(self.last_update_ns.swap(now, Ordering::Relaxed))
+    }
+
+    /// Called when the task is switched in on CPU `cpu_id`, ending a period in
+    /// the `Ready` state.
+    pub fn on_switch_in(&self, now: u64, cpu_id: usize) {
+        let ready_ns = self.new_period(now);
+        self.ready_time_ns.fetch_add(ready_ns, Ordering::Relaxed);
+        self.last_cpu.store(cpu_id, Ordering::Relaxed);
+    }
+
+    /// Called when the task is switched out, ending a period in the `Running`
+    /// state. `preempt` indicates whether the switch is involuntary.
+    ///
+    /// Returns the length of the period in nanoseconds.
+    pub fn on_switch_out(&self, now: u64, preempt: bool) -> u64 {
+        let run_ns = self.new_period(now);
+        self.cpu_time_ns.fetch_add(run_ns, Ordering::Relaxed);
+        self.nr_switches.fetch_add(1, Ordering::Relaxed);
+        if preempt {
+            self.nr_preempted.fetch_add(1, Ordering::Relaxed);
+        }
+        run_ns
+    }
+
+    /// Called when the task is woken up, ending a period in the `Blocked`
+    /// state.
+    pub fn on_unblock(&self, now: u64) {
+        let blocked_ns = self.new_period(now);
+        self.blocked_time_ns
+            .fetch_add(blocked_ns, Ordering::Relaxed);
+    }
+
+    /// Returns the ID of the CPU on which the task ran most recently.
+    pub fn last_cpu(&self) -> Option<usize> {
+        let last_cpu = self.last_cpu.load(Ordering::Relaxed);
+        (last_cpu != NO_CPU).then_some(last_cpu)
+    }
+
+    /// Takes a snapshot of the counters, taking the period in progress of the
+    /// given `state` into account.
+    pub fn snapshot(&self, state: TaskState) -> TaskStats {
+        let pending_ns = self.pending_ns(monotonic_time_nanos());
+        let mut cpu_time_ns = self.cpu_time_ns.load(Ordering::Relaxed);
+        let mut ready_time_ns = self.ready_time_ns.load(Ordering::Relaxed);
+        let mut blocked_time_ns = self.blocked_time_ns.load(Ordering::Relaxed);
+        match state {
+            TaskState::Running => cpu_time_ns += pending_ns,
+            TaskState::Ready => ready_time_ns += pending_ns,
+            TaskState::Blocked => blocked_time_ns += pending_ns,
+            TaskState::Exited => {}
+        }
+        let nr_switches = self.nr_switches.load(Ordering::Relaxed);
+        let nr_preempted = self.nr_preempted.load(Ordering::Relaxed);
+        TaskStats {
+            cpu_time: Duration::from_nanos(cpu_time_ns),
+            ready_time: Duration::from_nanos(ready_time_ns),
+            blocked_time: Duration::from_nanos(blocked_time_ns),
+            nr_voluntary_switches: nr_switches.saturating_sub(nr_preempted),
+            nr_involuntary_switches: nr_preempted,
+            last_cpu: self.last_cpu(),
+        }
+    }
+
+    /// Returns the time (in nanoseconds) spent in the current state.
+    pub fn pending_ns(&self, now: u64) -> u64 {
+        now.saturating_sub(self.last_update_ns.load(Ordering::Relaxed))
+    }
+}
+
+/// Adds a finished running period of a non-idle task to the total busy time.
+pub(crate) fn account_busy_time(run_ns: u64) {
+    BUSY_CPU_TIME_NANOS.fetch_add(run_ns, Ordering::Relaxed);
+}
+
+/// Returns the total CPU time consumed by all non-idle tasks since boot.
+///
+/// Time slices in progress on other CPUs are not included, only the one of
+/// the current task is.
+pub fn busy_cpu_time() -> Duration {
+    let curr = crate::current();
+    let mut busy_ns = BUSY_CPU_TIME_NANOS.load(Ordering::Relaxed);
+    if !curr.is_idle() {
+        busy_ns += curr.stats_counters().pending_ns(monotonic_time_nanos());
+    }
+    Duration::from_nanos(busy_ns)
+}
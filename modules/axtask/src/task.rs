@@ -1,20 +1,26 @@
 use alloc::{boxed::Box, string::String, sync::Arc};
 use core::ops::Deref;
-use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, Ordering};
-use core::{alloc::Layout, cell::UnsafeCell, fmt, ptr::NonNull};
+use core::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use core::{cell::UnsafeCell, fmt};
 
 #[cfg(feature = "smp")]
 use alloc::sync::Weak;
-#[cfg(feature = "preempt")]
-use core::sync::atomic::AtomicUsize;
 
-use kspin::SpinNoIrq;
+use axlockdep::spin::SpinNoIrq;
 use memory_addr::{align_up_4k, VirtAddr};
 
 use axhal::arch::TaskContext;
 #[cfg(feature = "tls")]
 use axhal::tls::TlsArea;
-
+#[cfg(feature = "paging")]
+use axmm::AddrSpace;
+#[cfg(feature = "paging")]
+use memory_addr::PhysAddr;
+
+use crate::cancel::CancelState;
+use crate::sched::{DeadlineParams, SchedAttr, SchedPolicy};
+use crate::stack::TaskStack;
+use crate::stats::{TaskStats, TaskStatsCounters};
 use crate::task_ext::AxTaskExt;
 use crate::{AxCpuMask, AxTask, AxTaskRef, WaitQueue};
 
@@ -25,7 +31,7 @@ pub struct TaskId(u64);
 /// The possible states of a task.
 #[repr(u8)]
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
-pub(crate) enum TaskState {
+pub enum TaskState {
     /// Task is running on some CPU.
     Running = 1,
     /// Task is ready to run on some scheduler's ready queue.
@@ -50,8 +56,13 @@ pub struct TaskInner {
     /// CPU affinity mask.
     cpumask: SpinNoIrq<AxCpuMask>,
 
+    /// Scheduling policy and priority.
+    sched_attr: SchedAttr,
+
     /// Mark whether the task is in the wait queue.
     in_wait_queue: AtomicBool,
+    /// The address of the futex that the task is waiting on, or 0.
+    futex_key: AtomicUsize,
 
     /// Used to indicate whether the task is running on a CPU.
     #[cfg(feature = "smp")]
@@ -74,6 +85,21 @@ pub struct TaskInner {
     exit_code: AtomicI32,
     wait_for_exit: WaitQueue,
 
+    /// Cancellation states and cleanup handlers.
+    cancel: CancelState,
+
+    /// CPU time and scheduling statistics.
+    stats: TaskStatsCounters,
+
+    /// Whether the watchdog reports the task if it's blocked for too long.
+    #[cfg(feature = "watchdog")]
+    hung_task_check: AtomicBool,
+
+    /// The address space of the user code run by the task, and the root of
+    /// its page table.
+    #[cfg(feature = "paging")]
+    user_aspace: Option<(Arc<SpinNoIrq<AddrSpace>>, PhysAddr)>,
+
     kstack: Option<TaskStack>,
     ctx: UnsafeCell<TaskContext>,
     task_ext: AxTaskExt,
@@ -92,6 +118,12 @@ impl TaskId {
     pub const fn as_u64(&self) -> u64 {
         self.0
     }
+
+    /// Creates a task ID from a `u64`, e.g., to look up the task by
+    /// [`get_task`](crate::get_task).
+    pub const fn from_u64(id: u64) -> Self {
+        Self(id)
+    }
 }
 
 impl From<u8> for TaskState {
@@ -118,7 +150,7 @@ impl TaskInner {
     {
         let mut t = Self::new_common(TaskId::new(), name);
         debug!("new task: {}", t.id_name());
-        let kstack = TaskStack::alloc(align_up_4k(stack_size));
+        let kstack = TaskStack::alloc(align_up_4k(stack_size), &t);
 
         #[cfg(feature = "tls")]
         let tls = VirtAddr::from(t.tls.tls_ptr() as usize);
@@ -149,6 +181,14 @@ impl TaskInner {
         alloc::format!("Task({}, {:?})", self.id.as_u64(), self.name)
     }
 
+    /// Returns a snapshot of the CPU time and scheduling statistics of the
+    /// task.
+    ///
+    /// The time spent in the current state so far is included.
+    pub fn stats(&self) -> TaskStats {
+        self.stats.snapshot(self.state())
+    }
+
     /// Wait for the task to exit, and return the exit code.
     ///
     /// It will return immediately if the task has already exited (but not dropped).
@@ -198,6 +238,50 @@ impl TaskInner {
         }
     }
 
+    /// Enables or disables the hung task detection of the watchdog for the
+    /// task, which is enabled by default.
+    ///
+    /// It should be disabled for tasks that wait for events indefinitely by
+    /// design, e.g., servers waiting for requests.
+    #[cfg(feature = "watchdog")]
+    pub fn set_hung_task_check(&self, enabled: bool) {
+        self.hung_task_check.store(enabled, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "watchdog")]
+    pub(crate) fn hung_task_check(&self) -> bool {
+        self.hung_task_check.load(Ordering::Relaxed)
+    }
+
+    /// Sets the address space of the user code run by the task, before the
+    /// task is spawned.
+    ///
+    /// Its page table is activated whenever the task is running, and the page
+    /// faults in it are resolved in it (see
+    /// [`axmm::handle_user_page_fault`]). It should be created by
+    /// [`axmm::new_user_aspace`], so that the kernel is still mapped.
+    #[cfg(feature = "paging")]
+    pub fn set_user_aspace(&mut self, aspace: Arc<SpinNoIrq<AddrSpace>>) {
+        let root = aspace.lock().page_table_root();
+        self.user_aspace = Some((aspace, root));
+    }
+
+    /// Returns the address space of the user code run by the task, if any.
+    #[cfg(feature = "paging")]
+    pub fn user_aspace(&self) -> Option<&Arc<SpinNoIrq<AddrSpace>>> {
+        self.user_aspace.as_ref().map(|(aspace, _)| aspace)
+    }
+
+    /// Returns the root of the page table to be activated when the task is
+    /// running.
+    #[cfg(feature = "paging")]
+    pub(crate) fn page_table_root(&self) -> PhysAddr {
+        match &self.user_aspace {
+            Some((_, root)) => *root,
+            None => axmm::kernel_page_table_root(),
+        }
+    }
+
     /// Gets the cpu affinity mask of the task.
     ///
     /// Returns the cpu affinity mask of the task in type [`AxCpuMask`].
@@ -214,6 +298,94 @@ impl TaskInner {
     pub fn set_cpumask(&self, cpumask: AxCpuMask) {
         *self.cpumask.lock() = cpumask
     }
+
+    /// Gets the scheduling policy of the task.
+    #[inline]
+    pub fn sched_policy(&self) -> SchedPolicy {
+        self.sched_attr.policy()
+    }
+
+    /// Gets the scheduling priority of the task.
+    ///
+    /// Its meaning depends on the scheduling policy, see [`SchedPolicy`].
+    #[inline]
+    pub fn sched_priority(&self) -> isize {
+        self.sched_attr.priority()
+    }
+
+    /// Sets the scheduling policy and priority of the task.
+    ///
+    /// It takes effect when the task is spawned, so it's intended for tasks
+    /// not spawned yet. Use [`set_task_sched_policy`] to change the policy of
+    /// a spawned task immediately.
+    ///
+    /// Returns `false` if the priority is invalid for the policy, or the policy
+    /// is real-time and the `sched_rt` feature is not enabled. Without
+    /// `sched_rt`, the priority of [`SchedPolicy::Normal`] is checked by the
+    /// scheduler when the task is spawned, and ignored if it's rejected.
+    ///
+    /// [`set_task_sched_policy`]: crate::set_task_sched_policy
+    pub fn set_sched_policy(&self, policy: SchedPolicy, prio: isize) -> bool {
+        if !policy.is_supported() || !policy.is_valid_priority(prio) {
+            return false;
+        }
+        self.sched_attr.set_policy(policy, prio);
+        true
+    }
+
+    /// Sets the [`SchedPolicy::Deadline`] policy with the given parameters.
+    ///
+    /// Like [`TaskInner::set_sched_policy`], it takes effect the next time the
+    /// task is put into a run queue. Use [`set_current_deadline`] for the
+    /// current task.
+    ///
+    /// The bandwidth is reserved on the first CPU in the affinity of the task
+    /// where the admission test passes, and the task is bound to that CPU.
+    ///
+    /// Returns `false` if the parameters are invalid, the admission test fails
+    /// on all CPUs, or the `sched_edf` feature is not enabled.
+    ///
+    /// [`set_current_deadline`]: crate::set_current_deadline
+    pub fn set_deadline_params(&self, params: DeadlineParams) -> bool {
+        #[cfg(feature = "sched_edf")]
+        return self.admit_deadline(params, self.cpumask());
+        #[cfg(not(feature = "sched_edf"))]
+        {
+            let _ = params;
+            false
+        }
+    }
+
+    /// Reserves the bandwidth for the deadline parameters on a CPU in
+    /// `cpumask`, binds the task to that CPU and sets the deadline policy.
+    #[cfg(feature = "sched_edf")]
+    pub(crate) fn admit_deadline(&self, params: DeadlineParams, cpumask: AxCpuMask) -> bool {
+        let Some(cpu) = self.sched_attr.dl().reserve(&params, cpumask) else {
+            return false;
+        };
+        self.set_cpumask(AxCpuMask::one_shot(cpu));
+        self.sched_attr.set_policy(SchedPolicy::Deadline, 0);
+        true
+    }
+
+    /// Gets the parameters of the task if it has the [`SchedPolicy::Deadline`]
+    /// policy.
+    pub fn deadline_params(&self) -> Option<DeadlineParams> {
+        #[cfg(feature = "sched_edf")]
+        if self.sched_policy().is_deadline() {
+            return Some(self.sched_attr.dl().params());
+        }
+        None
+    }
+
+    /// Returns the number of deadlines missed by the task, including the
+    /// periods in which it ran out of its runtime budget.
+    pub fn deadline_misses(&self) -> u64 {
+        #[cfg(feature = "sched_edf")]
+        return self.sched_attr.dl().nr_misses();
+        #[cfg(not(feature = "sched_edf"))]
+        0
+    }
 }
 
 // private methods
@@ -228,7 +400,9 @@ impl TaskInner {
             state: AtomicU8::new(TaskState::Ready as u8),
             // By default, the task is allowed to run on all CPUs.
             cpumask: SpinNoIrq::new(AxCpuMask::full()),
+            sched_attr: SchedAttr::new(),
             in_wait_queue: AtomicBool::new(false),
+            futex_key: AtomicUsize::new(0),
             #[cfg(feature = "irq")]
             timer_ticket_id: AtomicU64::new(0),
             #[cfg(feature = "smp")]
@@ -241,6 +415,12 @@ impl TaskInner {
             preempt_disable_count: AtomicUsize::new(0),
             exit_code: AtomicI32::new(0),
             wait_for_exit: WaitQueue::new(),
+            cancel: CancelState::new(),
+            stats: TaskStatsCounters::new(),
+            #[cfg(feature = "watchdog")]
+            hung_task_check: AtomicBool::new(true),
+            #[cfg(feature = "paging")]
+            user_aspace: None,
             kstack: None,
             ctx: UnsafeCell::new(TaskContext::new()),
             task_ext: AxTaskExt::empty(),
@@ -269,7 +449,9 @@ impl TaskInner {
     }
 
     pub(crate) fn into_arc(self) -> AxTaskRef {
-        Arc::new(AxTask::new(self))
+        let task = Arc::new(AxTask::new(self));
+        crate::registry::register(&task);
+        task
     }
 
     #[inline]
@@ -317,6 +499,50 @@ impl TaskInner {
         self.is_idle
     }
 
+    #[inline]
+    pub(crate) fn sched_attr(&self) -> &SchedAttr {
+        &self.sched_attr
+    }
+
+    #[inline]
+    pub(crate) fn stats_counters(&self) -> &TaskStatsCounters {
+        &self.stats
+    }
+
+    /// Returns the size and the maximum usage of the kernel stack, or zeros if
+    /// the task runs on the boot stack.
+    pub(crate) fn kernel_stack_usage(&self) -> (usize, usize) {
+        match &self.kstack {
+            Some(s) => (s.size(), s.max_used()),
+            None => (0, 0),
+        }
+    }
+
+    /// Returns the address range of the kernel stack, or [`None`] if the task
+    /// runs on the boot stack.
+    pub fn kernel_stack_range(&self) -> Option<core::ops::Range<usize>> {
+        self.kstack.as_ref().map(|s| {
+            let top = s.top().as_usize();
+            top - s.size()..top
+        })
+    }
+
+    /// Panics if the kernel stack of the task has overflowed, which is detected
+    /// by the canary word at the bottom of the stack.
+    #[cfg(not(all(feature = "paging", target_arch = "x86_64")))]
+    pub(crate) fn check_stack_canary(&self) {
+        if let Some(kstack) = &self.kstack {
+            if !kstack.canary_intact() {
+                panic!("stack overflow in {}", self.id_name());
+            }
+        }
+    }
+
+    #[inline]
+    pub(crate) fn cancel_state(&self) -> &CancelState {
+        &self.cancel
+    }
+
     #[inline]
     pub(crate) fn in_wait_queue(&self) -> bool {
         self.in_wait_queue.load(Ordering::Acquire)
@@ -327,6 +553,17 @@ impl TaskInner {
         self.in_wait_queue.store(in_wait_queue, Ordering::Release);
     }
 
+    #[inline]
+    pub(crate) fn futex_key(&self) -> usize {
+        self.futex_key.load(Ordering::Acquire)
+    }
+
+    /// Only changed with the lock of the futex bucket held.
+    #[inline]
+    pub(crate) fn set_futex_key(&self, key: usize) {
+        self.futex_key.store(key, Ordering::Release);
+    }
+
     /// Returns task's current timer ticket ID.
     #[inline]
     #[cfg(feature = "irq")]
@@ -469,31 +706,7 @@ impl fmt::Debug for TaskInner {
 impl Drop for TaskInner {
     fn drop(&mut self) {
         debug!("task drop: {}", self.id_name());
-    }
-}
-
-struct TaskStack {
-    ptr: NonNull<u8>,
-    layout: Layout,
-}
-
-impl TaskStack {
-    pub fn alloc(size: usize) -> Self {
-        let layout = Layout::from_size_align(size, 16).unwrap();
-        Self {
-            ptr: NonNull::new(unsafe { alloc::alloc::alloc(layout) }).unwrap(),
-            layout,
-        }
-    }
-
-    pub const fn top(&self) -> VirtAddr {
-        unsafe { core::mem::transmute(self.ptr.as_ptr().add(self.layout.size())) }
-    }
-}
-
-impl Drop for TaskStack {
-    fn drop(&mut self) {
-        unsafe { alloc::alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+        crate::registry::unregister(self.id);
     }
 }
 
@@ -32,6 +32,34 @@ fn test_sched_fifo() {
     }
 }
 
+#[test]
+fn test_steal_task() {
+    use scheduler::BaseScheduler;
+
+    use crate::api::{AxTask, Scheduler};
+    use crate::sched::StealTask;
+    use crate::TaskInner;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let mut scheduler = Scheduler::new();
+    for i in 0..4 {
+        let task = TaskInner::new(|| {}, format!("steal{}", i), 0x1000);
+        scheduler.add_task(std::sync::Arc::new(AxTask::new(task)));
+    }
+    let stolen = scheduler.steal_task(|task| task.name() == "steal2");
+    assert_eq!(stolen.unwrap().name(), "steal2");
+    assert!(scheduler.steal_task(|_| false).is_none());
+
+    // The other tasks keep their order in the FIFO queue.
+    let mut names = Vec::new();
+    while let Some(task) = scheduler.pick_next_task() {
+        names.push(String::from(task.name()));
+    }
+    assert_eq!(names, ["steal0", "steal1", "steal3"]);
+}
+
 #[test]
 fn test_fp_state_switch() {
     let _lock = SERIAL.lock();
@@ -128,3 +156,312 @@ fn test_task_join() {
         assert_eq!(tasks[i].join(), Some(i as _));
     }
 }
+
+#[test]
+fn test_task_stats() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    const NUM_YIELDS: u64 = 5;
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    let task = axtask::spawn_raw(
+        || {
+            for _ in 0..NUM_YIELDS {
+                axtask::yield_now();
+            }
+            FINISHED.fetch_add(1, Ordering::Relaxed);
+        },
+        "stats".into(),
+        0x1000,
+    );
+    let stats = task.stats();
+    assert_eq!(stats.last_cpu, None);
+    assert_eq!(stats.nr_voluntary_switches, 0);
+
+    // Keep the main task in the run queue, so that each yield is a real switch.
+    while FINISHED.load(Ordering::Relaxed) == 0 {
+        axtask::yield_now();
+    }
+    task.join();
+    let stats = task.stats();
+    println!("task_stats: {:?}", stats);
+    assert_eq!(stats.last_cpu, Some(0));
+    // Each yield switches out once, and so does the exit.
+    assert_eq!(stats.nr_voluntary_switches, NUM_YIELDS + 1);
+    assert_eq!(stats.nr_involuntary_switches, 0);
+}
+
+#[test]
+fn test_task_cancel() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static WQ: WaitQueue = WaitQueue::new();
+    static CLEANUPS: AtomicUsize = AtomicUsize::new(0);
+
+    let task = axtask::spawn_raw(
+        || {
+            axtask::push_cleanup_handler(|| {
+                CLEANUPS.fetch_add(1, Ordering::Relaxed);
+            });
+            // A cancellation request is held pending while disabled.
+            assert!(axtask::set_current_cancelable(false));
+            axtask::yield_now();
+            axtask::test_cancel();
+            assert!(!axtask::set_current_cancelable(true));
+            // The request is pending, so it returns immediately.
+            assert_eq!(WQ.wait_cancellable(), Err(axtask::Cancelled));
+            assert!(!current().in_wait_queue());
+            axtask::test_cancel();
+            unreachable!();
+        },
+        "cancel".into(),
+        0x1000,
+    );
+    axtask::yield_now();
+    assert!(axtask::cancel_task(&task));
+    assert_eq!(task.join(), Some(axtask::CANCELED_EXIT_CODE));
+    assert_eq!(CLEANUPS.load(Ordering::Relaxed), 1);
+    assert!(!axtask::cancel_task(&task));
+
+    // Wake up a task blocked in a cancellable wait.
+    let task = axtask::spawn_raw(
+        || {
+            assert_eq!(WQ.wait_until_cancellable(|| false), Err(axtask::Cancelled));
+            axtask::exit(1);
+        },
+        "cancel_wait".into(),
+        0x1000,
+    );
+    axtask::yield_now(); // let the task block
+    assert!(axtask::cancel_task(&task));
+    assert_eq!(task.join(), Some(1));
+    assert!(!WQ.notify_one(false));
+}
+
+#[test]
+fn test_task_registry() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let task = axtask::spawn_raw(axtask::yield_now, "registry".into(), 0x4000);
+    let id = task.id();
+    let found = axtask::get_task(id).unwrap();
+    assert!(std::sync::Arc::ptr_eq(&found, &task));
+    assert!(axtask::all_tasks().iter().any(|t| t.id() == id));
+    assert!(axtask::all_tasks().iter().any(|t| t.id() == current().id()));
+
+    let info = task.info();
+    assert_eq!(info.name, "registry");
+    assert_eq!(info.state, axtask::TaskState::Ready);
+    assert_eq!(info.cpu, None);
+    assert_eq!(info.stack_size, 0x4000);
+
+    task.join();
+    let info = task.info();
+    assert_eq!(info.state, axtask::TaskState::Exited);
+    assert_eq!(info.cpu, Some(0));
+    assert!(info.stack_used > 0 && info.stack_used <= info.stack_size);
+}
+
+#[test]
+fn test_async_executor() {
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    const NUM_TASKS: usize = 10;
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    static WQ: WaitQueue = WaitQueue::new();
+
+    let handles: Vec<_> = (0..NUM_TASKS)
+        .map(|i| {
+            crate::future::spawn(async move {
+                WQ.wait_until_async(|| COUNTER.load(Ordering::Acquire) > i)
+                    .await;
+                i * 2
+            })
+        })
+        .collect();
+
+    for _ in 0..NUM_TASKS {
+        COUNTER.fetch_add(1, Ordering::Release);
+        WQ.notify_all(false);
+        axtask::yield_now();
+    }
+
+    let outputs = crate::future::block_on(async {
+        let mut outputs = Vec::with_capacity(NUM_TASKS);
+        for handle in handles {
+            outputs.push(handle.await);
+        }
+        outputs
+    });
+    assert_eq!(outputs, (0..NUM_TASKS).map(|i| i * 2).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_wait_queue_rewake() {
+    use std::sync::Arc;
+    use std::task::{Wake, Waker};
+
+    static WQ: WaitQueue = WaitQueue::new();
+
+    // A waker that registers itself again whenever it's woken.
+    struct Rearm(AtomicUsize);
+
+    impl Wake for Rearm {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            WQ.register_waker(&Waker::from(self));
+        }
+    }
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let rearm = Arc::new(Rearm(AtomicUsize::new(0)));
+    let waker = Waker::from(rearm.clone());
+    WQ.register_waker(&waker);
+
+    assert!(WQ.notify_one(false));
+    assert_eq!(rearm.0.load(Ordering::Relaxed), 1);
+    WQ.notify_all(false);
+    assert_eq!(rearm.0.load(Ordering::Relaxed), 2);
+    assert!(WQ.unregister_waker(&waker));
+    assert!(!WQ.notify_one(false));
+}
+
+#[test]
+fn test_futex() {
+    use core::sync::atomic::AtomicU32;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    const NUM_TASKS: usize = 10;
+    static FUTEX: AtomicU32 = AtomicU32::new(0);
+    static TARGET: AtomicU32 = AtomicU32::new(0);
+    static WAITING: AtomicUsize = AtomicUsize::new(0);
+    static FINISHED: AtomicUsize = AtomicUsize::new(0);
+
+    assert_eq!(
+        axtask::futex_wait(&FUTEX, 1),
+        Err(axtask::FutexError::WouldBlock)
+    );
+
+    for _ in 0..NUM_TASKS {
+        axtask::spawn(|| {
+            WAITING.fetch_add(1, Ordering::Relaxed);
+            while FUTEX.load(Ordering::Acquire) == 0 {
+                let _ = axtask::futex_wait(&FUTEX, 0);
+            }
+            FINISHED.fetch_add(1, Ordering::Relaxed);
+        });
+    }
+    while WAITING.load(Ordering::Relaxed) < NUM_TASKS {
+        axtask::yield_now();
+    }
+    axtask::yield_now();
+
+    // Wake up one task, and move the others to `TARGET`.
+    FUTEX.store(1, Ordering::Release);
+    assert_eq!(
+        axtask::futex_requeue(&FUTEX, Some(0), 1, &TARGET, usize::MAX),
+        Err(axtask::FutexError::WouldBlock)
+    );
+    assert_eq!(
+        axtask::futex_requeue(&FUTEX, Some(1), 1, &TARGET, usize::MAX),
+        Ok(NUM_TASKS)
+    );
+    assert_eq!(axtask::futex_wake(&FUTEX, usize::MAX), 0);
+    while FINISHED.load(Ordering::Relaxed) < 1 {
+        axtask::yield_now();
+    }
+    assert_eq!(axtask::futex_wake(&TARGET, usize::MAX), NUM_TASKS - 1);
+    while FINISHED.load(Ordering::Relaxed) < NUM_TASKS {
+        axtask::yield_now();
+    }
+}
+
+#[test]
+fn test_rcu() {
+    use core::sync::atomic::{AtomicPtr, AtomicUsize};
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static DATA: AtomicPtr<usize> = AtomicPtr::new(core::ptr::null_mut());
+    static FREED: AtomicUsize = AtomicUsize::new(0);
+
+    DATA.store(Box::into_raw(Box::new(1)), Ordering::Release);
+    {
+        let _guard = axtask::rcu_read_lock();
+        let _nested = axtask::rcu_read_lock();
+        assert_eq!(unsafe { *DATA.load(Ordering::Acquire) }, 1);
+    }
+
+    let old = DATA.swap(Box::into_raw(Box::new(2)), Ordering::AcqRel) as usize;
+    axtask::synchronize_rcu();
+    drop(unsafe { Box::from_raw(old as *mut usize) });
+
+    let old = DATA.swap(core::ptr::null_mut(), Ordering::AcqRel) as usize;
+    axtask::call_rcu(move || {
+        drop(unsafe { Box::from_raw(old as *mut usize) });
+        FREED.fetch_add(1, Ordering::Relaxed);
+    });
+    axtask::rcu_barrier();
+    assert_eq!(FREED.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn test_set_sched_policy() {
+    use crate::{SchedPolicy, TaskInner};
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    let task = TaskInner::new(|| {}, "policy".into(), 0x1000);
+    assert!(task.set_sched_policy(SchedPolicy::Normal, 0));
+    assert_eq!(task.sched_policy(), SchedPolicy::Normal);
+    assert_eq!(
+        task.set_sched_policy(SchedPolicy::Fifo, 10),
+        cfg!(feature = "sched_rt")
+    );
+    assert!(!task.set_sched_policy(SchedPolicy::Fifo, 0));
+    assert!(!task.set_sched_policy(SchedPolicy::Deadline, 0));
+}
+
+#[cfg(feature = "sched_rt")]
+#[test]
+fn test_set_ready_task_sched_policy() {
+    use crate::SchedPolicy;
+
+    let _lock = SERIAL.lock();
+    INIT.call_once(axtask::init_scheduler);
+
+    static ORDER: Mutex<Vec<usize>> = Mutex::new(Vec::new());
+    ORDER.lock().unwrap().clear();
+
+    let tasks: Vec<_> = (0..3)
+        .map(|i| {
+            axtask::spawn_raw(
+                move || ORDER.lock().unwrap().push(i),
+                format!("policy{}", i),
+                0x1000,
+            )
+        })
+        .collect();
+    // The ready task is re-queued in the real-time class and runs first.
+    assert!(axtask::set_task_sched_policy(
+        &tasks[2],
+        SchedPolicy::Fifo,
+        10
+    ));
+    assert_eq!(tasks[2].sched_policy(), SchedPolicy::Fifo);
+
+    for task in &tasks {
+        task.join();
+    }
+    assert_eq!(ORDER.lock().unwrap()[0], 2);
+}
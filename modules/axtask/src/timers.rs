@@ -1,5 +1,14 @@
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+#[cfg(feature = "smp")]
+use alloc::vec::Vec;
+#[cfg(feature = "tickless")]
+use core::sync::atomic::AtomicBool;
 use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::Waker;
+use core::time::Duration;
 
+use axlockdep::spin::SpinNoIrq;
 use kernel_guard::NoOp;
 use lazyinit::LazyInit;
 use timer_list::{TimeValue, TimerEvent, TimerList};
@@ -11,7 +20,25 @@ use crate::{select_run_queue, AxTaskRef};
 static TIMER_TICKET_ID: AtomicU64 = AtomicU64::new(1);
 
 percpu_static! {
-    TIMER_LIST: LazyInit<TimerList<TaskWakeupEvent>> = LazyInit::new(),
+    TIMER_LIST: LazyInit<TimerList<AxTimerEvent>> = LazyInit::new(),
+}
+
+enum AxTimerEvent {
+    Wakeup(TaskWakeupEvent),
+    Callback(CallbackEvent),
+    /// Wakes up a future, which checks whether it's really expired when
+    /// polled, so stale events are harmless.
+    Waker(Waker),
+}
+
+impl TimerEvent for AxTimerEvent {
+    fn callback(self, now: TimeValue) {
+        match self {
+            Self::Wakeup(event) => event.callback(now),
+            Self::Callback(event) => event.callback(now),
+            Self::Waker(waker) => waker.wake(),
+        }
+    }
 }
 
 struct TaskWakeupEvent {
@@ -39,10 +66,158 @@ pub fn set_alarm_wakeup(deadline: TimeValue, task: AxTaskRef) {
     TIMER_LIST.with_current(|timer_list| {
         let ticket_id = TIMER_TICKET_ID.fetch_add(1, Ordering::AcqRel);
         task.set_timer_ticket(ticket_id);
-        timer_list.set(deadline, TaskWakeupEvent { ticket_id, task });
+        timer_list.set(
+            deadline,
+            AxTimerEvent::Wakeup(TaskWakeupEvent { ticket_id, task }),
+        );
     })
 }
 
+/// Wakes up the `waker` at `deadline`, for asynchronous timers.
+pub fn set_alarm_waker(deadline: TimeValue, waker: Waker) {
+    TIMER_LIST.with_current(|timer_list| {
+        timer_list.set(deadline, AxTimerEvent::Waker(waker));
+    })
+}
+
+/// A general-purpose timer, which calls a callback function when it expires.
+///
+/// A timer can be armed as one-shot or periodic, re-armed or cancelled at any
+/// time, including from within its own callback. Cloned handles refer to the
+/// same timer. A periodic timer keeps running until it's cancelled, even if
+/// all handles are dropped.
+///
+/// The callback runs in the timer IRQ handler of the CPU that armed the timer
+/// (or another CPU if it went offline), with IRQs and preemption disabled, so
+/// it must not block. It can wake up tasks to do more work.
+#[derive(Clone)]
+pub struct Timer {
+    inner: Arc<TimerInner>,
+}
+
+struct TimerInner {
+    callback: Box<dyn Fn(&Timer) + Send + Sync>,
+    state: SpinNoIrq<TimerState>,
+}
+
+struct TimerState {
+    /// Incremented each time the timer is armed or cancelled, to invalidate
+    /// the events in timer lists that have not expired yet.
+    generation: u64,
+    deadline: Option<TimeValue>,
+    interval: Option<Duration>,
+}
+
+struct CallbackEvent {
+    generation: u64,
+    timer: Timer,
+}
+
+impl TimerEvent for CallbackEvent {
+    fn callback(self, now: TimeValue) {
+        let mut state = self.timer.inner.state.lock();
+        if state.generation != self.generation {
+            // The timer has been re-armed or cancelled.
+            return;
+        }
+        match (state.deadline, state.interval) {
+            (Some(deadline), Some(interval)) => {
+                // Skip the periods that have been missed.
+                let mut next = deadline + interval;
+                if next <= now {
+                    let period_ns = interval.as_nanos();
+                    let missed = (now - next).as_nanos() / period_ns + 1;
+                    next += Duration::from_nanos((missed * period_ns) as u64);
+                }
+                state.deadline = Some(next);
+                self.timer.add_event(next, state.generation);
+            }
+            _ => state.deadline = None,
+        }
+        drop(state);
+        (self.timer.inner.callback)(&self.timer);
+    }
+}
+
+impl Timer {
+    /// Creates a new timer that calls `callback` when it expires.
+    ///
+    /// The timer is not armed until [`Timer::set`] or [`Timer::set_after`] is
+    /// called.
+    pub fn new<F>(callback: F) -> Self
+    where
+        F: Fn(&Timer) + Send + Sync + 'static,
+    {
+        Self {
+            inner: Arc::new(TimerInner {
+                callback: Box::new(callback),
+                state: SpinNoIrq::new(TimerState {
+                    generation: 0,
+                    deadline: None,
+                    interval: None,
+                }),
+            }),
+        }
+    }
+
+    fn add_event(&self, deadline: TimeValue, generation: u64) {
+        TIMER_LIST.with_current(|timer_list| {
+            timer_list.set(
+                deadline,
+                AxTimerEvent::Callback(CallbackEvent {
+                    generation,
+                    timer: self.clone(),
+                }),
+            );
+        })
+    }
+
+    /// Arms the timer to expire at `deadline` (in wall time). If `interval`
+    /// is not [`None`] or zero, it expires again every `interval` after that.
+    ///
+    /// The previous setting of the timer is replaced.
+    pub fn set(&self, deadline: TimeValue, interval: Option<Duration>) {
+        let mut state = self.inner.state.lock();
+        state.generation += 1;
+        state.deadline = Some(deadline);
+        state.interval = interval.filter(|interval| !interval.is_zero());
+        self.add_event(deadline, state.generation);
+    }
+
+    /// Arms the timer to expire after `delay`. If `interval` is not [`None`]
+    /// or zero, it expires again every `interval` after that.
+    ///
+    /// The previous setting of the timer is replaced.
+    pub fn set_after(&self, delay: Duration, interval: Option<Duration>) {
+        self.set(wall_time() + delay, interval)
+    }
+
+    /// Cancels the timer, returns whether it was armed.
+    pub fn cancel(&self) -> bool {
+        let mut state = self.inner.state.lock();
+        state.generation += 1;
+        state.interval = None;
+        state.deadline.take().is_some()
+    }
+
+    /// Returns whether the timer is armed.
+    pub fn is_armed(&self) -> bool {
+        self.inner.state.lock().deadline.is_some()
+    }
+
+    /// Returns the next expiration time (in wall time) of the timer, or
+    /// [`None`] if it's not armed.
+    pub fn deadline(&self) -> Option<TimeValue> {
+        self.inner.state.lock().deadline
+    }
+
+    /// Returns the interval of the periodic timer, or [`None`] if it's a
+    /// one-shot timer.
+    pub fn interval(&self) -> Option<Duration> {
+        self.inner.state.lock().interval
+    }
+}
+
 pub fn check_events() {
     loop {
         let now = wall_time();
@@ -52,6 +227,10 @@ pub fn check_events() {
         }
         .expire_one(now);
         if let Some((_deadline, event)) = event {
+            #[cfg(feature = "trace")]
+            axtrace::record(axtrace::TraceEvent::TimerExpire {
+                latency_ns: (now - _deadline).as_nanos() as u64,
+            });
             event.callback(now);
         } else {
             break;
@@ -59,6 +238,93 @@ pub fn check_events() {
     }
 }
 
+/// Moves all timer events of the current CPU, which is going offline, to the
+/// CPU `target`.
+///
+/// It must be called with IRQs disabled.
+#[cfg(feature = "smp")]
+pub(crate) fn migrate_events(target: usize) {
+    // Safety: IRQs are disabled at this time.
+    let timer_list = unsafe { TIMER_LIST.current_ref_mut_raw() };
+    let mut events = Vec::new();
+    while let Some(event) = timer_list.expire_one(TimeValue::MAX) {
+        events.push(event);
+    }
+    if events.is_empty() {
+        return;
+    }
+    debug!("migrate {} timer events to CPU {}", events.len(), target);
+    let arg = Box::into_raw(Box::new(events)) as usize;
+    if !axhal::ipi::run_on_cpu(target, receive_events, arg, true) {
+        // The target has just gone offline, keep them until we come back.
+        receive_events(arg);
+    }
+}
+
+#[cfg(feature = "smp")]
+fn receive_events(arg: usize) {
+    let events = unsafe { Box::from_raw(arg as *mut Vec<(TimeValue, AxTimerEvent)>) };
+    // Safety: IRQs are disabled at this time.
+    let timer_list = unsafe { TIMER_LIST.current_ref_mut_raw() };
+    for (deadline, event) in *events {
+        timer_list.set(deadline, event);
+    }
+}
+
+/// The interval of the periodic tick.
+#[cfg(feature = "tickless")]
+const TICK_INTERVAL_NANOS: u64 = axhal::time::NANOS_PER_SEC / axconfig::TICKS_PER_SEC as u64;
+
+/// The longest time an idle CPU sleeps with its periodic tick stopped, which
+/// bounds the latency of noticing tasks put into its run queue by other CPUs.
+#[cfg(feature = "tickless")]
+const MAX_IDLE_NANOS: u64 = axhal::time::NANOS_PER_SEC;
+
+/// Whether the periodic tick of each CPU is stopped.
+#[cfg(feature = "tickless")]
+static TICK_STOPPED: [AtomicBool; axconfig::SMP] = [STOPPED_REPEAT_VALUE; axconfig::SMP];
+#[cfg(feature = "tickless")]
+#[allow(clippy::declare_interior_mutable_const)]
+const STOPPED_REPEAT_VALUE: AtomicBool = AtomicBool::new(false);
+
+/// Stops the periodic tick of the current CPU, and programs the timer for the
+/// next timer event only.
+///
+/// It must be called by the idle task with IRQs disabled, when there are no
+/// ready tasks on the current CPU.
+#[cfg(feature = "tickless")]
+pub fn stop_tick() {
+    TICK_STOPPED[axhal::cpu::this_cpu_id()].store(true, Ordering::Release);
+    let now_ns = axhal::time::monotonic_time_nanos();
+    // Safety: IRQs are disabled at this time.
+    let next_ns = unsafe { TIMER_LIST.current_ref_raw() }
+        .next_deadline()
+        .map_or(u64::MAX, |deadline| {
+            (deadline.as_nanos() as u64).saturating_sub(axhal::time::epochoffset_nanos())
+        });
+    axhal::time::set_oneshot_timer(next_ns.min(now_ns + MAX_IDLE_NANOS));
+}
+
+/// Restarts the periodic tick of the current CPU if it's stopped.
+///
+/// It's called when the current CPU switches from the idle task to another
+/// task with IRQs disabled.
+#[cfg(feature = "tickless")]
+pub fn restart_tick() {
+    if TICK_STOPPED[axhal::cpu::this_cpu_id()].swap(false, Ordering::AcqRel) {
+        let now_ns = axhal::time::monotonic_time_nanos();
+        axhal::time::set_oneshot_timer(now_ns + TICK_INTERVAL_NANOS);
+    }
+}
+
+/// Called on timer IRQs, after the periodic tick has been programmed again by
+/// the IRQ handler.
+#[cfg(feature = "tickless")]
+#[inline]
+pub fn tick_restarted() {
+    TICK_STOPPED[axhal::cpu::this_cpu_id()].store(false, Ordering::Release);
+}
+
 pub fn init() {
     TIMER_LIST.with_current(|timer_list| {
         timer_list.init_once(TimerList::new());
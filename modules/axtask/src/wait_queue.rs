@@ -1,10 +1,13 @@
 use alloc::collections::VecDeque;
 use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
 
+use axlockdep::spin::{SpinNoIrq, SpinNoIrqGuard};
 use kernel_guard::{NoOp, NoPreemptIrqSave};
-use kspin::{SpinNoIrq, SpinNoIrqGuard};
 
-use crate::{current_run_queue, select_run_queue, AxTaskRef, CurrentTask};
+use crate::{current_run_queue, select_run_queue, AxTaskRef, Cancelled, CurrentTask};
 
 /// A queue to store sleeping tasks.
 ///
@@ -28,14 +31,43 @@ use crate::{current_run_queue, select_run_queue, AxTaskRef, CurrentTask};
 /// WQ.wait(); // block until `notify()` is called
 /// assert_eq!(VALUE.load(Ordering::Relaxed), 1);
 /// ```
+///
+/// Besides tasks, futures can wait in the queue as well, by registering their
+/// [`Waker`]s with [`WaitQueue::register_waker`] or awaiting
+/// [`WaitQueue::wait_until_async`]. A notification wakes up either a task or a
+/// waker, in the order they were queued.
 pub struct WaitQueue {
-    queue: SpinNoIrq<VecDeque<AxTaskRef>>,
+    queue: SpinNoIrq<VecDeque<Waiter>>,
+}
+
+/// A task or a future waiting in a [`WaitQueue`].
+pub(crate) enum Waiter {
+    Task(AxTaskRef),
+    Waker(Waker),
+}
+
+impl Waiter {
+    pub(crate) fn as_task(&self) -> Option<&AxTaskRef> {
+        match self {
+            Self::Task(task) => Some(task),
+            Self::Waker(_) => None,
+        }
+    }
+
+    pub(crate) fn is_task(&self, task: &AxTaskRef) -> bool {
+        matches!(self, Self::Task(t) if Arc::ptr_eq(t, task))
+    }
+
+    fn is_waker(&self, waker: &Waker) -> bool {
+        matches!(self, Self::Waker(w) if w.will_wake(waker))
+    }
 }
 
-pub(crate) type WaitQueueGuard<'a> = SpinNoIrqGuard<'a, VecDeque<AxTaskRef>>;
+pub(crate) type WaitQueueGuard<'a> = SpinNoIrqGuard<'a, VecDeque<Waiter>>;
 
 impl WaitQueue {
     /// Creates an empty wait queue.
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub const fn new() -> Self {
         Self {
             queue: SpinNoIrq::new(VecDeque::new()),
@@ -43,12 +75,18 @@ impl WaitQueue {
     }
 
     /// Creates an empty wait queue with space for at least `capacity` elements.
+    #[cfg_attr(feature = "lockdep", track_caller)]
     pub fn with_capacity(capacity: usize) -> Self {
         Self {
             queue: SpinNoIrq::new(VecDeque::with_capacity(capacity)),
         }
     }
 
+    /// Locks the queue, for the wait primitives built on top of wait queues.
+    pub(crate) fn lock(&self) -> WaitQueueGuard<'_> {
+        self.queue.lock()
+    }
+
     /// Cancel events by removing the task from the wait queue.
     /// If `from_timer_list` is true, try to remove the task from the timer list.
     fn cancel_events(&self, curr: CurrentTask, _from_timer_list: bool) {
@@ -56,7 +94,7 @@ impl WaitQueue {
         // the event from another queue.
         if curr.in_wait_queue() {
             // wake up by timer (timeout).
-            self.queue.lock().retain(|t| !curr.ptr_eq(t));
+            self.queue.lock().retain(|w| !w.is_task(curr.as_task_ref()));
             curr.set_in_wait_queue(false);
         }
 
@@ -73,6 +111,24 @@ impl WaitQueue {
         }
     }
 
+    /// Finishes a cancellable wait, like `cancel_events`.
+    ///
+    /// If the task was notified but returns [`Cancelled`], the notification is
+    /// passed on to another task in the wait queue, so that it's not lost.
+    fn finish_cancellable(
+        &self,
+        curr: CurrentTask,
+        from_timer_list: bool,
+        res: Result<(), Cancelled>,
+    ) -> Result<(), Cancelled> {
+        let notified = !curr.in_wait_queue();
+        self.cancel_events(curr, from_timer_list);
+        if res.is_err() && notified {
+            self.notify_one(false);
+        }
+        res
+    }
+
     /// Blocks the current task and put it into the wait queue, until other task
     /// notifies it.
     pub fn wait(&self) {
@@ -102,6 +158,39 @@ impl WaitQueue {
         self.cancel_events(curr, false);
     }
 
+    /// Like [`WaitQueue::wait`], but it's a cancellation point: returns
+    /// [`Cancelled`] as soon as the current task is cancelled by
+    /// [`cancel_task`](crate::cancel_task).
+    pub fn wait_cancellable(&self) -> Result<(), Cancelled> {
+        let res =
+            current_run_queue::<NoPreemptIrqSave>().cancellable_blocked_resched(self.queue.lock());
+        self.finish_cancellable(crate::current(), false, res)
+    }
+
+    /// Like [`WaitQueue::wait_until`], but it's a cancellation point: returns
+    /// [`Cancelled`] as soon as the current task is cancelled by
+    /// [`cancel_task`](crate::cancel_task), even if the condition is false.
+    pub fn wait_until_cancellable<F>(&self, condition: F) -> Result<(), Cancelled>
+    where
+        F: Fn() -> bool,
+    {
+        let curr = crate::current();
+        let mut res = Ok(());
+        loop {
+            let mut rq = current_run_queue::<NoPreemptIrqSave>();
+            let wq = self.queue.lock();
+            if condition() {
+                break;
+            }
+            res = rq.cancellable_blocked_resched(wq);
+            if res.is_err() {
+                break;
+            }
+            // Preemption may occur here.
+        }
+        self.finish_cancellable(curr, false, res)
+    }
+
     /// Blocks the current task and put it into the wait queue, until other tasks
     /// notify it, or the given duration has elapsed.
     #[cfg(feature = "irq")]
@@ -125,6 +214,31 @@ impl WaitQueue {
         timeout
     }
 
+    /// Like [`WaitQueue::wait_timeout`], but it's a cancellation point: returns
+    /// [`Cancelled`] as soon as the current task is cancelled by
+    /// [`cancel_task`](crate::cancel_task).
+    ///
+    /// Otherwise, returns whether the wait timed out.
+    #[cfg(feature = "irq")]
+    pub fn wait_timeout_cancellable(&self, dur: core::time::Duration) -> Result<bool, Cancelled> {
+        let mut rq = current_run_queue::<NoPreemptIrqSave>();
+        let curr = crate::current();
+        let deadline = axhal::time::wall_time() + dur;
+        debug!(
+            "task wait_timeout (cancellable): {} deadline={:?}",
+            curr.id_name(),
+            deadline
+        );
+        crate::timers::set_alarm_wakeup(deadline, curr.clone());
+
+        let res = rq.cancellable_blocked_resched(self.queue.lock());
+
+        let timeout = curr.in_wait_queue(); // still in the wait queue, must have timed out
+
+        // Always try to remove the task from the timer list.
+        self.finish_cancellable(curr, true, res).map(|_| timeout)
+    }
+
     /// Blocks the current task and put it into the wait queue, until the given
     /// `condition` becomes true, or the given duration has elapsed.
     ///
@@ -164,27 +278,81 @@ impl WaitQueue {
         timeout
     }
 
-    /// Wakes up one task in the wait queue, usually the first one.
+    /// Registers a waker in the wait queue, which is woken up (and removed) by
+    /// a later notification instead of a task.
     ///
-    /// If `resched` is true, the current task will be preempted when the
-    /// preemption is enabled.
-    pub fn notify_one(&self, resched: bool) -> bool {
+    /// It does nothing if an equivalent waker is already registered. Like
+    /// [`WaitQueue::wait_until`], the caller should check its condition again
+    /// after registering, in case the notification has been sent before.
+    pub fn register_waker(&self, waker: &Waker) {
+        let mut wq = self.queue.lock();
+        if !wq.iter().any(|w| w.is_waker(waker)) {
+            wq.push_back(Waiter::Waker(waker.clone()));
+        }
+    }
+
+    /// Removes a registered waker, returns `false` if it's not in the wait
+    /// queue, i.e., it has been notified.
+    pub fn unregister_waker(&self, waker: &Waker) -> bool {
         let mut wq = self.queue.lock();
-        if let Some(task) = wq.pop_front() {
-            unblock_one_task(task, resched);
+        if let Some(index) = wq.iter().position(|w| w.is_waker(waker)) {
+            wq.remove(index);
             true
         } else {
             false
         }
     }
 
-    /// Wakes all tasks in the wait queue.
+    /// Returns a future that waits in the wait queue until the given
+    /// `condition` becomes true.
+    ///
+    /// It's the asynchronous version of [`WaitQueue::wait_until`]. If the
+    /// future is dropped after it's notified but before it's ready, the
+    /// notification is passed on to another waiter.
+    pub fn wait_until_async<F>(&self, condition: F) -> WaitUntil<'_, F>
+    where
+        F: Fn() -> bool,
+    {
+        WaitUntil {
+            wq: self,
+            condition,
+            waker: None,
+        }
+    }
+
+    /// Wakes up one task or waker in the wait queue, usually the first one.
+    ///
+    /// If `resched` is true, the current task will be preempted when the
+    /// preemption is enabled.
+    pub fn notify_one(&self, resched: bool) -> bool {
+        let mut wq = self.queue.lock();
+        match wq.pop_front() {
+            Some(Waiter::Task(task)) => unblock_one_task(task, resched),
+            Some(Waiter::Waker(waker)) => {
+                // The waker may use this wait queue again (e.g. register
+                // itself), so call it without the lock held.
+                drop(wq);
+                waker.wake();
+            }
+            None => return false,
+        }
+        true
+    }
+
+    /// Wakes all tasks and wakers in the wait queue.
+    ///
+    /// Wakers registered again while being woken are kept in the queue.
     ///
     /// If `resched` is true, the current task will be preempted when the
     /// preemption is enabled.
     pub fn notify_all(&self, resched: bool) {
-        while self.notify_one(resched) {
-            // loop until the wait queue is empty
+        // Only wake up the waiters queued by now, as a woken waker may
+        // register itself again.
+        let num = self.queue.lock().len();
+        for _ in 0..num {
+            if !self.notify_one(resched) {
+                break;
+            }
         }
     }
 
@@ -194,8 +362,9 @@ impl WaitQueue {
     /// preemption is enabled.
     pub fn notify_task(&mut self, resched: bool, task: &AxTaskRef) -> bool {
         let mut wq = self.queue.lock();
-        if let Some(index) = wq.iter().position(|t| Arc::ptr_eq(t, task)) {
-            unblock_one_task(wq.remove(index).unwrap(), resched);
+        if let Some(index) = wq.iter().position(|w| w.is_task(task)) {
+            wq.remove(index);
+            unblock_one_task(task.clone(), resched);
             true
         } else {
             false
@@ -203,7 +372,7 @@ impl WaitQueue {
     }
 }
 
-fn unblock_one_task(task: AxTaskRef, resched: bool) {
+pub(crate) fn unblock_one_task(task: AxTaskRef, resched: bool) {
     // Mark task as not in wait queue.
     task.set_in_wait_queue(false);
     // Select run queue by the CPU set of the task.
@@ -211,3 +380,47 @@ fn unblock_one_task(task: AxTaskRef, resched: bool) {
     // lock of wait queue, where the irq and preemption are disabled.
     select_run_queue::<NoOp>(&task).unblock_task(task, resched)
 }
+
+/// The future returned by [`WaitQueue::wait_until_async`].
+pub struct WaitUntil<'a, F: Fn() -> bool> {
+    wq: &'a WaitQueue,
+    condition: F,
+    /// The waker registered in the wait queue, if any.
+    waker: Option<Waker>,
+}
+
+impl<F: Fn() -> bool> Future for WaitUntil<'_, F> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Safety: nothing is structurally pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let mut wq = this.wq.queue.lock();
+        if (this.condition)() {
+            if let Some(waker) = this.waker.take() {
+                wq.retain(|w| !w.is_waker(&waker));
+            }
+            return Poll::Ready(());
+        }
+        let waker = cx.waker();
+        if let Some(old) = this.waker.as_ref().filter(|old| !old.will_wake(waker)) {
+            wq.retain(|w| !w.is_waker(old));
+        }
+        if !wq.iter().any(|w| w.is_waker(waker)) {
+            wq.push_back(Waiter::Waker(waker.clone()));
+        }
+        this.waker = Some(waker.clone());
+        Poll::Pending
+    }
+}
+
+impl<F: Fn() -> bool> Drop for WaitUntil<'_, F> {
+    fn drop(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            if !self.wq.unregister_waker(&waker) {
+                // Notified but not ready, don't lose the notification.
+                self.wq.notify_one(false);
+            }
+        }
+    }
+}
@@ -0,0 +1,303 @@
+//! Soft lockup and hung task detection.
+//!
+//! A CPU is in a soft lockup if it has not scheduled for a long time while
+//! running a non-idle task with preemption disabled, e.g., the task spins in
+//! a critical section. It is checked by each CPU on its own timer ticks, and
+//! only with the `preempt` feature, since tasks may run as long as they like
+//! under cooperative scheduling.
+//!
+//! A task is hung if it has been blocked in a wait queue for a long time. All
+//! tasks are checked by the `watchdog` task, which is spawned when the hung
+//! task detection is enabled. It sleeps until the earliest time a task may
+//! become hung, so it rarely wakes up idle CPUs.
+//!
+//! The offending task is reported with its state and backtrace, and the
+//! system panics if [`WatchdogConfig::panic`] is set.
+
+use alloc::collections::BTreeMap;
+use core::ops::Range;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::time::Duration;
+
+use axhal::backtrace::Backtrace;
+use axhal::cpu::this_cpu_id;
+use axhal::time::monotonic_time_nanos;
+
+use crate::{AxTaskRef, TaskState, WaitQueue};
+
+/// Configuration of the watchdog.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct WatchdogConfig {
+    /// A CPU is reported if it has not scheduled for longer than this with
+    /// preemption disabled, or [`None`] to disable the soft lockup detection.
+    /// It's ignored without the `preempt` feature.
+    pub soft_lockup_timeout: Option<Duration>,
+    /// A task is reported if it has been blocked in a wait queue for longer
+    /// than this, or [`None`] to disable the hung task detection.
+    pub hung_task_timeout: Option<Duration>,
+    /// Whether to panic after reporting.
+    pub panic: bool,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self {
+            soft_lockup_timeout: Some(Duration::from_secs(20)),
+            hung_task_timeout: Some(Duration::from_secs(120)),
+            panic: false,
+        }
+    }
+}
+
+// Timeouts in nanoseconds, 0 means disabled.
+static SOFT_LOCKUP_TIMEOUT_NS: AtomicU64 = AtomicU64::new(20_000_000_000);
+static HUNG_TASK_TIMEOUT_NS: AtomicU64 = AtomicU64::new(120_000_000_000);
+static PANIC_ON_REPORT: AtomicBool = AtomicBool::new(false);
+
+/// Whether the task scheduler is initialized, so that the `watchdog` task can
+/// be spawned.
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+static HUNG_TASK_STARTED: AtomicBool = AtomicBool::new(false);
+/// The `watchdog` task waits here for the next check or a change of the
+/// hung task timeout.
+static HUNG_TASK_WQ: WaitQueue = WaitQueue::new();
+
+#[allow(clippy::declare_interior_mutable_const)]
+const LAST_SCHED_REPEAT_VALUE: AtomicU64 = AtomicU64::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const REPORTED_REPEAT_VALUE: AtomicBool = AtomicBool::new(false);
+
+/// Timestamp (in nanoseconds) of the last scheduling on each CPU, or 0 if the
+/// CPU has not scheduled yet.
+static LAST_SCHED_NS: [AtomicU64; axconfig::SMP] = [LAST_SCHED_REPEAT_VALUE; axconfig::SMP];
+/// Whether the soft lockup in progress on each CPU has been reported.
+static LOCKUP_REPORTED: [AtomicBool; axconfig::SMP] = [REPORTED_REPEAT_VALUE; axconfig::SMP];
+
+fn to_nanos(timeout: Option<Duration>) -> u64 {
+    timeout.map_or(0, |t| (t.as_nanos() as u64).max(1))
+}
+
+fn from_nanos(ns: u64) -> Option<Duration> {
+    (ns != 0).then(|| Duration::from_nanos(ns))
+}
+
+/// Sets the configuration of the watchdog.
+pub fn set_watchdog_config(config: WatchdogConfig) {
+    SOFT_LOCKUP_TIMEOUT_NS.store(to_nanos(config.soft_lockup_timeout), Ordering::Relaxed);
+    HUNG_TASK_TIMEOUT_NS.store(to_nanos(config.hung_task_timeout), Ordering::Relaxed);
+    PANIC_ON_REPORT.store(config.panic, Ordering::Relaxed);
+    HUNG_TASK_WQ.notify_one(false);
+    start_hung_task_detection();
+}
+
+/// Returns the current configuration of the watchdog.
+pub fn watchdog_config() -> WatchdogConfig {
+    WatchdogConfig {
+        soft_lockup_timeout: from_nanos(SOFT_LOCKUP_TIMEOUT_NS.load(Ordering::Relaxed)),
+        hung_task_timeout: from_nanos(HUNG_TASK_TIMEOUT_NS.load(Ordering::Relaxed)),
+        panic: PANIC_ON_REPORT.load(Ordering::Relaxed),
+    }
+}
+
+/// Records that the current CPU has scheduled.
+pub(crate) fn touch(now: u64) {
+    let cpu_id = this_cpu_id();
+    LAST_SCHED_NS[cpu_id].store(now, Ordering::Relaxed);
+    LOCKUP_REPORTED[cpu_id].store(false, Ordering::Relaxed);
+}
+
+/// Checks whether the current CPU is in a soft lockup, called on timer ticks.
+#[cfg(feature = "preempt")]
+pub(crate) fn check_soft_lockup() {
+    let timeout_ns = SOFT_LOCKUP_TIMEOUT_NS.load(Ordering::Relaxed);
+    if timeout_ns == 0 {
+        return;
+    }
+    let now = monotonic_time_nanos();
+    let curr = crate::current();
+    let cpu_id = this_cpu_id();
+    let last = LAST_SCHED_NS[cpu_id].load(Ordering::Relaxed);
+    // The idle task waits for IRQs, the interrupted task can be preempted
+    // (preemption is only disabled by the IRQ handler), or it's the first
+    // tick.
+    if curr.is_idle() || curr.can_preempt(1) || last == 0 {
+        touch(now);
+        return;
+    }
+    let stuck_ns = now.saturating_sub(last);
+    if stuck_ns <= timeout_ns || LOCKUP_REPORTED[cpu_id].swap(true, Ordering::Relaxed) {
+        return;
+    }
+    error!(
+        "watchdog: soft lockup - CPU#{} stuck for {}ms! [{}]",
+        cpu_id,
+        stuck_ns / 1_000_000,
+        curr.id_name()
+    );
+    let fp = axhal::arch::read_frame_pointer();
+    let backtrace = Backtrace::current(stack_range(curr.as_task_ref(), fp));
+    error!("backtrace:\n{}", backtrace);
+    if PANIC_ON_REPORT.load(Ordering::Relaxed) {
+        panic!("watchdog: soft lockup on CPU#{}", cpu_id);
+    }
+}
+
+/// Returns the kernel stack of the task, or a range above the frame pointer
+/// `fp` with the size of the boot stack if the task runs on the boot stack.
+fn stack_range(task: &AxTaskRef, fp: usize) -> Range<usize> {
+    task.kernel_stack_range()
+        .unwrap_or(fp..fp.saturating_add(axconfig::TASK_STACK_SIZE))
+}
+
+/// Reports a hung task.
+fn report_hung_task(task: &AxTaskRef, blocked_ns: u64) {
+    error!(
+        "watchdog: {} blocked for more than {}s, state: {:?}, last CPU: {:?}",
+        task.id_name(),
+        blocked_ns / 1_000_000_000,
+        task.state(),
+        task.stats_counters().last_cpu(),
+    );
+    // Safety: the task is blocked, and its stack is kept alive by `task`. The
+    // backtrace may be garbage if it's woken up meanwhile, but it's only read
+    // within the stack.
+    let backtrace = unsafe {
+        let ctx = &*task.ctx_mut_ptr();
+        let (_, fp) = ctx.switch_frame();
+        Backtrace::from_task_context(ctx, stack_range(task, fp))
+    };
+    error!("backtrace:\n{}", backtrace);
+}
+
+/// Tracks blocked tasks to report each blocking period only once.
+#[derive(Default)]
+struct HungTaskTracker {
+    /// Task ID -> start time of the blocking period that has been reported.
+    reported: BTreeMap<u64, u64>,
+}
+
+impl HungTaskTracker {
+    /// Checks the `blocked` tasks (ID, nanoseconds blocked, task) at `now`,
+    /// and calls `report` for each newly hung one.
+    ///
+    /// Returns whether any task was reported, and the earliest time a task
+    /// may become hung, i.e., the time of the next check.
+    fn check<T>(
+        &mut self,
+        now: u64,
+        timeout_ns: u64,
+        blocked: impl IntoIterator<Item = (u64, u64, T)>,
+        mut report: impl FnMut(T, u64),
+    ) -> (bool, u64) {
+        let mut hung = false;
+        // Tasks blocked from now on become hung after the whole timeout.
+        let mut next_check = now.saturating_add(timeout_ns);
+        let mut still_blocked = BTreeMap::new();
+        for (id, blocked_ns, task) in blocked {
+            let since = now.saturating_sub(blocked_ns);
+            if blocked_ns > timeout_ns {
+                if self.reported.get(&id) != Some(&since) {
+                    report(task, blocked_ns);
+                    hung = true;
+                }
+                still_blocked.insert(id, since);
+            } else {
+                next_check = next_check.min(since.saturating_add(timeout_ns + 1));
+            }
+        }
+        self.reported = still_blocked;
+        (hung, next_check)
+    }
+}
+
+fn hung_task_main() {
+    let mut tracker = HungTaskTracker::default();
+    loop {
+        let timeout_ns = HUNG_TASK_TIMEOUT_NS.load(Ordering::Relaxed);
+        if timeout_ns == 0 {
+            tracker.reported.clear();
+            HUNG_TASK_WQ.wait_until(|| HUNG_TASK_TIMEOUT_NS.load(Ordering::Relaxed) != 0);
+            continue;
+        }
+
+        let now = monotonic_time_nanos();
+        let blocked = crate::all_tasks().into_iter().filter_map(|task| {
+            if task.state() != TaskState::Blocked
+                || !task.in_wait_queue()
+                || !task.hung_task_check()
+            {
+                return None;
+            }
+            let blocked_ns = task.stats_counters().pending_ns(now);
+            Some((task.id().as_u64(), blocked_ns, task))
+        });
+        let (hung, next_check) = tracker.check(now, timeout_ns, blocked, |task, blocked_ns| {
+            report_hung_task(&task, blocked_ns)
+        });
+        if hung && PANIC_ON_REPORT.load(Ordering::Relaxed) {
+            panic!("watchdog: hung tasks detected");
+        }
+
+        let dur = Duration::from_nanos(next_check.saturating_sub(monotonic_time_nanos()));
+        HUNG_TASK_WQ.wait_timeout_until(dur, || {
+            HUNG_TASK_TIMEOUT_NS.load(Ordering::Relaxed) != timeout_ns
+        });
+    }
+}
+
+/// Spawns the `watchdog` task if the hung task detection is enabled and it's
+/// not spawned yet.
+fn start_hung_task_detection() {
+    if !INITIALIZED.load(Ordering::Acquire)
+        || HUNG_TASK_TIMEOUT_NS.load(Ordering::Relaxed) == 0
+        || HUNG_TASK_STARTED.swap(true, Ordering::AcqRel)
+    {
+        return;
+    }
+    let task = crate::spawn_raw(hung_task_main, "watchdog".into(), axconfig::TASK_STACK_SIZE);
+    task.set_hung_task_check(false);
+}
+
+/// Starts the hung task detection if it's enabled.
+pub(crate) fn init() {
+    INITIALIZED.store(true, Ordering::Release);
+    start_hung_task_detection();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HungTaskTracker;
+
+    const S: u64 = 1_000_000_000;
+
+    #[test]
+    fn test_hung_task_tracker() {
+        let mut tracker = HungTaskTracker::default();
+        let mut reported = alloc::vec::Vec::new();
+        let mut check = |tracker: &mut HungTaskTracker, now, blocked: &[(u64, u64)]| {
+            let blocked = blocked.iter().map(|&(id, ns)| (id, ns, id));
+            tracker.check(now, 10 * S, blocked, |id, _| reported.push(id))
+        };
+
+        // Nothing is blocked, the next check is after the whole timeout.
+        assert_eq!(check(&mut tracker, 100 * S, &[]), (false, 110 * S));
+        // Task 1 has been blocked for 4s, and becomes hung 6s later.
+        assert_eq!(
+            check(&mut tracker, 100 * S, &[(1, 4 * S)]),
+            (false, 106 * S + 1)
+        );
+        // Task 1 is hung, and reported once.
+        assert!(check(&mut tracker, 107 * S, &[(1, 11 * S)]).0);
+        assert_eq!(
+            check(&mut tracker, 108 * S, &[(1, 12 * S)]),
+            (false, 118 * S)
+        );
+        // Task 1 is blocked again since 109s, and reported again at 120s.
+        assert_eq!(
+            check(&mut tracker, 115 * S, &[(1, 6 * S)]),
+            (false, 119 * S + 1)
+        );
+        assert!(check(&mut tracker, 120 * S, &[(1, 11 * S)]).0);
+        assert_eq!(reported, [1, 1]);
+    }
+}
@@ -0,0 +1,384 @@
+//! [ArceOS](https://github.com/arceos-org/arceos) kernel event tracing.
+//!
+//! Kernel events, such as context switches, task wakeups, IRQs and timer
+//! expiries, are recorded by [`record`] into a ring buffer of each CPU, along
+//! with the timestamps. Recording is lock-free, so it can be done in any
+//! context, including IRQ handlers. Only the latest [`EVENTS_PER_CPU`] events
+//! of each CPU are kept.
+//!
+//! Tracing is disabled at boot, and can be switched at runtime by
+//! [`set_enabled`]. The recorded events can be exported by
+//! [`export_chrome_json`] in the [Chrome trace event format][1], which can be
+//! loaded by [Perfetto](https://ui.perfetto.dev) or `chrome://tracing`.
+//!
+//! The timestamps and CPU IDs are provided by the [`TraceIf`] interface, which
+//! must be implemented in other crates.
+//!
+//! [1]: https://docs.google.com/document/d/1CvAClvFfyA5R-PhYUmn5OOQtYMH4h6I0nSsKchNAySU
+
+#![cfg_attr(not(test), no_std)]
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{fence, AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+use crate_interface::call_interface;
+
+/// The number of events kept in the ring buffer of each CPU.
+pub const EVENTS_PER_CPU: usize = 4096;
+
+/// Extern interfaces that must be implemented in other crates.
+#[crate_interface::def_interface]
+pub trait TraceIf {
+    /// Gets the current monotonic time in nanoseconds.
+    fn monotonic_time_nanos() -> u64;
+
+    /// Gets the current CPU ID.
+    fn current_cpu_id() -> usize;
+}
+
+/// A traced kernel event.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TraceEvent {
+    /// The CPU switches from task `prev` to task `next`.
+    ContextSwitch {
+        /// ID of the previous task.
+        prev: u64,
+        /// ID of the next task.
+        next: u64,
+    },
+    /// A blocked task is woken up, and put into the run queue of `cpu`.
+    Wakeup {
+        /// ID of the woken task.
+        task: u64,
+        /// The CPU whose run queue the task is put into.
+        cpu: usize,
+    },
+    /// The CPU starts to handle an IRQ.
+    IrqEnter {
+        /// The IRQ number.
+        irq: usize,
+    },
+    /// The CPU finishes handling an IRQ.
+    IrqExit {
+        /// The IRQ number.
+        irq: usize,
+    },
+    /// A timer event expires.
+    TimerExpire {
+        /// How late the event is handled after its deadline, in nanoseconds.
+        latency_ns: u64,
+    },
+}
+
+impl TraceEvent {
+    fn encode(self) -> (u64, u64, u64) {
+        match self {
+            Self::ContextSwitch { prev, next } => (0, prev, next),
+            Self::Wakeup { task, cpu } => (1, task, cpu as u64),
+            Self::IrqEnter { irq } => (2, irq as u64, 0),
+            Self::IrqExit { irq } => (3, irq as u64, 0),
+            Self::TimerExpire { latency_ns } => (4, latency_ns, 0),
+        }
+    }
+
+    fn decode(kind: u64, arg0: u64, arg1: u64) -> Option<Self> {
+        Some(match kind {
+            0 => Self::ContextSwitch {
+                prev: arg0,
+                next: arg1,
+            },
+            1 => Self::Wakeup {
+                task: arg0,
+                cpu: arg1 as usize,
+            },
+            2 => Self::IrqEnter { irq: arg0 as usize },
+            3 => Self::IrqExit { irq: arg0 as usize },
+            4 => Self::TimerExpire { latency_ns: arg0 },
+            _ => return None,
+        })
+    }
+}
+
+/// A slot in the ring buffer.
+///
+/// `seq` is the index of the event plus one after the event is completely
+/// written, and zero while it's being written, like a sequence lock.
+struct Slot {
+    seq: AtomicUsize,
+    timestamp: AtomicU64,
+    kind: AtomicU64,
+    arg0: AtomicU64,
+    arg1: AtomicU64,
+}
+
+struct CpuBuffer {
+    /// Index of the next event to write.
+    head: AtomicUsize,
+    /// Index of the first event not cleared.
+    start: AtomicUsize,
+    slots: [Slot; EVENTS_PER_CPU],
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const SLOT_INIT: Slot = Slot {
+    seq: AtomicUsize::new(0),
+    timestamp: AtomicU64::new(0),
+    kind: AtomicU64::new(0),
+    arg0: AtomicU64::new(0),
+    arg1: AtomicU64::new(0),
+};
+
+#[allow(clippy::declare_interior_mutable_const)]
+const BUFFER_INIT: CpuBuffer = CpuBuffer {
+    head: AtomicUsize::new(0),
+    start: AtomicUsize::new(0),
+    slots: [SLOT_INIT; EVENTS_PER_CPU],
+};
+
+static BUFFERS: [CpuBuffer; axconfig::SMP] = [BUFFER_INIT; axconfig::SMP];
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+impl CpuBuffer {
+    fn push(&self, event: TraceEvent) {
+        // Reserve the slot before reading the clock, events recorded by
+        // nested IRQ handlers get their own slots.
+        let idx = self.head.fetch_add(1, Ordering::Relaxed);
+        let slot = &self.slots[idx % EVENTS_PER_CPU];
+        let (kind, arg0, arg1) = event.encode();
+        slot.seq.store(0, Ordering::Relaxed);
+        fence(Ordering::Release);
+        slot.timestamp.store(
+            call_interface!(TraceIf::monotonic_time_nanos),
+            Ordering::Relaxed,
+        );
+        slot.kind.store(kind, Ordering::Relaxed);
+        slot.arg0.store(arg0, Ordering::Relaxed);
+        slot.arg1.store(arg1, Ordering::Relaxed);
+        slot.seq.store(idx + 1, Ordering::Release);
+    }
+
+    /// Reads the event at index `idx`, returns [`None`] if it has been
+    /// overwritten or is being written.
+    fn read(&self, idx: usize) -> Option<(u64, TraceEvent)> {
+        let slot = &self.slots[idx % EVENTS_PER_CPU];
+        if slot.seq.load(Ordering::Acquire) != idx + 1 {
+            return None;
+        }
+        let timestamp = slot.timestamp.load(Ordering::Relaxed);
+        let kind = slot.kind.load(Ordering::Relaxed);
+        let arg0 = slot.arg0.load(Ordering::Relaxed);
+        let arg1 = slot.arg1.load(Ordering::Relaxed);
+        fence(Ordering::Acquire);
+        if slot.seq.load(Ordering::Relaxed) != idx + 1 {
+            return None;
+        }
+        Some((timestamp, TraceEvent::decode(kind, arg0, arg1)?))
+    }
+
+    /// Calls `f` on each recorded event, from the oldest to the latest.
+    fn for_each(&self, mut f: impl FnMut(u64, TraceEvent) -> fmt::Result) -> fmt::Result {
+        let head = self.head.load(Ordering::Acquire);
+        let start = self
+            .start
+            .load(Ordering::Relaxed)
+            .max(head.saturating_sub(EVENTS_PER_CPU));
+        for idx in start..head {
+            if let Some((timestamp, event)) = self.read(idx) {
+                f(timestamp, event)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Enables or disables tracing.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Release);
+}
+
+/// Whether tracing is enabled.
+#[inline]
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Records an event on the current CPU if tracing is enabled.
+#[inline]
+pub fn record(event: TraceEvent) {
+    if is_enabled() {
+        BUFFERS[call_interface!(TraceIf::current_cpu_id)].push(event);
+    }
+}
+
+/// Discards all events recorded so far.
+pub fn clear() {
+    for buf in &BUFFERS {
+        buf.start
+            .store(buf.head.load(Ordering::Acquire), Ordering::Relaxed);
+    }
+}
+
+/// Calls `f` on each recorded event of the CPU `cpu_id`, with its timestamp
+/// in nanoseconds, from the oldest to the latest.
+pub fn for_each_event(cpu_id: usize, f: impl FnMut(u64, TraceEvent) -> fmt::Result) -> fmt::Result {
+    BUFFERS[cpu_id].for_each(f)
+}
+
+/// The common fields of an exported event: the process ID, the thread ID (CPU
+/// ID), and the timestamp in microseconds.
+struct CommonFields {
+    cpu_id: usize,
+    ts_ns: u64,
+}
+
+impl fmt::Display for CommonFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "\"pid\":0,\"tid\":{},\"ts\":{}.{:03}",
+            self.cpu_id,
+            self.ts_ns / 1000,
+            self.ts_ns % 1000
+        )
+    }
+}
+
+/// Writes all recorded events to `w` in the Chrome trace event format (JSON).
+///
+/// Each CPU is shown as a thread. Running tasks and IRQ handlers are shown as
+/// slices, and wakeups and timer expiries are shown as instant events.
+pub fn export_chrome_json<W: Write + ?Sized>(w: &mut W) -> fmt::Result {
+    w.write_str("{\"traceEvents\":[")?;
+    let mut first = true;
+    let mut sep = |w: &mut W| {
+        if core::mem::take(&mut first) {
+            Ok(())
+        } else {
+            w.write_str(",\n")
+        }
+    };
+    for cpu_id in 0..axconfig::SMP {
+        sep(w)?;
+        write!(
+            w,
+            "{{\"ph\":\"M\",\"name\":\"thread_name\",\"pid\":0,\"tid\":{cpu_id},\
+             \"args\":{{\"name\":\"CPU {cpu_id}\"}}}}"
+        )?;
+        // Only end the slices that have begun in the exported events, the
+        // earlier events may have been overwritten.
+        let mut task_running = false;
+        let mut irq_depth = 0;
+        for_each_event(cpu_id, |ts_ns, event| {
+            let common = CommonFields { cpu_id, ts_ns };
+            match event {
+                TraceEvent::ContextSwitch { prev, next } => {
+                    if task_running {
+                        sep(w)?;
+                        write!(w, "{{\"ph\":\"E\",{common}}}")?;
+                    }
+                    task_running = true;
+                    sep(w)?;
+                    write!(
+                        w,
+                        "{{\"ph\":\"B\",\"name\":\"task {next}\",\"cat\":\"sched\",{common},\
+                         \"args\":{{\"prev\":{prev}}}}}"
+                    )
+                }
+                TraceEvent::Wakeup { task, cpu } => {
+                    sep(w)?;
+                    write!(
+                        w,
+                        "{{\"ph\":\"i\",\"s\":\"t\",\"name\":\"wakeup\",\"cat\":\"sched\",\
+                         {common},\"args\":{{\"task\":{task},\"cpu\":{cpu}}}}}"
+                    )
+                }
+                TraceEvent::IrqEnter { irq } => {
+                    irq_depth += 1;
+                    sep(w)?;
+                    write!(
+                        w,
+                        "{{\"ph\":\"B\",\"name\":\"irq {irq}\",\"cat\":\"irq\",{common}}}"
+                    )
+                }
+                TraceEvent::IrqExit { .. } => {
+                    if irq_depth == 0 {
+                        return Ok(());
+                    }
+                    irq_depth -= 1;
+                    sep(w)?;
+                    write!(w, "{{\"ph\":\"E\",{common}}}")
+                }
+                TraceEvent::TimerExpire { latency_ns } => {
+                    sep(w)?;
+                    write!(
+                        w,
+                        "{{\"ph\":\"i\",\"s\":\"t\",\"name\":\"timer\",\"cat\":\"timer\",\
+                         {common},\"args\":{{\"latency_ns\":{latency_ns}}}}}"
+                    )
+                }
+            }
+        })?;
+    }
+    w.write_str("],\"displayTimeUnit\":\"ns\"}\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static TIME: AtomicU64 = AtomicU64::new(0);
+
+    struct TraceIfImpl;
+
+    #[crate_interface::impl_interface]
+    impl TraceIf for TraceIfImpl {
+        fn monotonic_time_nanos() -> u64 {
+            TIME.fetch_add(1500, Ordering::Relaxed)
+        }
+
+        fn current_cpu_id() -> usize {
+            0
+        }
+    }
+
+    #[test]
+    fn test_export_chrome_json() {
+        record(TraceEvent::IrqEnter { irq: 1 });
+        set_enabled(true);
+        record(TraceEvent::IrqExit { irq: 1 });
+        record(TraceEvent::ContextSwitch { prev: 1, next: 2 });
+        record(TraceEvent::Wakeup { task: 3, cpu: 0 });
+        record(TraceEvent::ContextSwitch { prev: 2, next: 3 });
+        set_enabled(false);
+        record(TraceEvent::TimerExpire { latency_ns: 10 });
+
+        let mut events = Vec::new();
+        for_each_event(0, |ts, event| {
+            events.push((ts, event));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            events,
+            [
+                (0, TraceEvent::IrqExit { irq: 1 }),
+                (1500, TraceEvent::ContextSwitch { prev: 1, next: 2 }),
+                (3000, TraceEvent::Wakeup { task: 3, cpu: 0 }),
+                (4500, TraceEvent::ContextSwitch { prev: 2, next: 3 }),
+            ]
+        );
+
+        let mut json = String::new();
+        export_chrome_json(&mut json).unwrap();
+        assert!(json.starts_with("{\"traceEvents\":["));
+        assert!(!json.contains("irq 1")); // the IRQ exit has no matching entry
+        assert!(
+            json.contains("\"name\":\"task 2\",\"cat\":\"sched\",\"pid\":0,\"tid\":0,\"ts\":1.500")
+        );
+        assert_eq!(json.matches("\"ph\":\"E\"").count(), 1);
+
+        clear();
+        for_each_event(0, |_, _| panic!("events are not cleared")).unwrap();
+    }
+}
@@ -18,6 +18,11 @@ build_args := \
   $(verbose)
 
 RUSTFLAGS := -C link-arg=-T$(LD_SCRIPT) -C link-arg=-no-pie -C link-arg=-znostart-stop-gc
+ifneq ($(filter watchdog lockdep,$(FEATURES)),)
+  # for backtraces printed by the watchdog and lockdep
+  RUSTFLAGS += -C force-frame-pointers=yes
+endif
+
 RUSTDOCFLAGS := -Z unstable-options --enable-index-page -D rustdoc::broken_intra_doc_links
 
 ifeq ($(MAKECMDGOALS), doc_check_missing)
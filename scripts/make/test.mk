@@ -2,5 +2,11 @@
 
 define unit_test
   $(call run_cmd,cargo test,-p axfs $(1) --features "myfs" -- --nocapture)
+  $(call run_cmd,cargo test,-p axtask $(1) --features "sched_edf" -- sched_edf:: --nocapture)
+  $(call run_cmd,cargo test,-p axtask $(1) --features "smp" -- load_balance:: --nocapture)
+  $(call run_cmd,cargo test,-p axtask $(1) --features "watchdog" -- watchdog:: --nocapture)
+  $(call run_cmd,cargo test,-p axlockdep $(1) --features "enabled" -- --nocapture)
+  $(call run_cmd,cargo test,-p axsync $(1) --features "lockdep" -- lockdep:: --nocapture)
+  $(call run_cmd,cargo test,-p arceos_posix_api $(1) --features "pipe multitask" -- fd_ops:: --nocapture)
   $(call run_cmd,cargo test,--workspace $(1) -- --nocapture)
 endef
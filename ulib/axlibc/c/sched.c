@@ -1,3 +1,4 @@
+#include <errno.h>
 #include <sched.h>
 #include <stdio.h>
 
@@ -7,3 +8,31 @@ int sched_setaffinity(pid_t __pid, size_t __cpusetsize, const cpu_set_t *__cpuse
     unimplemented();
     return 0;
 }
+
+int sched_get_priority_max(int policy)
+{
+    switch (policy) {
+    case SCHED_OTHER:
+        return 0;
+    case SCHED_FIFO:
+    case SCHED_RR:
+        return 99;
+    default:
+        errno = EINVAL;
+        return -1;
+    }
+}
+
+int sched_get_priority_min(int policy)
+{
+    switch (policy) {
+    case SCHED_OTHER:
+        return 0;
+    case SCHED_FIFO:
+    case SCHED_RR:
+        return 1;
+    default:
+        errno = EINVAL;
+        return -1;
+    }
+}
@@ -0,0 +1,16 @@
+use crate::{ctypes, utils::e};
+use arceos_posix_api::sys_futex;
+use core::ffi::{c_int, c_long};
+
+/// The `futex` system call, to wait on or wake up tasks by an address.
+#[no_mangle]
+pub unsafe extern "C" fn futex(
+    uaddr: *mut u32,
+    futex_op: c_int,
+    val: u32,
+    timeout: *const ctypes::timespec,
+    uaddr2: *mut u32,
+    val3: u32,
+) -> c_long {
+    e(sys_futex(uaddr, futex_op, val, timeout, uaddr2, val3) as _) as _
+}
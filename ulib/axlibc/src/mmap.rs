@@ -0,0 +1,52 @@
+use core::ffi::{c_int, c_void};
+
+use arceos_posix_api::{sys_madvise, sys_mmap, sys_mprotect, sys_msync, sys_munmap};
+
+use crate::{ctypes, utils::e};
+
+/// Map files or devices into memory.
+///
+/// Return `MAP_FAILED` and set `errno` on failure.
+#[no_mangle]
+pub unsafe extern "C" fn mmap(
+    addr: *mut c_void,
+    len: ctypes::size_t,
+    prot: c_int,
+    flags: c_int,
+    fd: c_int,
+    off: ctypes::off_t,
+) -> *mut c_void {
+    let ret = sys_mmap(addr, len, prot, flags, fd, off);
+    // Error codes are returned as negative values, which are not page aligned.
+    let code = ret as isize;
+    if (-4095..0).contains(&code) {
+        crate::errno::set_errno(-code as c_int);
+        usize::MAX as *mut c_void // MAP_FAILED
+    } else {
+        ret
+    }
+}
+
+/// Unmap files or devices from memory.
+#[no_mangle]
+pub unsafe extern "C" fn munmap(addr: *mut c_void, len: ctypes::size_t) -> c_int {
+    e(sys_munmap(addr, len))
+}
+
+/// Set protection on a region of memory.
+#[no_mangle]
+pub unsafe extern "C" fn mprotect(addr: *mut c_void, len: ctypes::size_t, prot: c_int) -> c_int {
+    e(sys_mprotect(addr, len, prot))
+}
+
+/// Synchronize a file with a memory map.
+#[no_mangle]
+pub unsafe extern "C" fn msync(addr: *mut c_void, len: ctypes::size_t, flags: c_int) -> c_int {
+    e(sys_msync(addr, len, flags))
+}
+
+/// Give advice about use of memory.
+#[no_mangle]
+pub unsafe extern "C" fn madvise(addr: *mut c_void, len: ctypes::size_t, advice: c_int) -> c_int {
+    e(sys_madvise(addr, len, advice))
+}
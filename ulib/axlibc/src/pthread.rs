@@ -37,6 +37,66 @@ pub unsafe extern "C" fn pthread_join(
     e(api::sys_pthread_join(thread, retval))
 }
 
+/// Sets the scheduling policy and priority of the given thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_setschedparam(
+    thread: ctypes::pthread_t,
+    policy: c_int,
+    param: *const ctypes::sched_param,
+) -> c_int {
+    e(api::sys_pthread_setschedparam(thread, policy, param))
+}
+
+/// Gets the scheduling policy and priority of the given thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_getschedparam(
+    thread: ctypes::pthread_t,
+    policy: *mut c_int,
+    param: *mut ctypes::sched_param,
+) -> c_int {
+    e(api::sys_pthread_getschedparam(thread, policy, param))
+}
+
+/// Requests the cancellation of the given thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cancel(thread: ctypes::pthread_t) -> c_int {
+    e(api::sys_pthread_cancel(thread))
+}
+
+/// Enables or disables the cancellation of the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_setcancelstate(state: c_int, oldstate: *mut c_int) -> c_int {
+    e(api::sys_pthread_setcancelstate(state, oldstate))
+}
+
+/// Sets the cancellation type of the current thread.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_setcanceltype(ty: c_int, oldtype: *mut c_int) -> c_int {
+    e(api::sys_pthread_setcanceltype(ty, oldtype))
+}
+
+/// Exits the current thread if its cancellation has been requested.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_testcancel() {
+    api::sys_pthread_testcancel()
+}
+
+/// Registers a cleanup routine, used by the `pthread_cleanup_push` macro.
+#[no_mangle]
+pub unsafe extern "C" fn _pthread_cleanup_push(
+    _cb: *mut c_void,
+    routine: extern "C" fn(arg: *mut c_void),
+    arg: *mut c_void,
+) {
+    api::sys_pthread_cleanup_push(routine, arg)
+}
+
+/// Removes a cleanup routine, used by the `pthread_cleanup_pop` macro.
+#[no_mangle]
+pub unsafe extern "C" fn _pthread_cleanup_pop(_cb: *mut c_void, execute: c_int) {
+    api::sys_pthread_cleanup_pop(execute)
+}
+
 /// Initialize a mutex.
 #[no_mangle]
 pub unsafe extern "C" fn pthread_mutex_init(
@@ -57,3 +117,122 @@ pub unsafe extern "C" fn pthread_mutex_lock(mutex: *mut ctypes::pthread_mutex_t)
 pub unsafe extern "C" fn pthread_mutex_unlock(mutex: *mut ctypes::pthread_mutex_t) -> c_int {
     e(api::sys_pthread_mutex_unlock(mutex))
 }
+
+/// Initialize a condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_init(
+    cond: *mut ctypes::pthread_cond_t,
+    attr: *const ctypes::pthread_condattr_t,
+) -> c_int {
+    e(api::sys_pthread_cond_init(cond, attr))
+}
+
+/// Destroy a condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_destroy(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    e(api::sys_pthread_cond_destroy(cond))
+}
+
+/// Wait on a condition variable, the mutex is released during the wait.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_wait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+) -> c_int {
+    e(api::sys_pthread_cond_wait(cond, mutex))
+}
+
+/// Wait on a condition variable until the absolute time `abstime`.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_timedwait(
+    cond: *mut ctypes::pthread_cond_t,
+    mutex: *mut ctypes::pthread_mutex_t,
+    abstime: *const ctypes::timespec,
+) -> c_int {
+    e(api::sys_pthread_cond_timedwait(cond, mutex, abstime))
+}
+
+/// Wake up one thread waiting on the condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_signal(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    e(api::sys_pthread_cond_signal(cond))
+}
+
+/// Wake up all threads waiting on the condition variable.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_cond_broadcast(cond: *mut ctypes::pthread_cond_t) -> c_int {
+    e(api::sys_pthread_cond_broadcast(cond))
+}
+
+/// Initialize a reader-writer lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_init(
+    rwlock: *mut ctypes::pthread_rwlock_t,
+    attr: *const ctypes::pthread_rwlockattr_t,
+) -> c_int {
+    e(api::sys_pthread_rwlock_init(rwlock, attr))
+}
+
+/// Destroy a reader-writer lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_destroy(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    e(api::sys_pthread_rwlock_destroy(rwlock))
+}
+
+/// Acquire a read lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_rdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    e(api::sys_pthread_rwlock_rdlock(rwlock))
+}
+
+/// Try to acquire a read lock without blocking.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_tryrdlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    e(api::sys_pthread_rwlock_tryrdlock(rwlock))
+}
+
+/// Acquire the write lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_wrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    e(api::sys_pthread_rwlock_wrlock(rwlock))
+}
+
+/// Try to acquire the write lock without blocking.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_trywrlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    e(api::sys_pthread_rwlock_trywrlock(rwlock))
+}
+
+/// Release the read or write lock.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_rwlock_unlock(rwlock: *mut ctypes::pthread_rwlock_t) -> c_int {
+    e(api::sys_pthread_rwlock_unlock(rwlock))
+}
+
+/// Initialize a barrier for `count` threads.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_barrier_init(
+    barrier: *mut ctypes::pthread_barrier_t,
+    attr: *const ctypes::pthread_barrierattr_t,
+    count: core::ffi::c_uint,
+) -> c_int {
+    e(api::sys_pthread_barrier_init(barrier, attr, count))
+}
+
+/// Destroy a barrier.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_barrier_destroy(barrier: *mut ctypes::pthread_barrier_t) -> c_int {
+    e(api::sys_pthread_barrier_destroy(barrier))
+}
+
+/// Wait until all threads have reached the barrier.
+#[no_mangle]
+pub unsafe extern "C" fn pthread_barrier_wait(barrier: *mut ctypes::pthread_barrier_t) -> c_int {
+    // `PTHREAD_BARRIER_SERIAL_THREAD` is negative, but not an error.
+    let ret = api::sys_pthread_barrier_wait(barrier);
+    if ret == ctypes::PTHREAD_BARRIER_SERIAL_THREAD {
+        ret
+    } else {
+        e(ret)
+    }
+}
@@ -0,0 +1,16 @@
+use arceos_posix_api::sys_sigaction;
+use core::ffi::c_int;
+
+use crate::{ctypes, utils::e};
+
+/// Examine and change the action of a signal.
+///
+/// TODO: remove this function in future work
+#[no_mangle]
+pub unsafe extern "C" fn ax_sigaction(
+    signum: c_int,
+    act: *const ctypes::sigaction,
+    oldact: *mut ctypes::sigaction,
+) -> c_int {
+    e(sys_sigaction(signum, act, oldact))
+}
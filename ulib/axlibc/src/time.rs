@@ -19,3 +19,70 @@ pub unsafe extern "C" fn nanosleep(
 ) -> c_int {
     e(sys_nanosleep(req, rem))
 }
+
+/// Create a per-process timer.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+#[no_mangle]
+pub unsafe extern "C" fn timer_create(
+    clockid: ctypes::clockid_t,
+    sevp: *mut ctypes::sigevent,
+    timerid: *mut ctypes::timer_t,
+) -> c_int {
+    e(arceos_posix_api::sys_timer_create(clockid, sevp, timerid))
+}
+
+/// Delete a per-process timer.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+#[no_mangle]
+pub unsafe extern "C" fn timer_delete(timerid: ctypes::timer_t) -> c_int {
+    e(arceos_posix_api::sys_timer_delete(timerid))
+}
+
+/// Arm or disarm a per-process timer.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+#[no_mangle]
+pub unsafe extern "C" fn timer_settime(
+    timerid: ctypes::timer_t,
+    flags: c_int,
+    new_value: *const ctypes::itimerspec,
+    old_value: *mut ctypes::itimerspec,
+) -> c_int {
+    e(arceos_posix_api::sys_timer_settime(
+        timerid, flags, new_value, old_value,
+    ))
+}
+
+/// Get the time until the next expiration of a per-process timer.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+#[no_mangle]
+pub unsafe extern "C" fn timer_gettime(
+    timerid: ctypes::timer_t,
+    curr_value: *mut ctypes::itimerspec,
+) -> c_int {
+    e(arceos_posix_api::sys_timer_gettime(timerid, curr_value))
+}
+
+/// Get the overrun count of a per-process timer.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+#[no_mangle]
+pub unsafe extern "C" fn timer_getoverrun(timerid: ctypes::timer_t) -> c_int {
+    e(arceos_posix_api::sys_timer_getoverrun(timerid))
+}
+
+/// Set the value of an interval timer.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+#[no_mangle]
+pub unsafe extern "C" fn setitimer(
+    which: c_int,
+    new_value: *const ctypes::itimerval,
+    old_value: *mut ctypes::itimerval,
+) -> c_int {
+    e(arceos_posix_api::sys_setitimer(which, new_value, old_value))
+}
+
+/// Get the value of an interval timer.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+#[no_mangle]
+pub unsafe extern "C" fn getitimer(which: c_int, curr_value: *mut ctypes::itimerval) -> c_int {
+    e(arceos_posix_api::sys_getitimer(which, curr_value))
+}
@@ -1,5 +1,7 @@
 use arceos_posix_api::{sys_exit, sys_getpid};
 use core::ffi::c_int;
+#[cfg(all(feature = "multitask", feature = "irq"))]
+use core::ffi::c_uint;
 
 /// Get current thread ID.
 #[no_mangle]
@@ -18,3 +20,10 @@ pub unsafe extern "C" fn abort() -> ! {
 pub unsafe extern "C" fn exit(exit_code: c_int) -> ! {
     sys_exit(exit_code)
 }
+
+/// Schedule a `SIGALRM` after `seconds`.
+#[cfg(all(feature = "multitask", feature = "irq"))]
+#[no_mangle]
+pub unsafe extern "C" fn alarm(seconds: c_uint) -> c_uint {
+    arceos_posix_api::sys_alarm(seconds)
+}
@@ -22,17 +22,26 @@
 //!     - `alloc-buddy`: Use the buddy system allocator.
 //!     - `paging`: Enable page table manipulation.
 //!     - `tls`: Enable thread-local storage.
+//!     - `uspace`: Enable running code in user mode and handling its system calls.
 //! - Task management
 //!     - `multitask`: Enable multi-threading support.
 //!     - `sched_fifo`: Use the FIFO cooperative scheduler.
 //!     - `sched_rr`: Use the Round-robin preemptive scheduler.
 //!     - `sched_cfs`: Use the Completely Fair Scheduler (CFS) preemptive scheduler.
+//!     - `sched_rt`: Use the real-time scheduling class on top of the CFS scheduler.
+//!     - `sched_edf`: Use the earliest-deadline-first class on top of `sched_rt`.
+//!     - `tickless`: Stop the periodic timer tick on idle CPUs.
 //! - Upperlayer stacks
 //!     - `fs`: Enable file system support.
 //!     - `myfs`: Allow users to define their custom filesystems to override the default.
 //!     - `net`: Enable networking support.
+//!     - `net-async`: Enable asynchronous socket operations (requires `multitask`).
 //!     - `dns`: Enable DNS lookup support.
 //!     - `display`: Enable graphics support.
+//! - Debugging
+//!     - `trace`: Enable kernel event tracing, exported in the Chrome trace event format.
+//!     - `watchdog`: Report soft lockups and hung tasks with backtraces.
+//!     - `lockdep`: Report lock order inversions that may deadlock, and blocking locks taken with IRQs disabled.
 //! - Device drivers
 //!     - `bus-mmio`: Use device tree to probe all MMIO devices.
 //!     - `bus-pci`: Use PCI bus to probe all PCI devices.
@@ -75,3 +84,5 @@ pub mod time;
 pub mod fs;
 #[cfg(feature = "net")]
 pub mod net;
+#[cfg(feature = "multitask")]
+pub mod task;
@@ -1,5 +1,7 @@
 use super::{SocketAddr, ToSocketAddrs};
 use crate::io::{self, prelude::*};
+#[cfg(feature = "net-async")]
+use core::future::poll_fn;
 
 use arceos_api::net::{self as api, AxTcpSocketHandle};
 
@@ -43,6 +45,24 @@ impl TcpStream {
     pub fn shutdown(&self) -> io::Result<()> {
         api::ax_tcp_shutdown(&self.0)
     }
+
+    /// Reads data from the stream asynchronously.
+    ///
+    /// The returned future completes when some data is received, instead of
+    /// blocking the calling thread.
+    #[cfg(feature = "net-async")]
+    pub async fn read_async(&self, buf: &mut [u8]) -> io::Result<usize> {
+        poll_fn(|cx| api::ax_tcp_poll_recv(&self.0, cx, buf)).await
+    }
+
+    /// Writes data to the stream asynchronously.
+    ///
+    /// The returned future completes when some data is put into the send
+    /// buffer, instead of blocking the calling thread.
+    #[cfg(feature = "net-async")]
+    pub async fn write_async(&self, buf: &[u8]) -> io::Result<usize> {
+        poll_fn(|cx| api::ax_tcp_poll_send(&self.0, cx, buf)).await
+    }
 }
 
 impl Read for TcpStream {
@@ -102,4 +122,16 @@ impl TcpListener {
     pub fn accept(&self) -> io::Result<(TcpStream, SocketAddr)> {
         api::ax_tcp_accept(&self.0).map(|(a, b)| (TcpStream(a), b))
     }
+
+    /// Accept a new incoming connection from this listener asynchronously.
+    ///
+    /// It's the same as [`TcpListener::accept`], except that the returned
+    /// future completes when a connection is established, instead of blocking
+    /// the calling thread.
+    #[cfg(feature = "net-async")]
+    pub async fn accept_async(&self) -> io::Result<(TcpStream, SocketAddr)> {
+        poll_fn(|cx| api::ax_tcp_poll_accept(&self.0, cx))
+            .await
+            .map(|(a, b)| (TcpStream(a), b))
+    }
 }
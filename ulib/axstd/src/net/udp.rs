@@ -1,5 +1,7 @@
 use super::{SocketAddr, ToSocketAddrs};
 use crate::io;
+#[cfg(feature = "net-async")]
+use core::future::poll_fn;
 
 use arceos_api::net::{self as api, AxUdpSocketHandle};
 
@@ -41,6 +43,15 @@ impl UdpSocket {
         api::ax_udp_recv_from(&self.0, buf)
     }
 
+    /// Receives a single datagram message on the socket asynchronously.
+    ///
+    /// The returned future completes when a message is received, instead of
+    /// blocking the calling thread.
+    #[cfg(feature = "net-async")]
+    pub async fn recv_from_async(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
+        poll_fn(|cx| api::ax_udp_poll_recv_from(&self.0, cx, buf)).await
+    }
+
     /// Receives a single datagram message on the socket, without removing it from
     /// the queue. On success, returns the number of bytes read and the origin.
     pub fn peek_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocketAddr)> {
@@ -62,6 +73,19 @@ impl UdpSocket {
         }
     }
 
+    /// Sends data on the socket to the given address asynchronously.
+    ///
+    /// The returned future completes when the data is put into the send
+    /// buffer, instead of blocking the calling thread.
+    #[cfg(feature = "net-async")]
+    pub async fn send_to_async<A: ToSocketAddrs>(&self, buf: &[u8], addr: A) -> io::Result<usize> {
+        let addr = addr.to_socket_addrs()?.next();
+        match addr {
+            Some(addr) => poll_fn(|cx| api::ax_udp_poll_send_to(&self.0, cx, buf, addr)).await,
+            None => axerrno::ax_err!(InvalidInput, "no addresses to send data to"),
+        }
+    }
+
     /// Connects this UDP socket to a remote address, allowing the `send` and
     /// `recv` syscalls to be used to send data and also applies filters to only
     /// receive data from the specified address.
@@ -0,0 +1,50 @@
+//! A sleeping barrier.
+
+use core::fmt;
+
+use arceos_api::sync::{self as api, AxBarrierHandle};
+
+/// A barrier enables multiple threads to synchronize the beginning of some
+/// computation, similar to
+/// [`std::sync::Barrier`](https://doc.rust-lang.org/std/sync/struct.Barrier.html).
+pub struct Barrier {
+    inner: AxBarrierHandle,
+    num_threads: usize,
+}
+
+/// The result returned by [`Barrier::wait`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BarrierWaitResult(bool);
+
+impl BarrierWaitResult {
+    /// Returns `true` if the thread is the "leader" of this round. Exactly
+    /// one thread is the leader in each round.
+    pub fn is_leader(&self) -> bool {
+        self.0
+    }
+}
+
+impl Barrier {
+    /// Creates a new barrier that blocks a group of `n` threads.
+    pub const fn new(n: usize) -> Self {
+        Self {
+            inner: AxBarrierHandle::new(n),
+            num_threads: n,
+        }
+    }
+
+    /// Blocks the current thread until all threads have rendezvoused here.
+    ///
+    /// The barrier can be reused after all threads have rendezvoused.
+    pub fn wait(&self) -> BarrierWaitResult {
+        BarrierWaitResult(api::ax_barrier_wait(&self.inner))
+    }
+}
+
+impl fmt::Debug for Barrier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Barrier")
+            .field("num_threads", &self.num_threads)
+            .finish_non_exhaustive()
+    }
+}
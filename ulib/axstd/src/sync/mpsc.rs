@@ -0,0 +1,221 @@
+//! Multi-producer, single-consumer FIFO queue communication primitives.
+//!
+//! Similar to [`std::sync::mpsc`](https://doc.rust-lang.org/std/sync/mpsc/index.html).
+//! A channel is created by [`channel`] (asynchronous, with an unbounded
+//! buffer) or [`sync_channel`] (synchronous, with a bounded buffer). Blocked
+//! threads sleep until the other side makes progress or disconnects.
+//!
+//! The timed receive ([`Receiver::recv_timeout`]) requires the feature `irq`.
+
+use core::fmt;
+#[cfg(feature = "irq")]
+use core::time::Duration;
+
+use arceos_api::sync::{self as api, AxReceiverHandle, AxSenderHandle};
+
+#[doc(no_inline)]
+pub use arceos_api::sync::{
+    AxRecvError as RecvError, AxSendError as SendError, AxTryRecvError as TryRecvError,
+    AxTrySendError as TrySendError,
+};
+
+#[cfg(feature = "irq")]
+#[doc(no_inline)]
+pub use arceos_api::sync::AxRecvTimeoutError as RecvTimeoutError;
+
+/// The sending half of an asynchronous channel created by [`channel`].
+///
+/// Sending never blocks. It can be cloned to send to the same channel
+/// multiple times.
+pub struct Sender<T> {
+    inner: AxSenderHandle<T>,
+}
+
+/// The sending half of a synchronous channel created by [`sync_channel`].
+///
+/// Sending blocks when the buffer is full. It can be cloned to send to the
+/// same channel multiple times.
+pub struct SyncSender<T> {
+    inner: AxSenderHandle<T>,
+}
+
+/// The receiving half of a channel. It can only be owned by one thread.
+pub struct Receiver<T> {
+    inner: AxReceiverHandle<T>,
+}
+
+/// Creates a new asynchronous channel, returning the sender/receiver halves.
+///
+/// The channel has an unbounded buffer, so [`Sender::send`] never blocks.
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = api::ax_channel(None);
+    (Sender { inner: tx }, Receiver { inner: rx })
+}
+
+/// Creates a new synchronous, bounded channel, returning the sender/receiver
+/// halves.
+///
+/// [`SyncSender::send`] blocks when the buffer holds `bound` messages. If
+/// `bound` is 0, the channel becomes a rendezvous channel, where each send
+/// blocks until the message is received.
+pub fn sync_channel<T>(bound: usize) -> (SyncSender<T>, Receiver<T>) {
+    let (tx, rx) = api::ax_channel(Some(bound));
+    (SyncSender { inner: tx }, Receiver { inner: rx })
+}
+
+impl<T> Sender<T> {
+    /// Sends a value on this channel, never blocks.
+    ///
+    /// Returns the value back if the receiver is dropped.
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        api::ax_channel_send(&self.inner, t)
+    }
+}
+
+impl<T> SyncSender<T> {
+    /// Sends a value on this channel, blocking the current thread until there
+    /// is space in the buffer, or the value is received for a rendezvous
+    /// channel.
+    ///
+    /// Returns the value back if the receiver is dropped.
+    pub fn send(&self, t: T) -> Result<(), SendError<T>> {
+        api::ax_channel_send(&self.inner, t)
+    }
+
+    /// Tries to send a value on this channel without blocking.
+    pub fn try_send(&self, t: T) -> Result<(), TrySendError<T>> {
+        api::ax_channel_try_send(&self.inner, t)
+    }
+}
+
+impl<T> Receiver<T> {
+    /// Tries to receive a value without blocking.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        api::ax_channel_try_recv(&self.inner)
+    }
+
+    /// Receives a value, blocking the current thread until there is one.
+    ///
+    /// Returns [`RecvError`] if the channel is empty and all senders are
+    /// dropped.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        api::ax_channel_recv(&self.inner)
+    }
+
+    /// Receives a value, blocking the current thread until there is one or
+    /// the given duration has elapsed.
+    #[cfg(feature = "irq")]
+    #[doc(cfg(feature = "irq"))]
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        api::ax_channel_recv_timeout(&self.inner, timeout)
+    }
+
+    /// Returns an iterator that blocks waiting for values, until all senders
+    /// are dropped.
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter { rx: self }
+    }
+
+    /// Returns an iterator over the values currently in the channel, without
+    /// blocking.
+    pub fn try_iter(&self) -> TryIter<'_, T> {
+        TryIter { rx: self }
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> Clone for SyncSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for SyncSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SyncSender").finish_non_exhaustive()
+    }
+}
+
+impl<T> fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+/// An iterator over messages on a [`Receiver`], created by
+/// [`Receiver::iter`].
+#[derive(Debug)]
+pub struct Iter<'a, T: 'a> {
+    rx: &'a Receiver<T>,
+}
+
+/// An iterator that attempts to yield all pending values for a [`Receiver`],
+/// created by [`Receiver::try_iter`].
+#[derive(Debug)]
+pub struct TryIter<'a, T: 'a> {
+    rx: &'a Receiver<T>,
+}
+
+/// An owning iterator over messages on a [`Receiver`], created by
+/// [`IntoIterator::into_iter`].
+#[derive(Debug)]
+pub struct IntoIter<T> {
+    rx: Receiver<T>,
+}
+
+impl<T> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<T> Iterator for TryIter<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.try_recv().ok()
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Receiver<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    fn into_iter(self) -> Iter<'a, T> {
+        self.iter()
+    }
+}
+
+impl<T> IntoIterator for Receiver<T> {
+    type Item = T;
+    type IntoIter = IntoIter<T>;
+
+    fn into_iter(self) -> IntoIter<T> {
+        IntoIter { rx: self }
+    }
+}
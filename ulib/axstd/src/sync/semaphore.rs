@@ -0,0 +1,61 @@
+//! A sleeping counting semaphore.
+
+use core::fmt;
+use core::time::Duration;
+
+use arceos_api::sync::{self as api, AxSemaphoreHandle};
+
+/// A counting semaphore.
+///
+/// It holds a number of permits. [`Semaphore::acquire`] takes a permit, and
+/// blocks the current thread if there are none, while
+/// [`Semaphore::release`] returns a permit.
+pub struct Semaphore {
+    inner: AxSemaphoreHandle,
+}
+
+impl Semaphore {
+    /// Creates a new semaphore with the given number of permits.
+    pub const fn new(permits: usize) -> Self {
+        Self {
+            inner: AxSemaphoreHandle::new(permits),
+        }
+    }
+
+    /// Returns the number of available permits.
+    pub fn available_permits(&self) -> usize {
+        api::ax_semaphore_available_permits(&self.inner)
+    }
+
+    /// Acquires a permit, blocking the current thread until one is available.
+    pub fn acquire(&self) {
+        api::ax_semaphore_acquire(&self.inner, None);
+    }
+
+    /// Tries to acquire a permit without blocking, returns whether it
+    /// succeeded.
+    pub fn try_acquire(&self) -> bool {
+        api::ax_semaphore_try_acquire(&self.inner)
+    }
+
+    /// Acquires a permit, blocking the current thread until one is available
+    /// or the given duration has elapsed. Returns whether it succeeded.
+    ///
+    /// The timeout requires the feature `irq`, otherwise it's ignored.
+    pub fn acquire_timeout(&self, dur: Duration) -> bool {
+        api::ax_semaphore_acquire(&self.inner, Some(dur))
+    }
+
+    /// Releases a permit.
+    pub fn release(&self) {
+        api::ax_semaphore_release(&self.inner);
+    }
+}
+
+impl fmt::Debug for Semaphore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Semaphore")
+            .field("permits", &self.available_permits())
+            .finish()
+    }
+}
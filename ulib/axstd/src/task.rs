@@ -0,0 +1,92 @@
+//! Asynchronous tasks.
+//!
+//! Futures spawned by [`spawn`] run on a pool of executor threads provided by
+//! ArceOS, and [`block_on`] runs a future on the current thread. Pending
+//! futures are woken up by the events they wait for (e.g., timers and socket
+//! readiness), instead of being polled repeatedly.
+
+extern crate alloc;
+
+use alloc::sync::Arc;
+use core::future::Future;
+use core::pin::Pin;
+use core::time::Duration;
+
+use arceos_api::task::{self as api, AxAsyncTaskHandle};
+use arceos_api::time::AxTimeValue;
+
+use crate::sync::Mutex;
+
+#[doc(no_inline)]
+pub use alloc::task::Wake;
+#[doc(no_inline)]
+pub use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// A handle to join an asynchronous task spawned by [`spawn`].
+///
+/// It's a future that resolves to the output of the task. The task keeps
+/// running if the handle is dropped.
+pub struct JoinHandle<T> {
+    handle: AxAsyncTaskHandle,
+    packet: Arc<Mutex<Option<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Checks if the associated task has finished running its future.
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Blocks the current thread until the associated task finishes, and
+    /// returns its output.
+    pub fn join(self) -> T {
+        self.handle.join();
+        self.packet.lock().take().unwrap()
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        Pin::new(&mut self.handle)
+            .poll(cx)
+            .map(|_| self.packet.lock().take().unwrap())
+    }
+}
+
+/// Spawns a new asynchronous task, returning a [`JoinHandle`] for it.
+pub fn spawn<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    let packet = Arc::new(Mutex::new(None));
+    let their_packet = packet.clone();
+    let handle = api::ax_spawn_async(async move {
+        let output = future.await;
+        *their_packet.lock() = Some(output);
+    });
+    JoinHandle { handle, packet }
+}
+
+/// Runs a future to completion on the current thread, and returns its output.
+///
+/// The current thread is blocked while the future is pending.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut output = None;
+    api::ax_block_on(async {
+        output = Some(future.await);
+    });
+    output.unwrap()
+}
+
+/// Returns a future that completes after the given duration.
+pub fn sleep(dur: Duration) -> impl Future<Output = ()> {
+    sleep_until(arceos_api::time::ax_wall_time() + dur)
+}
+
+/// Returns a future that completes at the given deadline.
+pub fn sleep_until(deadline: AxTimeValue) -> impl Future<Output = ()> {
+    api::ax_sleep_until_async(deadline)
+}